@@ -2,11 +2,10 @@
 extern crate specs_derive;
 
 use rengine::camera::CameraView;
+use rengine::colors;
 use rengine::comp::Transform;
-use rengine::gui::{
-    widgets, GuiGraph, GuiLayoutSystem, GuiMouseMoveSystem, GuiSortSystem, WidgetBuilder,
-    WidgetEvent, WidgetEvents,
-};
+use rengine::draw2d::Canvas2d;
+use rengine::gui::{widgets, GuiGraph, WidgetBuilder, WidgetEvent, WidgetEvents};
 use rengine::res::DeltaTime;
 use rengine::specs::prelude::*;
 use rengine::{Context, Scene, Trans};
@@ -38,7 +37,6 @@ impl Scene for Intro {
 struct Game {
     entities: Vec<Entity>,
     widget_event_reader: shrev::ReaderId<WidgetEvent>,
-    gui_mouse_sys: GuiMouseMoveSystem,
 }
 
 impl Game {
@@ -50,7 +48,6 @@ impl Game {
         Game {
             entities: vec![],
             widget_event_reader: reader_id,
-            gui_mouse_sys: GuiMouseMoveSystem::new(),
         }
     }
 }
@@ -123,16 +120,20 @@ impl Scene for Game {
             },
         );
 
-        self.gui_mouse_sys.run_now(&ctx.world.res);
-        GuiSortSystem.run_now(&ctx.world.res);
-        GuiLayoutSystem.run_now(&ctx.world.res);
-
         ctx.world.exec(|widget_events: Read<'_, WidgetEvents>| {
             for ev in widget_events.read(&mut self.widget_event_reader) {
                 println!("Game::on_update {:?}", ev);
             }
         });
 
+        // Debug overlay, drawn again every frame
+        {
+            let mut canvas = ctx.world.write_resource::<Canvas2d>();
+            canvas.rect([8.0, 432.0], [160.0, 40.0], [0.0, 0.0, 0.0, 0.5]);
+            canvas.line([16.0, 452.0], [160.0, 452.0], 2.0, colors::RED);
+            canvas.circle([600.0, 440.0], 16.0, colors::GREEN);
+        }
+
         None
     }
 }
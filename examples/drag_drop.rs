@@ -0,0 +1,134 @@
+use rengine::gui::{
+    reparent_widget, widgets, Draggable, DropTarget, NodeId, WidgetBuilder, WidgetEvent,
+    WidgetEventKind, WidgetEvents,
+};
+use rengine::specs::prelude::*;
+use rengine::{Context, Scene, Trans};
+use std::error::Error;
+
+const SLOT_COUNT: usize = 3;
+const ICON_COUNT: usize = 4;
+
+struct Intro;
+
+impl Scene for Intro {
+    fn on_start(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+        Trans::replace(Game::new(ctx))
+    }
+}
+
+struct Game {
+    entities: Vec<Entity>,
+    /// Containers that icons can be dropped into.
+    slots: Vec<(Entity, NodeId)>,
+    widget_event_reader: shrev::ReaderId<WidgetEvent>,
+}
+
+impl Game {
+    fn new(ctx: &mut Context<'_>) -> Game {
+        let reader_id = ctx
+            .world
+            .exec(|mut widget_events: Write<'_, WidgetEvents>| widget_events.register_reader());
+
+        Game {
+            entities: vec![],
+            slots: vec![],
+            widget_event_reader: reader_id,
+        }
+    }
+}
+
+impl Scene for Game {
+    fn on_start(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+        let (group_id, group_node_id) = widgets::Container::vbox()
+            .with_margin([16.0, 16.0])
+            .build(&mut ctx.world, &mut ctx.graphics);
+        self.entities.push(group_id);
+
+        for s in 0..SLOT_COUNT {
+            let (slot_id, slot_node_id) = widgets::Container::hbox()
+                .child_of(group_node_id)
+                .with_tag(format!("Slot {}", s + 1))
+                .with_margin([8.0, 8.0])
+                .with_size([320.0, 80.0])
+                .build(&mut ctx.world, &mut ctx.graphics);
+            ctx.world
+                .write_storage::<DropTarget>()
+                .insert(slot_id, DropTarget)
+                .unwrap();
+            self.entities.push(slot_id);
+            self.slots.push((slot_id, slot_node_id));
+        }
+
+        // All icons start out in the first slot.
+        let first_slot_node_id = self.slots[0].1;
+        for i in 0..ICON_COUNT {
+            let (icon_id, _icon_node_id) = widgets::Button::text(format!("icon {}", i + 1))
+                .child_of(first_slot_node_id)
+                .size(64., 64.)
+                .background_image("examples/ui.png")
+                .background_src_rect([0, 0], [32, 32])
+                .tag(format!("Icon {}", i + 1))
+                .build(&mut ctx.world, &mut ctx.graphics);
+            ctx.world
+                .write_storage::<Draggable>()
+                .insert(icon_id, Draggable)
+                .unwrap();
+            self.entities.push(icon_id);
+        }
+
+        None
+    }
+
+    fn on_stop(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+        if let Err(err) = ctx.world.delete_entities(&self.entities) {
+            panic!(err);
+        }
+
+        self.entities.clear();
+        self.slots.clear();
+
+        None
+    }
+
+    fn on_update(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+        let mut drops = vec![];
+
+        ctx.world.exec(|widget_events: Read<'_, WidgetEvents>| {
+            for ev in widget_events.read(&mut self.widget_event_reader) {
+                if let WidgetEventKind::Dropped { target } = ev.kind {
+                    drops.push((ev.node_id, target));
+                }
+            }
+        });
+
+        for (node_id, target) in drops {
+            let target_node_id = self
+                .slots
+                .iter()
+                .find(|(slot_id, _)| *slot_id == target)
+                .map(|(_, slot_node_id)| *slot_node_id);
+
+            if let Some(target_node_id) = target_node_id {
+                if let Err(err) = reparent_widget(&ctx.world, node_id, target_node_id) {
+                    println!("Failed to move icon: {}", err);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let app = rengine::AppBuilder::new()
+        .title("Drag and Drop Example")
+        .size(640, 480)
+        .background_color([0.3, 0.4, 0.5, 1.0])
+        .init_scene(Intro)
+        .build()?;
+
+    app.run()?;
+
+    Ok(())
+}
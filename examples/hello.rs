@@ -18,6 +18,7 @@ use std::error::Error;
 use std::fmt;
 
 const BLOCK_TEX_PATH: &str = "examples/block.png";
+const BLOCK_ATLAS_PATH: &str = "examples/block.toml";
 
 struct EventReaderSystem;
 
@@ -126,28 +127,15 @@ impl Scene for Game {
         );
 
         // Test Pseudocube
-        let tex = GlTexture::from_bundle(
-            ctx.world
-                .write_resource::<GlTextureAssets>()
-                .load_texture(&mut ctx.graphics.factory_mut(), BLOCK_TEX_PATH),
-        );
-        let tex_rects = {
-            let tex_rect = tex.source_rect();
-            let back_rect = tex_rect.sub_rect([0, 0], [16, 16]);
-            let front_rect = tex_rect.sub_rect([16, 0], [16, 16]);
-            let left_rect = tex_rect.sub_rect([32, 0], [16, 16]);
-            let right_rect = tex_rect.sub_rect([0, 16], [16, 16]);
-            let bottom_rect = tex_rect.sub_rect([16, 16], [16, 16]);
-            let top_rect = tex_rect.sub_rect([32, 16], [16, 16]);
-            [
-                back_rect,
-                front_rect,
-                left_rect,
-                right_rect,
-                bottom_rect,
-                top_rect,
-            ]
-        };
+        let atlas = ctx
+            .world
+            .write_resource::<GlTextureAssets>()
+            .load_atlas(ctx.graphics.factory_mut(), BLOCK_TEX_PATH, BLOCK_ATLAS_PATH)
+            .expect("Failed to load block atlas");
+        let tex = atlas.texture().clone();
+        let tex_rects = atlas
+            .cube_rects(["back", "front", "left", "right", "bottom", "top"])
+            .expect("Block atlas is missing sides");
 
         self.entities.push(create_block(
             ctx,
@@ -7,5 +7,5 @@ print('Init Skelly Mod')
 --]]
 
 function on_init()
-  GAME:spawn_soldier('skelly:soldier:skelly_soldier', { position = { 0.5, 8.0 + 0.5, 15.5 } });
+  GAME:spawn_soldier('skelly:soldier:skelly_soldier', { position = { 0.5, 8.0, 15.5 } });
 end
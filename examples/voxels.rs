@@ -1,6 +1,6 @@
 extern crate rengine;
 
-use std::{borrow::Cow, error::Error};
+use std::{borrow::Cow, error::Error, fs::File, io::BufReader};
 
 use log::trace;
 use serde::Deserialize;
@@ -12,32 +12,48 @@ use rengine::camera::{
     OrbitalCameraControlSystem, SlideCamera, SlideCameraControlSystem,
 };
 use rengine::colors::WHITE;
-use rengine::comp::{GlTexture, MeshBuilder, Transform};
+use rengine::comp::{
+    Easing, GlTexture, MeshBuilder, Repeat, Tag, TagQuery, TexRect, Transform, Tween, Tweens,
+};
 use rengine::glm;
 use rengine::glutin::dpi::PhysicalPosition;
 use rengine::gui::text::TextBatch;
+use rengine::gui::{
+    HoveredWidget, ReadWidgetEvents, WidgetEventKind, WidgetEventReader, WidgetEvents,
+};
 use rengine::metrics::{builtin_metrics::*, DataPoint, MetricAggregate, MetricHub};
-use rengine::modding::{Mods, SceneHook, ScriptChannel};
+use rengine::modding::{Mods, SceneHook, ScriptChannel, ScriptCmd};
 use rengine::nalgebra::{Point3, Vector3};
 use rengine::option::lift2;
 use rengine::render::{create_light, Gizmo, GlossMaterial, Material, PointLight};
-use rengine::res::{DeltaTime, DeviceDimensions, TextureAssets};
+use rengine::res::{DeltaTime, DeviceDimensions, Rng, TextureAssets, ViewPort};
 use rengine::rlua::{UserData, UserDataMethods};
 use rengine::scripting;
 use rengine::scripting::prelude::*;
 use rengine::specs::prelude::*;
-use rengine::sprite::{Billboard, BillboardSystem};
+use rengine::sprite::{Billboard, BillboardLod, BillboardState, BillboardSystem};
+use rengine::sys::DebugRender;
 use rengine::util::FpsCounter;
 use rengine::voxel::{
-    raycast_from_camera, voxel_to_chunk, ChunkControl, ChunkCoord, ChunkMapping, ChunkUpkeepSystem,
-    DeformedBoxGen, VoxelArrayChunk, VoxelChunk, VoxelCoord, VoxelData, CHUNK_DIM8,
+    raycast_from_camera, voxel_to_chunk, AsyncChunkUpkeepSystem, ChunkControl, ChunkCoord,
+    ChunkMapping, ChunkMeshPending, DeformedBoxGen, RemeshFocus, VoxelArrayChunk, VoxelChunk,
+    VoxelCoord, VoxelData, CHUNK_DIM8,
 };
 use rengine::{AppBuilder, Context, GraphicContext, Scene, Trans};
 
 const BLOCK_TEX_PATH: &str = "examples/block.png";
+const BLOCK_ATLAS_PATH: &str = "examples/block.toml";
+const SKELLY_TEX_PATH: &str = "examples/skelly.png";
+const SKELLY_ATLAS_PATH: &str = "examples/skelly.toml";
+const PYRAMID_OBJ_PATH: &str = "examples/pyramid.obj";
+/// Shows the 1% low next to the average in the FPS counter.
+const SHOW_ONE_PERCENT_LOW: bool = true;
+const FPS_COUNTER_TAG: &str = "fps_counter";
+/// Seed of the jitter deforming the voxel meshes.
+const VOXEL_SEED: u64 = 0;
 type TileVoxelCtrl = ChunkControl<TileVoxel, VoxelArrayChunk<TileVoxel>>;
 type TileVoxelChunk = VoxelArrayChunk<TileVoxel>;
-type TileUpkeepSystem = ChunkUpkeepSystem<TileVoxel, TileVoxelChunk, DeformedBoxGen>;
+type TileUpkeepSystem = AsyncChunkUpkeepSystem<TileVoxel, TileVoxelChunk, DeformedBoxGen>;
 const EMPTY_TILE: u16 = 0;
 type CameraData<'a> = (
     Read<'a, ActiveCamera>,
@@ -92,7 +108,7 @@ fn create_chunk(
                 32.0,
             ),
         })
-        // .with(Gizmo)
+        .with(Gizmo)
         .with(TileVoxelChunk::new(chunk_id.clone()))
         .with(Transform::new().with_position([
             chunk_id.i as f32 * CHUNK_DIM8 as f32,
@@ -108,28 +124,59 @@ fn create_chunk(
     entity
 }
 
+fn create_model<V: Into<glm::Vec3>>(
+    world: &mut World,
+    graphics: &mut GraphicContext,
+    path: &str,
+    pos: V,
+    tex: GlTexture,
+) -> rengine::Result<Entity> {
+    let mesh = MeshBuilder::from_obj(BufReader::new(File::open(path)?))?.build(graphics);
+
+    Ok(world
+        .create_entity()
+        .with(Material::Gloss {
+            texture: tex,
+            material: GlossMaterial::new(
+                graphics,
+                [0.2, 0.2, 0.2, 1.0],
+                [0.9, 0.6, 0.3, 1.0],
+                [1.0, 1.0, 1.0, 1.0],
+                16.0,
+            ),
+        })
+        .with(mesh)
+        .with(Transform::default().with_position(pos))
+        .build())
+}
+
 fn create_sprite<V: Into<glm::Vec3>>(
     world: &mut World,
     graphics: &mut GraphicContext,
     pos: V,
     tex: GlTexture,
+    rect: TexRect,
 ) -> Entity {
+    let pos = pos.into();
+
+    // Bob up and down
+    let bob = Tween::position(pos, pos + glm::vec3(0.0, 0.25, 0.0), 0.75)
+        .ease(Easing::EaseInOutSine)
+        .repeat(Repeat::PingPong);
+
     world
         .create_entity()
         .with(Material::Basic { texture: tex })
         // .with(Gizmo)
-        .with(Billboard)
+        // Turns around the bottom, so it stays on the ground
+        .with(Billboard::default().with_anchor([0.0, -0.5, 0.0]))
         .with(
             MeshBuilder::new()
-                .quad_with_uvs(
-                    [0.0, 0.0, 0.0],
-                    [1.0, 1.0],
-                    [WHITE, WHITE, WHITE, WHITE],
-                    [[0.0, 0.25], [0.25, 0.25], [0.25, 0.0], [0.0, 0.0]],
-                )
+                .quad_with_rect([0.0, 0.0, 0.0], [1.0, 1.0], [WHITE; 4], rect)
                 .build(graphics),
         )
         .with(Transform::default().with_position(pos))
+        .with(Tweens::from(bob))
         .build()
 }
 
@@ -140,11 +187,12 @@ fn create_script_api(lua: &mut rengine::rlua::Lua, script_channel: ScriptChannel
 
         let spawn_skelly = ctx.create_function(
             move |_, (x, y, z): (rlua::Number, rlua::Number, rlua::Number)| {
-                // let mut sender = script_channel.clone();
                 println!("spawn_skelly({}, {}, {})", x, y, z);
 
-                // sender.send(x as u32);
-                sender.clone().send(x as u32);
+                sender.clone().send(ScriptCmd::SpawnEntity {
+                    proto: "skelly".to_string(),
+                    pos: [x as f32, y as f32, z as f32],
+                });
 
                 Ok(())
             },
@@ -157,9 +205,16 @@ fn create_script_api(lua: &mut rengine::rlua::Lua, script_channel: ScriptChannel
     });
 }
 
-fn handle_script_commands(_world: &World, cmds: &[u32]) {
+fn handle_script_commands(_world: &World, cmds: &[ScriptCmd]) {
     for cmd in cmds {
-        println!("handle script commands {}", cmd);
+        match cmd {
+            ScriptCmd::SpawnEntity { proto, pos } => {
+                println!("handle script spawn entity {} at {:?}", proto, pos);
+            }
+            ScriptCmd::PlaySound(path) => {
+                println!("handle script play sound {}", path);
+            }
+        }
     }
 }
 
@@ -238,15 +293,15 @@ impl<'a> UserData for LuaWorld<'a> {
                     .get::<SoldierPrototype>(proto_name.as_str())
                     .unwrap_or_else(|| panic!("No prototype registered called '{}'", proto_name));
 
-                let skelly_tex = GlTexture::from_bundle(
-                    lua_world
-                        .world
-                        .write_resource::<TextureAssets>()
-                        .load_texture(
-                            &mut lua_world.graphics.factory_mut(),
-                            proto.texture_path.as_str(),
-                        ),
-                );
+                let skelly_atlas = lua_world
+                    .world
+                    .write_resource::<TextureAssets>()
+                    .load_atlas(
+                        &mut lua_world.graphics.factory_mut(),
+                        proto.texture_path.as_str(),
+                        SKELLY_ATLAS_PATH,
+                    )
+                    .expect("Failed to load skelly atlas");
 
                 let param_pos: Value = params.get("position")?;
                 let pos: [f32; 3] = if let Value::Table(_) = param_pos {
@@ -259,7 +314,10 @@ impl<'a> UserData for LuaWorld<'a> {
                     &mut lua_world.world,
                     &mut lua_world.graphics,
                     pos,
-                    skelly_tex,
+                    skelly_atlas.texture().clone(),
+                    skelly_atlas
+                        .get("skelly")
+                        .expect("Skelly atlas is missing sprite"),
                 );
 
                 lua_world.entities.push(entity);
@@ -285,6 +343,9 @@ pub struct Game {
     camera_drift_sys: CameraDriftSystem,
     mouse_light_sys: MouseLightSystem,
     cursor_pos: PhysicalPosition,
+    brush: Brush,
+    brush_button: Option<Entity>,
+    widget_event_reader: Option<WidgetEventReader>,
     carve: bool,
     carved: bool,
     add: bool,
@@ -305,6 +366,9 @@ impl Game {
             camera_drift_sys: CameraDriftSystem::new(),
             mouse_light_sys: MouseLightSystem::default(),
             cursor_pos: PhysicalPosition::new(0., 0.),
+            brush: Brush::Add,
+            brush_button: None,
+            widget_event_reader: None,
             carve: false,
             carved: false,
             add: false,
@@ -330,37 +394,32 @@ impl Scene for Game {
         ctx.world.add_resource(TileVoxelCtrl::new());
         ctx.world.add_resource(ChunkMapping::new());
         ctx.world.register::<VoxelArrayChunk<TileVoxel>>();
+        ctx.world.register::<ChunkMeshPending>();
         ctx.world.register::<Billboard>();
+        ctx.world.register::<BillboardState>();
+        ctx.world.add_resource(BillboardLod::new(vec![32.0, 64.0]));
 
         // Load Texture
-        let tex = GlTexture::from_bundle(
-            ctx.world
-                .write_resource::<TextureAssets>()
-                .load_texture(&mut ctx.graphics.factory_mut(), BLOCK_TEX_PATH),
+        let atlas = ctx
+            .world
+            .write_resource::<TextureAssets>()
+            .load_atlas(ctx.graphics.factory_mut(), BLOCK_TEX_PATH, BLOCK_ATLAS_PATH)
+            .expect("Failed to load block atlas");
+        let tex = atlas.texture().clone();
+        let tex_rects = atlas
+            .cube_rects(["back", "front", "left", "right", "bottom", "top"])
+            .expect("Block atlas is missing sides");
+
+        // Setup system, meshing chunks on background threads,
+        // spreading bulk edits over a few frames
+        self.chunk_upkeep_sys = Some(
+            TileUpkeepSystem::with_workers(
+                DeformedBoxGen::with_seed(0.1, VOXEL_SEED, tex_rects),
+                2,
+            )
+            .with_remesh_budget(4),
         );
 
-        // Block Texture
-        let tex_rects = {
-            let tex_rect = tex.source_rect();
-            let back_rect = tex_rect.sub_rect([0, 0], [16, 16]);
-            let front_rect = tex_rect.sub_rect([16, 0], [16, 16]);
-            let left_rect = tex_rect.sub_rect([32, 0], [16, 16]);
-            let right_rect = tex_rect.sub_rect([0, 16], [16, 16]);
-            let bottom_rect = tex_rect.sub_rect([16, 16], [16, 16]);
-            let top_rect = tex_rect.sub_rect([32, 16], [16, 16]);
-            [
-                back_rect,
-                front_rect,
-                left_rect,
-                right_rect,
-                bottom_rect,
-                top_rect,
-            ]
-        };
-
-        // Setup system
-        self.chunk_upkeep_sys = Some(TileUpkeepSystem::new(DeformedBoxGen::new(0.1, tex_rects)));
-
         // Create Chunks
         for x in 0..2 {
             for y in 0..2 {
@@ -438,31 +497,59 @@ impl Scene for Game {
         );
 
         // Create Sprites
-        let _default_texture = GlTexture::from_bundle(
+        let default_texture = GlTexture::from_bundle(
             ctx.world
                 .write_resource::<TextureAssets>()
                 .default_texture(&mut ctx.graphics.factory_mut()),
         );
-        let skelly_tex = GlTexture::from_bundle(
-            ctx.world
-                .write_resource::<TextureAssets>()
-                .load_texture(&mut ctx.graphics.factory_mut(), "examples/skelly.png"),
-        );
+        let skelly_atlas = ctx
+            .world
+            .write_resource::<TextureAssets>()
+            .load_atlas(
+                ctx.graphics.factory_mut(),
+                SKELLY_TEX_PATH,
+                SKELLY_ATLAS_PATH,
+            )
+            .expect("Failed to load skelly atlas");
+        let skelly_rect = skelly_atlas
+            .get("skelly")
+            .expect("Skelly atlas is missing sprite");
 
         for x in 1..5 {
             for z in 1..5 {
                 self.entities.push(create_sprite(
                     &mut ctx.world,
                     &mut ctx.graphics,
-                    [x as f32 * 2.5, 8.0 + 0.5, z as f32 * 2.5],
-                    skelly_tex.clone(),
+                    [x as f32 * 2.5, 8.0, z as f32 * 2.5],
+                    skelly_atlas.texture().clone(),
+                    skelly_rect.clone(),
                 ));
             }
         }
 
+        // Model loaded from disk
+        match create_model(
+            &mut ctx.world,
+            &mut ctx.graphics,
+            PYRAMID_OBJ_PATH,
+            [-3.0, 8.0, 8.0],
+            default_texture,
+        ) {
+            Ok(entity) => self.entities.push(entity),
+            Err(err) => println!("Failed to load model: {}", err),
+        }
+
         // FPS Counter
-        self.entities
-            .push(rengine::util::create_fps_counter_widget(&mut ctx.world));
+        let fps_counter = rengine::util::create_shadowed_fps_counter_widget(
+            &mut ctx.world,
+            rengine::gui::Anchor::TopRight,
+            Some(rengine::gui::text::TextShadow::default()),
+        );
+        ctx.world
+            .write_storage::<Tag>()
+            .insert(fps_counter, Tag::new(FPS_COUNTER_TAG))
+            .expect("Failed to tag FPS counter");
+        self.entities.push(fps_counter);
 
         // Load Mod Meta
         ctx.world.exec(|mut mods: WriteExpect<Mods>| {
@@ -480,6 +567,7 @@ impl Scene for Game {
         self.mods
             .load_mods()
             .expect("game state error loading mods");
+        self.mods.set_rng(&ctx.world.read_resource::<Rng>());
         self.mods
             .data_stage()
             .expect("game state error during data stage");
@@ -545,12 +633,17 @@ impl Scene for Game {
             self.entities.push(btn_group_entity);
 
             let (btn_entity, _btn_id) = widgets::Button::text("Brush")
+                .tag("Brush")
                 .child_of(btn_group_node_id)
                 .size(64., 64.)
                 .background_image("examples/ui.png")
                 .background_src_rect([0, 0], [32, 32])
                 .build(&mut ctx.world, &mut ctx.graphics);
             self.entities.push(btn_entity);
+
+            self.brush_button = Some(btn_entity);
+            self.widget_event_reader =
+                Some(ctx.world.write_resource::<WidgetEvents>().register_reader());
         }
 
         // Execute mod start.
@@ -560,7 +653,12 @@ impl Scene for Game {
         let cmds =
             ctx.world.exec(
                 |mut mods: WriteExpect<Mods>| match mods.scene_hook(SceneHook::Start) {
-                    Ok(out_cmds) => out_cmds.unwrap_or_else(Vec::new),
+                    Ok((status, out_cmds)) => {
+                        if !status.is_delivered() {
+                            println!("{:?}", status);
+                        }
+                        out_cmds.unwrap_or_else(Vec::new)
+                    }
                     Err(e) => {
                         println!("{:?}", e);
                         vec![]
@@ -577,7 +675,12 @@ impl Scene for Game {
         let cmds =
             ctx.world.exec(
                 |mut mods: WriteExpect<Mods>| match mods.scene_hook(SceneHook::Stop) {
-                    Ok(out_cmds) => out_cmds.unwrap_or_else(Vec::new),
+                    Ok((status, out_cmds)) => {
+                        if !status.is_delivered() {
+                            println!("{:?}", status);
+                        }
+                        out_cmds.unwrap_or_else(Vec::new)
+                    }
                     Err(e) => {
                         println!("{:?}", e);
                         vec![]
@@ -613,8 +716,6 @@ impl Scene for Game {
         use glutin::VirtualKeyCode;
         use glutin::WindowEvent::*;
 
-        rengine::gui::GuiLayoutSystem.run_now(&ctx.world.res);
-
         if let WindowEvent { event, .. } = ev {
             match event {
                 CursorMoved { position, .. } => {
@@ -629,14 +730,30 @@ impl Scene for Game {
                             self.carved = false;
                         }
                     } else if button == &MouseButton::Left {
-                        self.add = state == &ElementState::Pressed && !self.added;
+                        // Clicks on widgets don't reach the voxels
+                        let over_widget = ctx.world.read_resource::<HoveredWidget>().has_widget();
+                        let pressed = state == &ElementState::Pressed && !over_widget;
+
+                        match self.brush {
+                            Brush::Add => self.add = pressed && !self.added,
+                            Brush::Carve => self.carve = pressed && !self.carved,
+                        }
 
                         if state == &ElementState::Released {
                             self.added = false;
+                            self.carved = false;
                         }
                     }
                 }
                 KeyboardInput { input, .. } => {
+                    // Chunk wireframes
+                    if input.virtual_keycode == Some(VirtualKeyCode::F3)
+                        && input.state == ElementState::Released
+                    {
+                        let enabled = ctx.world.write_resource::<DebugRender>().toggle();
+                        println!("Debug render: {}", enabled);
+                    }
+
                     if input.virtual_keycode == Some(VirtualKeyCode::F5)
                         && input.state == ElementState::Released
                     {
@@ -651,6 +768,7 @@ impl Scene for Game {
                             metrics.make_time_series(
                                 GRAPHICS_RENDER,
                                 MetricAggregate::Maximum,
+                                None,
                                 &mut timeseries,
                                 0,
                                 length,
@@ -678,6 +796,7 @@ impl Scene for Game {
                             metrics.make_time_series(
                                 GRAPHICS_DRAW_CALLS,
                                 MetricAggregate::Sum,
+                                None,
                                 &mut timeseries,
                                 0,
                                 length,
@@ -704,16 +823,61 @@ impl Scene for Game {
         None
     }
 
+    fn on_resize(&mut self, ctx: &mut Context<'_>, new_size: (u32, u32)) {
+        // Resources are updated before scenes are notified
+        let device_dim = ctx.world.read_resource::<DeviceDimensions>();
+        let physical_size: (u32, u32) = (*device_dim.physical_size()).into();
+        assert_eq!(physical_size, new_size);
+
+        trace!("Resized to {}x{}", new_size.0, new_size.1);
+    }
+
     fn on_update(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+        // Brush button toggles between adding and carving voxels
+        let brush_clicked = match (self.brush_button, self.widget_event_reader.as_mut()) {
+            (Some(button), Some(reader)) => ctx
+                .world
+                .read_resource::<WidgetEvents>()
+                .read_for(reader, button)
+                .any(|kind| *kind == WidgetEventKind::Released),
+            _ => false,
+        };
+        if brush_clicked {
+            self.brush = match self.brush {
+                Brush::Add => Brush::Carve,
+                Brush::Carve => Brush::Add,
+            };
+            println!("Brush: {:?}", self.brush);
+        }
+
         ctx.world.exec(
-            |(dt, mut text_batches, mut fps_counters): (
+            |(entities, dt, tags, mut text_batches, mut fps_counters): (
+                Entities<'_>,
                 Read<DeltaTime>,
+                ReadStorage<'_, Tag>,
                 WriteStorage<'_, TextBatch>,
                 WriteStorage<'_, FpsCounter>,
             )| {
-                for (text, counter) in (&mut text_batches, &mut fps_counters).join() {
-                    counter.add(dt.duration());
-                    text.replace(&format!("FPS: {:.2}", counter.fps()), WHITE);
+                let entity = match TagQuery::find_first(&entities, &tags, FPS_COUNTER_TAG) {
+                    Some(entity) => entity,
+                    None => return,
+                };
+                let widget = lift2(text_batches.get_mut(entity), fps_counters.get_mut(entity));
+
+                if let Some((text, counter)) = widget {
+                    counter.add(dt.unscaled());
+                    if SHOW_ONE_PERCENT_LOW {
+                        text.replace(
+                            &format!(
+                                "FPS: {:.2} (1% low: {:.2})",
+                                counter.fps(),
+                                counter.one_percent_low()
+                            ),
+                            WHITE,
+                        );
+                    } else {
+                        text.replace(&format!("FPS: {:.2}", counter.fps()), WHITE);
+                    }
                 }
             },
         );
@@ -724,6 +888,19 @@ impl Scene for Game {
         self.slide_camera_sys.run_now(&ctx.world.res);
         self.camera_drift_sys.run_now(&ctx.world.res);
 
+        // Remesh chunks nearest the camera first
+        let eye = ctx.world.exec(
+            |(active_camera, cam_views): (Read<'_, ActiveCamera>, ReadStorage<'_, CameraView>)| {
+                active_camera
+                    .camera_entity()
+                    .and_then(|entity| cam_views.get(entity))
+                    .map(|cam_view| cam_view.eye().coords)
+            },
+        );
+        if let Some(eye) = eye {
+            ctx.world.add_resource(RemeshFocus(eye));
+        }
+
         if let Some(ref mut chunk_upkeep_sys) = self.chunk_upkeep_sys {
             chunk_upkeep_sys.run_now(&ctx.world.res);
         }
@@ -812,6 +989,13 @@ impl Scene for Game {
     }
 }
 
+/// Voxel editing performed by the left mouse button.
+#[derive(Debug, Clone, Copy)]
+enum Brush {
+    Add,
+    Carve,
+}
+
 /// Point light entity that follows mouse around.
 struct MouseLight(Entity);
 
@@ -851,6 +1035,7 @@ impl<'a> System<'a> for MouseLightSystem {
             Read<'a, DeviceDimensions>,
             ReadStorage<'a, CameraView>,
             ReadStorage<'a, CameraProjection>,
+            ReadExpect<'a, ViewPort>,
         ),
     );
 
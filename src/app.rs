@@ -1,31 +1,48 @@
 use crate::camera::{
-    ActiveCamera, CameraProjection, CameraResizeSystem, CameraView, DollyCamera, FocusTarget,
-    GridCamera, OrbitalCamera, SlideCamera,
+    ActiveCamera, CameraFovAnimator, CameraProjection, CameraResizeSystem, CameraView, DollyCamera,
+    FocusTarget, GridCamera, OrbitalCamera, SlideCamera,
 };
 use crate::colors;
-use crate::comp::{GlTexture, Mesh, MeshCommandBuffer, MeshUpkeepSystem, Tag, Transform};
-use crate::draw2d::Canvas;
+use crate::comp::{
+    GlTexture, GlobalTransform, Mesh, MeshCommandBuffer, MeshUpkeepSystem, Parent, Tag, TagIndex,
+    TagIndexSystem, Transform, TransformHierarchySystem, TweenEvents, TweenSystem, Tweens, Wiggle,
+    WiggleSystem,
+};
+use crate::draw2d::Canvas2d;
 use crate::errors::*;
 use crate::gfx_types::*;
 use crate::graphics::GraphicContext;
 use crate::gui::{self, text, widgets, DrawGuiSystem, GuiGraph};
+#[cfg(feature = "debug-hot-reload")]
+use crate::hot_reload::HotReload;
 use crate::metrics::MetricHub;
 use crate::modding::Mods;
-use crate::render::{self, ChannelPair, Gizmo, Lights, Material, PointLight};
-use crate::res::{DeltaTime, DeviceDimensions, ViewPort};
+use crate::render::{
+    self, ChannelPair, Gizmo, Lights, Material, Pipeline, PointLight, RenderLayer,
+};
+use crate::res::{
+    DeviceDimensions, DeviceEvent, DeviceEvents, FrameCounter, FrameTimer, Rng, TextureAssets,
+    TextureEvents, TextureHandle, TimeScale, ViewPort,
+};
 use crate::scene::{Scene, SceneStack};
-use crate::sys::DrawSystem;
+use crate::spatial;
+use crate::sys::{
+    DebugRender, DrawSettings, DrawSystem, EntityCommands, EntityUpkeepSystem, TextureLoadSystem,
+    TextureUpkeepSystem,
+};
 use crate::util;
 
-use gfx::traits::FactoryExt;
 use gfx::Device;
 use gfx_glyph::{ab_glyph::FontArc, GlyphBrushBuilder};
 use glutin::{Api, ContextBuilder, EventsLoop, GlProfile, GlRequest, WindowBuilder};
 use log::{error, trace};
+use rayon::ThreadPoolBuilder;
+use shred::Resource;
 use specs::prelude::*;
 
 use std::path::Path;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 const DEFAULT_FONT_DATA: &[u8] = include_bytes!("../resources/fonts/DejaVuSans.ttf");
 
@@ -36,9 +53,13 @@ pub struct App<'comp, 'thread> {
     graphics: GraphicContext,
     world: World,
     dispatcher: Dispatcher<'comp, 'thread>,
+    /// Runs systems one after the other on the main thread.
+    sequential: bool,
     bkg_color: colors::Color,
     initial_scene: Option<Box<dyn Scene>>,
     mods: Option<(&'static str, &'static str)>,
+    max_delta_time: Option<Duration>,
+    delta_smoothing: usize,
 }
 
 impl<'a, 'b> App<'a, 'b> {
@@ -70,21 +91,34 @@ impl<'a, 'b> App<'a, 'b> {
             mut graphics,
             mut world,
             mut dispatcher,
+            sequential,
             initial_scene,
             bkg_color,
             mods,
+            max_delta_time,
+            delta_smoothing,
             ..
         } = self;
 
+        // Engine Resources
+        add_default_resources(&mut world);
+
         // Engine Components
         world.register::<Mesh>();
         world.register::<Transform>();
+        world.register::<Parent>();
+        world.register::<GlobalTransform>();
+        world.register::<Tweens>();
+        world.register::<Wiggle>();
         world.register::<Material>();
+        world.register::<render::MeshInstance>();
         world.register::<PointLight>();
         world.register::<Gizmo>();
+        world.register::<RenderLayer>();
         world.register::<CameraView>();
         world.register::<CameraProjection>();
         world.register::<FocusTarget>();
+        world.register::<CameraFovAnimator>();
         world.register::<OrbitalCamera>();
         world.register::<GridCamera>();
         world.register::<DollyCamera>();
@@ -92,19 +126,29 @@ impl<'a, 'b> App<'a, 'b> {
         world.register::<GlTexture>();
         world.register::<Tag>();
         world.register::<util::FpsCounter>();
+        world.register::<spatial::Bounds>();
 
         // GUI Components
         {
-            world.add_resource(gui::HoveredWidget::default());
-            world.add_resource(gui::PressedWidget::default());
-            world.add_resource(gui::WidgetEvents::new());
             world.register::<gui::GuiMesh>();
             world.register::<gui::BoundsRect>();
             world.register::<gui::Placement>();
+            world.register::<gui::Anchor>();
             world.register::<gui::Pack>();
             world.register::<gui::GlobalPosition>();
             world.register::<gui::Clickable>();
+            world.register::<gui::Focusable>();
+            world.register::<gui::Focused>();
+            world.register::<gui::Draggable>();
+            world.register::<gui::DropTarget>();
             world.register::<gui::ZDepth>();
+            world.register::<gui::Visibility>();
+            world.register::<gui::Enabled>();
+            world.register::<gui::Hidden>();
+            world.register::<gui::AlwaysOnTop>();
+            world.register::<gui::Tooltip>();
+            world.register::<gui::TooltipDelay>();
+            world.register::<widgets::ProgressBar>();
             world.register::<gui::text::TextBatch>();
             world.register::<widgets::Button>();
             world.register::<widgets::Container>();
@@ -114,9 +158,6 @@ impl<'a, 'b> App<'a, 'b> {
         // TODO: Add feature flag to disable metrics for release builds.
         // world.add_resource(MetricHub::default());
 
-        // Event Streams
-        world.add_resource::<Vec<glutin::Event>>(Vec::new());
-
         // Lights
         world.add_resource(Lights::new(&mut graphics, render::MAX_NUM_LIGHTS));
 
@@ -128,18 +169,18 @@ impl<'a, 'b> App<'a, 'b> {
 
         // Graphics Commands to allow allocating resources
         // from systems to draw thread.
-        world.add_resource(MeshCommandBuffer::new());
         let mesh_upkeep = MeshUpkeepSystem;
 
         // Assets
         // TODO: Place in world and allow for loading textures from game without needing factory (operation buffer?)
         let textures = GraphicContext::create_texture_cache();
         world.add_resource(textures);
+        world.register::<TextureHandle>();
 
         // Initial ViewPort Size
         let device_dimensions = match DeviceDimensions::from_window(&graphics.window) {
             Some(dim) => dim,
-            None => return Err(ErrorKind::WindowSize.into()),
+            None => return Err(Error::WindowSize),
         };
 
         // Implementation of Into<(u32, u2)> performs proper rounding
@@ -162,147 +203,38 @@ impl<'a, 'b> App<'a, 'b> {
         world.add_resource(ActiveCamera::new(camera_entity));
 
         // Update Camera on Resize
-        // TODO: message passing to notify systems of events
-        let mut camera_resize_system = CameraResizeSystem::new();
-
-        // Basic render PSO
-        {
-            // Shader program
-            let shader_program = graphics
-                .factory
-                .link_program(
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/basic_150.glslv"
-                    )),
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/basic_150.glslf"
-                    )),
-                )
-                .unwrap();
-
-            // Pipeline State Object
-            let pso = graphics
-                .factory
-                .create_pipeline_from_program(
-                    &shader_program,
-                    gfx::Primitive::TriangleList,
-                    gfx::state::Rasterizer::new_fill().with_cull_back(),
-                    pipe::new(),
-                )
-                .unwrap();
-
-            // Bundle program and pipeline state object together to avoid
-            // lifetime issues with world resources borrowing each other.
-            world.add_resource(PipelineBundle::new(pso, shader_program));
-        }
+        let mut camera_resize_system = CameraResizeSystem::new(&world);
 
-        // Gloss Material PSO
-        {
-            // Shader program
-            let shader_program = graphics
-                .factory
-                .link_program(
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gloss_150.glslv"
-                    )),
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gloss_150.glslf"
-                    )),
-                )
-                .unwrap();
-
-            // Pipeline State Object
-            let pso = graphics
-                .factory
-                .create_pipeline_from_program(
-                    &shader_program,
-                    gfx::Primitive::TriangleList,
-                    gfx::state::Rasterizer::new_fill().with_cull_back(),
-                    gloss_pipe::new(),
-                )
-                .expect("Failed to link gloss material shader");
-
-            // Bundle program and pipeline state object together to avoid
-            // lifetime issues with world resources borrowing each other.
-            world.add_resource(PipelineBundle::new(pso, shader_program));
-        }
+        // View port and aspect ratio policies apply from the first frame
+        let initial_size = DeviceEvent::resized(&world.read_resource::<DeviceDimensions>());
+        world
+            .write_resource::<DeviceEvents>()
+            .single_write(initial_size);
 
-        // Gizmo Wireframe PSO
-        {
-            let gizmo_shader = graphics
-                .factory
-                .link_program(
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gizmo_150.glslv"
-                    )),
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gizmo_150.glslf"
-                    )),
-                )
-                .unwrap();
-
-            let mut fillmode = gfx::state::Rasterizer::new_fill();
-            fillmode.method = gfx::state::RasterMethod::Line(1); // Render lines
-            let gizmo_pso = graphics
-                .factory
-                .create_pipeline_from_program(
-                    &gizmo_shader,
-                    gfx::Primitive::TriangleList,
-                    fillmode,
-                    gizmo_pipe::new(),
-                )
-                .expect("Failed to link wireframe material shader");
-
-            world.add_resource(PipelineBundle::new(gizmo_pso, gizmo_shader));
+        // Pipeline State Objects
+        for pipeline in Pipeline::ALL.iter() {
+            pipeline.create(graphics.factory_mut(), &mut world)?;
         }
 
-        // GUI PSO
-        {
-            let gui_shader = graphics
-                .factory
-                .link_program(
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gui_150.glslv"
-                    )),
-                    include_bytes!(concat!(
-                        env!("CARGO_MANIFEST_DIR"),
-                        "/src/shaders/gui_150.glslf"
-                    )),
-                )
-                .unwrap();
-
-            // GUI PSO
-            let pso = graphics
-                .factory
-                .create_pipeline_from_program(
-                    &gui_shader,
-                    gfx::Primitive::TriangleList,
-                    // TODO: Currently we're drawing quads backwards
-                    gfx::state::Rasterizer::new_fill().with_cull_back(),
-                    gui_pipe::new(),
-                )
-                .unwrap();
-
-            // Bundle program and pipeline state object together to avoid
-            // lifetime issues with world resources borrowing each other.
-            world.add_resource(PipelineBundle::new(pso, gui_shader));
-        }
+        // Reload textures and shaders when their files change
+        #[cfg(feature = "debug-hot-reload")]
+        let mut hot_reload = HotReload::new();
 
         // Encoder
         let mut channel = ChannelPair::new();
         channel.send_block(graphics.create_encoder())?;
 
+        // Lights
+        let mut point_light_system = render::PointLightSystem::new();
+
+        // World matrices of entities attached to parents
+        let mut transform_hierarchy_system = TransformHierarchySystem::new();
+
         // Renderer
         // TODO: Consider having a `Renderer` trait since it's being treated differently than other systems
         let mut renderer = DrawSystem::new(
             channel.clone(),
+            graphics.factory.clone(),
             graphics.render_target.clone(),
             graphics.depth_stencil.clone(),
         );
@@ -321,11 +253,18 @@ impl<'a, 'b> App<'a, 'b> {
         // Gui Rendering
         let mut gui_renderer = DrawGuiSystem::new(
             channel.clone(),
-            Canvas::new(&mut graphics, physical_w as u16, physical_h as u16).unwrap(),
+            graphics.factory.clone(),
             graphics.render_target.clone(),
             graphics.depth_stencil.clone(),
         );
 
+        // Gui Input
+        let mut gui_mouse_system = gui::GuiMouseMoveSystem::new();
+        let mut gui_keyboard_system = gui::GuiKeyboardSystem::new();
+        let mut gui_drag_system = gui::GuiDragSystem::new();
+        let mut gui_click_system = gui::GuiClickSystem::new(&world);
+        let mut gui_tooltip_system = gui::GuiTooltipSystem::new();
+
         // Modding
         if let Some((lib_name, mod_path)) = mods {
             let path = Path::new(mod_path);
@@ -338,6 +277,9 @@ impl<'a, 'b> App<'a, 'b> {
             world.add_resource(Mods::new(lib_name, path));
         }
 
+        // Entity lookup by tag
+        let mut tag_index_system = TagIndexSystem::new(&world);
+
         // Scenes
         let mut scene_stack = SceneStack::new();
 
@@ -345,12 +287,13 @@ impl<'a, 'b> App<'a, 'b> {
             Some(scene_box) => {
                 scene_stack.push_box(scene_box);
             }
-            None => return Err(ErrorKind::NoInitialScene.into()),
+            None => return Err(Error::NoInitialScene),
         }
 
         // Loop control
         let mut running = true;
         let mut last_time = Instant::now();
+        let mut frame_timer = FrameTimer::new(max_delta_time, delta_smoothing);
 
         // Buffer to copy events into, to avoid having to borrow
         // event stream from world.
@@ -359,7 +302,8 @@ impl<'a, 'b> App<'a, 'b> {
         while running {
             // Time elapsed since last iteration
             let new_time = Instant::now();
-            let delta_time = DeltaTime(new_time.duration_since(last_time));
+            let time_scale = *world.read_resource::<TimeScale>();
+            let delta_time = frame_timer.tick(new_time.duration_since(last_time), time_scale);
             last_time = new_time;
 
             // Prepare requested scene
@@ -367,13 +311,17 @@ impl<'a, 'b> App<'a, 'b> {
 
             // Prepare world with frame scoped resources
             world.add_resource(delta_time);
+            world.write_resource::<FrameCounter>().incr();
+
+            // Physical size of the window, when it was resized this frame
+            let mut new_size = None;
 
             // Drain user input events
             events_loop.poll_events(|event| {
                 events.push(event.clone());
 
                 // Global event handling
-                match event {
+                let resized = match event {
                     WindowEvent {
                         event: glutin::WindowEvent::CloseRequested,
                         ..
@@ -386,40 +334,66 @@ impl<'a, 'b> App<'a, 'b> {
                         if let Err(err) = scene_stack.clear(&mut world, &mut graphics) {
                             error!("{:?}", err);
                         }
+
+                        None
                     }
                     WindowEvent {
                         event: glutin::WindowEvent::Resized(logical_size),
                         ..
                     } => {
-                        // Coordinates use physical size
                         let dpi_factor = graphics.window.window().get_hidpi_factor();
-                        let physical_size = logical_size.to_physical(dpi_factor);
-                        // println!("dpi_factor={} {:?} {:?}", dpi_factor, physical_size, logical_size);
-
-                        // Required by some platforms
-                        graphics.window.resize(physical_size);
-
-                        // Update dimensions of frame buffer targets
-                        graphics.update_views();
-
-                        // Ensure no dangling shared references
-                        renderer.render_target = graphics.render_target.clone();
-                        renderer.depth_target = graphics.depth_stencil.clone();
-                        text_renderer.render_target = graphics.render_target.clone();
-                        text_renderer.depth_target = graphics.depth_stencil.clone();
-                        gui_renderer.render_target = graphics.render_target.clone();
-                        gui_renderer.depth_target = graphics.depth_stencil.clone();
-
-                        // Update view port/scissor rectangle for rendering systems
-                        let (win_w, win_h): (u32, u32) = physical_size.into();
-                        let vp = ViewPort::new((win_w as u16, win_h as u16));
-                        world.add_resource(vp);
-
-                        // Update cameras
-                        world.add_resource(DeviceDimensions::new(dpi_factor, logical_size));
-                        camera_resize_system.run_now(&world.res);
+                        Some((dpi_factor, logical_size))
                     }
-                    _ => (),
+                    WindowEvent {
+                        event: glutin::WindowEvent::HiDpiFactorChanged(dpi_factor),
+                        ..
+                    } => {
+                        world
+                            .write_resource::<DeviceEvents>()
+                            .single_write(DeviceEvent::DpiChanged { dpi_factor });
+
+                        // Logical size stays the same, but the frame
+                        // buffer must match the new physical size.
+                        graphics
+                            .window
+                            .window()
+                            .get_inner_size()
+                            .map(|logical_size| (dpi_factor, logical_size))
+                    }
+                    _ => None,
+                };
+
+                if let Some((dpi_factor, logical_size)) = resized {
+                    // Coordinates use physical size
+                    let device_dimensions = DeviceDimensions::new(dpi_factor, logical_size);
+                    let physical_size = device_dimensions.physical_size;
+
+                    // Required by some platforms
+                    graphics.window.resize(physical_size);
+
+                    // Update dimensions of frame buffer targets
+                    graphics.update_views();
+
+                    // Ensure no dangling shared references
+                    renderer.render_target = graphics.render_target.clone();
+                    renderer.depth_target = graphics.depth_stencil.clone();
+                    text_renderer.render_target = graphics.render_target.clone();
+                    text_renderer.depth_target = graphics.depth_stencil.clone();
+                    gui_renderer.render_target = graphics.render_target.clone();
+                    gui_renderer.depth_target = graphics.depth_stencil.clone();
+
+                    // Update view port/scissor rectangle for rendering systems
+                    let (win_w, win_h): (u32, u32) = physical_size.into();
+                    let vp = ViewPort::new((win_w as u16, win_h as u16));
+                    world.add_resource(vp);
+
+                    // Notify systems, such as the camera resize system
+                    world
+                        .write_resource::<DeviceEvents>()
+                        .single_write(DeviceEvent::resized(&device_dimensions));
+                    world.add_resource(device_dimensions);
+
+                    new_size = Some((win_w, win_h));
                 }
 
                 // Scene event handling
@@ -430,25 +404,90 @@ impl<'a, 'b> App<'a, 'b> {
                 event_stream.extend(events.drain(..));
             });
 
+            // Cameras and view port follow resize events before
+            // scenes are told about them
+            camera_resize_system.run_now(&world.res);
+
+            if let Some(new_size) = new_size {
+                // Anchored widgets depend on the device size
+                gui::invalidate_layout(&world);
+
+                scene_stack.dispatch_resize(&mut world, &mut graphics, new_size);
+            }
+
+            // Widget input, so scenes can query clicks during update.
+            // Drags go first, so a press that became a drag isn't a click.
+            gui_drag_system.run_now(&world.res);
+            gui_mouse_system.run_now(&world.res);
+            gui_keyboard_system.run_now(&world.res);
+            gui_click_system.run_now(&world.res);
+            gui_tooltip_system.maintain(&mut world, &mut graphics);
+
+            // Tags of entities created or deleted last frame
+            tag_index_system.run_now(&world.res);
+
             // Scene Update
             scene_stack.dispatch_update(&mut world, &mut graphics);
 
             // Pre-render
             {
                 let mut encoder = channel.recv_block()?;
-                encoder.clear(&graphics.render_target, bkg_color);
+
+                // Bars of a letter boxed camera replace the background.
+                let clear_color = world
+                    .read_resource::<ViewPort>()
+                    .bar_color()
+                    .unwrap_or(bkg_color);
+                encoder.clear(&graphics.render_target, clear_color);
                 encoder.clear_depth(&graphics.depth_stencil, 1.0);
 
+                // Textures loaded with mipmaps since the last frame
+                world
+                    .write_resource::<TextureAssets>()
+                    .generate_mipmaps(&mut encoder);
+
                 // Send encoder back
                 channel.send_block(encoder)?;
             }
 
             // Run systems
-            dispatcher.dispatch(&world.res);
+            if sequential {
+                dispatcher.dispatch_seq(&world.res);
+            } else {
+                dispatcher.dispatch(&world.res);
+            }
+
+            // Animate transforms
+            TweenSystem.run_now(&world.res);
+            WiggleSystem.run_now(&world.res);
+
+            // World matrices of entities attached to parents
+            transform_hierarchy_system.run_now(&world.res);
+
+            // Index entities for proximity queries in the next update
+            spatial::SpatialGridSystem.run_now(&world.res);
+
+            // Rebuild widgets that changed during the update
+            widgets::ProgressBarSystem.run_now(&world.res);
 
             // Allocate Graphic Resources
             mesh_upkeep.maintain(&mut graphics, world.system_data());
 
+            // Upload textures decoded in the background
+            world
+                .write_resource::<TextureAssets>()
+                .process_pending(graphics.factory_mut());
+            TextureLoadSystem.run_now(&world.res);
+
+            // Layout Gui, only when the graph has changed
+            if world.read_resource::<gui::LayoutDirty>().is_dirty() {
+                gui::GuiSortSystem.run_now(&world.res);
+                gui::GuiLayoutSystem.run_now(&world.res);
+            }
+
+            // Move lights to their entities
+            point_light_system.run_now(&world.res);
+
             // Render Components
             renderer.run_now(&world.res);
 
@@ -461,6 +500,10 @@ impl<'a, 'b> App<'a, 'b> {
             // Commit Render
             {
                 let mut encoder = channel.recv_block()?;
+
+                #[cfg(feature = "debug-hot-reload")]
+                hot_reload.update(&mut world, graphics.factory_mut(), &mut encoder);
+
                 encoder.flush(&mut graphics.device);
                 graphics.window.swap_buffers().unwrap();
 
@@ -468,6 +511,12 @@ impl<'a, 'b> App<'a, 'b> {
                 channel.send_block(encoder)?;
             }
 
+            // Evict textures that are no longer used
+            TextureUpkeepSystem.run_now(&world.res);
+
+            // Spawn and despawn entities queued during the frame
+            EntityUpkeepSystem.run_now(&world.res);
+
             // Deallocate
             graphics.device.cleanup();
             world.maintain();
@@ -485,6 +534,53 @@ impl<'a, 'b> App<'a, 'b> {
     }
 }
 
+/// Adds the resources used by the engine's systems, leaving
+/// the ones already added by the application in place.
+///
+/// Resources given to `AppBuilder::resource` are added to
+/// the world first, so they replace the engine defaults.
+fn add_default_resources(world: &mut World) {
+    // GUI
+    add_default(world, gui::HoveredWidget::default);
+    add_default(world, gui::PressedWidget::default);
+    add_default(world, gui::FocusedWidget::default);
+    add_default(world, gui::DraggedWidget::default);
+    add_default(world, gui::WidgetEvents::new);
+    add_default(world, gui::ButtonClicks::default);
+    add_default(world, gui::DisabledTint::default);
+    add_default(world, text::FontAssets::default);
+
+    // Event Streams
+    add_default(world, Vec::<glutin::Event>::new);
+    add_default(world, TweenEvents::new);
+    add_default(world, DeviceEvents::new);
+
+    // Gameplay
+    add_default(world, Rng::default);
+    add_default(world, FrameCounter::default);
+    add_default(world, EntityCommands::new);
+    add_default(world, TimeScale::default);
+    add_default(world, TagIndex::default);
+    add_default(world, spatial::SpatialGrid::default);
+
+    // Rendering
+    add_default(world, DrawSettings::default);
+    add_default(world, DebugRender::default);
+    add_default(world, Canvas2d::new);
+    add_default(world, MeshCommandBuffer::new);
+    add_default(world, TextureEvents::new);
+}
+
+fn add_default<R, F>(world: &mut World, create: F)
+where
+    R: Resource,
+    F: FnOnce() -> R,
+{
+    if !world.res.has_value::<R>() {
+        world.add_resource(create());
+    }
+}
+
 /// Builder for application
 ///
 /// Usage:
@@ -504,6 +600,11 @@ pub struct AppBuilder {
     bkg_color: colors::Color,
     initial_scene: Option<Box<dyn Scene>>,
     mods: Option<(&'static str, &'static str)>,
+    resources: Vec<Box<dyn FnOnce(&mut World)>>,
+    num_threads: Option<usize>,
+    system_batches: Vec<Vec<BatchedSystem>>,
+    max_delta_time: Option<Duration>,
+    delta_smoothing: usize,
 }
 
 impl Default for AppBuilder {
@@ -514,6 +615,11 @@ impl Default for AppBuilder {
             bkg_color: colors::BLACK,
             initial_scene: None,
             mods: None,
+            resources: Vec::new(),
+            num_threads: None,
+            system_batches: Vec::new(),
+            max_delta_time: None,
+            delta_smoothing: 1,
         }
     }
 }
@@ -559,6 +665,132 @@ impl AppBuilder {
         self
     }
 
+    /// Adds an application level resource to the world,
+    /// before the initial scene is started.
+    ///
+    /// ## Example
+    ///
+    /// ```ignore
+    /// struct GameConfig {
+    ///     level: u32,
+    /// }
+    ///
+    /// impl Scene for Game {
+    ///     fn on_start(&mut self, ctx: &mut Context<'_>) -> Option<Trans> {
+    ///         let level = ctx.world.read_resource::<GameConfig>().level;
+    ///         None
+    ///     }
+    /// }
+    ///
+    /// let app = rengine::AppBuilder::new()
+    ///     .resource(GameConfig { level: 1 })
+    ///     .init_scene(Game)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[inline]
+    pub fn resource<R>(mut self, resource: R) -> Self
+    where
+        R: Resource,
+    {
+        self.resources.push(Box::new(move |world: &mut World| {
+            world.add_resource(resource)
+        }));
+        self
+    }
+
+    /// Number of threads in the pool that runs the dispatcher's systems.
+    ///
+    /// With a single thread, systems are run one after the other on the
+    /// main thread, in the order they were added. When not given, the
+    /// dispatcher uses its default thread pool.
+    #[inline]
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Adds systems to the dispatcher as a batch, which may run
+    /// in parallel with each other.
+    ///
+    /// Systems in later batches only run after the whole batch
+    /// has finished. System names must be unique.
+    ///
+    /// ## Example
+    ///
+    /// ```ignore
+    /// let app = rengine::AppBuilder::new()
+    ///     .num_threads(4)
+    ///     .system_batch(vec![
+    ///         BatchedSystem::new(PhysicsSystem, "physics"),
+    ///         BatchedSystem::new(AiSystem, "ai"),
+    ///     ])
+    ///     .system_batch(vec![BatchedSystem::new(AnimationSystem, "animation")])
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[inline]
+    pub fn system_batch(mut self, systems: Vec<BatchedSystem>) -> Self {
+        self.system_batches.push(systems);
+        self
+    }
+
+    /// Longest frame duration given to systems as `DeltaTime`.
+    ///
+    /// Longer frames, like after the application was paused by
+    /// the operating system, are clamped to it so physics and
+    /// animation don't jump. When not given, frames aren't capped.
+    #[inline]
+    pub fn max_delta_time(mut self, max_delta_time: Duration) -> Self {
+        self.max_delta_time = Some(max_delta_time);
+        self
+    }
+
+    /// Number of frames averaged into the `DeltaTime` given to
+    /// systems, so uneven frame times don't make movement stutter.
+    ///
+    /// Frames are capped by `max_delta_time` before they're
+    /// averaged. Defaults to one frame, which is no smoothing.
+    #[inline]
+    pub fn delta_smoothing(mut self, frames: usize) -> Self {
+        self.delta_smoothing = frames;
+        self
+    }
+
+    /// Creates the dispatcher, containing the systems
+    /// registered with the builder.
+    fn create_dispatcher<'a, 'b>(&mut self) -> Result<Dispatcher<'a, 'b>> {
+        let mut builder = DispatcherBuilder::new();
+
+        if let Some(num_threads) = self.num_threads.filter(|n| *n > 1) {
+            let pool = ThreadPoolBuilder::new().num_threads(num_threads).build()?;
+            builder.add_pool(Arc::new(pool));
+        }
+
+        for batch in self.system_batches.drain(..) {
+            for system in batch {
+                (system.add)(&mut builder);
+            }
+
+            // Following batches wait for this one to finish.
+            builder.add_barrier();
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Creates the world, containing the resources
+    /// registered with the builder.
+    fn create_world(&mut self) -> World {
+        let mut world = World::new();
+
+        for add_resource in self.resources.drain(..) {
+            add_resource(&mut world);
+        }
+
+        world
+    }
+
     /// Consumes the builder and creates the application
     pub fn build<'a, 'b>(mut self) -> Result<App<'a, 'b>> {
         // Event Loop
@@ -598,10 +830,11 @@ impl AppBuilder {
         };
 
         // World
-        let world = World::new();
+        let world = self.create_world();
 
         // Dispatcher
-        let dispatcher = DispatcherBuilder::new().build();
+        let dispatcher = self.create_dispatcher()?;
+        let sequential = self.num_threads == Some(1);
 
         // Initial Scene
         let initial_scene = self.initial_scene.take();
@@ -611,9 +844,106 @@ impl AppBuilder {
             graphics,
             world,
             dispatcher,
+            sequential,
             bkg_color: self.bkg_color,
             initial_scene,
             mods: self.mods.take(),
+            max_delta_time: self.max_delta_time,
+            delta_smoothing: self.delta_smoothing,
         })
     }
 }
+
+/// System added to the dispatcher as part of a batch.
+pub struct BatchedSystem {
+    add: Box<dyn for<'a, 'b> FnOnce(&mut DispatcherBuilder<'a, 'b>)>,
+}
+
+impl BatchedSystem {
+    pub fn new<S>(system: S, name: &'static str) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+    {
+        BatchedSystem {
+            add: Box::new(move |builder: &mut DispatcherBuilder<'_, '_>| {
+                builder.add(system, name, &[])
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct GameConfig {
+        level: u32,
+    }
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    #[derive(Default)]
+    struct OtherCounter(u32);
+
+    struct CountSystem;
+
+    impl<'a> System<'a> for CountSystem {
+        type SystemData = Write<'a, Counter>;
+
+        fn run(&mut self, mut counter: Self::SystemData) {
+            counter.0 += 1;
+        }
+    }
+
+    struct OtherCountSystem;
+
+    impl<'a> System<'a> for OtherCountSystem {
+        type SystemData = (Read<'a, Counter>, Write<'a, OtherCounter>);
+
+        fn run(&mut self, (counter, mut other): Self::SystemData) {
+            other.0 += counter.0;
+        }
+    }
+
+    #[test]
+    fn test_builder_resources() {
+        let mut builder = AppBuilder::new()
+            .resource(GameConfig { level: 7 })
+            .resource(TimeScale(0.5))
+            .resource(DebugRender::new(true));
+
+        let mut world = builder.create_world();
+        assert_eq!(world.read_resource::<GameConfig>().level, 7);
+
+        // Engine defaults don't replace the application's resources,
+        // so they're still there when the initial scene starts.
+        add_default_resources(&mut world);
+        assert_eq!(world.read_resource::<GameConfig>().level, 7);
+        assert_eq!(world.read_resource::<TimeScale>().0, 0.5);
+        assert!(world.read_resource::<DebugRender>().is_enabled());
+        assert!(world.res.has_value::<DrawSettings>());
+    }
+
+    #[test]
+    fn test_builder_system_batches() {
+        for &num_threads in &[1, 2] {
+            let mut builder = AppBuilder::new()
+                .num_threads(num_threads)
+                .system_batch(vec![BatchedSystem::new(CountSystem, "count")])
+                .system_batch(vec![BatchedSystem::new(OtherCountSystem, "other_count")]);
+
+            let mut world = builder.create_world();
+            world.add_resource(Counter::default());
+            world.add_resource(OtherCounter::default());
+
+            let mut dispatcher = builder.create_dispatcher().unwrap();
+            dispatcher.dispatch(&world.res);
+            dispatcher.dispatch_seq(&world.res);
+
+            // Second batch runs after the first.
+            assert_eq!(world.read_resource::<Counter>().0, 2);
+            assert_eq!(world.read_resource::<OtherCounter>().0, 3);
+        }
+    }
+}
@@ -1,14 +1,25 @@
-use crate::angle::Deg;
+use crate::angle::{Deg, Rad};
+use crate::colors::Color;
+use crate::res::{letter_box_rect, pillarbox_rect};
 use nalgebra::{Matrix4, Point3};
 use specs::{Component, DenseVecStorage};
 
 const DEFAULT_SCALE_PIXELS: f32 = 1000.;
 
+/// Bounds of the field of view, in radians, keeping the
+/// perspective projection from degenerating.
+const MIN_FOV: f32 = 0.001;
+const MAX_FOV: f32 = ::std::f32::consts::PI - 0.001;
+
 #[derive(Component, Debug)]
 #[storage(DenseVecStorage)]
 pub struct CameraProjection {
     ortho: OrthographicSettings,
     persp: PerspectiveSettings,
+    aspect_policy: AspectRatioPolicy,
+
+    /// Aspect ratio preserved by the letter box, pillarbox and fit policies.
+    target_aspect_ratio: f32,
 }
 
 impl CameraProjection {
@@ -22,6 +33,66 @@ impl CameraProjection {
         camera_proj
     }
 
+    /// Sets how the camera fits a window with a different
+    /// aspect ratio than its target.
+    pub fn with_aspect_policy(mut self, policy: AspectRatioPolicy) -> Self {
+        self.aspect_policy = policy;
+        self
+    }
+
+    /// Sets the aspect ratio, width over height, kept by the
+    /// letter box, pillarbox and fit policies. Defaults to 16:9.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the aspect ratio isn't positive.
+    pub fn with_target_aspect_ratio(mut self, aspect_ratio: f32) -> Self {
+        assert!(aspect_ratio > 0.0, "Target aspect ratio must be positive");
+        self.target_aspect_ratio = aspect_ratio;
+        self
+    }
+
+    #[inline]
+    pub fn aspect_policy(&self) -> &AspectRatioPolicy {
+        &self.aspect_policy
+    }
+
+    #[inline]
+    pub fn target_aspect_ratio(&self) -> f32 {
+        self.target_aspect_ratio
+    }
+
+    /// Part of the device covered by the camera's view, according
+    /// to its aspect ratio policy.
+    ///
+    /// The device size is in physical pixels, and so is the rectangle,
+    /// which is centered in the device with the origin at the bottom left.
+    pub fn view_rect(&self, device_size: (u16, u16)) -> gfx::Rect {
+        let (dev_w, dev_h) = device_size;
+        let full = gfx::Rect {
+            x: 0,
+            y: 0,
+            w: dev_w,
+            h: dev_h,
+        };
+        if dev_w == 0 || dev_h == 0 {
+            return full;
+        }
+
+        let device_aspect = f32::from(dev_w) / f32::from(dev_h);
+        let target_aspect = self.target_aspect_ratio;
+
+        match self.aspect_policy {
+            AspectRatioPolicy::LetterBox { .. } if device_aspect < target_aspect => {
+                letter_box_rect(full, target_aspect)
+            }
+            AspectRatioPolicy::Pillarbox { .. } if device_aspect > target_aspect => {
+                pillarbox_rect(full, target_aspect)
+            }
+            _ => full,
+        }
+    }
+
     pub fn set_device_size(&mut self, device_size: (u16, u16)) {
         // Orthographic
         self.ortho.device_size = [device_size.0, device_size.1];
@@ -60,6 +131,22 @@ impl CameraProjection {
         Matrix4::new_perspective(aspect, fovy, near, far)
     }
 
+    /// Vertical field of view of the perspective projection, in radians.
+    #[inline]
+    pub fn fov(&self) -> f32 {
+        self.persp.fovy.as_radians()
+    }
+
+    /// Changes the vertical field of view of the perspective
+    /// projection, in radians.
+    ///
+    /// The field of view is kept between zero and half a turn,
+    /// exclusive. The projection matrix is recomputed by
+    /// `perspective`, so the change applies on the next draw.
+    pub fn set_fov(&mut self, fov_radians: f32) {
+        self.persp.fovy = Rad(fov_radians.max(MIN_FOV).min(MAX_FOV)).into();
+    }
+
     pub fn perspective_settings(&self) -> &PerspectiveSettings {
         &self.persp
     }
@@ -82,10 +169,47 @@ impl Default for CameraProjection {
                 // Aspect ratio must never be 0
                 aspect_ratio: 16. / 9.,
             },
+            aspect_policy: AspectRatioPolicy::default(),
+            target_aspect_ratio: 16. / 9.,
+        }
+    }
+}
+
+/// How a camera fits a window that doesn't match
+/// its target aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectRatioPolicy {
+    /// The view covers the whole window, and its aspect
+    /// ratio follows the window's.
+    Stretch,
+
+    /// When the window is taller than the target aspect ratio,
+    /// the view is centered with bars above and below.
+    LetterBox { color: Color },
+
+    /// When the window is wider than the target aspect ratio,
+    /// the view is centered with bars on the left and right.
+    Pillarbox { color: Color },
+}
+
+impl AspectRatioPolicy {
+    /// Color of the bars outside the view, if the policy has any.
+    pub fn bar_color(&self) -> Option<Color> {
+        match self {
+            AspectRatioPolicy::Stretch => None,
+            AspectRatioPolicy::LetterBox { color } | AspectRatioPolicy::Pillarbox { color } => {
+                Some(*color)
+            }
         }
     }
 }
 
+impl Default for AspectRatioPolicy {
+    fn default() -> Self {
+        AspectRatioPolicy::Stretch
+    }
+}
+
 #[derive(Debug)]
 struct OrthographicSettings {
     nearz: f32,
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+/// Frame time that easing factors are expressed in, matching
+/// a 60Hz display.
+pub const REFERENCE_FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// Scales an easing factor, the fraction of motion kept after a
+/// reference frame, to a frame of the given duration in seconds.
+///
+/// Applying the result every frame decays motion at the same rate
+/// regardless of frame rate.
+///
+/// ```
+/// # use rengine::camera::ease_for_frame;
+/// // Two frames at 120Hz ease as much as one frame at 60Hz.
+/// let half = ease_for_frame(0.9, 1.0 / 120.0);
+/// assert!((half * half - 0.9).abs() < 1.0e-6);
+/// ```
+pub fn ease_for_frame(ease: f32, dt: f32) -> f32 {
+    ease.powf(dt / REFERENCE_FRAME_TIME)
+}
+
+/// Easing factor for motion that decays to `1/e` of its speed over
+/// the given time constant.
+///
+/// A zero duration stops motion immediately.
+pub fn ease_from_time(time: Duration) -> f32 {
+    let secs = duration_secs(&time);
+
+    if secs > 0.0 {
+        (-REFERENCE_FRAME_TIME / secs).exp()
+    } else {
+        0.0
+    }
+}
+
+/// Time constant of the given easing factor, the inverse
+/// of `ease_from_time`.
+pub fn ease_to_time(ease: f32) -> Duration {
+    if ease <= 0.0 {
+        return Duration::from_secs(0);
+    }
+
+    let secs = -REFERENCE_FRAME_TIME / ease.min(1.0 - ::std::f32::EPSILON).ln();
+    Duration::from_micros((secs * 1_000_000.0).round() as u64)
+}
+
+fn duration_secs(time: &Duration) -> f32 {
+    time.as_secs() as f32 + time.subsec_nanos() as f32 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ease_time() {
+        assert!((ease_for_frame(0.9, REFERENCE_FRAME_TIME) - 0.9).abs() < 1.0e-6);
+        assert_eq!(0.0, ease_from_time(Duration::from_secs(0)));
+
+        let time = ease_to_time(0.9);
+        assert!((ease_from_time(time) - 0.9).abs() < 1.0e-4);
+
+        // Longer time constants keep more motion per frame.
+        assert!(ease_from_time(Duration::from_secs(1)) > ease_from_time(time));
+    }
+}
@@ -1,10 +1,37 @@
-use super::CameraView;
+use super::{ease_for_frame, ease_from_time, CameraView};
+use crate::res::DeltaTime;
 use nalgebra::{Point3, Vector3};
-use specs::{Component, DenseVecStorage, Join, ReadStorage, System, WriteStorage};
+use specs::{Component, DenseVecStorage, Join, Read, ReadStorage, System, WriteStorage};
+use std::time::Duration;
+
+/// Default interpolation speed, high enough that the camera
+/// arrives at its target on the first tick.
+pub const DEFAULT_LERP_SPEED: f32 = 1.0e6;
+
+/// Fraction of the distance to the focus target left after
+/// each 60Hz frame of drifting.
+const DEFAULT_DRIFT_EASE: f32 = 0.5;
+
+/// Default rate at which `FocusFollowSystem` closes the distance
+/// to the target, per second.
+pub const DEFAULT_FOLLOW_SMOOTHING: f32 = 8.0;
 
 #[derive(Component, Debug)]
 #[storage(DenseVecStorage)]
-pub struct FocusTarget(Point3<f32>);
+pub struct FocusTarget {
+    target: Point3<f32>,
+    /// Fraction of the distance between the camera's previous
+    /// target and the new target covered per second.
+    lerp_speed: f32,
+    /// Camera target when the current transition started,
+    /// and the progress towards the new target.
+    transition: Option<(Point3<f32>, f32)>,
+    /// Exponential decay rate of the distance to the target,
+    /// used by `FocusFollowSystem`.
+    smoothing: f32,
+    /// Maximum distance per second covered by `FocusFollowSystem`.
+    max_follow_speed: Option<f32>,
+}
 
 impl FocusTarget {
     pub fn new() -> Self {
@@ -15,12 +42,43 @@ impl FocusTarget {
     where
         P: Into<Point3<f32>>,
     {
-        FocusTarget(pos.into())
+        FocusTarget {
+            target: pos.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the speed at which `FocusLerpSystem` moves the camera
+    /// towards the target.
+    ///
+    /// A speed of `1.0` arrives at the target in one second.
+    pub fn with_lerp_speed(mut self, lerp_speed: f32) -> Self {
+        self.lerp_speed = lerp_speed;
+        self
+    }
+
+    /// Sets how quickly `FocusFollowSystem` catches up to the target.
+    ///
+    /// The distance left shrinks by a factor of `e` every
+    /// `1.0 / smoothing` seconds, so higher values follow more tightly.
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Limits the speed at which `FocusFollowSystem` catches up to
+    /// the target, in world units per second.
+    ///
+    /// Keeps the camera from whipping across the world when the
+    /// target teleports far away.
+    pub fn with_max_follow_speed(mut self, speed: f32) -> Self {
+        self.max_follow_speed = Some(speed);
+        self
     }
 
     #[inline]
     pub fn position(&self) -> &Point3<f32> {
-        &self.0
+        &self.target
     }
 
     #[inline]
@@ -28,41 +86,215 @@ impl FocusTarget {
     where
         P: Into<Point3<f32>>,
     {
-        self.0 = pos.into();
+        let pos = pos.into();
+
+        // A new target restarts the transition from wherever
+        // the camera currently is.
+        if pos != self.target {
+            self.target = pos;
+            self.transition = None;
+        }
+    }
+
+    #[inline]
+    pub fn lerp_speed(&self) -> f32 {
+        self.lerp_speed
+    }
+
+    #[inline]
+    pub fn set_lerp_speed(&mut self, lerp_speed: f32) {
+        self.lerp_speed = lerp_speed;
+    }
+
+    #[inline]
+    pub fn smoothing(&self) -> f32 {
+        self.smoothing
+    }
+
+    #[inline]
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing;
+    }
+
+    #[inline]
+    pub fn max_follow_speed(&self) -> Option<f32> {
+        self.max_follow_speed
+    }
+
+    #[inline]
+    pub fn set_max_follow_speed(&mut self, speed: Option<f32>) {
+        self.max_follow_speed = speed;
     }
 }
 
 impl Default for FocusTarget {
     fn default() -> Self {
-        FocusTarget(Point3::new(0.0, 0.0, 0.0))
+        FocusTarget {
+            target: Point3::new(0.0, 0.0, 0.0),
+            lerp_speed: DEFAULT_LERP_SPEED,
+            transition: None,
+            smoothing: DEFAULT_FOLLOW_SMOOTHING,
+            max_follow_speed: None,
+        }
     }
 }
 
-/// Interpolates camera views towards their focus targets.
+/// Moves camera views towards their focus targets at the
+/// target's `lerp_speed`.
+///
+/// The camera position is shifted along with its target.
 #[derive(Debug, Default)]
-pub struct CameraDriftSystem;
+pub struct FocusLerpSystem;
+
+impl FocusLerpSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for FocusLerpSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, CameraView>,
+        WriteStorage<'a, FocusTarget>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (dt, mut camera_views, mut focus_targets) = data;
+
+        for (camera_view, focus_target) in (&mut camera_views, &mut focus_targets).join() {
+            let goal = focus_target.target;
+            let (origin, progress) = focus_target
+                .transition
+                .get_or_insert((*camera_view.target(), 0.0));
+
+            // Is camera at rest?
+            if (goal - camera_view.target()).magnitude() <= ::std::f32::EPSILON {
+                continue;
+            }
+
+            *progress = (*progress + focus_target.lerp_speed * dt.as_secs_float()).min(1.0);
+            let mut new_target = *origin + (goal - *origin) * *progress;
+
+            // Clamp to the goal when close enough.
+            if (goal - new_target).magnitude() <= ::std::f32::EPSILON {
+                new_target = goal;
+            }
+
+            // Both camera and target positions will be shifted.
+            let camera_diff: Vector3<f32> = camera_view.position() - camera_view.target();
+            camera_view.set_position(new_target + camera_diff);
+            camera_view.look_at(new_target);
+        }
+    }
+}
+
+/// Smoothly follows moving focus targets, damping the camera's
+/// approach at each target's `smoothing` rate.
+///
+/// The remaining distance decays exponentially over time, so the
+/// camera never overshoots the target, and follows at the same rate
+/// regardless of frame rate. The camera position is shifted along
+/// with its target.
+#[derive(Debug, Default)]
+pub struct FocusFollowSystem;
+
+impl FocusFollowSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for FocusFollowSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, CameraView>,
+        ReadStorage<'a, FocusTarget>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (dt, mut camera_views, focus_targets) = data;
+        let dt = dt.as_secs_float();
+
+        for (camera_view, focus_target) in (&mut camera_views, &focus_targets).join() {
+            let offset = focus_target.target - camera_view.target();
+            let distance = offset.magnitude();
+
+            // Is camera at rest?
+            if distance <= ::std::f32::EPSILON {
+                continue;
+            }
+
+            let mut step = distance * (1.0 - (-focus_target.smoothing * dt).exp());
+            if let Some(max_speed) = focus_target.max_follow_speed {
+                step = step.min(max_speed * dt);
+            }
+
+            // Clamp to the goal when close enough.
+            let new_target = if distance - step <= ::std::f32::EPSILON {
+                focus_target.target
+            } else {
+                camera_view.target() + offset * (step / distance)
+            };
+
+            // Both camera and target positions will be shifted.
+            let camera_diff: Vector3<f32> = camera_view.position() - camera_view.target();
+            camera_view.set_position(new_target + camera_diff);
+            camera_view.look_at(new_target);
+        }
+    }
+}
+
+/// Interpolates camera views towards their focus targets.
+///
+/// The camera eases in, covering the same fraction of the remaining
+/// distance in the same time, regardless of frame rate.
+#[derive(Debug)]
+pub struct CameraDriftSystem {
+    /// Fraction of the distance left after a frame at 60Hz.
+    ease: f32,
+}
 
 impl CameraDriftSystem {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Sets how quickly the camera drifts to its target, as the time
+    /// it takes to cover all but `1/e` of the distance.
+    pub fn with_drift_time(mut self, time: Duration) -> Self {
+        self.ease = ease_from_time(time);
+        self
+    }
+}
+
+impl Default for CameraDriftSystem {
+    fn default() -> Self {
+        CameraDriftSystem {
+            ease: DEFAULT_DRIFT_EASE,
+        }
+    }
 }
 
 impl<'a> System<'a> for CameraDriftSystem {
-    type SystemData = (WriteStorage<'a, CameraView>, ReadStorage<'a, FocusTarget>);
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, CameraView>,
+        ReadStorage<'a, FocusTarget>,
+    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut camera_views, focus_targets) = data;
+        let (dt, mut camera_views, focus_targets) = data;
+        let time = 1.0 - ease_for_frame(self.ease, dt.as_secs_float());
 
         for (camera_view, focus_target) in (&mut camera_views, &focus_targets).join() {
-            let proximity = (camera_view.target() - focus_target.0).magnitude();
+            let proximity = (camera_view.target() - focus_target.target).magnitude();
 
             // Is camera at rest?
             if proximity > ::std::f32::EPSILON {
                 // Tri-linear interpolate towards grid camera target
-                let time = 0.50;
                 let new_target =
-                    camera_view.target() + ((focus_target.0 - camera_view.target()) * time);
+                    camera_view.target() + ((focus_target.target - camera_view.target()) * time);
                 // Both camera and target positions will be shifted.
                 let camera_diff: Vector3<f32> = camera_view.position() - camera_view.target();
                 camera_view.set_position(new_target + camera_diff);
@@ -71,3 +303,84 @@ impl<'a> System<'a> for CameraDriftSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::{Builder, RunNow, World};
+    use std::time::Duration;
+
+    #[test]
+    fn test_focus_lerp() {
+        let mut world = World::new();
+        world.register::<CameraView>();
+        world.register::<FocusTarget>();
+
+        let mut camera_view = CameraView::new();
+        camera_view.set_position(Point3::new(0.0, 5.0, 5.0));
+        camera_view.look_at(Point3::new(0.0, 0.0, 0.0));
+        let camera = world
+            .create_entity()
+            .with(camera_view)
+            .with(FocusTarget::with_target([10.0, 0.0, 0.0]).with_lerp_speed(1.0))
+            .build();
+
+        // Half a second in ticks of 50 milliseconds.
+        world.add_resource(DeltaTime::new(Duration::from_millis(50)));
+        let mut lerp_sys = FocusLerpSystem::new();
+        for _ in 0..10 {
+            lerp_sys.run_now(&world.res);
+        }
+
+        let camera_views = world.read_storage::<CameraView>();
+        let camera_view = camera_views.get(camera).unwrap();
+        assert!((camera_view.target().x - 5.0).abs() < 1.0e-4);
+        assert!((camera_view.position().x - 5.0).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_focus_follow() {
+        let mut world = World::new();
+        world.register::<CameraView>();
+        world.register::<FocusTarget>();
+
+        let mut camera_view = CameraView::new();
+        camera_view.set_position(Point3::new(0.0, 5.0, 5.0));
+        camera_view.look_at(Point3::new(0.0, 0.0, 0.0));
+        let camera = world
+            .create_entity()
+            .with(camera_view)
+            .with(FocusTarget::with_target([10.0, 0.0, 0.0]).with_smoothing(5.0))
+            .build();
+
+        // Converges without overshooting.
+        world.add_resource(DeltaTime::new(Duration::from_millis(16)));
+        let mut follow_sys = FocusFollowSystem::new();
+        let mut last_x = 0.0;
+        for _ in 0..200 {
+            follow_sys.run_now(&world.res);
+
+            let camera_views = world.read_storage::<CameraView>();
+            let x = camera_views.get(camera).unwrap().target().x;
+            assert!(x >= last_x);
+            assert!(x <= 10.0);
+            last_x = x;
+        }
+        assert!((last_x - 10.0).abs() < 1.0e-4);
+
+        // Catching up to a teleported target is limited.
+        {
+            let mut focus_targets = world.write_storage::<FocusTarget>();
+            let focus_target = focus_targets.get_mut(camera).unwrap();
+            focus_target.set_max_follow_speed(Some(20.0));
+            focus_target.set_position([1000.0, 0.0, 0.0]);
+        }
+        world.add_resource(DeltaTime::new(Duration::from_millis(500)));
+        follow_sys.run_now(&world.res);
+
+        let camera_views = world.read_storage::<CameraView>();
+        let camera_view = camera_views.get(camera).unwrap();
+        assert!((camera_view.target().x - 20.0).abs() < 1.0e-3);
+        assert!((camera_view.position().x - 20.0).abs() < 1.0e-3);
+    }
+}
@@ -0,0 +1,113 @@
+use super::CameraProjection;
+use crate::res::DeltaTime;
+use specs::{Component, DenseVecStorage, Join, Read, ReadStorage, System, WriteStorage};
+
+/// Difference in radians at which the field of view is
+/// considered to have arrived at its target.
+const FOV_EPSILON: f32 = 1.0e-4;
+
+/// Animates the field of view of a camera's projection towards
+/// a target, for zoom effects like a sniper scope.
+#[derive(Component, Debug)]
+#[storage(DenseVecStorage)]
+pub struct CameraFovAnimator {
+    /// Field of view to animate towards, in radians.
+    pub target_fov: f32,
+    /// Fraction of the remaining difference covered per second.
+    ///
+    /// Higher values zoom faster, easing out as the field of
+    /// view nears its target.
+    pub speed: f32,
+}
+
+impl CameraFovAnimator {
+    pub fn new(target_fov: f32, speed: f32) -> Self {
+        CameraFovAnimator { target_fov, speed }
+    }
+}
+
+/// Interpolates the field of view of cameras towards the target
+/// of their `CameraFovAnimator`.
+///
+/// Cameras that have arrived at their target are left alone.
+#[derive(Debug, Default)]
+pub struct CameraFovSystem;
+
+impl CameraFovSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for CameraFovSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, CameraProjection>,
+        ReadStorage<'a, CameraFovAnimator>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (dt, mut camera_projs, animators) = data;
+
+        for (camera_proj, animator) in (&mut camera_projs, &animators).join() {
+            let current = camera_proj.fov();
+            let diff = animator.target_fov - current;
+
+            // Has the zoom finished?
+            if diff.abs() < FOV_EPSILON {
+                continue;
+            }
+
+            let progress = (animator.speed * dt.as_secs_float()).max(0.0).min(1.0);
+            let mut new_fov = current + diff * progress;
+
+            // Snap to the target when close enough.
+            if (animator.target_fov - new_fov).abs() < FOV_EPSILON {
+                new_fov = animator.target_fov;
+            }
+
+            camera_proj.set_fov(new_fov);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::{Builder, RunNow, World};
+    use std::time::Duration;
+
+    #[test]
+    fn test_fov_animation() {
+        let mut world = World::new();
+        world.register::<CameraProjection>();
+        world.register::<CameraFovAnimator>();
+
+        let initial_fov = CameraProjection::new().fov();
+        let target_fov = initial_fov * 0.5;
+        let camera = world
+            .create_entity()
+            .with(CameraProjection::new())
+            .with(CameraFovAnimator::new(target_fov, 10.0))
+            .build();
+
+        world.add_resource(DeltaTime::new(Duration::from_millis(16)));
+        let mut fov_sys = CameraFovSystem::new();
+        let mut last_fov = initial_fov;
+        for _ in 0..10 {
+            fov_sys.run_now(&world.res);
+
+            let fov = world
+                .read_storage::<CameraProjection>()
+                .get(camera)
+                .unwrap()
+                .fov();
+            assert!(fov < last_fov);
+            assert!(fov >= target_fov);
+            last_fov = fov;
+        }
+
+        // Most of the way to the target.
+        assert!(last_fov - target_fov < (initial_fov - target_fov) * 0.25);
+    }
+}
@@ -2,7 +2,9 @@ mod active_camera;
 mod camera_proj;
 mod camera_view;
 mod dolly;
+mod ease;
 mod focus;
+mod fov;
 mod grid;
 mod orbital;
 mod resize_sys;
@@ -12,7 +14,9 @@ pub use active_camera::*;
 pub use camera_proj::*;
 pub use camera_view::*;
 pub use dolly::*;
+pub use ease::*;
 pub use focus::*;
+pub use fov::*;
 pub use grid::*;
 pub use orbital::*;
 pub use resize_sys::*;
@@ -1,16 +1,24 @@
-use super::{ActiveCamera, CameraView};
+use super::{ease_for_frame, ease_from_time, ease_to_time, ActiveCamera, CameraView};
 use crate::angle::Rad;
 use crate::option::lift2;
-use crate::res::DeviceDimensions;
+use crate::res::{DeltaTime, DeviceDimensions};
 use glutin::{dpi::PhysicalPosition, ElementState, Event};
 use nalgebra::{Point3, Rotation3, Unit, UnitQuaternion, Vector3};
 use specs::{Component, DenseVecStorage, Read, ReadExpect, ReadStorage, System, WriteStorage};
+use std::time::Duration;
+
+/// Fraction of the rotation kept after each 60Hz frame, once
+/// the cursor stops.
+const DEFAULT_STOP_EASE: f32 = 0.9;
 
 /// Marks a camera to have arcball rotation controls.
 #[derive(Component, Debug)]
 #[storage(DenseVecStorage)]
 pub struct OrbitalCamera {
-    /// Value between 0.0 and 1.0 to control the deceleration
+    /// Value between 0.0 and 1.0 to control the deceleration.
+    ///
+    /// Fraction of the rotation speed kept after a frame at 60Hz,
+    /// scaled by the actual frame time.
     stop_ease: f32,
 
     /// Denominator for rotate speed.
@@ -34,6 +42,21 @@ impl OrbitalCamera {
         self.stop_ease
     }
 
+    /// Time constant of the deceleration, the time it takes the
+    /// rotation to slow down to `1/e` of its speed.
+    pub fn stop_ease_time(&self) -> Duration {
+        ease_to_time(self.stop_ease)
+    }
+
+    /// Sets how long the camera keeps rotating after the cursor
+    /// stops, as the time it takes to slow down to `1/e` of its
+    /// speed.
+    ///
+    /// The default is about 160 milliseconds.
+    pub fn set_stop_ease_time(&mut self, time: Duration) {
+        self.stop_ease = ease_from_time(time);
+    }
+
     #[inline]
     pub fn rotate_speed(&self) -> f32 {
         self.rotate_speed
@@ -43,7 +66,7 @@ impl OrbitalCamera {
 impl Default for OrbitalCamera {
     fn default() -> Self {
         OrbitalCamera {
-            stop_ease: 0.9,
+            stop_ease: DEFAULT_STOP_EASE,
             rotate_speed: 1024.0,
         }
     }
@@ -61,6 +84,7 @@ pub struct OrbitalCameraControlSystem {
 #[derive(SystemData)]
 pub struct OrbitalCameraControlSystemData<'a>(
     Read<'a, Vec<Event>>,
+    Read<'a, DeltaTime>,
     ReadExpect<'a, DeviceDimensions>,
     Read<'a, ActiveCamera>,
     WriteStorage<'a, CameraView>,
@@ -91,6 +115,7 @@ impl<'a> System<'a> for OrbitalCameraControlSystem {
 
         let OrbitalCameraControlSystemData(
             events,
+            dt,
             device_dim,
             active_camera,
             mut camera_views,
@@ -151,10 +176,8 @@ impl<'a> System<'a> for OrbitalCameraControlSystem {
                 .and_then(|e| orbital_cameras.get(e));
 
             if let Some(orbital) = maybe_orbital {
-                self.cursor_diff = [
-                    self.cursor_diff[0] * orbital.stop_ease,
-                    self.cursor_diff[1] * orbital.stop_ease,
-                ];
+                let ease = ease_for_frame(orbital.stop_ease, dt.as_secs_float());
+                self.cursor_diff = [self.cursor_diff[0] * ease, self.cursor_diff[1] * ease];
             }
         }
 
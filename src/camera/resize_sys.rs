@@ -1,36 +1,267 @@
-use crate::camera::CameraProjection;
-use crate::res::DeviceDimensions;
-use specs::{Join, Read, System, WriteStorage};
+use crate::camera::{ActiveCamera, AspectRatioPolicy, CameraProjection};
+use crate::colors;
+use crate::res::{DeviceDimensions, DeviceEvent, DeviceEvents, ViewPort, ViewPortPolicy};
+use shrev::ReaderId;
+use specs::{Join, Read, System, World, Write, WriteStorage};
 
 /// Update all cameras on window resize events.
 ///
 /// This is required so that the world view does not distort when
 /// the window is stretched.
-pub struct CameraResizeSystem;
-
-impl CameraResizeSystem {
-    pub fn new() -> Self {
-        Default::default()
-    }
+///
+/// A `ViewPortPolicy` resource, other than `Stretch`, narrows the
+/// `ViewPort` to a fixed aspect ratio or scale, leaving black bars over
+/// the unused part of the window, and all cameras are sized to it.
+///
+/// Otherwise cameras with a letter box or pillarbox policy keep their
+/// target aspect ratio, and for the active camera the `ViewPort` is
+/// narrowed to its view.
+///
+/// Rectangles are calculated in physical pixels, and projections are
+/// sized to them in logical pixels, so both agree on HiDPI displays.
+///
+/// Cameras are only updated on frames where a `DeviceEvent::Resized`
+/// was published to `DeviceEvents`.
+pub struct CameraResizeSystem {
+    reader: ReaderId<DeviceEvent>,
 }
 
-impl Default for CameraResizeSystem {
-    fn default() -> Self {
-        CameraResizeSystem
+impl CameraResizeSystem {
+    pub fn new(world: &World) -> Self {
+        CameraResizeSystem {
+            reader: world.write_resource::<DeviceEvents>().register_reader(),
+        }
     }
 }
 
 impl<'a> System<'a> for CameraResizeSystem {
     type SystemData = (
+        Read<'a, DeviceEvents>,
         Read<'a, DeviceDimensions>,
+        Option<Read<'a, ViewPortPolicy>>,
+        Option<Read<'a, ActiveCamera>>,
+        Option<Write<'a, ViewPort>>,
         WriteStorage<'a, CameraProjection>,
     );
 
-    fn run(&mut self, (dim, mut cam_views): Self::SystemData) {
-        let (dev_w, dev_h): (u32, u32) = dim.logical_size.into();
+    fn run(
+        &mut self,
+        (device_events, dim, policy, active_camera, view_port, mut cam_views): Self::SystemData,
+    ) {
+        // Reading moves the reader past all pending events.
+        let resized = device_events.read(&mut self.reader).any(|ev| match ev {
+            DeviceEvent::Resized { .. } => true,
+            DeviceEvent::DpiChanged { .. } => false,
+        });
+        if !resized {
+            return;
+        }
+
+        let (phys_w, phys_h): (u32, u32) = dim.physical_size.into();
+        let device_size = (phys_w as u16, phys_h as u16);
+        let dpi_factor = dim.dpi_factor;
+
+        // Stretching leaves the view port to the cameras.
+        let policy_rect = match policy {
+            Some(ref policy) if **policy != ViewPortPolicy::Stretch => {
+                Some(policy.view_rect(device_size))
+            }
+            _ => None,
+        };
 
         for (ref mut view,) in (&mut cam_views,).join() {
-            view.set_device_size((dev_w as u16, dev_h as u16));
+            let rect = policy_rect.unwrap_or_else(|| view.view_rect(device_size));
+            let logical_w = (f64::from(rect.w) / dpi_factor).round() as u16;
+            let logical_h = (f64::from(rect.h) / dpi_factor).round() as u16;
+            view.set_device_size((logical_w, logical_h));
+        }
+
+        let mut view_port = match view_port {
+            Some(view_port) => view_port,
+            None => return,
+        };
+
+        if let Some(rect) = policy_rect {
+            view_port.rect = rect;
+            view_port.bar_color = Some(colors::BLACK);
+            return;
         }
+
+        let active_view = active_camera
+            .and_then(|active_camera| active_camera.camera_entity())
+            .and_then(|entity| cam_views.get(entity));
+
+        if let Some(view) = active_view {
+            // Stretched cameras leave the view port as is, which may
+            // already cover only part of the window.
+            if *view.aspect_policy() != AspectRatioPolicy::Stretch {
+                view_port.rect = view.view_rect(device_size);
+                view_port.bar_color = view.aspect_policy().bar_color();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use glutin::dpi::LogicalSize;
+    use specs::{Builder, RunNow, World};
+
+    fn create_world(policy: AspectRatioPolicy) -> (World, CameraResizeSystem) {
+        let mut world = World::new();
+        world.register::<CameraProjection>();
+        world.add_resource(DeviceEvents::new());
+        let system = CameraResizeSystem::new(&world);
+
+        let camera = world
+            .create_entity()
+            .with(CameraProjection::with_device_size((1280, 720)).with_aspect_policy(policy))
+            .build();
+        world.add_resource(ActiveCamera::new(camera));
+        (world, system)
+    }
+
+    fn resize(world: &mut World, system: &mut CameraResizeSystem, width: u16, height: u16) {
+        let dim = DeviceDimensions::new(1.0, LogicalSize::new(f64::from(width), f64::from(height)));
+        world
+            .write_resource::<DeviceEvents>()
+            .single_write(DeviceEvent::resized(&dim));
+        world.add_resource(ViewPort::new((width, height)));
+        world.add_resource(dim);
+        system.run_now(&world.res);
+    }
+
+    #[test]
+    fn test_letter_box() {
+        let policy = AspectRatioPolicy::LetterBox {
+            color: colors::BLACK,
+        };
+        let (mut world, mut system) = create_world(policy);
+
+        // 16:9 fits exactly.
+        resize(&mut world, &mut system, 1280, 720);
+        assert_eq!(720, world.read_resource::<ViewPort>().scissor_rect().h);
+
+        // 4:3 leaves bars of 75 pixels above and below.
+        resize(&mut world, &mut system, 800, 600);
+        let view_port = world.read_resource::<ViewPort>();
+        let rect = view_port.scissor_rect();
+        assert_eq!((0, 75, 800, 450), (rect.x, rect.y, rect.w, rect.h));
+        assert_eq!(Some(colors::BLACK), view_port.bar_color());
+
+        let cam_projs = world.read_storage::<CameraProjection>();
+        let cam_proj = cam_projs.join().next().unwrap();
+        let aspect_ratio = cam_proj.perspective_settings().aspect_ratio();
+        assert!((aspect_ratio - 16.0 / 9.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_pillarbox() {
+        let policy = AspectRatioPolicy::Pillarbox {
+            color: colors::GREY,
+        };
+        let (mut world, mut system) = create_world(policy);
+
+        // Too tall for pillars.
+        resize(&mut world, &mut system, 800, 600);
+        let rect = *world.read_resource::<ViewPort>().scissor_rect();
+        assert_eq!((0, 0, 800, 600), (rect.x, rect.y, rect.w, rect.h));
+
+        // 21:9 leaves bars of 200 pixels left and right.
+        resize(&mut world, &mut system, 1680, 720);
+        let rect = *world.read_resource::<ViewPort>().scissor_rect();
+        assert_eq!((200, 0, 1280, 720), (rect.x, rect.y, rect.w, rect.h));
+    }
+
+    #[test]
+    fn test_stretch() {
+        let (mut world, mut system) = create_world(AspectRatioPolicy::Stretch);
+
+        resize(&mut world, &mut system, 800, 600);
+        let view_port = world.read_resource::<ViewPort>();
+        assert_eq!(600, view_port.scissor_rect().h);
+        assert_eq!(None, view_port.bar_color());
+
+        let cam_projs = world.read_storage::<CameraProjection>();
+        let cam_proj = cam_projs.join().next().unwrap();
+        let aspect_ratio = cam_proj.perspective_settings().aspect_ratio();
+        assert!((aspect_ratio - 4.0 / 3.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_resize_events() {
+        let policy = AspectRatioPolicy::LetterBox {
+            color: colors::BLACK,
+        };
+        let (mut world, mut system) = create_world(policy);
+        resize(&mut world, &mut system, 800, 600);
+
+        // Without a resize event the view port is left as is.
+        world.add_resource(ViewPort::new((800, 600)));
+        world
+            .write_resource::<DeviceEvents>()
+            .single_write(DeviceEvent::DpiChanged { dpi_factor: 2.0 });
+        system.run_now(&world.res);
+        assert_eq!(600, world.read_resource::<ViewPort>().scissor_rect().h);
+
+        system.run_now(&world.res);
+        assert_eq!(600, world.read_resource::<ViewPort>().scissor_rect().h);
+    }
+
+    #[test]
+    fn test_letter_box_hidpi() {
+        let policy = AspectRatioPolicy::LetterBox {
+            color: colors::BLACK,
+        };
+        let (mut world, mut system) = create_world(policy);
+
+        // 400x300 logical window at 2x scale
+        let dim = DeviceDimensions::new(2.0, LogicalSize::new(400.0, 300.0));
+        world
+            .write_resource::<DeviceEvents>()
+            .single_write(DeviceEvent::resized(&dim));
+        world.add_resource(ViewPort::new((800, 600)));
+        world.add_resource(dim);
+        system.run_now(&world.res);
+
+        // The view port is in physical pixels.
+        let rect = *world.read_resource::<ViewPort>().scissor_rect();
+        assert_eq!((0, 75, 800, 450), (rect.x, rect.y, rect.w, rect.h));
+
+        // The projection is sized to the same rectangle, in logical pixels.
+        let cam_projs = world.read_storage::<CameraProjection>();
+        let cam_proj = cam_projs.join().next().unwrap();
+        let aspect_ratio = cam_proj.perspective_settings().aspect_ratio();
+        assert!((aspect_ratio - 16.0 / 9.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_view_port_policy() {
+        let (mut world, mut system) = create_world(AspectRatioPolicy::Stretch);
+        world.add_resource(ViewPortPolicy::IntegerScale {
+            base_w: 320,
+            base_h: 180,
+        });
+
+        // HiDPI window of 1280x800 physical pixels, scaled by 4.
+        let dim = DeviceDimensions::new(2.0, LogicalSize::new(640.0, 400.0));
+        world
+            .write_resource::<DeviceEvents>()
+            .single_write(DeviceEvent::resized(&dim));
+        world.add_resource(ViewPort::new((1280, 800)));
+        world.add_resource(dim);
+        system.run_now(&world.res);
+
+        let view_port = world.read_resource::<ViewPort>();
+        let rect = view_port.scissor_rect();
+        assert_eq!((0, 40, 1280, 720), (rect.x, rect.y, rect.w, rect.h));
+        assert_eq!(Some(colors::BLACK), view_port.bar_color());
+
+        // The projection is sized to the same rectangle, in logical pixels.
+        let cam_projs = world.read_storage::<CameraProjection>();
+        let cam_proj = cam_projs.join().next().unwrap();
+        let aspect_ratio = cam_proj.perspective_settings().aspect_ratio();
+        assert!((aspect_ratio - 16.0 / 9.0).abs() < 1.0e-5);
     }
 }
@@ -1,3 +1,7 @@
+pub mod grid3d;
 pub mod ordered_dag;
+pub mod pool;
 
+pub use grid3d::Grid3D;
 pub use ordered_dag::OrderedDag;
+pub use pool::{Pool, PoolHandle};
@@ -0,0 +1,197 @@
+//! Fixed size 3D array.
+use std::ops::{Index, IndexMut};
+
+/// Three dimensional grid of values, stored in a flat array.
+///
+/// Values are laid out along the X axis first, then Y, then Z,
+/// the same as voxel chunk data. The value at `[x, y, z]` is
+/// at index `x + y * size_x + z * size_x * size_y`.
+///
+/// # Example
+///
+/// ```
+/// use rengine::collections::Grid3D;
+///
+/// let mut light_levels = Grid3D::new([4, 4, 4], 0u8);
+/// light_levels.set([1, 2, 3], 15);
+///
+/// assert_eq!(light_levels.get([1, 2, 3]), Some(&15));
+/// assert_eq!(light_levels.get([4, 0, 0]), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid3D<T> {
+    data: Vec<T>,
+    size: [usize; 3],
+}
+
+impl<T> Grid3D<T> {
+    /// Creates a grid with every cell set to the default value.
+    pub fn new(size: [usize; 3], default: T) -> Self
+    where
+        T: Clone,
+    {
+        Grid3D {
+            data: vec![default; size[0] * size[1] * size[2]],
+            size,
+        }
+    }
+
+    /// Creates a grid with each cell set to the
+    /// value returned for its coordinate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::Grid3D;
+    ///
+    /// let heights = Grid3D::from_fn([2, 3, 2], |[_, y, _]| y);
+    /// assert_eq!(heights.get([1, 2, 0]), Some(&2));
+    /// ```
+    pub fn from_fn<F>(size: [usize; 3], f: F) -> Self
+    where
+        F: Fn([usize; 3]) -> T,
+    {
+        let len = size[0] * size[1] * size[2];
+        let data = (0..len).map(|index| f(coord_of(size, index))).collect();
+
+        Grid3D { data, size }
+    }
+
+    #[inline]
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    /// Number of cells in the grid.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Checks whether the coordinate is inside the grid.
+    #[inline]
+    pub fn in_bounds(&self, coord: [usize; 3]) -> bool {
+        let [x, y, z] = coord;
+        x < self.size[0] && y < self.size[1] && z < self.size[2]
+    }
+
+    /// Index into the flat array of the value at the coordinate,
+    /// or None when the coordinate is outside the grid.
+    #[inline]
+    pub fn index_of(&self, coord: [usize; 3]) -> Option<usize> {
+        if self.in_bounds(coord) {
+            let [x, y, z] = coord;
+            Some(x + y * self.size[0] + z * self.size[0] * self.size[1])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, coord: [usize; 3]) -> Option<&T> {
+        self.index_of(coord).map(|index| &self.data[index])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, coord: [usize; 3]) -> Option<&mut T> {
+        match self.index_of(coord) {
+            Some(index) => Some(&mut self.data[index]),
+            None => None,
+        }
+    }
+
+    /// Replaces the value at the coordinate, returning the old value.
+    ///
+    /// Returns None, and drops the value, when the coordinate
+    /// is outside the grid.
+    pub fn set(&mut self, coord: [usize; 3], value: T) -> Option<T> {
+        self.get_mut(coord)
+            .map(|cell| ::std::mem::replace(cell, value))
+    }
+
+    /// Iterates the cells with their coordinates, in
+    /// the order they're stored.
+    pub fn iter(&self) -> impl Iterator<Item = ([usize; 3], &T)> {
+        let size = self.size;
+        self.data
+            .iter()
+            .enumerate()
+            .map(move |(index, value)| (coord_of(size, index), value))
+    }
+
+    /// Values in the order they're stored.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+}
+
+/// Coordinate of the value at an index into the flat array.
+#[inline]
+fn coord_of(size: [usize; 3], index: usize) -> [usize; 3] {
+    let layer = size[0] * size[1];
+    [index % size[0], (index % layer) / size[0], index / layer]
+}
+
+impl<T> Index<[usize; 3]> for Grid3D<T> {
+    type Output = T;
+
+    fn index(&self, coord: [usize; 3]) -> &T {
+        self.get(coord)
+            .unwrap_or_else(|| panic!("Coordinate {:?} out of grid bounds {:?}", coord, self.size))
+    }
+}
+
+impl<T> IndexMut<[usize; 3]> for Grid3D<T> {
+    fn index_mut(&mut self, coord: [usize; 3]) -> &mut T {
+        let size = self.size;
+        self.get_mut(coord)
+            .unwrap_or_else(|| panic!("Coordinate {:?} out of grid bounds {:?}", coord, size))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flat_index() {
+        // Same layout as `VoxelArrayChunk` data
+        const DIM: usize = 8;
+        let mut flat = [0usize; DIM * DIM * DIM];
+        for (i, cell) in flat.iter_mut().enumerate() {
+            *cell = i;
+        }
+
+        let grid = Grid3D::from_fn([DIM, DIM, DIM], |[x, y, z]| x + y * DIM + z * DIM * DIM);
+        assert_eq!(&flat[..], grid.as_slice());
+
+        for (coord, value) in grid.iter() {
+            assert_eq!(grid.index_of(coord), Some(*value));
+        }
+    }
+
+    #[test]
+    fn test_uneven_size() {
+        let mut grid = Grid3D::new([3, 2, 4], 0);
+        assert_eq!(24, grid.len());
+        assert!(grid.in_bounds([2, 1, 3]));
+        assert!(!grid.in_bounds([3, 0, 0]));
+        assert!(!grid.in_bounds([0, 2, 0]));
+        assert!(!grid.in_bounds([0, 0, 4]));
+
+        assert_eq!(Some(0), grid.set([2, 1, 3], 7));
+        assert_eq!(None, grid.set([0, 0, 4], 7));
+        assert_eq!(7, grid[[2, 1, 3]]);
+        assert_eq!(Some(&7), grid.as_slice().last());
+
+        // Iteration matches stored order
+        let coords: Vec<[usize; 3]> = grid.iter().map(|(coord, _)| coord).take(4).collect();
+        assert_eq!(vec![[0, 0, 0], [1, 0, 0], [2, 0, 0], [0, 1, 0]], coords);
+    }
+}
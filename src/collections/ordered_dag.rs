@@ -49,6 +49,7 @@ where
         self.nodes.insert(Node {
             value: node_value,
             edges: vec![],
+            parents: vec![],
         })
     }
 
@@ -75,11 +76,12 @@ where
         let node_id = self.nodes.insert(Node {
             value: node_value,
             edges: vec![],
+            parents: vec![],
         });
 
         if let Some(pid) = parent_id {
-            // Won't return error because no outgoing edges exist yet.
-            self.set_edge_unchecked(pid, node_id, E::default()).unwrap();
+            // Can't create a cycle because no outgoing edges exist yet.
+            self.set_edge_unchecked(pid, node_id, E::default());
         }
 
         node_id
@@ -87,9 +89,41 @@ where
 
     /// Add an edge between two nodes.
     ///
-    /// Does nothing if an edge already exists.
-    pub fn add_edge(&mut self, _source_id: NodeId, _target_id: NodeId, _edge_value: E) {
-        unimplemented!();
+    /// Does nothing if an edge already exists, keeping its value.
+    ///
+    /// # Errors
+    ///
+    /// Returns errors when either node does not exist, or when the edge
+    /// would create a cycle. The graph is left unchanged on error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::{OrderedDag, ordered_dag::OrderedGraphError};
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert(2);
+    /// assert_eq!(graph.add_edge(node_1, node_2, 7), Ok(()));
+    ///
+    /// // Existing edges are kept.
+    /// assert_eq!(graph.add_edge(node_1, node_2, 3), Ok(()));
+    /// assert_eq!(graph.remove_edge(node_1, node_2), Some(7));
+    ///
+    /// graph.add_edge(node_1, node_2, 0).unwrap();
+    /// assert_eq!(graph.add_edge(node_2, node_1, 0), Err(OrderedGraphError::Cycle));
+    /// ```
+    pub fn add_edge(
+        &mut self,
+        source_id: NodeId,
+        target_id: NodeId,
+        edge_value: E,
+    ) -> Result<(), OrderedGraphError> {
+        match self.nodes.get(source_id) {
+            Some(node) if node.edges.iter().any(|e| e.child == target_id) => Ok(()),
+            _ => self.set_edge(source_id, target_id, edge_value),
+        }
     }
 
     /// Add or update an edge netween two nodes.
@@ -125,10 +159,13 @@ where
         target_id: NodeId,
         edge_value: E,
     ) -> Result<(), OrderedGraphError> {
-        if let Some(index) = self.set_edge_unchecked(source_id, target_id, edge_value) {
+        if self
+            .set_edge_unchecked(source_id, target_id, edge_value)
+            .is_some()
+        {
             if let Some(_in_node) = self.check_cycle(source_id) {
                 // Cycle detected, remove newly inserted edge.
-                let _ = self.nodes.get_mut(source_id).unwrap().edges.remove(index);
+                self.remove_edge(source_id, target_id);
                 Err(OrderedGraphError::Cycle)
             } else {
                 Ok(())
@@ -141,30 +178,67 @@ where
     /// Add or update an edge without checking for cycles.
     ///
     /// Returns the index of the inserted edge on success, or None
-    /// if the target node doesn't exist.
+    /// if either node doesn't exist.
     fn set_edge_unchecked(
         &mut self,
         source_id: NodeId,
         target_id: NodeId,
         edge_value: E,
     ) -> Option<usize> {
-        if let Some(node) = self.nodes.get_mut(source_id) {
-            if let Some(idx) = node.edges.iter().position(|e| e.child == target_id) {
-                // Edge exists. Replace value.
-                node.edges.get_mut(idx).unwrap().value = edge_value;
-                Some(idx)
-            } else {
-                node.edges.push(Edge {
-                    value: edge_value,
-                    child: target_id,
-                });
-                Some(node.edges.len() - 1)
-            }
+        if !self.nodes.contains_key(target_id) {
+            return None;
+        }
+
+        let node = self.nodes.get_mut(source_id)?;
+        if let Some(idx) = node.edges.iter().position(|e| e.child == target_id) {
+            // Edge exists. Replace value.
+            node.edges.get_mut(idx).unwrap().value = edge_value;
+            Some(idx)
         } else {
-            None
+            node.edges.push(Edge {
+                value: edge_value,
+                child: target_id,
+            });
+            let idx = node.edges.len() - 1;
+
+            // Keep the reverse index in sync.
+            let target = self.nodes.get_mut(target_id).unwrap();
+            target.parents.push(source_id);
+            Some(idx)
         }
     }
 
+    /// Removes the edge between two nodes.
+    ///
+    /// Returns the value of the removed edge, or None if
+    /// there was no edge.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert_at(2, Some(node_1));
+    ///
+    /// assert_eq!(graph.remove_edge(node_1, node_2), Some(0));
+    /// assert_eq!(graph.remove_edge(node_1, node_2), None);
+    /// assert_eq!(graph.parent(node_2), None);
+    /// ```
+    pub fn remove_edge(&mut self, source_id: NodeId, target_id: NodeId) -> Option<E> {
+        let node = self.nodes.get_mut(source_id)?;
+        let idx = node.edges.iter().position(|e| e.child == target_id)?;
+        let edge = node.edges.remove(idx);
+
+        if let Some(target) = self.nodes.get_mut(target_id) {
+            target.parents.retain(|id| *id != source_id);
+        }
+
+        Some(edge.value)
+    }
+
     /// Borrows a reference the a node value.
     ///
     /// Returns None if it does not exist.
@@ -202,6 +276,210 @@ where
         self.nodes.get_mut(node_id).map(|n| &mut n.value)
     }
 
+    /// Finds a node with an edge going to the given node.
+    ///
+    /// When the node has more than one parent, the one
+    /// connected first is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert_at(2, Some(node_1));
+    /// assert_eq!(graph.parent(node_2), Some(node_1));
+    /// assert_eq!(graph.parent(node_1), None);
+    /// ```
+    pub fn parent(&self, node_id: NodeId) -> Option<NodeId> {
+        self.parents(node_id).next()
+    }
+
+    /// Iterates the nodes with an edge going to the given node, in
+    /// the order they were connected.
+    ///
+    /// Parents are kept in a reverse index, so this doesn't
+    /// search the graph.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert(2);
+    /// let node_3 = graph.insert_at(3, Some(node_1));
+    /// graph.add_edge(node_2, node_3, 0).unwrap();
+    ///
+    /// let parents: Vec<_> = graph.parents(node_3).collect();
+    /// assert_eq!(parents, vec![node_1, node_2]);
+    /// assert_eq!(graph.parents(node_1).count(), 0);
+    /// ```
+    pub fn parents(&self, node_id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .get(node_id)
+            .into_iter()
+            .flat_map(|n| n.parents.iter().cloned())
+    }
+
+    /// Removes a single node, and all edges going to or from it.
+    ///
+    /// The children of the node are kept, and become roots
+    /// if they have no other parents.
+    ///
+    /// Returns the node value, or None if the node does not exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    /// use rengine::collections::ordered_dag::Walker;
+    ///
+    /// //     a
+    /// //    / \
+    /// //   b   c
+    /// //   |
+    /// //   d
+    ///
+    /// let mut graph: OrderedDag<&'static str, i64> = OrderedDag::new();
+    /// let node_1 = graph.insert("a");
+    /// let node_2 = graph.insert_at("b", Some(node_1));
+    /// let node_3 = graph.insert_at("c", Some(node_1));
+    /// let node_4 = graph.insert_at("d", Some(node_2));
+    ///
+    /// assert_eq!(graph.remove_node(node_2), Some("b"));
+    /// assert_eq!(graph.remove_node(node_2), None);
+    ///
+    /// // Walkers no longer visit the removed node, or its children.
+    /// let mut walker = graph.walk_pre_order(node_1);
+    /// let mut result = String::new();
+    ///
+    /// while let Some(node_id) = walker.next(&graph) {
+    ///     result.push_str(graph.node(node_id).unwrap());
+    /// }
+    ///
+    /// assert_eq!(result.as_str(), "ac");
+    ///
+    /// // The child was detached.
+    /// assert_eq!(graph.parent(node_4), None);
+    /// assert_eq!(graph.node(node_4), Some(&"d"));
+    /// assert_eq!(graph.parent(node_3), Some(node_1));
+    /// assert_eq!(graph.out_edge_len(node_1), Some(1));
+    /// ```
+    pub fn remove_node(&mut self, node_id: NodeId) -> Option<N> {
+        let node = self.nodes.remove(node_id)?;
+
+        for parent_id in node.parents {
+            if let Some(parent) = self.nodes.get_mut(parent_id) {
+                parent.edges.retain(|e| e.child != node_id);
+            }
+        }
+
+        for edge in node.edges {
+            if let Some(child) = self.nodes.get_mut(edge.child) {
+                child.parents.retain(|id| *id != node_id);
+            }
+        }
+
+        Some(node.value)
+    }
+
+    /// Removes the given node, and all nodes reachable from it.
+    ///
+    /// Edges from the remaining nodes to any removed node are
+    /// removed as well, so the subtree is detached from its parent.
+    ///
+    /// Returns the removed node values in pre-order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert_at(2, Some(node_1));
+    /// let node_3 = graph.insert_at(3, Some(node_2));
+    /// let node_4 = graph.insert_at(4, Some(node_1));
+    ///
+    /// assert_eq!(graph.remove_subtree(node_2), vec![2, 3]);
+    /// assert_eq!(graph.len(), 2);
+    /// assert_eq!(graph.node(node_3), None);
+    /// assert_eq!(graph.out_edge_len(node_1), Some(1));
+    /// ```
+    pub fn remove_subtree(&mut self, node_id: NodeId) -> Vec<N> {
+        let node_ids: Vec<NodeId> = self
+            .walk_pre_order(node_id)
+            .iter(self)
+            .map(|(id, _)| id)
+            .collect();
+
+        // Nodes shared by multiple parents are visited more than once,
+        // and are already removed on later visits.
+        node_ids
+            .into_iter()
+            .filter_map(|id| self.remove_node(id))
+            .collect()
+    }
+
+    /// Detaches the node from its parents, and attaches it as
+    /// the last child of the given parent.
+    ///
+    /// # Errors
+    ///
+    /// Returns errors when either node does not exist, or when the
+    /// new parent is the node itself or one of its descendants. The
+    /// graph is left unchanged on error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::collections::{OrderedDag, ordered_dag::OrderedGraphError};
+    ///
+    /// let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert(1);
+    /// let node_2 = graph.insert_at(2, Some(node_1));
+    /// let node_3 = graph.insert_at(3, Some(node_2));
+    ///
+    /// assert_eq!(graph.reparent(node_3, node_1), Ok(()));
+    /// assert_eq!(graph.parent(node_3), Some(node_1));
+    /// assert_eq!(graph.out_edge_len(node_2), Some(0));
+    ///
+    /// assert_eq!(graph.reparent(node_1, node_3), Err(OrderedGraphError::Cycle));
+    /// assert_eq!(graph.parent(node_1), None);
+    /// ```
+    pub fn reparent(&mut self, node_id: NodeId, parent_id: NodeId) -> Result<(), OrderedGraphError>
+    where
+        E: Default,
+    {
+        if !self.nodes.contains_key(node_id) || !self.nodes.contains_key(parent_id) {
+            return Err(OrderedGraphError::NodeDoesNotExist);
+        }
+
+        if self
+            .walk_pre_order(node_id)
+            .iter(self)
+            .any(|(id, _)| id == parent_id)
+        {
+            return Err(OrderedGraphError::Cycle);
+        }
+
+        let old_parents: Vec<NodeId> = self.parents(node_id).collect();
+        for old_parent_id in old_parents {
+            self.remove_edge(old_parent_id, node_id);
+        }
+
+        self.set_edge_unchecked(parent_id, node_id, E::default());
+
+        Ok(())
+    }
+
     /// The number of edges going out of the given node.
     ///
     /// Returns None if the node does not exist.
@@ -309,42 +587,88 @@ where
         }
     }
 
+    /// Sorts the edges of a single node by their values.
+    ///
+    /// The sort is stable, so children with equal edge values keep
+    /// their current order. Does nothing if the node does not exist.
+    ///
+    /// ```
+    /// use rengine::collections::OrderedDag;
+    /// use rengine::collections::ordered_dag::Walker;
+    ///
+    /// let mut graph: OrderedDag<&'static str, i64> = OrderedDag::new();
+    ///
+    /// let node_1 = graph.insert("a");
+    /// let node_2 = graph.insert_at("b", Some(node_1));
+    /// let node_3 = graph.insert_at("c", Some(node_1));
+    /// let node_4 = graph.insert_at("d", Some(node_1));
+    ///
+    /// // Move "b" to the back
+    /// graph.set_edge(node_1, node_2, 1).unwrap();
+    /// graph.sort_node(node_1);
+    ///
+    /// let children: Vec<&str> = graph
+    ///     .walk_children(node_1)
+    ///     .iter(&graph)
+    ///     .map(|(_, node_val)| *node_val)
+    ///     .collect();
+    /// assert_eq!(children, vec!["c", "d", "b"]);
+    /// ```
+    pub fn sort_node(&mut self, node_id: NodeId) {
+        if let Some(node) = self.nodes.get_mut(node_id) {
+            node.edges.sort_by(|a, b| a.value.cmp(&b.value));
+        }
+    }
+
+    /// Searches depth-first for a cycle reachable from the given node.
+    ///
+    /// Returns the node with the edge that closes the cycle. Uses an
+    /// explicit stack, so deep graphs don't overflow the call stack.
     fn check_cycle(&self, start_node_id: NodeId) -> Option<NodeId> {
-        let mut state: HashMap<NodeId, VisitColor> = HashMap::new();
-
-        fn dfs_visit<N, E: Ord>(
-            g: &OrderedDag<N, E>,
-            u: NodeId,
-            s: &mut HashMap<NodeId, VisitColor>,
-        ) -> Option<NodeId> {
-            let child_iter = g.nodes.get(u).unwrap().edges.iter().map(|e| e.child);
-            for v in child_iter {
-                let color = *s.entry(v).or_insert(VisitColor::White);
-                if color == VisitColor::Grey {
-                    // Cycle detected. Return parent node.
-                    return Some(u);
-                }
+        self.search_cycle(start_node_id, &mut HashMap::new())
+    }
 
-                if color == VisitColor::Black {
-                    return None;
-                }
+    /// Searches for a cycle, skipping nodes already searched
+    /// with the same state.
+    fn search_cycle(
+        &self,
+        start_node_id: NodeId,
+        state: &mut HashMap<NodeId, VisitColor>,
+    ) -> Option<NodeId> {
+        // Nodes being visited, with the index of their next edge.
+        let mut stack: Vec<(NodeId, usize)> = vec![(start_node_id, 0)];
+        state.insert(start_node_id, VisitColor::Grey);
 
-                s.insert(v, VisitColor::Grey);
-                let result = dfs_visit(g, v, s);
-                if result.is_some() {
-                    // Cycle found
-                    return result;
-                }
-            }
+        while let Some(&(u, cursor)) = stack.last() {
+            let next_child = self
+                .nodes
+                .get(u)
+                .and_then(|n| n.edges.get(cursor))
+                .map(|e| e.child);
 
-            let c = s.entry(u).or_insert(VisitColor::Black);
-            *c = VisitColor::Black;
+            match next_child {
+                Some(v) => {
+                    stack.last_mut().unwrap().1 += 1;
 
-            None
+                    match state.get(&v).cloned().unwrap_or(VisitColor::White) {
+                        // Cycle detected. Return parent node.
+                        VisitColor::Grey => return Some(u),
+                        // Already searched, continue with the next sibling.
+                        VisitColor::Black => {}
+                        VisitColor::White => {
+                            state.insert(v, VisitColor::Grey);
+                            stack.push((v, 0));
+                        }
+                    }
+                }
+                None => {
+                    state.insert(u, VisitColor::Black);
+                    stack.pop();
+                }
+            }
         }
 
-        state.insert(start_node_id, VisitColor::Grey);
-        dfs_visit(self, start_node_id, &mut state)
+        None
     }
 
     /// Builds a string representation of the whole graph.
@@ -540,6 +864,8 @@ where
 struct Node<N, E: Ord> {
     value: N,
     edges: Vec<E>,
+    /// Nodes with edges going to this node.
+    parents: Vec<NodeId>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -724,6 +1050,208 @@ where
     }
 }
 
+// ------------- //
+// Serialization //
+// ------------- //
+
+#[cfg(feature = "serde-serialize")]
+mod serde_impl {
+    use super::*;
+    use serde::de;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Persisted form of a graph, where node ids are replaced
+    /// with indices into the list of nodes.
+    #[derive(Serialize, Deserialize)]
+    struct PersistedDag<N, E> {
+        nodes: Vec<PersistedNode<N, E>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct PersistedNode<N, E> {
+        value: N,
+        edges: Vec<PersistedEdge<E>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct PersistedEdge<E> {
+        target: usize,
+        value: E,
+    }
+
+    impl<N, E> OrderedDag<N, E>
+    where
+        E: Ord,
+    {
+        /// Indices that node ids are replaced with when the
+        /// graph is serialized.
+        ///
+        /// Record these before saving, to fix up references to
+        /// nodes kept outside the graph when it's loaded again.
+        pub fn persisted_ids(&self) -> HashMap<NodeId, usize> {
+            self.nodes
+                .keys()
+                .enumerate()
+                .map(|(index, node_id)| (node_id, index))
+                .collect()
+        }
+
+        /// Deserializes a graph, and returns the new id of each node,
+        /// indexed by the persisted index of the node.
+        ///
+        /// # Errors
+        ///
+        /// Fails when an edge points at a node that doesn't exist,
+        /// or when the edges form a cycle.
+        pub fn deserialize_with_ids<'de, D>(
+            deserializer: D,
+        ) -> Result<(Self, Vec<NodeId>), D::Error>
+        where
+            D: Deserializer<'de>,
+            N: Deserialize<'de>,
+            E: Deserialize<'de>,
+        {
+            let persisted = PersistedDag::<N, E>::deserialize(deserializer)?;
+            let mut graph = OrderedDag::new();
+            let mut edges = Vec::with_capacity(persisted.nodes.len());
+
+            let node_ids: Vec<NodeId> = persisted
+                .nodes
+                .into_iter()
+                .map(|node| {
+                    edges.push(node.edges);
+                    graph.insert(node.value)
+                })
+                .collect();
+
+            // Edges are added in their persisted order,
+            // so children keep their order.
+            for (source_id, node_edges) in node_ids.iter().zip(edges) {
+                for edge in node_edges {
+                    let target_id = node_ids.get(edge.target).ok_or_else(|| {
+                        de::Error::custom(format!("edge to unknown node {}", edge.target))
+                    })?;
+                    graph.set_edge_unchecked(*source_id, *target_id, edge.value);
+                }
+            }
+
+            let mut state = HashMap::new();
+            for node_id in node_ids.iter() {
+                if !state.contains_key(node_id)
+                    && graph.search_cycle(*node_id, &mut state).is_some()
+                {
+                    return Err(de::Error::custom(OrderedGraphError::Cycle));
+                }
+            }
+
+            Ok((graph, node_ids))
+        }
+    }
+
+    /// Node ids are replaced with dense indices, in the order
+    /// given by `OrderedDag::persisted_ids`.
+    impl<N, E> Serialize for OrderedDag<N, E>
+    where
+        N: Serialize,
+        E: Ord + Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let persisted_ids = self.persisted_ids();
+            let nodes = self
+                .nodes
+                .values()
+                .map(|node| PersistedNode {
+                    value: &node.value,
+                    edges: node
+                        .edges
+                        .iter()
+                        .map(|edge| PersistedEdge {
+                            target: persisted_ids[&edge.child],
+                            value: &edge.value,
+                        })
+                        .collect(),
+                })
+                .collect();
+
+            PersistedDag { nodes }.serialize(serializer)
+        }
+    }
+
+    impl<'de, N, E> Deserialize<'de> for OrderedDag<N, E>
+    where
+        N: Deserialize<'de>,
+        E: Ord + Deserialize<'de>,
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            OrderedDag::deserialize_with_ids(deserializer).map(|(graph, _)| graph)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn children(graph: &OrderedDag<String, i64>, node_id: NodeId) -> Vec<(&str, i64)> {
+            graph.nodes[node_id]
+                .edges
+                .iter()
+                .map(|edge| (graph.nodes[edge.child].value.as_str(), edge.value))
+                .collect()
+        }
+
+        #[test]
+        fn test_round_trip() {
+            let mut graph: OrderedDag<String, i64> = OrderedDag::new();
+            let root = graph.insert("root".to_string());
+            let a = graph.insert("a".to_string());
+            let b = graph.insert("b".to_string());
+            let c = graph.insert("c".to_string());
+            graph.set_edge(root, c, 3).unwrap();
+            graph.set_edge(root, b, 2).unwrap();
+            graph.set_edge(root, a, 1).unwrap();
+
+            // Reuse the slot of a removed node
+            graph.remove_node(b);
+            let d = graph.insert("d".to_string());
+            graph.set_edge(c, d, 4).unwrap();
+            graph.set_edge(a, d, 5).unwrap();
+
+            let persisted_ids = graph.persisted_ids();
+            let contents = toml::to_string(&graph).unwrap();
+            let mut deserializer = toml::Deserializer::new(&contents);
+            let (loaded, node_ids) =
+                OrderedDag::<String, i64>::deserialize_with_ids(&mut deserializer).unwrap();
+
+            assert_eq!(graph.len(), loaded.len());
+            for node_id in &[root, a, c, d] {
+                let loaded_id = node_ids[persisted_ids[node_id]];
+                assert_eq!(graph.node(*node_id), loaded.node(loaded_id));
+                assert_eq!(children(&graph, *node_id), children(&loaded, loaded_id));
+            }
+
+            let loaded_d = node_ids[persisted_ids[&d]];
+            assert_eq!(2, loaded.parents(loaded_d).count());
+        }
+
+        #[test]
+        fn test_invalid_edges() {
+            let unknown_node = "[[nodes]]\nvalue = 1\nedges = [{ target = 1, value = 0 }]\n";
+            let result: Result<OrderedDag<i64, i64>, _> = toml::from_str(unknown_node);
+            assert!(result.is_err());
+
+            let cycle = "[[nodes]]\nvalue = 1\nedges = [{ target = 0, value = 0 }]\n";
+            let result: Result<OrderedDag<i64, i64>, _> = toml::from_str(cycle);
+            assert!(result.is_err());
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -741,4 +1269,76 @@ mod test {
         println!("{}", graph.string());
         assert!(graph.check_cycle(node_1).is_some());
     }
+
+    #[test]
+    fn test_parent_index() {
+        let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+
+        let node_1 = graph.insert(1);
+        let node_2 = graph.insert_at(2, Some(node_1));
+        let node_3 = graph.insert_at(3, Some(node_2));
+        let node_4 = graph.insert_at(4, Some(node_1));
+
+        graph.reparent(node_3, node_4).unwrap();
+        assert_eq!(graph.parents(node_3).collect::<Vec<_>>(), vec![node_4]);
+
+        // Failed cycle check leaves no trace in the index.
+        assert!(graph.set_edge(node_3, node_1, 0).is_err());
+        assert_eq!(graph.parents(node_1).count(), 0);
+
+        graph.add_edge(node_2, node_3, 0).unwrap();
+        graph.remove_subtree(node_4);
+        assert_eq!(graph.node(node_3), None);
+        assert_eq!(graph.out_edge_len(node_2), Some(0));
+        assert_eq!(graph.parents(node_2).collect::<Vec<_>>(), vec![node_1]);
+
+        // Every edge has a matching parent entry.
+        for (node_id, node) in graph.nodes.iter() {
+            for edge in &node.edges {
+                assert!(graph.parents(edge.child).any(|id| id == node_id));
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_cycle_deep_chain() {
+        let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+
+        let node_ids: Vec<NodeId> = (0..100_000).map(|i| graph.insert(i)).collect();
+        for pair in node_ids.windows(2) {
+            graph.set_edge_unchecked(pair[0], pair[1], 0).unwrap();
+        }
+        let first = node_ids[0];
+        let last = *node_ids.last().unwrap();
+        assert_eq!(graph.check_cycle(first), None);
+
+        let result = graph.set_edge(last, first, 0);
+        assert_eq!(result, Err(OrderedGraphError::Cycle));
+        assert_eq!(graph.out_edge_len(last), Some(0));
+    }
+
+    #[test]
+    fn test_check_cycle_behind_black_sibling() {
+        let mut graph: OrderedDag<i64, i64> = OrderedDag::new();
+
+        //   a
+        //  / \
+        // b <- c
+        //       \
+        //        d -> a
+        //
+        // `b` is fully searched before `c`, so it's black when
+        // visited again as the first child of `c`.
+        let a = graph.insert(1);
+        let b = graph.insert(2);
+        let c = graph.insert(3);
+        let d = graph.insert(4);
+        graph.set_edge(a, b, 0).unwrap();
+        graph.set_edge(a, c, 0).unwrap();
+        graph.set_edge(c, b, 0).unwrap();
+        graph.set_edge(c, d, 0).unwrap();
+
+        assert_eq!(graph.set_edge(d, a, 0), Err(OrderedGraphError::Cycle));
+        assert_eq!(graph.parent(a), None);
+    }
 }
@@ -0,0 +1,203 @@
+//! Pre-allocated object pool.
+//!
+//! Systems that spawn and despawn many short lived objects every
+//! frame, like particles, can reuse pooled values instead of
+//! allocating new ones.
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// Fixed set of values that are handed out, and returned when
+/// no longer needed.
+///
+/// Acquiring moves a value out of the pool into a `PoolHandle`, and
+/// releasing moves it back, so neither allocates. Values keep their
+/// state between uses, and should be reset by the caller.
+///
+/// A handle that is dropped instead of released takes its value
+/// with it, and its slot is never used again.
+///
+/// # Example
+///
+/// ```
+/// use rengine::collections::Pool;
+///
+/// let mut pool: Pool<Vec<u8>> = Pool::new(2, || Vec::with_capacity(64));
+///
+/// let mut buffer = pool.acquire().unwrap();
+/// buffer.push(1);
+/// assert_eq!(pool.len_free(), 1);
+///
+/// pool.release(buffer);
+/// assert_eq!(pool.len_free(), 2);
+/// ```
+pub struct Pool<T> {
+    items: Vec<Option<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Pool<T> {
+    /// Creates a pool filled with the given number of values.
+    pub fn new<F>(capacity: usize, init: F) -> Self
+    where
+        F: Fn() -> T,
+    {
+        Pool {
+            items: (0..capacity).map(|_| Some(init())).collect(),
+            // Free list pops from the back, so the first slot is used first.
+            free: (0..capacity).rev().collect(),
+        }
+    }
+
+    /// Takes a value out of the pool.
+    ///
+    /// Returns None when all values are in use.
+    pub fn acquire(&mut self) -> Option<PoolHandle<T>> {
+        let index = self.free.pop()?;
+        let item = self.items[index].take().expect("Free pool slot is empty");
+        Some(PoolHandle { index, item })
+    }
+
+    /// Takes a value out of the pool, creating a new
+    /// one when all values are in use.
+    ///
+    /// The new value stays in the pool when released.
+    pub fn try_acquire_or_grow<F>(&mut self, init: F) -> PoolHandle<T>
+    where
+        F: Fn() -> T,
+    {
+        match self.acquire() {
+            Some(handle) => handle,
+            None => {
+                let index = self.items.len();
+                self.items.push(None);
+                PoolHandle {
+                    index,
+                    item: init(),
+                }
+            }
+        }
+    }
+
+    /// Returns a value to the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle was acquired from another pool.
+    pub fn release(&mut self, handle: PoolHandle<T>) {
+        let PoolHandle { index, item } = handle;
+        match self.items.get_mut(index) {
+            Some(slot) if slot.is_none() => {
+                *slot = Some(item);
+                self.free.push(index);
+            }
+            _ => panic!("Pool handle {} was not acquired from this pool", index),
+        }
+    }
+
+    /// Number of values that can be acquired.
+    #[inline]
+    pub fn len_free(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Number of values the pool holds, including
+    /// the values in use.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.items.len()
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("capacity", &self.capacity())
+            .field("len_free", &self.len_free())
+            .finish()
+    }
+}
+
+/// Value acquired from a `Pool`.
+///
+/// Must be given back with `Pool::release` to be reused.
+#[derive(Debug)]
+pub struct PoolHandle<T> {
+    index: usize,
+    item: T,
+}
+
+impl<T> PoolHandle<T> {
+    /// Slot of the value in its pool.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Deref for PoolHandle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl<T> DerefMut for PoolHandle<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_acquire_release() {
+        let counter = Cell::new(0);
+        let next_id = || {
+            counter.set(counter.get() + 1);
+            counter.get()
+        };
+        let mut pool = Pool::new(8, next_id);
+
+        let mut handles: Vec<_> = (0..8).map(|_| pool.acquire().unwrap()).collect();
+        assert!(pool.acquire().is_none());
+        assert_eq!(0, pool.len_free());
+
+        for handle in handles.drain(4..) {
+            pool.release(handle);
+        }
+        assert_eq!(4, pool.len_free());
+
+        while let Some(handle) = pool.acquire() {
+            handles.push(handle);
+        }
+
+        // Every value is handed out exactly once.
+        let mut ids: Vec<i32> = handles.iter().map(|handle| **handle).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(8, ids.len());
+        assert_eq!(8, counter.get());
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut pool = Pool::new(1, || 0);
+
+        let mut first = pool.try_acquire_or_grow(|| 0);
+        let second = pool.try_acquire_or_grow(|| 1);
+        *first += 10;
+        assert_eq!(2, pool.capacity());
+        assert_ne!(first.index(), second.index());
+
+        pool.release(first);
+        pool.release(second);
+        assert_eq!(2, pool.len_free());
+
+        let handle = pool.acquire().unwrap();
+        assert_eq!(1, *handle);
+    }
+}
@@ -1,3 +1,4 @@
+use super::{upload_geometry, LocalGeometry, TexRect};
 use crate::colors::Color;
 use crate::gfx_types::{Transform, Vertex};
 use crate::graphics::GraphicContext;
@@ -12,11 +13,22 @@ pub struct GuiMesh {
     pub(crate) vbuf: Buffer<gfx_device::Resources, Vertex>,
     pub(crate) slice: Slice<gfx_device::Resources>,
     pub(crate) transbuf: Buffer<gfx_device::Resources, Transform>,
+    /// Geometry retained so `DrawGuiSystem` can merge widgets into
+    /// batched draw calls. `None` when the mesh is too large.
+    pub(crate) local: Option<LocalGeometry>,
+}
+
+impl GuiMesh {
+    /// Indicates whether the renderer may merge this mesh with others.
+    #[inline]
+    pub fn is_batchable(&self) -> bool {
+        self.local.is_some()
+    }
 }
 
 pub struct GuiMeshBuilder {
     vertices: Vec<Vertex>,
-    indices: Vec<u16>,
+    indices: Vec<u32>,
 }
 
 impl Default for GuiMeshBuilder {
@@ -35,8 +47,8 @@ impl GuiMeshBuilder {
 
     /// New vertices will be inserted starting here
     #[inline]
-    fn next_index(&self) -> u16 {
-        self.vertices.len() as u16
+    fn next_index(&self) -> u32 {
+        self.vertices.len() as u32
     }
 
     /// Creates a quad with an origin at the bottom left.
@@ -75,20 +87,35 @@ impl GuiMeshBuilder {
         self
     }
 
+    /// Creates a quad textured with the given rectangle, such
+    /// as a named sprite of a `TextureAtlas`.
+    pub fn quad_with_rect<V>(
+        self,
+        position: V,
+        size: [f32; 2],
+        colors: [Color; 4],
+        rect: TexRect,
+    ) -> Self
+    where
+        V: Into<glm::Vec2>,
+    {
+        self.quad(position, size, colors, rect.into())
+    }
+
     pub fn nine_patch(self) -> Self {
         unimplemented!()
     }
 
     pub fn build(self, ctx: &mut GraphicContext) -> GuiMesh {
-        let (vbuf, slice) = ctx
-            .factory
-            .create_vertex_buffer_with_slice(&self.vertices[..], &self.indices[..]);
+        let (vbuf, slice) = upload_geometry(&mut ctx.factory, &self.vertices, &self.indices);
         let transbuf = ctx.factory.create_constant_buffer(1);
+        let local = LocalGeometry::new(self.vertices, &self.indices);
 
         GuiMesh {
             vbuf,
             slice,
             transbuf,
+            local,
         }
     }
 }
@@ -104,5 +131,6 @@ where
         uv,
         normal: [0.0, 0.0, 1.0],
         color,
+        layer: 0.0,
     }
 }
@@ -0,0 +1,339 @@
+use super::Transform;
+use glm::{Mat3x3, Mat4x4, Vec3};
+use log::error;
+use nalgebra::U3;
+use specs::{Component, DenseVecStorage, Entities, Entity, Join, System, WriteStorage};
+use std::collections::HashMap;
+
+/// Attaches an entity to another, so its `Transform`
+/// is relative to the parent's.
+///
+/// When the parent is deleted, the child is left
+/// where it was in the world.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(DenseVecStorage)]
+pub struct Parent(pub Entity);
+
+impl Parent {
+    #[inline]
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+}
+
+/// World matrix of an entity, combining its `Transform`
+/// with the transforms of its parents.
+///
+/// Maintained by `TransformHierarchySystem`.
+#[derive(Component, Debug, Clone)]
+#[storage(DenseVecStorage)]
+pub struct GlobalTransform {
+    matrix: Mat4x4,
+}
+
+impl GlobalTransform {
+    #[inline]
+    pub fn matrix(&self) -> &Mat4x4 {
+        &self.matrix
+    }
+
+    /// Position in world space.
+    #[inline]
+    pub fn position(&self) -> Vec3 {
+        self.matrix.column(3).xyz()
+    }
+
+    /// World matrix of an entity, which is its local `Transform`
+    /// when it has no `GlobalTransform`.
+    pub fn or_local(global: Option<&GlobalTransform>, transform: &Transform) -> Mat4x4 {
+        global
+            .map(|global| global.matrix)
+            .unwrap_or_else(|| transform.matrix())
+    }
+}
+
+/// Computes the `GlobalTransform` of every entity with a `Transform`.
+///
+/// Children whose parent was deleted keep their last world pose, and have
+/// their `Parent` removed. Parents without a `Transform` don't move their
+/// children. Cycles of parents are broken by removing a `Parent`.
+#[derive(Default)]
+pub struct TransformHierarchySystem {
+    /// World matrices of the entities in a hierarchy, so
+    /// shared ancestors are only computed once.
+    world_matrices: HashMap<Entity, Mat4x4>,
+    /// Entities from a child up to its first ancestor with
+    /// a known world matrix.
+    chain: Vec<Entity>,
+    /// Entities collected before their storages are changed.
+    buffer: Vec<Entity>,
+}
+
+impl TransformHierarchySystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for TransformHierarchySystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, GlobalTransform>,
+    );
+
+    fn run(&mut self, (entities, mut transforms, mut parents, mut globals): Self::SystemData) {
+        let TransformHierarchySystem {
+            world_matrices,
+            chain,
+            buffer,
+        } = self;
+
+        // Orphans stay at the world pose of the last frame.
+        buffer.clear();
+        buffer.extend(
+            (&entities, &parents)
+                .join()
+                .filter(|(_, parent)| !entities.is_alive(parent.0))
+                .map(|(entity, _)| entity),
+        );
+
+        for entity in buffer.drain(..) {
+            parents.remove(entity);
+
+            if let (Some(transform), Some(global)) =
+                (transforms.get_mut(entity), globals.get(entity))
+            {
+                transform.set_matrix(&global.matrix);
+            }
+        }
+
+        // Only entities with a parent have to walk the hierarchy.
+        world_matrices.clear();
+        buffer.extend(
+            (&entities, &transforms, &parents)
+                .join()
+                .map(|(entity, _, _)| entity),
+        );
+
+        for entity in buffer.iter().cloned() {
+            // Walk up to the first ancestor with a known world matrix.
+            chain.clear();
+            let mut base = Mat4x4::identity();
+            let mut next = Some(entity);
+
+            while let Some(current) = next {
+                if let Some(matrix) = world_matrices.get(&current) {
+                    base = *matrix;
+                    break;
+                }
+                chain.push(current);
+
+                next = match parents.get(current).map(Parent::entity) {
+                    Some(parent) if chain.contains(&parent) => {
+                        error!("Transform hierarchy cycle at entity {:?}", current);
+                        parents.remove(current);
+                        None
+                    }
+                    parent => parent,
+                };
+            }
+
+            for current in chain.drain(..).rev() {
+                if let Some(transform) = transforms.get(current) {
+                    base *= transform.matrix();
+                }
+                world_matrices.insert(current, base);
+            }
+        }
+
+        for entity in buffer.drain(..) {
+            let matrix = world_matrices[&entity];
+            if let Err(err) = globals.insert(entity, GlobalTransform { matrix }) {
+                error!("Failed to insert global transform: {}", err);
+            }
+        }
+
+        // Entities without a parent are placed by their own transform.
+        for (entity, transform, _) in (&entities, &transforms, !&parents).join() {
+            let matrix = transform.matrix();
+            if let Err(err) = globals.insert(entity, GlobalTransform { matrix }) {
+                error!("Failed to insert global transform: {}", err);
+            }
+        }
+
+        buffer.extend(
+            (&entities, &globals, !&transforms)
+                .join()
+                .map(|(entity, _, _)| entity),
+        );
+        for entity in buffer.drain(..) {
+            globals.remove(entity);
+        }
+    }
+}
+
+/// Methods to place the `Transform` using a matrix
+impl Transform {
+    /// Sets the position, rotation and scale of the transform
+    /// from a matrix, keeping its anchor.
+    ///
+    /// Shearing, as created by rotating children of non-uniformly
+    /// scaled parents, can't be represented and is lost.
+    pub fn set_matrix(&mut self, matrix: &Mat4x4) {
+        // Transform matrices scale after rotating,
+        // so each row is scaled.
+        let linear: Mat3x3 = matrix.fixed_slice::<U3, U3>(0, 0).into_owned();
+        let mut scale = glm::vec3(
+            linear.row(0).norm(),
+            linear.row(1).norm(),
+            linear.row(2).norm(),
+        );
+        if linear.determinant() < 0.0 {
+            // Mirrored
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = linear;
+        for i in 0..3 {
+            if scale[i].abs() > ::std::f32::EPSILON {
+                let row = rotation.row(i) / scale[i];
+                rotation.set_row(i, &row);
+            }
+        }
+
+        // The anchor is moved to the origin before rotating.
+        let translation = matrix.column(3).xyz() + linear * self.anchor;
+        self.pos = translation.component_div(&scale.map(|s| {
+            if s.abs() > ::std::f32::EPSILON {
+                s
+            } else {
+                1.0
+            }
+        }));
+        self.rot = glm::mat3_to_quat(&rotation);
+        self.scale = scale;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::comp::{Y_AXIS, Z_AXIS};
+    use specs::{Builder, RunNow, World};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn create_world() -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+        world.register::<GlobalTransform>();
+        world
+    }
+
+    fn world_position(world: &World, entity: Entity) -> Vec3 {
+        world
+            .read_storage::<GlobalTransform>()
+            .get(entity)
+            .unwrap()
+            .position()
+    }
+
+    #[test]
+    fn test_grandparent_rotation() {
+        let mut world = create_world();
+        let grandparent = world
+            .create_entity()
+            .with(Transform::new().with_rotation(FRAC_PI_2, Y_AXIS))
+            .build();
+        let parent = world
+            .create_entity()
+            .with(Transform::new().with_position([1.0, 0.0, 0.0]))
+            .with(Parent(grandparent))
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::new().with_position([1.0, 0.0, 0.0]))
+            .with(Parent(parent))
+            .build();
+
+        TransformHierarchySystem::new().run_now(&world.res);
+
+        // Turned a quarter around the Y axis, from X to negative Z.
+        let pos = world_position(&world, child);
+        assert!((pos - glm::vec3(0.0, 0.0, -2.0)).norm() < 1.0e-5);
+        let pos = world_position(&world, grandparent);
+        assert!(pos.norm() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_deleted_parent() {
+        let mut world = create_world();
+        let parent = world
+            .create_entity()
+            .with(
+                Transform::new()
+                    .with_position([0.0, 3.0, 0.0])
+                    .with_rotation(FRAC_PI_2, Z_AXIS),
+            )
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::new().with_position([2.0, 0.0, 0.0]))
+            .with(Parent(parent))
+            .build();
+
+        TransformHierarchySystem::new().run_now(&world.res);
+        let before = world_position(&world, child);
+        assert!((before - glm::vec3(0.0, 5.0, 0.0)).norm() < 1.0e-5);
+
+        world.delete_entity(parent).unwrap();
+        world.maintain();
+        TransformHierarchySystem::new().run_now(&world.res);
+
+        assert!(world.read_storage::<Parent>().get(child).is_none());
+        assert!((world_position(&world, child) - before).norm() < 1.0e-5);
+
+        let transforms = world.read_storage::<Transform>();
+        let transform = transforms.get(child).unwrap();
+        assert!((transform.position() - before).norm() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut world = create_world();
+        let a = world.create_entity().with(Transform::new()).build();
+        let b = world.create_entity().with(Transform::new()).build();
+        world
+            .write_storage::<Parent>()
+            .insert(a, Parent(b))
+            .unwrap();
+        world
+            .write_storage::<Parent>()
+            .insert(b, Parent(a))
+            .unwrap();
+
+        TransformHierarchySystem::new().run_now(&world.res);
+
+        // One link is broken, and both are placed.
+        assert_eq!(1, world.read_storage::<Parent>().join().count());
+        assert_eq!(2, world.read_storage::<GlobalTransform>().join().count());
+    }
+
+    #[test]
+    fn test_set_matrix() {
+        let original = Transform::new()
+            .with_anchor([0.5, 0.5, 0.0])
+            .with_position([1.0, 2.0, 3.0])
+            .with_scale([2.0, 1.0, 0.5])
+            .with_rotation(1.0, Y_AXIS);
+
+        let mut transform = Transform::new().with_anchor([0.5, 0.5, 0.0]);
+        transform.set_matrix(&original.matrix());
+
+        assert!((transform.matrix() - original.matrix()).norm() < 1.0e-5);
+        assert!((transform.position() - original.position()).norm() < 1.0e-5);
+        assert!((transform.scale() - original.scale()).norm() < 1.0e-5);
+    }
+}
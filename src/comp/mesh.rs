@@ -1,26 +1,210 @@
+use super::obj::parse_obj;
 use crate::colors::{Color, WHITE};
-use crate::comp::TexRect;
+use crate::comp::{GuiMesh, GuiMeshBuilder, TexRect};
+use crate::errors;
 use crate::gfx_types::{Transform, Vertex};
 use crate::graphics::GraphicContext;
 use gfx::handle::Buffer;
 use gfx::traits::FactoryExt;
 use gfx::Slice;
 use specs::prelude::*;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+use std::io::BufRead;
 
 // http://ilkinulas.github.io/development/unity/2016/05/06/uv-mapping.html
 
+/// Meshes with at most this many vertices keep a copy of their
+/// geometry in main memory, so the renderer can merge them into
+/// batched draw calls.
+pub const BATCH_VERTEX_LIMIT: usize = 256;
+
+/// How far apart the flat normals of triangles sharing a vertex
+/// may be, as one minus the cosine of their angle, before the
+/// vertex is split.
+const FLAT_NORMAL_TOLERANCE: f32 = 1.0e-5;
+
+/// Number of vertices addressable by 16-bit indices.
+const MAX_U16_VERTICES: usize = ::std::u16::MAX as usize + 1;
+
+/// Width of the indices a mesh is drawn with.
+///
+/// Meshes are built with 32-bit indices, and uploaded with 16-bit
+/// indices when they have few enough vertices, to save memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexFormat {
+    U16,
+    U32,
+}
+
+impl IndexFormat {
+    /// Narrowest format that can address the given number of vertices.
+    #[inline]
+    pub fn for_vertex_count(vertex_count: usize) -> Self {
+        if vertex_count <= MAX_U16_VERTICES {
+            IndexFormat::U16
+        } else {
+            IndexFormat::U32
+        }
+    }
+}
+
 #[derive(Component)]
 #[storage(DenseVecStorage)]
 pub struct Mesh {
     pub(crate) vbuf: Buffer<gfx_device::Resources, Vertex>,
     pub(crate) slice: Slice<gfx_device::Resources>,
     pub(crate) transbuf: Buffer<gfx_device::Resources, Transform>,
+    /// Model space geometry retained for batching. `None` when the
+    /// mesh is too large to be worth merging.
+    pub(crate) local: Option<LocalGeometry>,
+    vertex_count: u32,
+    index_count: u32,
+    index_format: IndexFormat,
+    /// Minimum and maximum corners of the geometry, in model space.
+    aabb: (glm::Vec3, glm::Vec3),
+    /// Centroid of the vertices, and the distance to the furthest vertex.
+    bounding_sphere: (glm::Vec3, f32),
+}
+
+impl Mesh {
+    /// Indicates whether the renderer may merge this mesh with others.
+    #[inline]
+    pub fn is_batchable(&self) -> bool {
+        self.local.is_some()
+    }
+
+    #[inline]
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    #[inline]
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Width of the indices in the mesh's index buffer.
+    #[inline]
+    pub fn index_format(&self) -> IndexFormat {
+        self.index_format
+    }
+
+    /// Axis aligned bounding box of the geometry in model space,
+    /// as its minimum and maximum corners.
+    #[inline]
+    pub fn aabb(&self) -> (glm::Vec3, glm::Vec3) {
+        self.aabb
+    }
+
+    /// Sphere containing the geometry in model space, as the
+    /// centroid of the vertices and a radius.
+    #[inline]
+    pub fn bounding_sphere(&self) -> (glm::Vec3, f32) {
+        self.bounding_sphere
+    }
+}
+
+/// Copy of a mesh's vertices and indices, in model space.
+pub(crate) struct LocalGeometry {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u16>,
+}
+
+impl LocalGeometry {
+    /// Retains the geometry when the mesh is small enough to be batched.
+    pub(crate) fn new(vertices: Vec<Vertex>, indices: &[u32]) -> Option<Self> {
+        if vertices.len() <= BATCH_VERTEX_LIMIT {
+            Some(LocalGeometry {
+                vertices,
+                indices: indices.iter().map(|index| *index as u16).collect(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Uploads vertices and indices to graphics memory, using 16-bit
+/// indices when they can address all vertices.
+pub(crate) fn upload_geometry(
+    factory: &mut gfx_device::Factory,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> (
+    Buffer<gfx_device::Resources, Vertex>,
+    Slice<gfx_device::Resources>,
+) {
+    match IndexFormat::for_vertex_count(vertices.len()) {
+        IndexFormat::U16 => {
+            let indices: Vec<u16> = indices.iter().map(|index| *index as u16).collect();
+            factory.create_vertex_buffer_with_slice(vertices, &indices[..])
+        }
+        IndexFormat::U32 => factory.create_vertex_buffer_with_slice(vertices, indices),
+    }
+}
+
+/// Ring of vertices in a surface of revolution.
+struct LatheRow {
+    /// Distance from the y axis.
+    radius: f32,
+    /// Height relative to the center of the shape.
+    y: f32,
+    /// Normal pointing away from, and along, the y axis.
+    normal: [f32; 2],
+    /// Texture coordinate along the height of the shape.
+    v: f32,
+}
+
+impl LatheRow {
+    /// Row on a sphere at the angle from the north pole, shifted
+    /// along the y axis by the offset.
+    fn on_sphere(theta: f32, radius: f32, offset: f32, v: f32) -> Self {
+        let (sin, cos) = theta.sin_cos();
+
+        LatheRow {
+            // Exactly zero at the poles, which have no band of quads.
+            radius: if sin.abs() < 1.0e-6 {
+                0.0
+            } else {
+                radius * sin
+            },
+            y: offset + radius * cos,
+            normal: [sin, cos],
+            v,
+        }
+    }
+}
+
+/// Bit patterns of a vector, for using it as a key.
+fn float_bits(vector: [f32; 3]) -> [u32; 3] {
+    let [x, y, z] = vector;
+    [x.to_bits(), y.to_bits(), z.to_bits()]
+}
+
+/// Normal of a quad with its corners in counter-clockwise order,
+/// from the cross product of its diagonals.
+///
+/// Returns `None` when the quad is degenerate.
+fn quad_normal(corners: [[f32; 3]; 4]) -> Option<[f32; 3]> {
+    let [a, b, c, d] = corners;
+    let diagonal_ac = glm::Vec3::from(c) - glm::Vec3::from(a);
+    let diagonal_bd = glm::Vec3::from(d) - glm::Vec3::from(b);
+    let normal = glm::cross(&diagonal_ac, &diagonal_bd);
+
+    if normal.norm() > ::std::f32::EPSILON {
+        Some(normal.normalize().into())
+    } else {
+        None
+    }
 }
 
 pub struct MeshBuilder {
     vertices: Vec<Vertex>,
-    indices: Vec<u16>,
+    indices: Vec<u32>,
+    /// Set when geometry was added from arbitrary points, so its
+    /// normals may not match its faces.
+    normals_dirty: bool,
 }
 
 impl Default for MeshBuilder {
@@ -28,6 +212,7 @@ impl Default for MeshBuilder {
         MeshBuilder {
             vertices: vec![],
             indices: vec![],
+            normals_dirty: false,
         }
     }
 }
@@ -37,10 +222,166 @@ impl MeshBuilder {
         Default::default()
     }
 
+    /// Loads the geometry of a Wavefront OBJ model.
+    ///
+    /// Positions, texture coordinates and normals are read, and faces
+    /// are triangulated. Faces without normals get flat normals. Vertices
+    /// are deduplicated, so 16-bit indices are used whenever possible.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let file = BufReader::new(File::open("examples/pyramid.obj")?);
+    /// let mesh = MeshBuilder::from_obj(file)?.build(&mut ctx.graphics);
+    /// ```
+    pub fn from_obj<R: BufRead>(reader: R) -> errors::Result<MeshBuilder> {
+        let geometry = parse_obj(reader)?;
+
+        Ok(MeshBuilder {
+            vertices: geometry.vertices,
+            indices: geometry.indices,
+            normals_dirty: false,
+        })
+    }
+
     /// New vertices will be inserted starting here
     #[inline]
-    fn next_index(&self) -> u16 {
-        self.vertices.len() as u16
+    fn next_index(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    /// Width of the indices the mesh will be built with, depending
+    /// on the number of vertices added so far.
+    #[inline]
+    pub fn index_format(&self) -> IndexFormat {
+        IndexFormat::for_vertex_count(self.vertices.len())
+    }
+
+    /// Checks whether geometry was added whose normals may be wrong,
+    /// since the normals were last recalculated.
+    #[inline]
+    pub fn normals_dirty(&self) -> bool {
+        self.normals_dirty
+    }
+
+    /// Calculates the normals of all vertices from the triangles
+    /// they're part of.
+    ///
+    /// Each triangle's normal is the cross product of two of its edges,
+    /// following the counter-clockwise winding.
+    ///
+    /// Flat normals give each triangle its own normal. Vertices shared
+    /// by triangles facing different ways are split, so the mesh may
+    /// gain vertices.
+    ///
+    /// Smooth normals are the normalized sum of the normals of all
+    /// triangles touching a position, weighted by the triangle's angle
+    /// at that corner, so how quads are split into triangles doesn't
+    /// skew the result. Separate vertices at the same position, like at
+    /// texture seams, get the same normal.
+    ///
+    /// Vertices that aren't part of any triangle keep their normals.
+    pub fn recalculate_normals(&mut self, smooth: bool) {
+        if smooth {
+            self.recalculate_smooth_normals();
+        } else {
+            self.recalculate_flat_normals();
+        }
+
+        self.normals_dirty = false;
+    }
+
+    /// Recalculates the normals of the geometry added so far.
+    ///
+    /// See `recalculate_normals`.
+    pub fn with_normals_recalculated(mut self, smooth: bool) -> Self {
+        self.recalculate_normals(smooth);
+        self
+    }
+
+    /// Normal of a triangle, with a length of twice its area.
+    fn triangle_normal(&self, triangle: &[u32]) -> glm::Vec3 {
+        let pos_a = glm::Vec3::from(self.vertices[triangle[0] as usize].pos);
+        let pos_b = glm::Vec3::from(self.vertices[triangle[1] as usize].pos);
+        let pos_c = glm::Vec3::from(self.vertices[triangle[2] as usize].pos);
+
+        glm::cross(&(pos_b - pos_a), &(pos_c - pos_a))
+    }
+
+    fn recalculate_smooth_normals(&mut self) {
+        let mut normals: HashMap<[u32; 3], glm::Vec3> = HashMap::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            let face_normal = self.triangle_normal(triangle);
+            if face_normal.norm() <= ::std::f32::EPSILON {
+                // Degenerate triangle
+                continue;
+            }
+            let face_normal = face_normal.normalize();
+
+            for corner in 0..3 {
+                let pos = |offset: usize| {
+                    glm::Vec3::from(self.vertices[triangle[(corner + offset) % 3] as usize].pos)
+                };
+                let angle = glm::angle(&(pos(1) - pos(0)), &(pos(2) - pos(0)));
+
+                let key = float_bits(self.vertices[triangle[corner] as usize].pos);
+                *normals.entry(key).or_insert_with(glm::Vec3::zeros) += face_normal * angle;
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            if let Some(normal) = normals.get(&float_bits(vertex.pos)) {
+                if normal.norm() > ::std::f32::EPSILON {
+                    vertex.normal = normal.normalize().into();
+                }
+            }
+        }
+    }
+
+    fn recalculate_flat_normals(&mut self) {
+        // Normal each vertex has been given by a triangle so far.
+        let mut assigned: Vec<Option<glm::Vec3>> = vec![None; self.vertices.len()];
+        // Copies of vertices split off for differently facing triangles,
+        // keyed by the original index and normal.
+        let mut splits: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+        for first in (0..self.indices.len() / 3 * 3).step_by(3) {
+            let face_normal = self.triangle_normal(&self.indices[first..first + 3]);
+            if face_normal.norm() <= ::std::f32::EPSILON {
+                // Degenerate triangle
+                continue;
+            }
+            let normal = face_normal.normalize();
+
+            for slot in first..first + 3 {
+                let index = self.indices[slot];
+
+                match assigned[index as usize] {
+                    None => {
+                        assigned[index as usize] = Some(normal);
+                        self.vertices[index as usize].normal = normal.into();
+                    }
+                    Some(existing) => {
+                        if glm::dot(&existing, &normal) > 1.0 - FLAT_NORMAL_TOLERANCE {
+                            continue;
+                        }
+
+                        let normal: [f32; 3] = normal.into();
+                        let key = (index, float_bits(normal));
+                        let vertices = &mut self.vertices;
+                        let split = *splits.entry(key).or_insert_with(|| {
+                            let mut vertex = vertices[index as usize];
+                            vertex.normal = normal;
+                            vertices.push(vertex);
+                            vertices.len() as u32 - 1
+                        });
+
+                        self.indices[slot] = split;
+                    }
+                }
+            }
+        }
     }
 
     /// Create a pseudocube from the given points, representing the corners.
@@ -55,6 +396,10 @@ impl MeshBuilder {
     /// | p5    | 1 | 0 | 1 |
     /// | p6    | 1 | 1 | 0 |
     /// | p7    | 1 | 1 | 1 |
+    ///
+    /// Each face gets a normal from its corners, so faces of deformed
+    /// cubes are lit correctly. Faces that aren't planar get an average
+    /// normal, which can be refined with `recalculate_normals`.
     pub fn pseudocube_points<V>(mut self, points: [V; 8], texture_rects: [TexRect; 6]) -> Self
     where
         V: Into<glm::Vec3>,
@@ -72,6 +417,7 @@ impl MeshBuilder {
         ];
         let [back_tex, front_tex, left_tex, right_tex, bottom_tex, top_tex] = texture_rects;
         let index = self.next_index();
+        self.normals_dirty = true;
 
         // Back Quad
         let normal = glm::vec3(0., 0., -1.).into();
@@ -81,24 +427,28 @@ impl MeshBuilder {
                 uv: [back_tex.x(), back_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p0,
                 uv: [back_tex.w(), back_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p2,
                 uv: [back_tex.w(), back_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p6,
                 uv: [back_tex.x(), back_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -116,24 +466,28 @@ impl MeshBuilder {
                 uv: [front_tex.x(), front_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p5,
                 uv: [front_tex.w(), front_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p7,
                 uv: [front_tex.w(), front_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p3,
                 uv: [front_tex.x(), front_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -151,24 +505,28 @@ impl MeshBuilder {
                 uv: [left_tex.x(), left_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p1,
                 uv: [left_tex.w(), left_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p3,
                 uv: [left_tex.w(), left_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p2,
                 uv: [left_tex.x(), left_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -186,24 +544,28 @@ impl MeshBuilder {
                 uv: [right_tex.x(), right_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p4,
                 uv: [right_tex.w(), right_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p6,
                 uv: [right_tex.w(), right_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p7,
                 uv: [right_tex.x(), right_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -221,24 +583,28 @@ impl MeshBuilder {
                 uv: [bottom_tex.x(), bottom_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p4,
                 uv: [bottom_tex.w(), bottom_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p5,
                 uv: [bottom_tex.w(), bottom_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p1,
                 uv: [bottom_tex.x(), bottom_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -256,24 +622,28 @@ impl MeshBuilder {
                 uv: [top_tex.x(), top_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p6,
                 uv: [top_tex.w(), top_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p2,
                 uv: [top_tex.w(), top_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: p3,
                 uv: [top_tex.x(), top_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -283,9 +653,43 @@ impl MeshBuilder {
         // triangle 12
         self.indices.extend(&[index + 20, index + 22, index + 23]);
 
+        // Replace the axis aligned normals when the corners are deformed.
+        for face in self.vertices[index as usize..].chunks_exact_mut(4) {
+            let corners = [face[0].pos, face[1].pos, face[2].pos, face[3].pos];
+
+            if let Some(normal) = quad_normal(corners) {
+                for vertex in face {
+                    vertex.normal = normal;
+                }
+            }
+        }
+
         self
     }
 
+    /// Create a pseudocube from the given points, with each face
+    /// covering a whole layer of a texture array.
+    ///
+    /// Points are ordered as in `pseudocube_points`, and layers in
+    /// the same order as its texture rectangles: back, front, left,
+    /// right, bottom and top.
+    pub fn pseudocube_points_layered<V>(self, points: [V; 8], layers: [u32; 6]) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        let start = self.vertices.len();
+        let mut builder = self.pseudocube_points(points, Default::default());
+
+        // Each face is a quad of four vertices.
+        for (face, vertices) in builder.vertices[start..].chunks_mut(4).enumerate() {
+            for vertex in vertices {
+                vertex.layer = layers[face] as f32;
+            }
+        }
+
+        builder
+    }
+
     pub fn pseudocube<V>(mut self, position: V, size: [f32; 3], texture_rects: [TexRect; 6]) -> Self
     where
         V: Into<glm::Vec3>,
@@ -303,24 +707,28 @@ impl MeshBuilder {
                 uv: [back_tex.x(), back_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y, pos.z],
                 uv: [back_tex.w(), back_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z],
                 uv: [back_tex.w(), back_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y + h, pos.z],
                 uv: [back_tex.x(), back_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -338,24 +746,28 @@ impl MeshBuilder {
                 uv: [front_tex.x(), front_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y, pos.z + d],
                 uv: [front_tex.w(), front_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y + h, pos.z + d],
                 uv: [front_tex.w(), front_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z + d],
                 uv: [front_tex.x(), front_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -373,24 +785,28 @@ impl MeshBuilder {
                 uv: [left_tex.x(), left_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y, pos.z + d],
                 uv: [left_tex.w(), left_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z + d],
                 uv: [left_tex.w(), left_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z],
                 uv: [left_tex.x(), left_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -408,24 +824,28 @@ impl MeshBuilder {
                 uv: [right_tex.x(), right_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y, pos.z],
                 uv: [right_tex.w(), right_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y + h, pos.z],
                 uv: [right_tex.w(), right_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y + h, pos.z + d],
                 uv: [right_tex.x(), right_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -443,24 +863,28 @@ impl MeshBuilder {
                 uv: [bottom_tex.x(), bottom_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y, pos.z],
                 uv: [bottom_tex.w(), bottom_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y, pos.z + d],
                 uv: [bottom_tex.w(), bottom_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y, pos.z + d],
                 uv: [bottom_tex.x(), bottom_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -478,24 +902,28 @@ impl MeshBuilder {
                 uv: [top_tex.x(), top_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z],
                 uv: [top_tex.w(), top_tex.h()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x, pos.y + h, pos.z + d],
                 uv: [top_tex.w(), top_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
             Vertex {
                 pos: [pos.x + w, pos.y + h, pos.z + d],
                 uv: [top_tex.x(), top_tex.y()],
                 normal,
                 color: WHITE,
+                layer: 0.0,
             },
         ]);
 
@@ -520,6 +948,30 @@ impl MeshBuilder {
         )
     }
 
+    /// Creates a quad textured with the given rectangle, such as
+    /// a named sprite of a `TextureAtlas`.
+    ///
+    /// Billboard sprites sharing an atlas are built this way, so they
+    /// can be drawn without switching textures.
+    ///
+    /// ```ignore
+    /// let mesh = MeshBuilder::new()
+    ///     .quad_with_rect([0.0, 0.0, 0.0], [1.0, 1.0], [WHITE; 4], atlas.get("skelly").unwrap())
+    ///     .build(graphics);
+    /// ```
+    pub fn quad_with_rect<V>(
+        self,
+        position: V,
+        size: [f32; 2],
+        colors: [Color; 4],
+        rect: TexRect,
+    ) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        self.quad_with_uvs(position, size, colors, rect.into())
+    }
+
     pub fn quad_with_uvs<V>(
         mut self,
         position: V,
@@ -544,6 +996,7 @@ impl MeshBuilder {
                 uv: uvs[0],
                 normal,
                 color: colors[0],
+                layer: 0.0,
             },
             // Bottom Right
             Vertex {
@@ -551,6 +1004,7 @@ impl MeshBuilder {
                 uv: uvs[1],
                 normal,
                 color: colors[1],
+                layer: 0.0,
             },
             // Top Right
             Vertex {
@@ -558,6 +1012,7 @@ impl MeshBuilder {
                 uv: uvs[2],
                 normal,
                 color: colors[2],
+                layer: 0.0,
             },
             // Top Left
             Vertex {
@@ -565,6 +1020,7 @@ impl MeshBuilder {
                 uv: uvs[3],
                 normal,
                 color: colors[3],
+                layer: 0.0,
             },
         ]);
 
@@ -606,9 +1062,11 @@ impl MeshBuilder {
         let [p1, p2, p3, p4] = points;
         let [p1, p2, p3, p4] = [p1.into(), p2.into(), p3.into(), p4.into()];
         let index = self.next_index();
+        self.normals_dirty = true;
 
-        // TODO: Calculate normal
-        let normal = glm::vec3(0., 0., 1.).into();
+        // Degenerate quads face the viewer.
+        let normal =
+            quad_normal([p1.into(), p2.into(), p3.into(), p4.into()]).unwrap_or([0.0, 0.0, 1.0]);
 
         self.vertices.extend(&[
             // Bottom Left
@@ -617,6 +1075,7 @@ impl MeshBuilder {
                 uv: uvs[0],
                 normal,
                 color: colors[0],
+                layer: 0.0,
             },
             // Bottom Right
             Vertex {
@@ -624,6 +1083,7 @@ impl MeshBuilder {
                 uv: uvs[1],
                 normal,
                 color: colors[1],
+                layer: 0.0,
             },
             // Top Right
             Vertex {
@@ -631,6 +1091,7 @@ impl MeshBuilder {
                 uv: uvs[2],
                 normal,
                 color: colors[2],
+                layer: 0.0,
             },
             // Top Left
             Vertex {
@@ -638,6 +1099,7 @@ impl MeshBuilder {
                 uv: uvs[3],
                 normal,
                 color: colors[3],
+                layer: 0.0,
             },
         ]);
 
@@ -650,21 +1112,417 @@ impl MeshBuilder {
         self
     }
 
+    /// Create a sphere with smooth normals, made of rings of
+    /// latitude divided into segments of longitude.
+    ///
+    /// Texture coordinates are equirectangular, wrapping the whole
+    /// texture around the sphere, with the top of the texture at
+    /// the north pole.
+    ///
+    /// # Panics
+    ///
+    /// Panics when there are fewer than 3 segments or 2 rings.
+    pub fn sphere<V>(mut self, center: V, radius: f32, segments: u32, rings: u32) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        assert!(segments >= 3, "Sphere needs at least 3 segments");
+        assert!(rings >= 2, "Sphere needs at least 2 rings");
+
+        let rows: Vec<LatheRow> = (0..=rings)
+            .map(|ring| {
+                let theta = ring as f32 / rings as f32 * PI;
+                LatheRow::on_sphere(theta, radius, 0.0, ring as f32 / rings as f32)
+            })
+            .collect();
+
+        self.lathe(center.into(), segments, &rows);
+        self
+    }
+
+    /// Create a cylinder standing along the y axis, with smooth
+    /// normals around its side and flat caps.
+    ///
+    /// The texture wraps around the side, with the top of the
+    /// texture at the top of the cylinder. The caps map the
+    /// texture onto a circle.
+    ///
+    /// # Panics
+    ///
+    /// Panics when there are fewer than 3 segments.
+    pub fn cylinder<V>(mut self, center: V, radius: f32, height: f32, segments: u32) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        assert!(segments >= 3, "Cylinder needs at least 3 segments");
+
+        let center = center.into();
+        let half = height / 2.0;
+        let rows = [
+            LatheRow {
+                radius,
+                y: half,
+                normal: [1.0, 0.0],
+                v: 0.0,
+            },
+            LatheRow {
+                radius,
+                y: -half,
+                normal: [1.0, 0.0],
+                v: 1.0,
+            },
+        ];
+
+        self.lathe(center, segments, &rows);
+        self.disc(center + glm::vec3(0.0, half, 0.0), radius, segments, true);
+        self.disc(center - glm::vec3(0.0, half, 0.0), radius, segments, false);
+        self
+    }
+
+    /// Create a cone standing along the y axis, with its base at
+    /// the bottom and its apex at the top.
+    ///
+    /// The side has smooth normals, and the texture wraps around
+    /// it like a cylinder. The base maps the texture onto a circle.
+    ///
+    /// # Panics
+    ///
+    /// Panics when there are fewer than 3 segments.
+    pub fn cone<V>(mut self, center: V, radius: f32, height: f32, segments: u32) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        assert!(segments >= 3, "Cone needs at least 3 segments");
+
+        let center = center.into();
+        let half = height / 2.0;
+
+        // Side normals lean up by the slope of the side.
+        let normal = [height, radius];
+        let rows = [
+            LatheRow {
+                radius: 0.0,
+                y: half,
+                normal,
+                v: 0.0,
+            },
+            LatheRow {
+                radius,
+                y: -half,
+                normal,
+                v: 1.0,
+            },
+        ];
+
+        self.lathe(center, segments, &rows);
+        self.disc(center - glm::vec3(0.0, half, 0.0), radius, segments, false);
+        self
+    }
+
+    /// Create a capsule standing along the y axis, as a cylinder
+    /// with hemispheres on both ends, with smooth normals.
+    ///
+    /// The height includes the hemispheres, so a capsule not taller
+    /// than its diameter is a sphere. Each hemisphere has half the
+    /// rings. Texture coordinates wrap around the capsule, with the
+    /// top of the texture at the top.
+    ///
+    /// # Panics
+    ///
+    /// Panics when there are fewer than 3 segments, or when the
+    /// rings aren't an even number of at least 2.
+    pub fn capsule<V>(
+        mut self,
+        center: V,
+        radius: f32,
+        height: f32,
+        segments: u32,
+        rings: u32,
+    ) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        assert!(segments >= 3, "Capsule needs at least 3 segments");
+        assert!(
+            rings >= 2 && rings % 2 == 0,
+            "Capsule needs an even number of rings, at least 2"
+        );
+
+        let half_cylinder = (height / 2.0 - radius).max(0.0);
+        let half_rings = rings / 2;
+
+        // The equator is repeated, once for each hemisphere, which
+        // adds the cylinder between them.
+        let bands = rings + 1;
+        let mut rows = Vec::with_capacity(bands as usize + 1);
+        for ring in 0..=rings {
+            let theta = ring as f32 / rings as f32 * PI;
+            let offset = if ring < half_rings {
+                half_cylinder
+            } else {
+                -half_cylinder
+            };
+
+            if ring == half_rings {
+                let v = ring as f32 / bands as f32;
+                rows.push(LatheRow::on_sphere(theta, radius, half_cylinder, v));
+            }
+
+            let row = if ring < half_rings { ring } else { ring + 1 };
+            let v = row as f32 / bands as f32;
+            rows.push(LatheRow::on_sphere(theta, radius, offset, v));
+        }
+
+        self.lathe(center.into(), segments, &rows);
+        self
+    }
+
+    /// Create a flat rectangle divided into a grid of quads, facing
+    /// the direction of the normal.
+    ///
+    /// The width runs along the tangent, and the height along the
+    /// binormal, which is the cross product of the normal and the
+    /// tangent. The tangent doesn't have to be perpendicular to the
+    /// normal, only its perpendicular part is used.
+    ///
+    /// The texture is stretched across the whole plane, with the
+    /// left edge at the negative tangent side.
+    ///
+    /// # Panics
+    ///
+    /// Panics when either subdivision is zero, or when the tangent
+    /// is parallel to the normal.
+    pub fn plane<V>(
+        mut self,
+        center: V,
+        normal: V,
+        tangent: V,
+        size: [f32; 2],
+        subdivisions: [u32; 2],
+        color: Color,
+    ) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        let [columns, rows] = subdivisions;
+        assert!(columns > 0 && rows > 0, "Plane needs subdivisions");
+
+        let (center, normal, tangent) = (center.into(), normal.into(), tangent.into());
+        let normal = normal.normalize();
+        let tangent = tangent - normal * glm::dot(&tangent, &normal);
+        assert!(
+            tangent.norm() > ::std::f32::EPSILON,
+            "Plane tangent must not be parallel to its normal"
+        );
+        let tangent = tangent.normalize();
+        let binormal = glm::cross(&normal, &tangent);
+
+        let index = self.next_index();
+        let [width, height] = size;
+
+        for row in 0..=rows {
+            let v = row as f32 / rows as f32;
+
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let pos = center + tangent * (u - 0.5) * width + binormal * (v - 0.5) * height;
+
+                self.vertices.push(Vertex {
+                    pos: pos.into(),
+                    uv: [u, v],
+                    normal: normal.into(),
+                    color,
+                    layer: 0.0,
+                });
+            }
+        }
+
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let bottom_left = index + row * stride + column;
+                let (bottom_right, top_left) = (bottom_left + 1, bottom_left + stride);
+                let top_right = top_left + 1;
+
+                // Counter-clockwise when seen from the normal's side
+                self.indices.extend(&[bottom_left, bottom_right, top_right]);
+                self.indices.extend(&[bottom_left, top_right, top_left]);
+            }
+        }
+
+        self
+    }
+
+    /// Create a square plane on the XZ plane, centered on
+    /// the origin and facing up.
+    ///
+    /// See `plane`.
+    pub fn ground_plane(self, size: f32, subdivisions: u32) -> Self {
+        self.plane(
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [size, size],
+            [subdivisions, subdivisions],
+            WHITE,
+        )
+    }
+
+    /// Adds a surface of revolution around the y axis, connecting
+    /// each row to the next with a band of quads.
+    ///
+    /// Vertices are repeated at the seam, so the texture can wrap
+    /// around. Rows with no radius, at the poles, are connected to
+    /// their neighbours with triangles.
+    fn lathe(&mut self, center: glm::Vec3, segments: u32, rows: &[LatheRow]) {
+        let index = self.next_index();
+        let columns = segments + 1;
+
+        for row in rows {
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let (sin, cos) = (u * 2.0 * PI).sin_cos();
+                let [normal_out, normal_up] = row.normal;
+
+                self.vertices.push(Vertex {
+                    pos: [
+                        center.x + row.radius * sin,
+                        center.y + row.y,
+                        center.z + row.radius * cos,
+                    ],
+                    uv: [u, row.v],
+                    normal: glm::vec3(normal_out * sin, normal_up, normal_out * cos)
+                        .normalize()
+                        .into(),
+                    color: WHITE,
+                    layer: 0.0,
+                });
+            }
+        }
+
+        for (row, pair) in rows.windows(2).enumerate() {
+            let top = index + row as u32 * columns;
+            let bottom = top + columns;
+
+            for segment in 0..segments {
+                let (top_left, top_right) = (top + segment, top + segment + 1);
+                let (bottom_left, bottom_right) = (bottom + segment, bottom + segment + 1);
+
+                // Counter-clockwise when seen from outside
+                if pair[0].radius > 0.0 {
+                    self.indices.extend(&[bottom_left, top_right, top_left]);
+                }
+                if pair[1].radius > 0.0 {
+                    self.indices.extend(&[bottom_left, bottom_right, top_right]);
+                }
+            }
+        }
+    }
+
+    /// Adds a flat circle facing up or down along the y axis, made
+    /// of a triangle fan around its center.
+    fn disc(&mut self, center: glm::Vec3, radius: f32, segments: u32, facing_up: bool) {
+        let index = self.next_index();
+        let normal = if facing_up {
+            [0.0, 1.0, 0.0]
+        } else {
+            [0.0, -1.0, 0.0]
+        };
+
+        self.vertices.push(Vertex {
+            pos: center.into(),
+            uv: [0.5, 0.5],
+            normal,
+            color: WHITE,
+            layer: 0.0,
+        });
+
+        for segment in 0..segments {
+            let (sin, cos) = (segment as f32 / segments as f32 * 2.0 * PI).sin_cos();
+            self.vertices.push(Vertex {
+                pos: [center.x + radius * sin, center.y, center.z + radius * cos],
+                uv: [0.5 + sin * 0.5, 0.5 + cos * 0.5],
+                normal,
+                color: WHITE,
+                layer: 0.0,
+            });
+        }
+
+        for segment in 0..segments {
+            let current = index + 1 + segment;
+            let next = index + 1 + (segment + 1) % segments;
+
+            if facing_up {
+                self.indices.extend(&[index, current, next]);
+            } else {
+                self.indices.extend(&[index, next, current]);
+            }
+        }
+    }
+
     /// Allocate mesh on graphics memory
+    ///
+    /// Meshes with more vertices than 16-bit indices can address
+    /// are drawn with 32-bit indices.
     pub fn build(self, ctx: &mut GraphicContext) -> Mesh {
-        let (vbuf, slice) = ctx
-            .factory
-            .create_vertex_buffer_with_slice(&self.vertices[..], &self.indices[..]);
+        let index_format = self.index_format();
+        let (vbuf, slice) = upload_geometry(&mut ctx.factory, &self.vertices, &self.indices);
         let transbuf = ctx.factory.create_constant_buffer(1);
+        let vertex_count = self.vertices.len() as u32;
+        let index_count = self.indices.len() as u32;
+        let aabb = compute_aabb(&self.vertices);
+        let bounding_sphere = compute_bounding_sphere(&self.vertices);
+        let local = LocalGeometry::new(self.vertices, &self.indices);
 
         Mesh {
             vbuf,
             slice,
             transbuf,
+            local,
+            vertex_count,
+            index_count,
+            index_format,
+            aabb,
+            bounding_sphere,
         }
     }
 }
 
+/// Minimum and maximum corners of the box containing the vertices.
+fn compute_aabb(vertices: &[Vertex]) -> (glm::Vec3, glm::Vec3) {
+    if vertices.is_empty() {
+        return (glm::Vec3::zeros(), glm::Vec3::zeros());
+    }
+
+    let inf = std::f32::INFINITY;
+    vertices.iter().fold(
+        (glm::vec3(inf, inf, inf), glm::vec3(-inf, -inf, -inf)),
+        |(min, max), vertex| {
+            let pos = glm::Vec3::from(vertex.pos);
+            (glm::min2(&min, &pos), glm::max2(&max, &pos))
+        },
+    )
+}
+
+/// Centroid of the vertices, and the distance from it to
+/// the furthest vertex.
+fn compute_bounding_sphere(vertices: &[Vertex]) -> (glm::Vec3, f32) {
+    if vertices.is_empty() {
+        return (glm::Vec3::zeros(), 0.0);
+    }
+
+    let sum = vertices.iter().fold(glm::Vec3::zeros(), |acc, vertex| {
+        acc + glm::Vec3::from(vertex.pos)
+    });
+    let centroid = sum / vertices.len() as f32;
+    let radius = vertices
+        .iter()
+        .map(|vertex| glm::distance(&centroid, &glm::Vec3::from(vertex.pos)))
+        .fold(0.0, f32::max);
+
+    (centroid, radius)
+}
+
 #[derive(Default)]
 pub struct MeshCommandBuffer(VecDeque<MeshCmd>);
 
@@ -684,6 +1542,8 @@ impl MeshCommandBuffer {
 
 pub enum MeshCmd {
     AllocateMesh(Entity, MeshBuilder),
+    /// Allocates a widget's mesh, replacing the existing one.
+    AllocateGuiMesh(Entity, GuiMeshBuilder),
 }
 
 pub struct MeshUpkeepSystem;
@@ -703,6 +1563,7 @@ impl MeshUpkeepSystem {
         let MeshUpkeepData {
             mut mesh_cmds,
             mut meshes,
+            mut gui_meshes,
         } = data;
 
         while let Some(cmd) = mesh_cmds.pop() {
@@ -714,6 +1575,11 @@ impl MeshUpkeepSystem {
                         .insert(entity, builder.build(graphics_context))
                         .expect("Failed to insert mesh");
                 }
+                AllocateGuiMesh(entity, builder) => {
+                    gui_meshes
+                        .insert(entity, builder.build(graphics_context))
+                        .expect("Failed to insert GUI mesh");
+                }
             }
         }
     }
@@ -723,4 +1589,355 @@ impl MeshUpkeepSystem {
 pub struct MeshUpkeepData<'a> {
     mesh_cmds: Write<'a, MeshCommandBuffer>,
     meshes: WriteStorage<'a, Mesh>,
+    gui_meshes: WriteStorage<'a, GuiMesh>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Corners of a cube from the origin to one.
+    fn unit_cube() -> Vec<Vertex> {
+        let mut vertices = vec![];
+        for &x in &[0.0, 1.0] {
+            for &y in &[0.0, 1.0] {
+                for &z in &[0.0, 1.0] {
+                    vertices.push(Vertex {
+                        pos: [x, y, z],
+                        uv: [0.0, 0.0],
+                        normal: [0.0, 0.0, 1.0],
+                        color: WHITE,
+                        layer: 0.0,
+                    });
+                }
+            }
+        }
+        vertices
+    }
+
+    #[test]
+    fn test_pseudocube_layers() {
+        let builder = MeshBuilder::new().pseudocube_points_layered(
+            [
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 1.0, 1.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 0.0, 1.0],
+                [1.0, 1.0, 0.0],
+                [1.0, 1.0, 1.0],
+            ],
+            [0, 1, 2, 3, 4, 5],
+        );
+
+        let layers: Vec<f32> = builder.vertices.iter().map(|vertex| vertex.layer).collect();
+        assert_eq!(24, layers.len());
+        assert_eq!([1.0; 4], layers[4..8]);
+        assert_eq!([5.0; 4], layers[20..24]);
+
+        // Each face covers its whole layer.
+        assert_eq!([0.0, 1.0], builder.vertices[0].uv);
+    }
+
+    #[test]
+    fn test_recalculate_normals() {
+        let mut builder = MeshBuilder::new().quad_with_points(
+            [
+                glm::vec3(0.0, 0.0, 0.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(1.0, 1.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ],
+            [WHITE; 4],
+            [[0.0, 0.0]; 4],
+        );
+        assert!(builder.normals_dirty());
+
+        // Normals deliberately pointing the wrong way.
+        for vertex in &mut builder.vertices {
+            vertex.normal = [1.0, 0.0, 0.0];
+        }
+
+        let builder = builder.with_normals_recalculated(false);
+        assert!(!builder.normals_dirty());
+        assert_eq!(4, builder.vertices.len());
+        for vertex in &builder.vertices {
+            assert_eq!([0.0, 0.0, 1.0], vertex.normal);
+        }
+    }
+
+    #[test]
+    fn test_tilted_quad_normal() {
+        let builder = MeshBuilder::new().quad_with_points(
+            [
+                glm::vec3(0.0, 0.0, 0.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(1.0, 1.0, 1.0),
+                glm::vec3(0.0, 1.0, 1.0),
+            ],
+            [WHITE; 4],
+            [[0.0, 0.0]; 4],
+        );
+
+        // Tilted 45 degrees back around the x axis
+        let expected = glm::vec3(0.0, -1.0, 1.0).normalize();
+        for vertex in &builder.vertices {
+            assert!((glm::Vec3::from(vertex.normal) - expected).norm() < 1.0e-6);
+        }
+
+        // Degenerate quads face the viewer.
+        let builder = MeshBuilder::new().quad_with_points(
+            [glm::vec3(1.0, 1.0, 1.0); 4],
+            [WHITE; 4],
+            [[0.0, 0.0]; 4],
+        );
+        assert_eq!([0.0, 0.0, 1.0], builder.vertices[0].normal);
+    }
+
+    #[test]
+    fn test_quad_with_rect() {
+        let builder = MeshBuilder::new().quad_with_rect(
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0],
+            [WHITE; 4],
+            TexRect::default(),
+        );
+
+        // Top of the image is at the top of the quad.
+        let uvs: Vec<[f32; 2]> = builder.vertices.iter().map(|v| v.uv).collect();
+        assert_eq!(vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]], uvs);
+    }
+
+    #[test]
+    fn test_recalculate_flat_and_smooth_normals() {
+        let cube = || {
+            let rect = TexRect::default;
+            MeshBuilder::new().pseudocube(
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 1.0],
+                [rect(), rect(), rect(), rect(), rect(), rect()],
+            )
+        };
+
+        // Faces of the cube don't share vertices, so none are split.
+        let flat = cube().with_normals_recalculated(false);
+        assert_eq!(24, flat.vertices.len());
+        for vertex in &flat.vertices {
+            assert_eq!(1, vertex.normal.iter().filter(|n| n.abs() == 1.0).count());
+        }
+
+        // Corners point away from the center.
+        let smooth = cube().with_normals_recalculated(true);
+        assert_eq!(24, smooth.vertices.len());
+        for vertex in &smooth.vertices {
+            let outward = (glm::Vec3::from(vertex.pos) - glm::vec3(0.5, 0.5, 0.5)).normalize();
+            assert!((glm::Vec3::from(vertex.normal) - outward).norm() < 1.0e-5);
+        }
+
+        // Vertex shared by triangles facing different ways is split.
+        let mut bent = MeshBuilder::new();
+        bent.vertices = unit_cube()[..4].to_vec();
+        bent.indices = vec![0, 1, 3, 0, 2, 1];
+        bent.recalculate_normals(false);
+        assert_eq!(6, bent.vertices.len());
+    }
+
+    #[test]
+    fn test_sheared_pseudocube_normals() {
+        // Top of the cube shifted along x.
+        let points = [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [2.0, 1.0, 0.0],
+            [2.0, 1.0, 1.0],
+        ];
+        let rect = TexRect::default;
+        let builder = MeshBuilder::new()
+            .pseudocube_points(points, [rect(), rect(), rect(), rect(), rect(), rect()]);
+        assert_eq!(24, builder.vertices.len());
+
+        // Left and right faces lean over, the rest keep their axis normals.
+        let left = glm::vec3(-1.0, 1.0, 0.0).normalize();
+        let right = glm::vec3(1.0, -1.0, 0.0).normalize();
+        let expected = |normal: [f32; 3]| {
+            let normal = glm::Vec3::from(normal);
+            (normal - left).norm() < 1.0e-6 || (normal - right).norm() < 1.0e-6
+        };
+        let leaning = builder.vertices.iter().filter(|v| expected(v.normal));
+        assert_eq!(8, leaning.count());
+
+        // Every face points out of its own triangles.
+        for triangle in builder.indices.chunks_exact(3) {
+            let normal = builder.triangle_normal(triangle).normalize();
+            for index in triangle {
+                let vertex_normal = glm::Vec3::from(builder.vertices[*index as usize].normal);
+                assert!((vertex_normal - normal).norm() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_plane() {
+        let builder = MeshBuilder::new().plane(
+            [1.0, 2.0, 3.0],
+            [0.0, 0.0, 2.0],
+            // Not perpendicular to the normal
+            [1.0, 0.0, 1.0],
+            [4.0, 2.0],
+            [4, 3],
+            WHITE,
+        );
+        assert_eq!(5 * 4, builder.vertices.len());
+        assert_eq!(4 * 3 * 6, builder.indices.len());
+
+        // Texture covers the whole plane, from corner to corner.
+        let first = builder.vertices.first().unwrap();
+        assert_eq!([-1.0, 1.0, 3.0], first.pos);
+        assert_eq!([0.0, 0.0], first.uv);
+        let last = builder.vertices.last().unwrap();
+        assert_eq!([3.0, 3.0, 3.0], last.pos);
+        assert_eq!([1.0, 1.0], last.uv);
+
+        for triangle in builder.indices.chunks_exact(3) {
+            let normal = builder.triangle_normal(triangle).normalize();
+            assert!((normal - glm::vec3(0.0, 0.0, 1.0)).norm() < 1.0e-6);
+        }
+
+        let builder = MeshBuilder::new().ground_plane(10.0, 4);
+        assert_eq!(5 * 5, builder.vertices.len());
+        assert_eq!(4 * 4 * 6, builder.indices.len());
+        for vertex in &builder.vertices {
+            assert_eq!([0.0, 1.0, 0.0], vertex.normal);
+            assert!(vertex.pos[1].abs() < ::std::f32::EPSILON);
+        }
+        for triangle in builder.indices.chunks_exact(3) {
+            assert!(builder.triangle_normal(triangle).y > 0.0);
+        }
+
+        let builder = MeshBuilder::new().ground_plane(1.0, 1);
+        assert_eq!(4, builder.vertices.len());
+        assert_eq!(6, builder.indices.len());
+    }
+
+    #[test]
+    fn test_large_mesh_index_format() {
+        let mut builder = MeshBuilder::new();
+        for i in 0..17_500 {
+            builder = builder.quad([i as f32, 0.0, 0.0], [1.0, 1.0], [WHITE; 4]);
+        }
+
+        // More vertices than 16-bit indices can address.
+        assert_eq!(70_000, builder.vertices.len());
+        assert_eq!(IndexFormat::U32, builder.index_format());
+
+        // Indices don't wrap around.
+        for (quad, indices) in builder.indices.chunks_exact(6).enumerate() {
+            assert_eq!(quad as u32 * 4, indices[0]);
+            assert!(indices.iter().all(|index| *index < 70_000));
+        }
+        assert_eq!(Some(&69_999), builder.indices.iter().max());
+
+        assert_eq!(IndexFormat::U16, IndexFormat::for_vertex_count(65_536));
+        assert_eq!(IndexFormat::U32, IndexFormat::for_vertex_count(65_537));
+    }
+
+    /// Checks that normals are unit length, and that triangles wind
+    /// counter-clockwise when seen from the side their normals face.
+    fn assert_primitive(builder: &MeshBuilder) {
+        for vertex in &builder.vertices {
+            let length = glm::Vec3::from(vertex.normal).norm();
+            assert!((length - 1.0).abs() < 1.0e-5, "normal length {}", length);
+        }
+
+        for triangle in builder.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                &builder.vertices[triangle[0] as usize],
+                &builder.vertices[triangle[1] as usize],
+                &builder.vertices[triangle[2] as usize],
+            ];
+            let (pos_a, pos_b, pos_c) = (
+                glm::Vec3::from(a.pos),
+                glm::Vec3::from(b.pos),
+                glm::Vec3::from(c.pos),
+            );
+            let face_normal = glm::cross(&(pos_b - pos_a), &(pos_c - pos_a));
+            let vertex_normal =
+                glm::Vec3::from(a.normal) + glm::Vec3::from(b.normal) + glm::Vec3::from(c.normal);
+
+            assert!(
+                face_normal.norm() > 0.0,
+                "degenerate triangle {:?}",
+                triangle
+            );
+            assert!(
+                glm::dot(&face_normal, &vertex_normal) > 0.0,
+                "triangle {:?}",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn test_primitives() {
+        let sphere = MeshBuilder::new().sphere([0.0, 0.0, 0.0], 1.0, 8, 4);
+        assert_eq!(5 * 9, sphere.vertices.len());
+        assert_eq!(6 * 8 * 3, sphere.indices.len());
+        assert_primitive(&sphere);
+
+        let cylinder = MeshBuilder::new().cylinder([0.0, 0.0, 0.0], 1.0, 2.0, 8);
+        assert_eq!(2 * 9 + 2 * 9, cylinder.vertices.len());
+        assert_eq!(6 * 8 + 2 * 3 * 8, cylinder.indices.len());
+        assert_primitive(&cylinder);
+
+        let cone = MeshBuilder::new().cone([0.0, 0.0, 0.0], 1.0, 2.0, 8);
+        assert_eq!(2 * 9 + 9, cone.vertices.len());
+        assert_eq!(3 * 8 + 3 * 8, cone.indices.len());
+        assert_primitive(&cone);
+
+        let capsule = MeshBuilder::new().capsule([0.0, 0.0, 0.0], 0.5, 2.0, 8, 4);
+        assert_eq!(6 * 9, capsule.vertices.len());
+        assert_eq!(6 * 8 * 4, capsule.indices.len());
+        assert_primitive(&capsule);
+
+        // Top of the capsule, including the hemisphere
+        let top = capsule
+            .vertices
+            .iter()
+            .map(|vertex| vertex.pos[1])
+            .fold(::std::f32::MIN, f32::max);
+        assert!((top - 1.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_primitives_compose() {
+        let builder = MeshBuilder::new()
+            .sphere([0.0, 0.0, 0.0], 1.0, 8, 4)
+            .cylinder([3.0, 0.0, 0.0], 1.0, 2.0, 8);
+
+        // Indices of the second primitive follow the first.
+        assert_eq!(45 + 36, builder.vertices.len());
+        let first_cylinder_index = builder.indices[6 * 8 * 3];
+        assert!(first_cylinder_index >= 45);
+        assert_primitive(&builder);
+    }
+
+    #[test]
+    fn test_mesh_bounds() {
+        let vertices = unit_cube();
+
+        let (min, max) = compute_aabb(&vertices);
+        assert_eq!(glm::vec3(0.0, 0.0, 0.0), min);
+        assert_eq!(glm::vec3(1.0, 1.0, 1.0), max);
+
+        let (center, radius) = compute_bounding_sphere(&vertices);
+        assert_eq!(glm::vec3(0.5, 0.5, 0.5), center);
+        assert!((radius - 0.75_f32.sqrt()).abs() < 0.0001);
+
+        assert_eq!((glm::Vec3::zeros(), 0.0), compute_bounding_sphere(&[]));
+    }
 }
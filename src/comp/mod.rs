@@ -1,11 +1,20 @@
 //! Components
 
+mod gui_mesh;
+mod hierarchy;
 mod mesh;
+mod obj;
 mod tag;
 mod tex;
 mod transform;
+mod tween;
+mod wiggle;
 
+pub use gui_mesh::*;
+pub use hierarchy::*;
 pub use mesh::*;
 pub use tag::*;
 pub use tex::*;
 pub use transform::*;
+pub use tween::*;
+pub use wiggle::*;
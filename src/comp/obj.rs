@@ -0,0 +1,282 @@
+//! Parsing of Wavefront OBJ models.
+use crate::colors::WHITE;
+use crate::errors::MeshError;
+use crate::gfx_types::Vertex;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Vertices and triangle indices parsed from an OBJ file.
+pub(crate) struct ObjGeometry {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u32>,
+}
+
+/// Normal of a vertex, either from the file, or calculated from
+/// the face when the file omits it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalKey {
+    Index(usize),
+    /// Bit pattern of the face normal, so vertices of coplanar
+    /// faces are shared.
+    Flat([u32; 3]),
+}
+
+/// Position, texture coordinate and normal making up a unique vertex.
+type VertexKey = (usize, Option<usize>, NormalKey);
+
+/// Parses positions, texture coordinates and normals of an OBJ model.
+///
+/// Faces with more than three corners are triangulated as fans, so
+/// they're expected to be convex. Faces without normals get flat
+/// normals, calculated from their corners. Identical vertices are
+/// shared between faces.
+///
+/// Texture coordinates are flipped vertically, since OBJ places the
+/// origin at the bottom of the image. Materials, groups and other
+/// statements are ignored.
+pub(crate) fn parse_obj<R: BufRead>(reader: R) -> Result<ObjGeometry, MeshError> {
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut uvs: Vec<[f32; 2]> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut lookup: HashMap<VertexKey, u32> = HashMap::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = line_index + 1;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => positions.push(parse_floats(tokens, line_no)?),
+            Some("vt") => {
+                let [u, v]: [f32; 2] = parse_floats(tokens, line_no)?;
+                uvs.push([u, 1.0 - v]);
+            }
+            Some("vn") => normals.push(parse_floats(tokens, line_no)?),
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| {
+                        parse_corner(token, positions.len(), uvs.len(), normals.len(), line_no)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if corners.len() < 3 {
+                    return Err(MeshError::Parse {
+                        line: line_no,
+                        message: "face has fewer than 3 corners".to_owned(),
+                    });
+                }
+
+                let flat_normal = face_normal(corners.iter().map(|c| positions[c.0]));
+                let mut face_indices = Vec::with_capacity(corners.len());
+
+                for (pos, uv, normal) in corners {
+                    let normal_key = match normal {
+                        Some(index) => NormalKey::Index(index),
+                        None => NormalKey::Flat([
+                            flat_normal[0].to_bits(),
+                            flat_normal[1].to_bits(),
+                            flat_normal[2].to_bits(),
+                        ]),
+                    };
+                    let key = (pos, uv, normal_key);
+
+                    let index = match lookup.get(&key) {
+                        Some(index) => *index,
+                        None => {
+                            let index = vertices.len() as u32;
+                            vertices.push(Vertex {
+                                pos: positions[pos],
+                                uv: uv.map(|uv| uvs[uv]).unwrap_or([0.0, 0.0]),
+                                normal: normal.map(|n| normals[n]).unwrap_or(flat_normal),
+                                color: WHITE,
+                                layer: 0.0,
+                            });
+                            lookup.insert(key, index);
+                            index
+                        }
+                    };
+
+                    face_indices.push(index);
+                }
+
+                // Triangle fan, keeping the counter-clockwise winding
+                for i in 1..face_indices.len() - 1 {
+                    indices.extend(&[face_indices[0], face_indices[i], face_indices[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ObjGeometry { vertices, indices })
+}
+
+/// Parses the leading numbers of a statement, ignoring any
+/// optional trailing components.
+fn parse_floats<'a, A, I>(mut tokens: I, line: usize) -> Result<A, MeshError>
+where
+    A: Default + AsMut<[f32]>,
+    I: Iterator<Item = &'a str>,
+{
+    let mut values = A::default();
+
+    for value in values.as_mut().iter_mut() {
+        let token = tokens.next().ok_or_else(|| MeshError::Parse {
+            line,
+            message: "missing coordinate".to_owned(),
+        })?;
+
+        *value = token.parse().map_err(|_| MeshError::Parse {
+            line,
+            message: format!("invalid number '{}'", token),
+        })?;
+    }
+
+    Ok(values)
+}
+
+/// Parses a face corner, in the form `v`, `v/vt`, `v//vn` or `v/vt/vn`,
+/// into zero-based indices.
+fn parse_corner(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+    line: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), MeshError> {
+    let mut parts = token.split('/');
+
+    let pos = match parts.next() {
+        Some(part) => resolve_index(part, position_count, line)?,
+        None => None,
+    };
+    let uv = match parts.next() {
+        Some(part) => resolve_index(part, uv_count, line)?,
+        None => None,
+    };
+    let normal = match parts.next() {
+        Some(part) => resolve_index(part, normal_count, line)?,
+        None => None,
+    };
+
+    match pos {
+        Some(pos) => Ok((pos, uv, normal)),
+        None => Err(MeshError::Parse {
+            line,
+            message: format!("face corner '{}' has no position", token),
+        }),
+    }
+}
+
+/// Converts a one-based, or negative relative, OBJ index into a
+/// zero-based index. Empty indices are `None`.
+fn resolve_index(part: &str, count: usize, line: usize) -> Result<Option<usize>, MeshError> {
+    if part.is_empty() {
+        return Ok(None);
+    }
+
+    let invalid = || MeshError::Parse {
+        line,
+        message: format!("invalid index '{}'", part),
+    };
+    let index: i64 = part.parse().map_err(|_| invalid())?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(invalid());
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+/// Normal of a polygon using Newell's method, which tolerates
+/// slightly non-planar faces.
+fn face_normal<I>(corners: I) -> [f32; 3]
+where
+    I: Iterator<Item = [f32; 3]>,
+{
+    let corners: Vec<glm::Vec3> = corners.map(glm::Vec3::from).collect();
+    let mut normal = glm::Vec3::zeros();
+
+    for (i, current) in corners.iter().enumerate() {
+        let next = corners[(i + 1) % corners.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    if normal.norm() > ::std::f32::EPSILON {
+        normal.normalize().into()
+    } else {
+        [0.0, 0.0, 0.0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_obj_flat_normals() {
+        let obj = include_bytes!("../../examples/pyramid.obj");
+        let geometry = parse_obj(&obj[..]).unwrap();
+
+        // Base quad shares its corners, the sides don't share
+        // corners since their normals differ.
+        assert_eq!(4 + 4 * 3, geometry.vertices.len());
+        assert_eq!(2 * 3 + 4 * 3, geometry.indices.len());
+
+        for index in &geometry.indices[..6] {
+            assert_eq!([0.0, -1.0, 0.0], geometry.vertices[*index as usize].normal);
+        }
+
+        // Texture coordinates are flipped vertically.
+        assert_eq!([0.0, 1.0], geometry.vertices[0].uv);
+
+        // Front face leans towards positive z.
+        let front = geometry.vertices[geometry.indices[6] as usize].normal;
+        assert!(front[1] > 0.0 && front[2] > 0.0);
+        assert!(front[0].abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_parse_obj_shared_vertices() {
+        let obj = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vn 0 0 1
+            f -4//1 -3//1 -2//1 -1//1
+            f 1//1 3//1 4//1
+        ";
+        let geometry = parse_obj(obj.as_bytes()).unwrap();
+
+        assert_eq!(4, geometry.vertices.len());
+        assert_eq!(vec![0, 1, 2, 0, 2, 3, 0, 2, 3], geometry.indices);
+        assert_eq!([0.0, 0.0, 1.0], geometry.vertices[3].normal);
+    }
+
+    #[test]
+    fn test_parse_obj_errors() {
+        let invalid_index = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 4\n";
+        match parse_obj(invalid_index.as_bytes()) {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(4, line),
+            _ => panic!("expected parse error"),
+        }
+
+        let too_few_corners = "v 0 0 0\nv 1 0 0\nf 1 2\n";
+        assert!(parse_obj(too_few_corners.as_bytes()).is_err());
+
+        let missing_coordinate = "v 0 0\n";
+        assert!(parse_obj(missing_coordinate.as_bytes()).is_err());
+    }
+}
@@ -1,8 +1,13 @@
 //! User defined name attached to an entity for easy searching.
+use shrev::ReaderId;
 use specs::prelude::*;
+use specs::storage::ComponentEvent;
+use specs::world::Index;
+use std::collections::HashMap;
 use std::{fmt, string::ToString};
 
 #[derive(Component, Debug, Clone)]
+#[storage(FlaggedStorage)]
 pub struct Tag(String);
 
 impl Tag {
@@ -25,3 +30,339 @@ impl fmt::Display for Tag {
         fmt::Display::fmt(&self.0, f)
     }
 }
+
+/// Lookup of entities by their `Tag`.
+///
+/// Maintained by `TagIndexSystem`, so entities created or
+/// deleted during a frame are found once the system has run.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::comp::{Tag, TagIndex, TagIndexSystem};
+/// # use rengine::specs::{Builder, RunNow, World};
+/// let mut world = World::new();
+/// world.register::<Tag>();
+/// world.add_resource(TagIndex::default());
+/// let mut tag_index_system = TagIndexSystem::new(&world);
+///
+/// let player = world.create_entity().with(Tag::new("player")).build();
+/// tag_index_system.run_now(&world.res);
+///
+/// assert_eq!(Some(player), world.read_resource::<TagIndex>().get("player"));
+/// ```
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    entities: HashMap<String, Vec<Entity>>,
+    tags: HashMap<Index, (Entity, String)>,
+}
+
+impl TagIndex {
+    /// First entity that was tagged with the given name.
+    pub fn get(&self, tag: &str) -> Option<Entity> {
+        self.get_all(tag).first().cloned()
+    }
+
+    /// All entities tagged with the given name, in
+    /// the order they were tagged.
+    pub fn get_all(&self, tag: &str) -> &[Entity] {
+        self.entities.get(tag).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Finds the entity with a unique tag.
+    ///
+    /// When more than one entity has the tag, the
+    /// first one tagged is returned.
+    #[inline]
+    pub fn find_by_tag(&self, tag: &str) -> Option<Entity> {
+        self.get(tag)
+    }
+
+    /// Finds all entities with the tag, as an owned list that
+    /// can be kept while the world is modified.
+    pub fn find_all_by_tag(&self, tag: &str) -> Vec<Entity> {
+        self.get_all(tag).to_vec()
+    }
+
+    /// Tag of the entity, as it was when last indexed.
+    pub fn tag_of(&self, entity: Entity) -> Option<&str> {
+        self.tags
+            .get(&entity.id())
+            .filter(|(tagged, _)| *tagged == entity)
+            .map(|(_, tag)| tag.as_str())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    fn insert(&mut self, entity: Entity, tag: &Tag) {
+        self.remove(entity.id());
+
+        let tag: &str = tag.as_ref();
+        self.entities
+            .entry(tag.to_owned())
+            .or_insert_with(Vec::new)
+            .push(entity);
+        self.tags.insert(entity.id(), (entity, tag.to_owned()));
+    }
+
+    fn remove(&mut self, id: Index) {
+        if let Some((entity, tag)) = self.tags.remove(&id) {
+            let is_empty = match self.entities.get_mut(&tag) {
+                Some(entities) => {
+                    entities.retain(|e| *e != entity);
+                    entities.is_empty()
+                }
+                None => false,
+            };
+
+            if is_empty {
+                self.entities.remove(&tag);
+            }
+        }
+    }
+}
+
+/// Searches the `Tag` storage of a system directly.
+///
+/// Unlike the `TagIndex`, tags added earlier in the same
+/// frame are found, at the cost of visiting every tag.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::comp::{Tag, TagQuery};
+/// # use rengine::specs::{Builder, World};
+/// let mut world = World::new();
+/// world.register::<Tag>();
+/// let player = world.create_entity().with(Tag::new("player")).build();
+///
+/// let found = TagQuery::find_first(&world.entities(), &world.read_storage(), "player");
+/// assert_eq!(Some(player), found);
+/// ```
+pub struct TagQuery;
+
+impl TagQuery {
+    /// First entity in storage order with the given tag.
+    pub fn find_first(
+        entities: &Entities<'_>,
+        tags: &ReadStorage<'_, Tag>,
+        name: &str,
+    ) -> Option<Entity> {
+        (entities, tags)
+            .join()
+            .find(|(_, tag)| tag.as_ref() == name)
+            .map(|(entity, _)| entity)
+    }
+
+    /// All entities with the given tag, in storage order.
+    pub fn find_all(
+        entities: &Entities<'_>,
+        tags: &ReadStorage<'_, Tag>,
+        name: &str,
+    ) -> Vec<Entity> {
+        (entities, tags)
+            .join()
+            .filter(|(_, tag)| tag.as_ref() == name)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+}
+
+/// Keeps the `TagIndex` in sync with the `Tag` storage.
+pub struct TagIndexSystem {
+    reader: ReaderId<ComponentEvent>,
+}
+
+impl TagIndexSystem {
+    /// Creates the system, and indexes the tags
+    /// already in the world.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `TagIndex` resource was not added to the world.
+    pub fn new(world: &World) -> Self {
+        let mut tags = world.write_storage::<Tag>();
+        let reader = tags.register_reader();
+
+        let entities = world.entities();
+        let mut tag_index = world.write_resource::<TagIndex>();
+        for (entity, tag) in (&entities, &tags).join() {
+            tag_index.insert(entity, tag);
+        }
+
+        TagIndexSystem { reader }
+    }
+}
+
+impl<'a> System<'a> for TagIndexSystem {
+    type SystemData = (Entities<'a>, ReadStorage<'a, Tag>, Write<'a, TagIndex>);
+
+    fn run(&mut self, (entities, tags, mut tag_index): Self::SystemData) {
+        for event in tags.channel().read(&mut self.reader) {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                    let entity = entities.entity(*id);
+                    match tags.get(entity) {
+                        Some(tag) => tag_index.insert(entity, tag),
+                        // Removed later in the same frame
+                        None => tag_index.remove(*id),
+                    }
+                }
+                ComponentEvent::Removed(id) => tag_index.remove(*id),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_world() -> (World, TagIndexSystem) {
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.add_resource(TagIndex::default());
+        let system = TagIndexSystem::new(&world);
+        (world, system)
+    }
+
+    #[test]
+    fn test_tag_change() {
+        let (mut world, mut system) = create_world();
+        let entity = world.create_entity().with(Tag::new("Brush")).build();
+        system.run_now(&world.res);
+        assert_eq!(Some(entity), world.read_resource::<TagIndex>().get("Brush"));
+
+        world
+            .write_storage::<Tag>()
+            .insert(entity, Tag::new("Eraser"))
+            .unwrap();
+        system.run_now(&world.res);
+
+        let tag_index = world.read_resource::<TagIndex>();
+        assert_eq!(None, tag_index.get("Brush"));
+        assert_eq!(Some(entity), tag_index.get("Eraser"));
+        assert_eq!(Some("Eraser"), tag_index.tag_of(entity));
+    }
+
+    #[test]
+    fn test_entity_deletion() {
+        let (mut world, mut system) = create_world();
+        let entity = world.create_entity().with(Tag::new("player")).build();
+        system.run_now(&world.res);
+
+        world.delete_entity(entity).unwrap();
+        world.maintain();
+        system.run_now(&world.res);
+
+        let tag_index = world.read_resource::<TagIndex>();
+        assert_eq!(None, tag_index.get("player"));
+        assert!(tag_index.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_tags() {
+        let (mut world, mut system) = create_world();
+        let first = world.create_entity().with(Tag::new("enemy")).build();
+        let second = world.create_entity().with(Tag::new("enemy")).build();
+        system.run_now(&world.res);
+
+        {
+            let tag_index = world.read_resource::<TagIndex>();
+            assert_eq!(Some(first), tag_index.get("enemy"));
+            assert_eq!(&[first, second], tag_index.get_all("enemy"));
+        }
+
+        world.write_storage::<Tag>().remove(first);
+        system.run_now(&world.res);
+
+        let tag_index = world.read_resource::<TagIndex>();
+        assert_eq!(Some(second), tag_index.get("enemy"));
+        assert_eq!(1, tag_index.get_all("enemy").len());
+        assert!(tag_index.get_all("boss").is_empty());
+    }
+
+    #[test]
+    fn test_other_thread() {
+        let mut world = World::new();
+        let player = world.create_entity().build();
+        let mut tag_index = TagIndex::default();
+        tag_index.insert(player, &Tag::new("player"));
+
+        // Systems in the dispatcher read the index from worker threads.
+        let found = std::thread::spawn(move || {
+            (
+                tag_index.get("player"),
+                tag_index.tag_of(player).map(str::to_owned),
+            )
+        })
+        .join()
+        .unwrap();
+        assert_eq!((Some(player), Some("player".to_owned())), found);
+    }
+
+    #[test]
+    fn test_existing_tags() {
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.add_resource(TagIndex::default());
+        let entity = world.create_entity().with(Tag::new("camera")).build();
+
+        TagIndexSystem::new(&world);
+
+        let tag_index = world.read_resource::<TagIndex>();
+        assert_eq!(Some(entity), tag_index.get("camera"));
+    }
+
+    #[test]
+    fn test_find_by_tag() {
+        let (mut world, mut system) = create_world();
+        let player = world.create_entity().with(Tag::new("player")).build();
+        let goblin = world.create_entity().with(Tag::new("goblin")).build();
+        let orc = world.create_entity().with(Tag::new("goblin")).build();
+        system.run_now(&world.res);
+
+        {
+            let tag_index = world.read_resource::<TagIndex>();
+            assert_eq!(Some(player), tag_index.find_by_tag("player"));
+            assert_eq!(vec![goblin, orc], tag_index.find_all_by_tag("goblin"));
+            assert_eq!(None, tag_index.find_by_tag("dragon"));
+        }
+
+        // Deleted entities are dropped once the world is maintained
+        world.delete_entity(goblin).unwrap();
+        world.maintain();
+        system.run_now(&world.res);
+
+        let tag_index = world.read_resource::<TagIndex>();
+        assert_eq!(vec![orc], tag_index.find_all_by_tag("goblin"));
+        assert_eq!(2, tag_index.len());
+    }
+
+    #[test]
+    fn test_tag_query() {
+        let mut world = World::new();
+        world.register::<Tag>();
+        let first = world.create_entity().with(Tag::new("enemy")).build();
+        let player = world.create_entity().with(Tag::new("player")).build();
+        let second = world.create_entity().with(Tag::new("enemy")).build();
+
+        let entities = world.entities();
+        let tags = world.read_storage::<Tag>();
+        let enemies = TagQuery::find_all(&entities, &tags, "enemy");
+        assert_eq!(vec![first, second], enemies);
+        assert_eq!(Some(first), TagQuery::find_first(&entities, &tags, "enemy"));
+        let found = TagQuery::find_first(&entities, &tags, "player");
+        assert_eq!(Some(player), found);
+        assert_eq!(None, TagQuery::find_first(&entities, &tags, "boss"));
+        assert!(TagQuery::find_all(&entities, &tags, "boss").is_empty());
+    }
+}
@@ -1,10 +1,21 @@
-use crate::res::AssetBundle;
+use crate::errors::TextureError;
+use crate::gfx_types::{ColorFormat, GraphicsEncoder};
+use crate::res::{AssetBundle, SamplerDesc};
+use gfx::texture::{AaMode, Kind, Mipmap};
+use gfx_device::{Factory, Resources};
+use image::RgbaImage;
 use nalgebra::Vector2;
 use specs::{Component, DenseVecStorage};
+use std::path::Path;
 use std::sync::Arc;
 
 // TODO: Consider renaming to TextureSampler, TextureHandle or ImmutableTexture
 
+/// Texture used to draw an entity.
+///
+/// Clones share the same texture on the graphics card. A texture
+/// loaded through `TextureAssets` stays cached while any of its
+/// `GlTexture` components are alive.
 #[derive(Component, Clone)]
 #[storage(DenseVecStorage)]
 pub struct GlTexture {
@@ -25,6 +36,182 @@ impl GlTexture {
             size: Vector2::new(1., 1.),
         }
     }
+
+    /// Replaces the pixels in a rectangle of the texture.
+    ///
+    /// The data is tightly packed `RGBA8`, row by row, and must
+    /// contain exactly `size[0] * size[1] * 4` bytes.
+    ///
+    /// The update is recorded in the encoder, so it is applied in
+    /// order with draw calls. Textures are shared through
+    /// `TextureAssets`, so every widget or mesh using this texture
+    /// will see the new pixels.
+    pub fn sub_update(
+        &mut self,
+        encoder: &mut GraphicsEncoder,
+        offset: [u16; 2],
+        size: [u16; 2],
+        data: &[u8],
+    ) -> Result<(), TextureError> {
+        check_region(self.bundle.tex_size, offset, size, data.len())?;
+
+        let info = gfx::texture::NewImageInfo {
+            xoffset: offset[0],
+            yoffset: offset[1],
+            zoffset: 0,
+            width: size[0],
+            height: size[1],
+            depth: 0,
+            format: (),
+            mipmap: 0,
+        };
+
+        encoder
+            .update_texture::<gfx::format::R8_G8_B8_A8, ColorFormat>(
+                &self.bundle.tex,
+                None,
+                info,
+                gfx::memory::cast_slice(data),
+            )
+            .map_err(|err| TextureError::Update(format!("{:?}", err)))
+    }
+}
+
+/// Tiles of equal size, stored as the layers of a 2D texture array.
+///
+/// Each tile is sampled on its own, selected by the `layer` of the
+/// vertices, so filtering never bleeds into neighbouring tiles like
+/// it does in an atlas. Drawn with `Material::GlossArray`.
+///
+/// Clones share the same texture on the graphics card.
+#[derive(Clone)]
+pub struct GlTextureArray {
+    tile_size: (u32, u32),
+    layer_count: u32,
+    pub(crate) view: gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
+    pub(crate) sampler: gfx::handle::Sampler<Resources>,
+}
+
+impl GlTextureArray {
+    /// Creates a texture array with a layer for each image, in order.
+    ///
+    /// Layers of an array share their dimensions, so all images
+    /// must be the same size.
+    pub fn from_images(
+        factory: &mut Factory,
+        images: &[RgbaImage],
+        sampler: SamplerDesc,
+    ) -> Result<Self, TextureError> {
+        let sizes: Vec<(u32, u32)> = images.iter().map(|img| img.dimensions()).collect();
+        let (width, height) = check_layer_sizes(&sizes)?;
+
+        let kind = Kind::D2Array(
+            width as u16,
+            height as u16,
+            images.len() as u16,
+            AaMode::Single,
+        );
+        let data: Vec<&[u8]> = images.iter().map(|img| &**img).collect();
+        let (_, view) = gfx::Factory::create_texture_immutable_u8::<ColorFormat>(
+            factory,
+            kind,
+            Mipmap::Provided,
+            &data,
+        )
+        .map_err(|err| TextureError::Update(format!("{:?}", err)))?;
+
+        Ok(GlTextureArray {
+            tile_size: (width, height),
+            layer_count: images.len() as u32,
+            view,
+            // Layers are uploaded without mipmaps.
+            sampler: gfx::Factory::create_sampler(
+                factory,
+                sampler.with_mipmaps(false).sampler_info(),
+            ),
+        })
+    }
+
+    /// Loads images from disk, creating a layer for each, in order.
+    pub fn load<P>(
+        factory: &mut Factory,
+        paths: &[P],
+        sampler: SamplerDesc,
+    ) -> Result<Self, TextureError>
+    where
+        P: AsRef<Path>,
+    {
+        let images = paths
+            .iter()
+            .map(|path| {
+                image::open(path)
+                    .map(|img| img.to_rgba())
+                    .map_err(|err| TextureError::Decode(err.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        GlTextureArray::from_images(factory, &images, sampler)
+    }
+
+    /// Size of each layer in pixels.
+    #[inline]
+    pub fn tile_size(&self) -> (u32, u32) {
+        self.tile_size
+    }
+
+    #[inline]
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+}
+
+/// Checks that there are layers, and that they are the same
+/// size, returning that size.
+fn check_layer_sizes(sizes: &[(u32, u32)]) -> Result<(u32, u32), TextureError> {
+    let expected = *sizes.first().ok_or(TextureError::NoLayers)?;
+
+    for (layer, actual) in sizes.iter().enumerate() {
+        if *actual != expected {
+            return Err(TextureError::LayerSizeMismatch {
+                layer,
+                expected,
+                actual: *actual,
+            });
+        }
+    }
+
+    Ok(expected)
+}
+
+/// Checks that the rectangle fits inside the texture, and the
+/// data covers it exactly.
+fn check_region(
+    tex_size: (u32, u32),
+    offset: [u16; 2],
+    size: [u16; 2],
+    data_len: usize,
+) -> Result<(), TextureError> {
+    let (width, height) = tex_size;
+
+    if u32::from(offset[0]) + u32::from(size[0]) > width
+        || u32::from(offset[1]) + u32::from(size[1]) > height
+    {
+        return Err(TextureError::OutOfBounds {
+            offset,
+            size,
+            tex_size,
+        });
+    }
+
+    let expected = size[0] as usize * size[1] as usize * 4;
+    if data_len != expected {
+        return Err(TextureError::DataLength {
+            expected,
+            actual: data_len,
+        });
+    }
+
+    Ok(())
 }
 
 #[derive(Clone)]
@@ -81,6 +268,17 @@ impl TexRect {
     }
 }
 
+impl Default for TexRect {
+    /// Rectangle covering the whole of a one pixel texture.
+    fn default() -> Self {
+        TexRect {
+            pixel_size: Vector2::new(1, 1),
+            pos: Vector2::new(0., 0.),
+            size: Vector2::new(1., 1.),
+        }
+    }
+}
+
 impl Into<[[f32; 2]; 4]> for TexRect {
     #[inline]
     fn into(self) -> [[f32; 2]; 4] {
@@ -92,3 +290,45 @@ impl Into<[[f32; 2]; 4]> for TexRect {
         ]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_region() {
+        let red: Vec<u8> = (0..4).flat_map(|_| vec![0xFF, 0x00, 0x00, 0xFF]).collect();
+
+        assert_eq!(Ok(()), check_region((4, 4), [0, 0], [2, 2], red.len()));
+        assert_eq!(Ok(()), check_region((4, 4), [2, 2], [2, 2], red.len()));
+        assert_eq!(
+            Err(TextureError::OutOfBounds {
+                offset: [3, 0],
+                size: [2, 2],
+                tex_size: (4, 4),
+            }),
+            check_region((4, 4), [3, 0], [2, 2], red.len())
+        );
+        assert_eq!(
+            Err(TextureError::DataLength {
+                expected: 16,
+                actual: 12,
+            }),
+            check_region((4, 4), [0, 0], [2, 2], 12)
+        );
+    }
+
+    #[test]
+    fn test_check_layer_sizes() {
+        assert_eq!(Ok((16, 16)), check_layer_sizes(&[(16, 16), (16, 16)]));
+        assert_eq!(Err(TextureError::NoLayers), check_layer_sizes(&[]));
+        assert_eq!(
+            Err(TextureError::LayerSizeMismatch {
+                layer: 2,
+                expected: (16, 16),
+                actual: (32, 16),
+            }),
+            check_layer_sizes(&[(16, 16), (16, 16), (32, 16)])
+        );
+    }
+}
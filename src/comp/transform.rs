@@ -28,7 +28,7 @@ impl Transform {
         m.append_translation_mut(&self.pos);
         m = m * nalgebra_glm::quat_to_mat4(&self.rot);
         m.append_nonuniform_scaling_mut(&self.scale);
-        m.append_translation_mut(&(-self.anchor));
+        m.prepend_translation_mut(&(-self.anchor));
 
         m
     }
@@ -64,6 +64,8 @@ impl Transform {
 
 /// Builder methods that consume the `Transform` and returns it
 impl Transform {
+    /// Point of the model, in model space, that is placed at the
+    /// position and rotated around.
     #[inline]
     pub fn with_anchor<V>(mut self, anchor: V) -> Self
     where
@@ -227,6 +229,14 @@ impl Transform {
     {
         self.scale = scale.into();
     }
+
+    #[inline]
+    pub fn set_anchor<V>(&mut self, anchor: V)
+    where
+        V: Into<Vec3>,
+    {
+        self.anchor = anchor.into();
+    }
 }
 
 impl Default for Transform {
@@ -0,0 +1,456 @@
+use super::Transform;
+use crate::res::DeltaTime;
+use glm::{Qua, Vec3};
+use shrev::EventChannel;
+use specs::{
+    Component, DenseVecStorage, Entities, Entity, Join, Read, System, Write, WriteStorage,
+};
+use std::f32::consts::PI;
+
+/// Curve mapping the linear progress of a tween, from
+/// `0.0` to `1.0`, to the fraction of the change applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseInQuad,
+    EaseOutQuad,
+    EaseInOutQuad,
+    EaseInCubic,
+    EaseOutCubic,
+    EaseInOutCubic,
+    EaseInOutSine,
+}
+
+impl Easing {
+    /// Eased fraction for the given progress, which
+    /// is clamped to the range `[0.0, 1.0]`.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t,
+            Easing::EaseOutQuad => t * (2.0 - t),
+            Easing::EaseInOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            }
+            Easing::EaseInCubic => t * t * t,
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - 4.0 * (1.0 - t).powi(3)
+                }
+            }
+            Easing::EaseInOutSine => 0.5 - (t * PI).cos() * 0.5,
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+/// What a tween does after reaching its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Stops, and is removed from the entity.
+    Once,
+    /// Starts over from the beginning.
+    Loop,
+    /// Plays backwards to the beginning, then forwards again.
+    PingPong,
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Repeat::Once
+    }
+}
+
+/// Field of the `Transform` animated by a tween.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweenProperty {
+    Position,
+    Rotation,
+    Scale,
+}
+
+#[derive(Debug, Clone)]
+enum TweenValues {
+    Position(Vec3, Vec3),
+    Rotation(Qua<f32>, Qua<f32>),
+    Scale(Vec3, Vec3),
+}
+
+/// Animation of one field of an entity's `Transform`,
+/// from a start value to an end value.
+///
+/// ## Example
+///
+/// ```
+/// # use rengine::comp::{Easing, Repeat, Tween, Tweens};
+/// let bob = Tween::position([0.0, 0.0, 0.0], [0.0, 0.5, 0.0], 1.0)
+///     .ease(Easing::EaseInOutSine)
+///     .repeat(Repeat::PingPong);
+/// let grow = Tween::scale([1.0, 1.0, 1.0], [2.0, 2.0, 2.0], 0.5);
+///
+/// // Played at the same time
+/// let tweens = Tweens::new().with(bob).with(grow);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tween {
+    values: TweenValues,
+    /// Length of one pass from start to end, in seconds.
+    duration: f32,
+    /// Time played, in seconds.
+    elapsed: f32,
+    easing: Easing,
+    repeat: Repeat,
+}
+
+impl Tween {
+    fn new(values: TweenValues, duration: f32) -> Self {
+        Tween {
+            values,
+            duration,
+            elapsed: 0.0,
+            easing: Easing::default(),
+            repeat: Repeat::default(),
+        }
+    }
+
+    /// Moves the transform between two positions
+    /// over the given number of seconds.
+    pub fn position<V>(from: V, to: V, secs: f32) -> Self
+    where
+        V: Into<Vec3>,
+    {
+        Tween::new(TweenValues::Position(from.into(), to.into()), secs)
+    }
+
+    /// Turns the transform between two rotations, along
+    /// the shortest arc, over the given number of seconds.
+    pub fn rotation(from: Qua<f32>, to: Qua<f32>, secs: f32) -> Self {
+        Tween::new(TweenValues::Rotation(from, to), secs)
+    }
+
+    /// Scales the transform between two sizes over
+    /// the given number of seconds.
+    pub fn scale<V>(from: V, to: V, secs: f32) -> Self
+    where
+        V: Into<Vec3>,
+    {
+        Tween::new(TweenValues::Scale(from.into(), to.into()), secs)
+    }
+
+    pub fn ease(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    #[inline]
+    pub fn property(&self) -> TweenProperty {
+        match self.values {
+            TweenValues::Position(..) => TweenProperty::Position,
+            TweenValues::Rotation(..) => TweenProperty::Rotation,
+            TweenValues::Scale(..) => TweenProperty::Scale,
+        }
+    }
+
+    /// Whether the tween has played to its end, and won't repeat.
+    ///
+    /// Tweens without a duration finish immediately.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        (self.repeat == Repeat::Once || self.duration <= 0.0) && self.elapsed >= self.duration
+    }
+
+    /// Linear progress of the current pass, from `0.0` to `1.0`.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        match self.repeat {
+            Repeat::Once => (self.elapsed / self.duration).min(1.0),
+            Repeat::Loop => (self.elapsed % self.duration) / self.duration,
+            Repeat::PingPong => {
+                let phase = (self.elapsed % (self.duration * 2.0)) / self.duration;
+                if phase <= 1.0 {
+                    phase
+                } else {
+                    2.0 - phase
+                }
+            }
+        }
+    }
+
+    /// Plays the tween for the given number of seconds,
+    /// and updates the transform.
+    pub fn advance(&mut self, dt: f32, transform: &mut Transform) {
+        self.elapsed += dt;
+
+        // Keeps precision when repeating forever.
+        let period = match self.repeat {
+            Repeat::Once => None,
+            Repeat::Loop => Some(self.duration),
+            Repeat::PingPong => Some(self.duration * 2.0),
+        };
+        if let Some(period) = period.filter(|period| *period > 0.0) {
+            self.elapsed %= period;
+        }
+
+        let t = self.easing.apply(self.progress());
+
+        match self.values {
+            TweenValues::Position(from, to) => transform.pos = glm::lerp(&from, &to, t),
+            TweenValues::Rotation(from, to) => transform.rot = glm::quat_slerp(&from, &to, t),
+            TweenValues::Scale(from, to) => transform.scale = glm::lerp(&from, &to, t),
+        }
+    }
+}
+
+/// Tweens played at the same time on an entity's `Transform`.
+///
+/// Tweens are removed when they finish, and the component is
+/// removed when it has none left.
+#[derive(Component, Debug, Clone, Default)]
+#[storage(DenseVecStorage)]
+pub struct Tweens {
+    tweens: Vec<Tween>,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with(mut self, tween: Tween) -> Self {
+        self.push(tween);
+        self
+    }
+
+    pub fn push(&mut self, tween: Tween) {
+        self.tweens.push(tween);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tweens.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tweens.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tween> {
+        self.tweens.iter()
+    }
+}
+
+impl From<Tween> for Tweens {
+    fn from(tween: Tween) -> Self {
+        Tweens::new().with(tween)
+    }
+}
+
+pub type TweenEvents = EventChannel<TweenEvent>;
+
+/// Sent when a tween finishes playing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweenEvent {
+    pub entity: Entity,
+    pub property: TweenProperty,
+}
+
+/// Plays the `Tweens` of entities, and sends a `TweenEvent`
+/// for each tween that finishes, when the world has
+/// `TweenEvents`.
+#[derive(Default)]
+pub struct TweenSystem;
+
+impl<'a> System<'a> for TweenSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Option<Write<'a, TweenEvents>>,
+        WriteStorage<'a, Tweens>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, delta_time, mut events, mut tweens, mut transforms): Self::SystemData,
+    ) {
+        let dt = delta_time.as_secs_float();
+        let mut finished = vec![];
+
+        for (entity, tweens, transform) in (&entities, &mut tweens, &mut transforms).join() {
+            for tween in tweens.tweens.iter_mut() {
+                tween.advance(dt, transform);
+
+                if tween.is_finished() {
+                    if let Some(ref mut events) = events {
+                        events.single_write(TweenEvent {
+                            entity,
+                            property: tween.property(),
+                        });
+                    }
+                }
+            }
+
+            tweens.tweens.retain(|tween| !tween.is_finished());
+            if tweens.is_empty() {
+                finished.push(entity);
+            }
+        }
+
+        for entity in finished {
+            tweens.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::comp::Y_AXIS;
+    use specs::{Builder, RunNow, World};
+    use std::f32::consts::FRAC_PI_2;
+    use std::time::Duration;
+
+    fn create_world() -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Tweens>();
+        world.add_resource(TweenEvents::new());
+        world.add_resource(DeltaTime::new(Duration::from_millis(250)));
+        world
+    }
+
+    #[test]
+    fn test_easing() {
+        let easings = [
+            Easing::Linear,
+            Easing::EaseInQuad,
+            Easing::EaseOutQuad,
+            Easing::EaseInOutQuad,
+            Easing::EaseInCubic,
+            Easing::EaseOutCubic,
+            Easing::EaseInOutCubic,
+            Easing::EaseInOutSine,
+        ];
+
+        for easing in easings.iter() {
+            assert!(easing.apply(0.0).abs() < 1.0e-6, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1.0e-6, "{:?}", easing);
+            assert!((easing.apply(2.0) - 1.0).abs() < 1.0e-6, "{:?}", easing);
+        }
+
+        // Symmetric curves are halfway at the middle
+        assert!((Easing::EaseInOutCubic.apply(0.5) - 0.5).abs() < 1.0e-6);
+        assert!((Easing::EaseInOutSine.apply(0.5) - 0.5).abs() < 1.0e-6);
+        assert!(Easing::EaseInCubic.apply(0.25) < 0.25);
+    }
+
+    #[test]
+    fn test_tween_system() {
+        let mut world = create_world();
+        let mut reader = world.write_resource::<TweenEvents>().register_reader();
+
+        let tweens = Tweens::new()
+            .with(Tween::position([0.0, 0.0, 0.0], [4.0, 0.0, 0.0], 1.0))
+            .with(Tween::scale([1.0, 1.0, 1.0], [2.0, 2.0, 2.0], 0.5));
+        let entity = world
+            .create_entity()
+            .with(Transform::new())
+            .with(tweens)
+            .build();
+
+        let mut system = TweenSystem;
+        system.run_now(&world.res);
+        {
+            let transforms = world.read_storage::<Transform>();
+            let transform = transforms.get(entity).unwrap();
+            assert!((transform.position().x - 1.0).abs() < 1.0e-6);
+            assert!((transform.scale().x - 1.5).abs() < 1.0e-6);
+        }
+
+        // Scale finishes first
+        system.run_now(&world.res);
+        {
+            let events = world.read_resource::<TweenEvents>();
+            let finished: Vec<_> = events.read(&mut reader).cloned().collect();
+            assert_eq!(
+                vec![TweenEvent {
+                    entity,
+                    property: TweenProperty::Scale
+                }],
+                finished
+            );
+            assert_eq!(1, world.read_storage::<Tweens>().get(entity).unwrap().len());
+        }
+
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+        system.run_now(&world.res);
+
+        let transforms = world.read_storage::<Transform>();
+        let transform = transforms.get(entity).unwrap();
+        assert!((transform.position().x - 4.0).abs() < 1.0e-6);
+        assert!((transform.scale().x - 2.0).abs() < 1.0e-6);
+        assert!(world.read_storage::<Tweens>().get(entity).is_none());
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut transform = Transform::new();
+
+        let mut tween =
+            Tween::position([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 1.0).repeat(Repeat::PingPong);
+        tween.advance(1.5, &mut transform);
+        assert!((transform.position().x - 0.5).abs() < 1.0e-6);
+        tween.advance(1.0, &mut transform);
+        assert!((transform.position().x - 0.5).abs() < 1.0e-6);
+        assert!(!tween.is_finished());
+
+        let mut tween = Tween::position([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 1.0).repeat(Repeat::Loop);
+        tween.advance(2.25, &mut transform);
+        assert!((transform.position().x - 0.25).abs() < 1.0e-6);
+        assert!(!tween.is_finished());
+
+        // No duration
+        let mut tween = Tween::position([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 0.0).repeat(Repeat::Loop);
+        tween.advance(0.1, &mut transform);
+        assert!((transform.position().x - 1.0).abs() < 1.0e-6);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn test_rotation_slerp() {
+        let from = Qua::identity();
+        let to = glm::quat_rotate(&Qua::identity(), FRAC_PI_2, &Y_AXIS.into());
+
+        let mut transform = Transform::new();
+        Tween::rotation(from, to, 1.0).advance(0.5, &mut transform);
+
+        // Halfway is a quarter of the way from X to negative Z.
+        let x = glm::quat_rotate_vec3(transform.rotation(), &glm::vec3(1.0, 0.0, 0.0));
+        let expected = glm::vec3(1.0, 0.0, -1.0).normalize();
+        assert!((x - expected).norm() < 1.0e-5);
+    }
+}
@@ -0,0 +1,198 @@
+use super::Transform;
+use crate::res::DeltaTime;
+use glm::Vec3;
+use specs::{Component, DenseVecStorage, Join, Read, System, WriteStorage};
+use std::f32::consts::PI;
+
+/// Back and forth motion of an entity's `Transform` along an axis.
+///
+/// The offset from the rest position follows a sine wave of
+/// the elapsed time, `axis * amplitude * sin(2π * frequency * t + phase)`,
+/// so the motion is the same at any frame rate.
+///
+/// The position is moved by the change in offset each frame, leaving
+/// other movement of the entity intact. Wiggles don't combine with
+/// position tweens, which overwrite the position.
+///
+/// For the random jitter of voxel corners, see `voxel::WiggleParams`.
+///
+/// ## Example
+///
+/// ```
+/// # use rengine::comp::Wiggle;
+/// // Bob up and down by a quarter unit, twice a second.
+/// let wiggle = Wiggle::new()
+///     .with_amplitude(0.25)
+///     .with_frequency(2.0)
+///     .with_axis([0.0, 1.0, 0.0]);
+/// ```
+#[derive(Component, Debug, Clone)]
+#[storage(DenseVecStorage)]
+pub struct Wiggle {
+    /// Largest distance from the rest position.
+    amplitude: f32,
+    /// Number of full swings per second.
+    frequency: f32,
+    /// Normalised direction of the motion.
+    axis: Vec3,
+    /// Offset into the wave, in radians.
+    phase: f32,
+    /// Time played, in seconds, wrapped to one period.
+    elapsed: f32,
+    /// Offset currently applied to the transform.
+    offset: Vec3,
+}
+
+impl Wiggle {
+    /// Wiggle with an amplitude of `0.1`, once per
+    /// second, along the Y axis.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Sets the direction of the motion. The axis is normalised,
+    /// so its length doesn't affect the amplitude.
+    pub fn with_axis<V>(mut self, axis: V) -> Self
+    where
+        V: Into<Vec3>,
+    {
+        self.axis = glm::normalize(&axis.into());
+        self
+    }
+
+    /// Sets the offset into the wave, in radians, so
+    /// entities with the same wiggle can move out of step.
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    #[inline]
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    #[inline]
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    #[inline]
+    pub fn axis(&self) -> &Vec3 {
+        &self.axis
+    }
+
+    #[inline]
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Offset from the rest position after the given
+    /// number of seconds.
+    pub fn offset_at(&self, secs: f32) -> Vec3 {
+        let angle = 2.0 * PI * self.frequency * secs + self.phase;
+        self.axis * (self.amplitude * angle.sin())
+    }
+
+    /// Plays the wiggle for the given number of seconds,
+    /// and moves the transform.
+    pub fn advance(&mut self, dt: f32, transform: &mut Transform) {
+        self.elapsed += dt;
+
+        // Keeps precision when playing forever.
+        if self.frequency > 0.0 {
+            self.elapsed %= 1.0 / self.frequency;
+        }
+
+        let offset = self.offset_at(self.elapsed);
+        transform.translate(offset - self.offset);
+        self.offset = offset;
+    }
+}
+
+impl Default for Wiggle {
+    fn default() -> Self {
+        Wiggle {
+            amplitude: 0.1,
+            frequency: 1.0,
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            phase: 0.0,
+            elapsed: 0.0,
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Plays the `Wiggle` of entities, driven by `DeltaTime`.
+#[derive(Default)]
+pub struct WiggleSystem;
+
+impl<'a> System<'a> for WiggleSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, Wiggle>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (delta_time, mut wiggles, mut transforms): Self::SystemData) {
+        let dt = delta_time.as_secs_float();
+
+        for (wiggle, transform) in (&mut wiggles, &mut transforms).join() {
+            wiggle.advance(dt, transform);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::{Builder, RunNow, World};
+    use std::time::Duration;
+
+    #[test]
+    fn test_wiggle_system() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Wiggle>();
+
+        let wiggle = Wiggle::new()
+            .with_amplitude(2.0)
+            .with_frequency(0.5)
+            .with_axis([0.0, 0.0, 3.0])
+            .with_phase(0.25);
+        let entity = world
+            .create_entity()
+            .with(Transform::new().with_position([1.0, 0.0, 0.0]))
+            .with(wiggle)
+            .build();
+
+        // Uneven frame times end at the same offset as the
+        // elapsed time formula.
+        let frames = [(16, 10), (50, 3), (16, 7)];
+        let mut elapsed = 0.0;
+        for (millis, count) in frames.iter() {
+            world.add_resource(DeltaTime::new(Duration::from_millis(*millis)));
+            for _ in 0..*count {
+                WiggleSystem.run_now(&world.res);
+                elapsed += *millis as f32 / 1000.0;
+            }
+
+            let expected = 2.0 * (2.0 * PI * 0.5 * elapsed + 0.25).sin();
+            let transforms = world.read_storage::<Transform>();
+            let position = transforms.get(entity).unwrap().position();
+            assert!((position.x - 1.0).abs() < 1.0e-5);
+            assert!(position.y.abs() < 1.0e-5);
+            assert!((position.z - expected).abs() < 1.0e-4, "{}", elapsed);
+        }
+    }
+}
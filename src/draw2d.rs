@@ -1,5 +1,7 @@
 //! 2D graphics.
 
 mod canvas;
+mod canvas2d;
 
 pub use canvas::*;
+pub use canvas2d::*;
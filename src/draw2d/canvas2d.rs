@@ -0,0 +1,403 @@
+use crate::colors::{Color, WHITE};
+use crate::comp::GlTexture;
+use crate::gfx_types::Vertex;
+use gfx::handle::Buffer;
+use gfx::Factory;
+use gfx_device::Resources;
+use nalgebra::Vector2;
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Depth of immediate mode primitives, in front of all widgets.
+///
+/// Widgets are given depths counting down from zero, in
+/// draw order.
+const PRIMITIVE_Z_DEPTH: f32 = -60000.0;
+
+/// Bounds on the number of edges used to approximate a circle.
+const MIN_CIRCLE_SEGMENTS: usize = 12;
+const MAX_CIRCLE_SEGMENTS: usize = 128;
+
+/// Number of vertices and indices the canvas buffers start with.
+const INITIAL_BUFFER_LEN: usize = 1024;
+
+/// Immediate mode 2D drawing in screen space, for debug overlays
+/// and HUD elements that don't need the widget graph.
+///
+/// Primitives drawn with `rect`, `line`, `circle` and `textured_rect`
+/// are collected during the frame, and drawn over the GUI by
+/// `DrawGuiSystem` at the end of the frame, which then clears them.
+/// Coordinates are in logical pixels, with the origin at the top left
+/// of the window, the same as widgets.
+///
+/// ## Example
+///
+/// ```ignore
+/// let mut canvas = ctx.world.write_resource::<Canvas2d>();
+/// canvas.rect([8.0, 8.0], [120.0, 24.0], [0.0, 0.0, 0.0, 0.5]);
+/// canvas.line([0.0, 0.0], [100.0, 100.0], 2.0, colors::RED);
+/// canvas.circle([320.0, 240.0], 16.0, colors::GREEN);
+/// ```
+#[derive(Default)]
+pub struct Canvas2d {
+    batch: CanvasBatch,
+}
+
+impl Canvas2d {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Draws a rectangle filled with a solid color.
+    pub fn rect(&mut self, pos: [f32; 2], size: [f32; 2], color: Color) {
+        self.batch.push_rect(pos, size, color, None, FULL_UVS);
+    }
+
+    /// Draws a straight line, centered on the points, `width`
+    /// logical pixels wide.
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], width: f32, color: Color) {
+        self.batch.push_line(a, b, width, color);
+    }
+
+    /// Draws a filled circle.
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: Color) {
+        self.batch.push_circle(center, radius, color);
+    }
+
+    /// Draws a rectangle filled with part of a texture.
+    ///
+    /// The texture coordinates are in the same order as `GuiMeshBuilder::quad`.
+    pub fn textured_rect(
+        &mut self,
+        pos: [f32; 2],
+        size: [f32; 2],
+        texture: &GlTexture,
+        uv: [[f32; 2]; 4],
+    ) {
+        self.batch.push_rect(pos, size, WHITE, Some(texture), uv);
+    }
+
+    /// Primitives drawn since the canvas was last cleared.
+    #[inline]
+    pub(crate) fn batch(&self) -> &CanvasBatch {
+        &self.batch
+    }
+
+    /// Removes all primitives drawn so far.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.batch.clear();
+    }
+}
+
+// ---------- //
+// Primitives //
+// ---------- //
+
+/// Texture coordinates covering a whole texture.
+const FULL_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+/// Vertices of primitives drawn to a canvas during a frame.
+///
+/// Consecutive primitives sampling the same texture are
+/// merged into a single draw.
+#[derive(Default)]
+pub(crate) struct CanvasBatch {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    /// Texture of each draw, or `None` for solid colors, and the
+    /// range of indices it covers.
+    draws: Vec<(Option<GlTexture>, Range<u32>)>,
+}
+
+impl CanvasBatch {
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    pub(crate) fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    #[inline]
+    pub(crate) fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    #[inline]
+    pub(crate) fn draws(&self) -> &[(Option<GlTexture>, Range<u32>)] {
+        &self.draws
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.draws.clear();
+    }
+
+    fn push_rect(
+        &mut self,
+        pos: [f32; 2],
+        size: [f32; 2],
+        color: Color,
+        texture: Option<&GlTexture>,
+        uvs: [[f32; 2]; 4],
+    ) {
+        let [x, y] = pos;
+        let [w, h] = size;
+
+        // Same vertex order as `GuiMeshBuilder::quad`.
+        self.push_quad(
+            [[x, y], [x + w, y], [x + w, y + h], [x, y + h]],
+            [uvs[3], uvs[2], uvs[1], uvs[0]],
+            color,
+            texture,
+        );
+    }
+
+    fn push_line(&mut self, a: [f32; 2], b: [f32; 2], width: f32, color: Color) {
+        let (start, end) = (Vector2::new(a[0], a[1]), Vector2::new(b[0], b[1]));
+        let direction = end - start;
+        let length = direction.norm();
+        if length <= std::f32::EPSILON {
+            return;
+        }
+
+        // Rotating a rectangle keeps its winding, so the corners
+        // are in the same order as a rectangle's.
+        let offset = Vector2::new(-direction.y, direction.x) * (width * 0.5 / length);
+        let corners = [start - offset, end - offset, end + offset, start + offset];
+
+        self.push_quad(
+            [
+                [corners[0].x, corners[0].y],
+                [corners[1].x, corners[1].y],
+                [corners[2].x, corners[2].y],
+                [corners[3].x, corners[3].y],
+            ],
+            [FULL_UVS[3], FULL_UVS[2], FULL_UVS[1], FULL_UVS[0]],
+            color,
+            None,
+        );
+    }
+
+    /// Circle as a fan of triangles around its center, with more
+    /// edges for larger circles.
+    fn push_circle(&mut self, center: [f32; 2], radius: f32, color: Color) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        // Edges roughly four pixels long.
+        let segments = ((2.0 * PI * radius / 4.0).ceil() as usize)
+            .max(MIN_CIRCLE_SEGMENTS)
+            .min(MAX_CIRCLE_SEGMENTS);
+
+        let index = self.vertices.len() as u32;
+        let vertex = |pos: [f32; 2]| Vertex {
+            pos: [pos[0], pos[1], PRIMITIVE_Z_DEPTH],
+            uv: [0.5, 0.5],
+            normal: [0.0, 0.0, 1.0],
+            color,
+            layer: 0.0,
+        };
+
+        self.vertices.push(vertex(center));
+        for i in 0..segments {
+            let angle = 2.0 * PI * i as f32 / segments as f32;
+            self.vertices.push(vertex([
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]));
+        }
+
+        // Same winding as rectangles.
+        let start = self.indices.len() as u32;
+        for i in 0..segments as u32 {
+            let next = (i + 1) % segments as u32;
+            self.indices
+                .extend(&[index, index + 1 + next, index + 1 + i]);
+        }
+        let end = self.indices.len() as u32;
+
+        self.push_draw(None, start..end);
+    }
+
+    fn push_quad(
+        &mut self,
+        positions: [[f32; 2]; 4],
+        uvs: [[f32; 2]; 4],
+        color: Color,
+        texture: Option<&GlTexture>,
+    ) {
+        let index = self.vertices.len() as u32;
+
+        for (pos, uv) in positions.iter().zip(uvs.iter()) {
+            self.vertices.push(Vertex {
+                pos: [pos[0], pos[1], PRIMITIVE_Z_DEPTH],
+                uv: *uv,
+                normal: [0.0, 0.0, 1.0],
+                color,
+                layer: 0.0,
+            });
+        }
+
+        // Clockwise, like GUI meshes, because the y-axis is
+        // inverted in the shader.
+        let start = self.indices.len() as u32;
+        self.indices.extend(&[index, index + 2, index + 1]);
+        self.indices.extend(&[index, index + 3, index + 2]);
+        let end = self.indices.len() as u32;
+
+        self.push_draw(texture, start..end);
+    }
+
+    /// Extends the last draw with the given indices when it
+    /// samples the same texture, otherwise starts a new draw.
+    fn push_draw(&mut self, texture: Option<&GlTexture>, indices: Range<u32>) {
+        let same_texture = |other: &Option<GlTexture>| match (other, texture) {
+            (Some(a), Some(b)) => Arc::ptr_eq(&a.bundle, &b.bundle),
+            (None, None) => true,
+            _ => false,
+        };
+
+        let is_same_draw = self
+            .draws
+            .last()
+            .map_or(false, |(other, _)| same_texture(other));
+
+        match self.draws.last_mut() {
+            Some((_, range)) if is_same_draw => range.end = indices.end,
+            _ => self.draws.push((texture.cloned(), indices)),
+        }
+    }
+}
+
+/// Dynamic graphics memory that canvas primitives are uploaded
+/// to each frame.
+///
+/// The buffers are reused between frames, and doubled in size
+/// when they run out of room.
+pub(crate) struct CanvasBuffers {
+    pub(crate) vbuf: Buffer<Resources, Vertex>,
+    pub(crate) ibuf: Buffer<Resources, u32>,
+}
+
+impl CanvasBuffers {
+    pub(crate) fn new(factory: &mut gfx_device::Factory) -> Self {
+        CanvasBuffers {
+            vbuf: create_buffer(factory, INITIAL_BUFFER_LEN, gfx::buffer::Role::Vertex),
+            ibuf: create_buffer(factory, INITIAL_BUFFER_LEN, gfx::buffer::Role::Index),
+        }
+    }
+
+    /// Grows the buffers, if required, to hold the given number
+    /// of vertices and indices.
+    ///
+    /// Replaced buffers do not keep their previous contents.
+    pub(crate) fn reserve(
+        &mut self,
+        factory: &mut gfx_device::Factory,
+        vertex_count: usize,
+        index_count: usize,
+    ) {
+        if self.vbuf.len() < vertex_count {
+            let len = vertex_count.next_power_of_two();
+            self.vbuf = create_buffer(factory, len, gfx::buffer::Role::Vertex);
+        }
+
+        if self.ibuf.len() < index_count {
+            let len = index_count.next_power_of_two();
+            self.ibuf = create_buffer(factory, len, gfx::buffer::Role::Index);
+        }
+    }
+}
+
+fn create_buffer<T>(
+    factory: &mut gfx_device::Factory,
+    len: usize,
+    role: gfx::buffer::Role,
+) -> Buffer<Resources, T>
+where
+    T: gfx::traits::Pod,
+{
+    factory
+        .create_buffer(
+            len,
+            role,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty(),
+        )
+        .expect("Failed to create canvas buffer")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_canvas_batch() {
+        let mut batch = CanvasBatch::new();
+        batch.push_rect([10.0, 10.0], [32.0, 16.0], WHITE, None, FULL_UVS);
+        batch.push_line([0.0, 0.0], [100.0, 50.0], 2.0, WHITE);
+        batch.push_rect([0.0, 0.0], [8.0, 8.0], WHITE, None, FULL_UVS);
+
+        // Zero length lines are skipped.
+        batch.push_line([4.0, 4.0], [4.0, 4.0], 2.0, WHITE);
+
+        assert_eq!(12, batch.vertices().len());
+        assert_eq!(18, batch.indices().len());
+
+        // Solid colors share a single draw.
+        assert_eq!(1, batch.draws().len());
+        assert_eq!(0..18, batch.draws()[0].1);
+
+        assert_eq!([42.0, 26.0, PRIMITIVE_Z_DEPTH], batch.vertices()[2].pos);
+
+        // Line is centered on its end points.
+        let horizontal = {
+            let mut batch = CanvasBatch::new();
+            batch.push_line([0.0, 10.0], [20.0, 10.0], 4.0, WHITE);
+            batch
+                .vertices()
+                .iter()
+                .map(|v| [v.pos[0], v.pos[1]])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![[0.0, 8.0], [20.0, 8.0], [20.0, 12.0], [0.0, 12.0]],
+            horizontal
+        );
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert!(batch.vertices().is_empty());
+    }
+
+    #[test]
+    fn test_canvas_circle() {
+        let mut batch = CanvasBatch::new();
+        batch.push_circle([50.0, 50.0], 10.0, WHITE);
+
+        // Center and rim
+        let segments = batch.vertices().len() - 1;
+        assert!(segments >= MIN_CIRCLE_SEGMENTS);
+        assert_eq!(segments * 3, batch.indices().len());
+        assert_eq!([50.0, 50.0, PRIMITIVE_Z_DEPTH], batch.vertices()[0].pos);
+        for vertex in &batch.vertices()[1..] {
+            let distance = Vector2::new(vertex.pos[0] - 50.0, vertex.pos[1] - 50.0).norm();
+            assert!((distance - 10.0).abs() < 0.001);
+        }
+
+        // Merged with the following rectangle.
+        batch.push_rect([0.0, 0.0], [8.0, 8.0], WHITE, None, FULL_UVS);
+        assert_eq!(1, batch.draws().len());
+    }
+}
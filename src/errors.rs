@@ -1,53 +1,279 @@
 #![allow(clippy::large_enum_variant)]
 
+use crate::collections::ordered_dag::OrderedGraphError;
 use crate::gfx_types::GraphicsEncoder;
 use crate::scene::SceneError;
 use crossbeam::channel::{RecvError, SendError};
 use glutin::CreationError;
+use rayon::ThreadPoolBuildError;
+use std::fmt;
+use thiserror::Error;
 
-error_chain! {
-    // Names driven by convention.
-    types {
-        Error, ErrorKind, ResultExt, Result;
+/// Specialised `Result` for engine operations.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Top level engine error, wrapping the error
+/// of each domain.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to retrieve window size")]
+    WindowSize,
+
+    #[error("no initial scene configured")]
+    NoInitialScene,
+
+    #[error(transparent)]
+    SceneTransition(#[from] SceneError),
+
+    #[error(transparent)]
+    ModLoad(#[from] ModLoadError),
+
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+
+    #[error(transparent)]
+    Render(#[from] RenderError),
+
+    #[error(transparent)]
+    Mesh(#[from] MeshError),
+
+    #[error(transparent)]
+    Io(#[from] IoError),
+
+    #[error("failed to build system thread pool: {0}")]
+    ThreadPool(#[from] ThreadPoolBuildError),
+}
+
+/// Failure to find or read mods.
+#[derive(Debug, Error)]
+pub enum ModLoadError {
+    #[error("failed to load mods, invalid mod name '{0}'")]
+    InvalidName(String),
+
+    #[error("failed to parse mod meta file: {0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+/// Failure in a script runner, or its Lua state.
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("error in Lua script: {0}")]
+    Lua(#[from] rlua::Error),
+
+    #[error("script runner thread panic")]
+    Thread,
+
+    #[error("did not receive command buffer back from script runner")]
+    Dispatch,
+
+    /// Errors gathered from multiple script runners.
+    #[error("{}", join_lines(.0))]
+    Composite(Vec<Error>),
+}
+
+/// Failure in the window or graphics device.
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("failed to create window: {0}")]
+    GlutinCreate(#[from] CreationError),
+
+    #[error("failed to receive graphics encoder")]
+    EncoderRecv(#[from] RecvError),
+
+    #[error("failed to send graphics encoder")]
+    EncoderSend(#[from] SendError<GraphicsEncoder>),
+
+    #[error(transparent)]
+    Texture(#[from] TextureError),
+
+    #[error(transparent)]
+    Atlas(#[from] AtlasError),
+
+    #[error("failed to create pipeline: {0}")]
+    Pipeline(String),
+}
+
+/// Failure updating the contents of a texture.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TextureError {
+    #[error("region at {offset:?} of size {size:?} is outside texture of size {tex_size:?}")]
+    OutOfBounds {
+        offset: [u16; 2],
+        size: [u16; 2],
+        tex_size: (u32, u32),
+    },
+
+    #[error("expected {expected} bytes of RGBA8 pixel data, got {actual}")]
+    DataLength { expected: usize, actual: usize },
+
+    #[error("failed to update texture: {0}")]
+    Update(String),
+
+    #[error("failed to decode texture image: {0}")]
+    Decode(String),
+
+    #[error("image size changed from {old:?} to {new:?}, restart to reload the texture")]
+    SizeChanged { old: (u32, u32), new: (u32, u32) },
+
+    #[error("texture array needs at least one layer")]
+    NoLayers,
+
+    #[error("texture array layer {layer} is {actual:?}, but layers are {expected:?}")]
+    LayerSizeMismatch {
+        layer: usize,
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+}
+
+/// Failure loading or saving an input map.
+#[derive(Debug, Error)]
+pub enum InputMapError {
+    #[error("failed to read or write input map: {0}")]
+    Io(#[from] ::std::io::Error),
+
+    #[error("failed to parse input map: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("failed to serialize input map: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("unknown input in input map: {0}")]
+    UnknownInput(String),
+}
+
+/// Failure loading a mesh.
+#[derive(Debug, Error)]
+pub enum MeshError {
+    #[error("failed to read mesh: {0}")]
+    Io(#[from] ::std::io::Error),
+
+    #[error("invalid mesh on line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+/// Failure modifying the GUI graph.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GuiGraphError {
+    #[error("can't move widget into its own subtree")]
+    Cycle,
+
+    #[error("widget node does not exist")]
+    NodeDoesNotExist,
+
+    #[error("widget has no parent")]
+    NoParent,
+}
+
+impl From<OrderedGraphError> for GuiGraphError {
+    fn from(err: OrderedGraphError) -> Self {
+        match err {
+            OrderedGraphError::Cycle => GuiGraphError::Cycle,
+            OrderedGraphError::NodeDoesNotExist => GuiGraphError::NodeDoesNotExist,
+        }
     }
+}
+
+/// Failure loading or packing a texture atlas.
+#[derive(Debug, Error)]
+pub enum AtlasError {
+    #[error("failed to read atlas descriptor: {0}")]
+    Io(#[from] ::std::io::Error),
+
+    #[error("failed to parse atlas descriptor: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("failed to load atlas image: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("image '{name}' of size {size:?} does not fit in an atlas of size {max_size:?}")]
+    DoesNotFit {
+        name: String,
+        size: (u32, u32),
+        max_size: (u32, u32),
+    },
+
+    #[error("sprite '{name}' is outside the atlas image of size {tex_size:?}")]
+    OutOfBounds { name: String, tex_size: (u32, u32) },
+}
+
+/// Number was NaN where a number was expected.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("number is NaN")]
+pub struct NanError;
 
-    foreign_links {
-        Fmt(::std::fmt::Error);
-        Io(::std::io::Error);
-        SceneTransition(SceneError);
-        EncoderRecv(RecvError);
-        GlutinCreate(CreationError);
+/// Failure reading or writing data.
+#[derive(Debug, Error)]
+pub enum IoError {
+    #[error(transparent)]
+    Io(#[from] ::std::io::Error),
 
-        // `error-chain` does not currently support polymorphism.
-        GraphicsEncoderSend(SendError<GraphicsEncoder>);
-        Lua(rlua::Error);
-        Toml(toml::de::Error);
+    #[error(transparent)]
+    Fmt(#[from] fmt::Error),
+}
+
+// Allows `?` on foreign errors in functions
+// returning the top level `Result`.
+
+impl From<::std::io::Error> for Error {
+    fn from(err: ::std::io::Error) -> Self {
+        Error::Io(err.into())
     }
+}
 
-    errors {
-        WindowSize {
-            description("failed to retrieve window size")
-            display("failed to retrieve window size")
-        }
-        NoInitialScene {
-            description("no initial scene configured")
-            display("no initial scene configured")
-        }
-        ModLoad {
-            description("failed to load mods")
-            display("failed to load mods")
-        }
-        ModScriptThread {
-            description("script runner thread panic")
-            display("script runner thread panic")
-        }
-        ModDispatch {
-            description("did not receive command buffer back from script runner")
-            display("did not receive command buffer back from script runner")
-        }
-        ModComposite(v: Vec<Error>) {
-            description("multiple script failures")
-            display("multiple script failures: {} errors", v.len())
-        }
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Error::Io(err.into())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::ModLoad(err.into())
+    }
+}
+
+impl From<rlua::Error> for Error {
+    fn from(err: rlua::Error) -> Self {
+        Error::Script(err.into())
+    }
+}
+
+impl From<CreationError> for Error {
+    fn from(err: CreationError) -> Self {
+        Error::Render(err.into())
+    }
+}
+
+impl From<RecvError> for Error {
+    fn from(err: RecvError) -> Self {
+        Error::Render(err.into())
     }
 }
+
+impl From<SendError<GraphicsEncoder>> for Error {
+    fn from(err: SendError<GraphicsEncoder>) -> Self {
+        Error::Render(err.into())
+    }
+}
+
+impl From<TextureError> for Error {
+    fn from(err: TextureError) -> Self {
+        Error::Render(err.into())
+    }
+}
+
+impl From<AtlasError> for Error {
+    fn from(err: AtlasError) -> Self {
+        Error::Render(err.into())
+    }
+}
+
+/// Displays each error on its own line.
+pub(crate) fn join_lines<E: fmt::Display>(errors: &[E]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
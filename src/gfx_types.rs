@@ -16,12 +16,22 @@ gfx_defines! {
         uv: [f32; 2] = "a_Uv",
         normal: [f32; 3] = "a_Normal",
         color: [f32; 4] = "a_Color",
+        // Layer of a texture array, ignored by single textures
+        layer: f32 = "a_Layer",
     }
 
     constant Transform {
         transform: [[f32; 4]; 4] = "u_Transform",
     }
 
+    // Model matrix columns of a single instance
+    vertex Instance {
+        model_0: [f32; 4] = "a_Model0",
+        model_1: [f32; 4] = "a_Model1",
+        model_2: [f32; 4] = "a_Model2",
+        model_3: [f32; 4] = "a_Model3",
+    }
+
     // Note: Never use vec3 inside uniform block
     constant GlossMaterial {
         ambient: [f32; 4] = "u_Ambient",
@@ -36,6 +46,8 @@ gfx_defines! {
         ambient: [f32; 4] = "ambient",
         diffuse: [f32; 4] = "diffuse",
         specular: [f32; 4] = "specular",
+        // Constant, linear and quadratic coefficients, padded to a vec4
+        attenuation: [f32; 4] = "attenuation",
     }
 
     pipeline pipe {
@@ -64,6 +76,30 @@ gfx_defines! {
             gfx::preset::depth::LESS_EQUAL_WRITE,
     }
 
+    pipeline instanced_pipe {
+        vbuf: gfx::VertexBuffer<Vertex> = (),
+
+        // Per instance model transform
+        instances: gfx::InstanceBuffer<Instance> = (),
+
+        // Simple texture sampler
+        sampler: gfx::TextureSampler<[f32; 4]> = "t_Sampler",
+
+        // View
+        view: gfx::Global<[[f32; 4]; 4]> = "u_View",
+
+        // Projection
+        proj: gfx::Global<[[f32; 4]; 4]> = "u_Proj",
+
+        // Enables the scissor test
+        scissor: gfx::Scissor = (),
+
+        render_target: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+
+        depth_target: gfx::DepthTarget<DepthFormat> =
+            gfx::preset::depth::LESS_EQUAL_WRITE,
+    }
+
     pipeline gloss_pipe {
         vbuf: gfx::VertexBuffer<Vertex> = (),
 
@@ -124,6 +160,25 @@ gfx_defines! {
         depth_target: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
     }
 
+    // Writes depth only, with no colour output
+    pipeline depth_pipe {
+        vbuf: gfx::VertexBuffer<Vertex> = (),
+
+        // Model Transform Matrix
+        model: gfx::Global<[[f32; 4]; 4]> = "u_Model",
+
+        // View
+        view: gfx::Global<[[f32; 4]; 4]> = "u_View",
+
+        // Projection
+        proj: gfx::Global<[[f32; 4]; 4]> = "u_Proj",
+
+        // Enables the scissor test
+        scissor: gfx::Scissor = (),
+
+        depth_target: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
+    }
+
     pipeline line_pipe {
         vbuf: gfx::VertexBuffer<Vertex> = (),
 
@@ -155,6 +210,9 @@ gfx_defines! {
         // Projection
         proj: gfx::Global<[[f32; 4]; 4]> = "u_Proj",
 
+        // Colour multiplied with the whole widget
+        tint: gfx::Global<[f32; 4]> = "u_Tint",
+
         // Enables the scissor test
         scissor: gfx::Scissor = (),
 
@@ -166,6 +224,19 @@ gfx_defines! {
     }
 }
 
+impl From<nalgebra::Matrix4<f32>> for Instance {
+    fn from(matrix: nalgebra::Matrix4<f32>) -> Self {
+        let [model_0, model_1, model_2, model_3]: [[f32; 4]; 4] = matrix.into();
+
+        Instance {
+            model_0,
+            model_1,
+            model_2,
+            model_3,
+        }
+    }
+}
+
 pub type PipelineStateObject = gfx::PipelineState<gfx_device::Resources, pipe::Meta>;
 pub type GizmoPso = gfx::PipelineState<gfx_device::Resources, gizmo_pipe::Meta>;
 pub type LinePso = gfx::PipelineState<gfx_device::Resources, line_pipe::Meta>;
@@ -182,3 +253,40 @@ impl<M> PipelineBundle<M> {
         PipelineBundle { pso, program }
     }
 }
+
+/// Pipelines used for drawing opaque meshes when the depth
+/// prepass is enabled.
+pub struct DepthPrepassBundle {
+    /// Fills the depth buffer, without running any lighting.
+    pub(crate) depth: PipelineBundle<depth_pipe::Meta>,
+    /// Gloss pipeline that tests against the depth written by the
+    /// prepass, without writing depth itself.
+    pub(crate) gloss: PipelineBundle<gloss_pipe::Meta>,
+}
+
+/// Gloss pipelines sampling a texture array, for `Material::GlossArray`.
+pub struct GlossArrayBundle {
+    /// Writes depth, for drawing without a depth prepass.
+    pub(crate) gloss: PipelineBundle<gloss_pipe::Meta>,
+    /// Tests against the depth written by the prepass, without
+    /// writing depth itself.
+    pub(crate) prepass: PipelineBundle<gloss_pipe::Meta>,
+}
+
+impl GlossArrayBundle {
+    pub fn new(
+        gloss: PipelineBundle<gloss_pipe::Meta>,
+        prepass: PipelineBundle<gloss_pipe::Meta>,
+    ) -> Self {
+        GlossArrayBundle { gloss, prepass }
+    }
+}
+
+impl DepthPrepassBundle {
+    pub fn new(
+        depth: PipelineBundle<depth_pipe::Meta>,
+        gloss: PipelineBundle<gloss_pipe::Meta>,
+    ) -> Self {
+        DepthPrepassBundle { depth, gloss }
+    }
+}
@@ -1,25 +1,34 @@
 use crate::collections::ordered_dag::prelude::*;
 use crate::collections::ordered_dag::{ChildrenWalk, PostOrderWalk, PreOrderWalk};
+use crate::errors::GuiGraphError;
+use log::warn;
 use specs::Entity;
 
 pub use crate::collections::ordered_dag::NodeId;
+pub use crate::comp::{GuiMesh, GuiMeshBuilder};
 
 mod builder;
+mod drag;
 mod draw;
+mod focus;
 mod layout;
-mod mesh;
 mod proj;
 mod systems;
 pub mod text;
+mod tooltip;
+mod visibility;
 mod widget;
 pub mod widgets;
 
 pub use builder::*;
+pub use drag::*;
 pub use draw::*;
+pub use focus::*;
 pub use layout::*;
-pub use mesh::*;
 pub use proj::*;
 pub use systems::*;
+pub use tooltip::*;
+pub use visibility::*;
 pub use widget::*;
 
 // TODO: Cleaning up Widgets when scene is stopped
@@ -68,6 +77,108 @@ impl GuiGraph {
         self.graph.node(node_id).cloned()
     }
 
+    /// Retrieve the node id of the widget's parent.
+    ///
+    /// Returns `None` for the root widget, or when
+    /// the node does not exist.
+    pub fn parent_id(&self, node_id: NodeId) -> Option<NodeId> {
+        self.graph.parent(node_id)
+    }
+
+    /// Number of hops from the widget up to the root.
+    ///
+    /// The root, and nodes that don't exist, have a depth of zero.
+    pub fn depth(&self, node_id: NodeId) -> usize {
+        let mut depth = 0;
+        let mut cursor = node_id;
+
+        while let Some(parent_id) = self.graph.parent(cursor) {
+            depth += 1;
+            cursor = parent_id;
+        }
+
+        depth
+    }
+
+    /// Deepest node that has both nodes as descendants, or is
+    /// one of the nodes itself.
+    ///
+    /// Returns `None` when the nodes are in separate trees.
+    pub fn common_ancestor(&self, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let mut ancestors = vec![a];
+        while let Some(parent_id) = self.graph.parent(*ancestors.last().unwrap()) {
+            ancestors.push(parent_id);
+        }
+
+        let mut cursor = Some(b);
+        while let Some(node_id) = cursor {
+            if ancestors.contains(&node_id) {
+                return Some(node_id);
+            }
+            cursor = self.graph.parent(node_id);
+        }
+
+        None
+    }
+
+    /// Detaches the widget from its parent, and removes
+    /// it and all its descendants from the graph.
+    ///
+    /// Returns the entities of the removed widgets, which are
+    /// still alive in the world. Use `despawn_widget` to delete
+    /// them as well.
+    ///
+    /// The root widget can not be removed.
+    pub fn remove_subtree(&mut self, node_id: NodeId) -> Vec<Entity> {
+        if node_id == self.root_id {
+            warn!("Attempted to remove GUI root widget");
+            return vec![];
+        }
+
+        self.graph.remove_subtree(node_id)
+    }
+
+    /// Moves the widget, and its descendants, to the end of the
+    /// given parent's children.
+    ///
+    /// Fails with `GuiGraphError::Cycle` when the new parent is the
+    /// widget itself, or one of its descendants. Layout is not
+    /// invalidated; use `reparent_widget` to also schedule a layout
+    /// pass of the affected widgets.
+    pub fn reparent(&mut self, node_id: NodeId, new_parent: NodeId) -> Result<(), GuiGraphError> {
+        self.graph.reparent(node_id, new_parent)?;
+
+        Ok(())
+    }
+
+    /// Sets the sort key of the widget among its siblings, and
+    /// sorts the children of its parent.
+    ///
+    /// Siblings are ordered from lowest to highest, and widgets later
+    /// in the order are drawn on top. Siblings with the same order keep
+    /// their current positions relative to each other.
+    ///
+    /// Fails with `GuiGraphError::NoParent` for the root widget. Layout
+    /// is not invalidated; use `reorder_widget` to also schedule a
+    /// layout pass of the parent.
+    pub fn set_child_order(
+        &mut self,
+        node_id: NodeId,
+        order_index: u16,
+    ) -> Result<(), GuiGraphError> {
+        let parent_id = match self.graph.parent(node_id) {
+            Some(parent_id) => parent_id,
+            None if self.graph.node(node_id).is_some() => return Err(GuiGraphError::NoParent),
+            None => return Err(GuiGraphError::NodeDoesNotExist),
+        };
+
+        self.graph
+            .set_edge(parent_id, node_id, Child { order_index })?;
+        self.graph.sort_node(parent_id);
+
+        Ok(())
+    }
+
     /// Remove all widgets in the GUI that are associated
     /// with the given entities.
     pub fn delete_entities(&mut self, _entities: &[Entity]) {
@@ -189,3 +300,95 @@ fn pretty_print_gui(graph: &OrderedDag<Entity, Child>, node_id: NodeId, level: i
         cursor += 1;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::{Builder, World};
+
+    #[test]
+    fn test_reparent() {
+        let mut world = World::new();
+        let mut entity = || world.create_entity().build();
+
+        let mut gui_graph = GuiGraph::with_root(entity());
+        let root_id = gui_graph.root_id();
+        let old_parent = gui_graph.insert_entity(entity(), None);
+        let new_parent = gui_graph.insert_entity(entity(), None);
+
+        // Subtree of 3 nodes
+        let a = gui_graph.insert_entity(entity(), Some(old_parent));
+        let b = gui_graph.insert_entity(entity(), Some(a));
+        let c = gui_graph.insert_entity(entity(), Some(b));
+        assert_eq!(4, gui_graph.depth(c));
+
+        gui_graph.reparent(a, new_parent).unwrap();
+
+        // Old parent lost the edge, and the new parent gained it.
+        assert!(gui_graph
+            .walk_children(old_parent)
+            .next(&gui_graph)
+            .is_none());
+        let mut children = gui_graph.walk_children(new_parent);
+        assert_eq!(Some(a), children.next(&gui_graph));
+        assert_eq!(None, children.next(&gui_graph));
+
+        // Subtree moved along.
+        assert_eq!(Some(new_parent), gui_graph.parent_id(a));
+        assert_eq!(Some(a), gui_graph.parent_id(b));
+        assert_eq!(Some(b), gui_graph.parent_id(c));
+        assert_eq!(4, gui_graph.depth(c));
+        assert_eq!(0, gui_graph.depth(root_id));
+
+        // Can't move into own subtree.
+        assert_eq!(Err(GuiGraphError::Cycle), gui_graph.reparent(a, c));
+        assert_eq!(Err(GuiGraphError::Cycle), gui_graph.reparent(a, a));
+        assert_eq!(Some(new_parent), gui_graph.parent_id(a));
+
+        assert_eq!(
+            Some(root_id),
+            gui_graph.common_ancestor(old_parent, new_parent)
+        );
+        assert_eq!(Some(a), gui_graph.common_ancestor(c, a));
+    }
+
+    #[test]
+    fn test_set_child_order() {
+        let mut world = World::new();
+        let mut entity = || world.create_entity().build();
+
+        let mut gui_graph = GuiGraph::with_root(entity());
+        let root_id = gui_graph.root_id();
+        let a = gui_graph.insert_entity(entity(), None);
+        let b = gui_graph.insert_entity(entity(), None);
+        let c = gui_graph.insert_entity(entity(), None);
+        let a_child = gui_graph.insert_entity(entity(), Some(a));
+
+        let pre_order = |gui_graph: &GuiGraph| {
+            let mut walker = gui_graph.walk_dfs_pre_order(root_id);
+            let mut node_ids = vec![];
+            while let Some(node_id) = walker.next(gui_graph) {
+                node_ids.push(node_id);
+            }
+            node_ids
+        };
+        assert_eq!(vec![root_id, a, a_child, b, c], pre_order(&gui_graph));
+
+        // Bring to front, with its subtree.
+        gui_graph.set_child_order(a, 10).unwrap();
+        assert_eq!(vec![root_id, b, c, a, a_child], pre_order(&gui_graph));
+
+        gui_graph.set_child_order(b, 5).unwrap();
+        assert_eq!(vec![root_id, c, b, a, a_child], pre_order(&gui_graph));
+
+        // Moved into a parent with ordered children.
+        gui_graph.reparent(c, a).unwrap();
+        assert_eq!(vec![root_id, b, a, a_child, c], pre_order(&gui_graph));
+
+        let result = gui_graph.set_child_order(root_id, 1);
+        assert_eq!(Err(GuiGraphError::NoParent), result);
+        gui_graph.remove_subtree(b);
+        let result = gui_graph.set_child_order(b, 1);
+        assert_eq!(Err(GuiGraphError::NodeDoesNotExist), result);
+    }
+}
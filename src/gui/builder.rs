@@ -23,4 +23,12 @@ impl<'a> GuiBuilder<'a> {
 
 pub trait WidgetBuilder {
     fn build(self, world: &mut World, graphics: &mut GraphicContext) -> (Entity, NodeId);
+
+    /// Builds the widget without a graphics context, for testing
+    /// layout and events without a GPU.
+    ///
+    /// The widget gets all its components except the ones needing
+    /// graphics resources, like its `GuiMesh` and texture, so it
+    /// isn't drawn.
+    fn build_headless(self, world: &mut World) -> (Entity, NodeId);
 }
@@ -0,0 +1,600 @@
+//! Dragging widgets with the mouse, and dropping them onto other widgets.
+use super::{
+    find_widget, AlwaysOnTop, BoundsRect, Enabled, FindWidgetData, GlobalPosition, GuiGraph,
+    Hidden, LayoutDirty, NodeId, Placement, WidgetEvent, WidgetEventKind,
+};
+use crate::res::{DeviceDimensions, ViewPort};
+use glutin::{ElementState, Event, WindowEvent};
+use log::warn;
+use nalgebra::Vector2;
+use shrev::EventChannel;
+use specs::prelude::*;
+
+/// Distance, in logical pixels, the cursor has to move while
+/// pressing a draggable widget before a drag starts.
+pub const DEFAULT_DRAG_THRESHOLD: f32 = 4.0;
+
+/// Moves `Draggable` widgets along with the mouse cursor, and drops
+/// them onto `DropTarget` widgets.
+///
+/// The dragged widget follows the cursor by changing its `Placement`,
+/// and is drawn above other widgets. When the button is released
+/// the widget returns to its original placement, and either a
+/// `Dropped` or `DragCancelled` event is emitted. Moving the widget
+/// to its new parent is up to the receiver of the event.
+///
+/// Should run before `GuiMouseMoveSystem`, which leaves out the
+/// release event of a press that turned into a drag, even when the
+/// drag started and ended within the same frame.
+///
+/// A dragged widget that is despawned ends the drag without an event.
+pub struct GuiDragSystem {
+    threshold: f32,
+    /// Last known mouse cursor position, in logical coordinates.
+    mouse_pos: [f32; 2],
+    /// Draggable widget pressed, and where the cursor was at the time.
+    candidate: Option<(Entity, NodeId, [f32; 2])>,
+    /// Placement offset of the dragged widget before the drag.
+    origin: Vector2<f32>,
+}
+
+impl GuiDragSystem {
+    pub fn new() -> Self {
+        GuiDragSystem {
+            threshold: DEFAULT_DRAG_THRESHOLD,
+            mouse_pos: [0.0, 0.0],
+            candidate: None,
+            origin: Vector2::zeros(),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Offset of the dragged widget from its original
+    /// placement, following the cursor.
+    fn drag_offset(&self) -> Vector2<f32> {
+        match self.candidate {
+            Some((_, _, [x, y])) => {
+                self.origin + Vector2::new(self.mouse_pos[0] - x, self.mouse_pos[1] - y)
+            }
+            None => self.origin,
+        }
+    }
+}
+
+impl Default for GuiDragSystem {
+    fn default() -> Self {
+        GuiDragSystem::new()
+    }
+}
+
+impl<'a> System<'a> for GuiDragSystem {
+    type SystemData = GuiDragData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let GuiDragData {
+            entities,
+            events,
+            mut gui_events,
+            gui_graph,
+            mut dragged,
+            mut layout_dirty,
+            draggables,
+            drop_targets,
+            mut placements,
+            mut always_on_top,
+            bounds_rects,
+            global_positions,
+            hidden,
+            enabled,
+            view_port,
+            device_dim,
+        } = data;
+
+        let root_id = gui_graph.root_id();
+
+        // Drags that ended last frame were already seen by the mouse system.
+        dragged.released = None;
+
+        // The dragged widget may have been despawned.
+        if let Some(entity) = dragged.entity() {
+            if !entities.is_alive(entity) {
+                dragged.widget = None;
+                self.candidate = None;
+            }
+        }
+        if let Some((entity, _, _)) = self.candidate {
+            if !entities.is_alive(entity) {
+                self.candidate = None;
+            }
+        }
+
+        for ev in events.iter() {
+            let event = match ev {
+                Event::WindowEvent { event, .. } => event,
+                _ => continue,
+            };
+
+            match event {
+                WindowEvent::CursorMoved { position, .. } => {
+                    self.mouse_pos = [position.x as f32, position.y as f32];
+
+                    if !dragged.has_widget() {
+                        match self.candidate {
+                            Some((entity, node_id, [x, y])) => {
+                                let distance =
+                                    Vector2::new(self.mouse_pos[0] - x, self.mouse_pos[1] - y);
+                                if distance.norm() < self.threshold {
+                                    continue;
+                                }
+
+                                self.origin = placements
+                                    .get(entity)
+                                    .map(|placement| *placement.offset())
+                                    .unwrap_or_else(Vector2::zeros);
+                                dragged.set(entity, node_id);
+
+                                if let Err(err) = always_on_top.insert(entity, AlwaysOnTop) {
+                                    warn!("Failed to drag widget {:?}: {}", entity, err);
+                                }
+
+                                gui_events.single_write(WidgetEvent {
+                                    entity,
+                                    node_id,
+                                    kind: WidgetEventKind::DragStarted,
+                                    window_event: event.clone(),
+                                });
+                            }
+                            None => continue,
+                        }
+                    }
+
+                    if let Some((entity, node_id)) = dragged.widget() {
+                        let offset = self.drag_offset();
+                        match placements.get_mut(entity) {
+                            Some(placement) => placement.set_offset(offset),
+                            None => {
+                                let placement = Placement::from_vector(offset);
+                                if let Err(err) = placements.insert(entity, placement) {
+                                    warn!("Failed to drag widget {:?}: {}", entity, err);
+                                }
+                            }
+                        }
+
+                        // The widget's own position is derived from its parent.
+                        let parent_id = gui_graph.parent_id(node_id).unwrap_or(root_id);
+                        layout_dirty.mark(parent_id, root_id);
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    ..
+                } => {
+                    let mouse_pos = self.mouse_pos;
+                    self.candidate = find_widget(
+                        FindWidgetData {
+                            gui_graph: &gui_graph,
+                            global_positions: &global_positions,
+                            bounds_rects: &bounds_rects,
+                            hidden: &hidden,
+                            enabled: &enabled,
+                            view_port: &view_port,
+                            device_dim: &device_dim,
+                        },
+                        mouse_pos,
+                        |entity| draggables.contains(entity),
+                    )
+                    .map(|(entity, node_id)| (entity, node_id, mouse_pos));
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    ..
+                } => {
+                    self.candidate = None;
+
+                    if let Some((entity, node_id)) = dragged.clear() {
+                        // The dragged widget, and its children, are under the cursor.
+                        let mut dragged_entities = vec![];
+                        let mut walker = gui_graph.walk_dfs_pre_order(node_id);
+                        while let Some(next_id) = walker.next(&gui_graph) {
+                            dragged_entities.extend(gui_graph.get_entity(next_id));
+                        }
+
+                        let target = find_widget(
+                            FindWidgetData {
+                                gui_graph: &gui_graph,
+                                global_positions: &global_positions,
+                                bounds_rects: &bounds_rects,
+                                hidden: &hidden,
+                                enabled: &enabled,
+                                view_port: &view_port,
+                                device_dim: &device_dim,
+                            },
+                            self.mouse_pos,
+                            |entity| {
+                                drop_targets.contains(entity) && !dragged_entities.contains(&entity)
+                            },
+                        );
+
+                        if let Some(placement) = placements.get_mut(entity) {
+                            placement.set_offset(self.origin);
+                        }
+                        always_on_top.remove(entity);
+                        let parent_id = gui_graph.parent_id(node_id).unwrap_or(root_id);
+                        layout_dirty.mark(parent_id, root_id);
+
+                        gui_events.single_write(WidgetEvent {
+                            entity,
+                            node_id,
+                            kind: match target {
+                                Some((target, _)) => WidgetEventKind::Dropped { target },
+                                None => WidgetEventKind::DragCancelled,
+                            },
+                            window_event: event.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+pub struct GuiDragData<'a> {
+    entities: Entities<'a>,
+    events: Read<'a, Vec<Event>>,
+    gui_events: Write<'a, EventChannel<WidgetEvent>>,
+    gui_graph: ReadExpect<'a, GuiGraph>,
+    dragged: Write<'a, DraggedWidget>,
+    layout_dirty: Write<'a, LayoutDirty>,
+    draggables: ReadStorage<'a, Draggable>,
+    drop_targets: ReadStorage<'a, DropTarget>,
+    placements: WriteStorage<'a, Placement>,
+    always_on_top: WriteStorage<'a, AlwaysOnTop>,
+    bounds_rects: ReadStorage<'a, BoundsRect>,
+    global_positions: ReadStorage<'a, GlobalPosition>,
+    hidden: ReadStorage<'a, Hidden>,
+    enabled: ReadStorage<'a, Enabled>,
+    view_port: ReadExpect<'a, ViewPort>,
+    device_dim: ReadExpect<'a, DeviceDimensions>,
+}
+
+// --------- //
+// Resources //
+// --------- //
+
+/// Widget currently being dragged by the mouse.
+#[derive(Debug, Default)]
+pub struct DraggedWidget {
+    widget: Option<(Entity, NodeId)>,
+    /// Widget whose drag ended during the current frame.
+    released: Option<Entity>,
+}
+
+impl DraggedWidget {
+    #[inline]
+    pub fn entity(&self) -> Option<Entity> {
+        self.widget.map(|(e, _)| e)
+    }
+
+    #[inline]
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.widget.map(|(_, n)| n)
+    }
+
+    #[inline]
+    pub fn widget(&self) -> Option<(Entity, NodeId)> {
+        self.widget
+    }
+
+    #[inline]
+    pub fn set(&mut self, entity: Entity, node_id: NodeId) {
+        self.widget = Some((entity, node_id))
+    }
+
+    #[inline]
+    pub fn has_widget(&self) -> bool {
+        self.widget.is_some()
+    }
+
+    /// Ends the drag, returning the widget that was dragged.
+    #[inline]
+    pub fn clear(&mut self) -> Option<(Entity, NodeId)> {
+        let widget = self.widget.take();
+        self.released = widget.map(|(e, _)| e);
+        widget
+    }
+
+    /// Checks whether the drag of the given widget ended during the
+    /// current frame, so its release isn't treated as a click.
+    #[inline]
+    pub fn was_released(&self, entity: Entity) -> bool {
+        self.released == Some(entity)
+    }
+}
+
+// ---------- //
+// Components //
+// ---------- //
+
+/// Marks a widget that can be dragged with the mouse.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Draggable;
+
+/// Marks a widget that dragged widgets can be dropped onto.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct DropTarget;
+
+#[cfg(test)]
+mod test {
+    use super::super::{Clickable, GuiMouseMoveSystem, HoveredWidget, PressedWidget, WidgetEvents};
+    use super::*;
+    use crate::comp::Tag;
+    use glutin::dpi::{LogicalPosition, LogicalSize};
+    use glutin::{DeviceId, ModifiersState, MouseButton, WindowId};
+    use shrev::ReaderId;
+
+    struct DragWorld {
+        world: World,
+        reader: ReaderId<WidgetEvent>,
+        drag_sys: GuiDragSystem,
+        mouse_sys: GuiMouseMoveSystem,
+        widget: Entity,
+        target: Entity,
+    }
+
+    fn create_world() -> DragWorld {
+        let mut world = World::new();
+        world.register::<Draggable>();
+        world.register::<DropTarget>();
+        world.register::<Placement>();
+        world.register::<AlwaysOnTop>();
+        world.register::<BoundsRect>();
+        world.register::<GlobalPosition>();
+        world.register::<Hidden>();
+        world.register::<Enabled>();
+        world.register::<Clickable>();
+        world.register::<Tag>();
+        world.add_resource::<Vec<Event>>(Vec::new());
+        world.add_resource(WidgetEvents::new());
+        world.add_resource(DraggedWidget::default());
+        world.add_resource(HoveredWidget::default());
+        world.add_resource(PressedWidget::default());
+        world.add_resource(LayoutDirty::default());
+        world.add_resource(ViewPort::new((800, 600)));
+        world.add_resource(DeviceDimensions::new(1.0, LogicalSize::new(800.0, 600.0)));
+
+        let root = world
+            .create_entity()
+            .with(BoundsRect::new(800.0, 600.0))
+            .with(GlobalPosition::new(0.0, 0.0))
+            .build();
+        let widget = world
+            .create_entity()
+            .with(BoundsRect::new(50.0, 50.0))
+            .with(GlobalPosition::new(10.0, 10.0))
+            .with(Draggable)
+            .with(Clickable)
+            .build();
+        let target = world
+            .create_entity()
+            .with(BoundsRect::new(100.0, 100.0))
+            .with(GlobalPosition::new(200.0, 10.0))
+            .with(DropTarget)
+            .build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        gui_graph.insert_entity(widget, None);
+        gui_graph.insert_entity(target, None);
+        world.add_resource(gui_graph);
+
+        let reader = world.write_resource::<WidgetEvents>().register_reader();
+
+        DragWorld {
+            world,
+            reader,
+            drag_sys: GuiDragSystem::new(),
+            mouse_sys: GuiMouseMoveSystem::new(),
+            widget,
+            target,
+        }
+    }
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn cursor_moved(x: f64, y: f64) -> Event {
+        window_event(WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: LogicalPosition::new(x, y),
+            modifiers: ModifiersState::default(),
+        })
+    }
+
+    fn mouse_input(state: ElementState) -> Event {
+        window_event(WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::default(),
+        })
+    }
+
+    /// Runs the drag system, then the mouse system, over the given
+    /// events, and returns the kinds of widget events emitted.
+    fn run_frame(drag_world: &mut DragWorld, events: Vec<Event>) -> Vec<(Entity, WidgetEventKind)> {
+        let DragWorld {
+            world,
+            reader,
+            drag_sys,
+            mouse_sys,
+            ..
+        } = drag_world;
+        *world.write_resource::<Vec<Event>>() = events;
+
+        drag_sys.run_now(&world.res);
+        mouse_sys.run_now(&world.res);
+
+        world
+            .read_resource::<WidgetEvents>()
+            .read(reader)
+            .map(|ev| (ev.entity, ev.kind.clone()))
+            .collect()
+    }
+
+    fn has_kind(events: &[(Entity, WidgetEventKind)], kind: WidgetEventKind) -> bool {
+        events.iter().any(|(_, k)| *k == kind)
+    }
+
+    /// Presses the widget and moves the cursor past the threshold.
+    fn start_drag(drag_world: &mut DragWorld) {
+        run_frame(
+            drag_world,
+            vec![cursor_moved(20.0, 20.0), mouse_input(ElementState::Pressed)],
+        );
+        let events = run_frame(drag_world, vec![cursor_moved(40.0, 40.0)]);
+        assert!(has_kind(&events, WidgetEventKind::DragStarted));
+    }
+
+    #[test]
+    fn test_drag_start() {
+        let mut drag_world = create_world();
+        let widget = drag_world.widget;
+
+        run_frame(
+            &mut drag_world,
+            vec![cursor_moved(20.0, 20.0), mouse_input(ElementState::Pressed)],
+        );
+
+        // Within the threshold the press may still be a click.
+        let events = run_frame(&mut drag_world, vec![cursor_moved(22.0, 20.0)]);
+        assert!(!has_kind(&events, WidgetEventKind::DragStarted));
+        assert!(!drag_world
+            .world
+            .read_resource::<DraggedWidget>()
+            .has_widget());
+
+        let events = run_frame(&mut drag_world, vec![cursor_moved(40.0, 40.0)]);
+        assert_eq!(vec![(widget, WidgetEventKind::DragStarted)], events);
+
+        let world = &drag_world.world;
+        assert_eq!(
+            Some(widget),
+            world.read_resource::<DraggedWidget>().entity()
+        );
+        assert!(world.read_storage::<AlwaysOnTop>().contains(widget));
+        let placements = world.read_storage::<Placement>();
+        assert_eq!(
+            Vector2::new(20.0, 20.0),
+            *placements.get(widget).unwrap().offset()
+        );
+    }
+
+    #[test]
+    fn test_drag_drop() {
+        let mut drag_world = create_world();
+        let (widget, target) = (drag_world.widget, drag_world.target);
+        start_drag(&mut drag_world);
+
+        let events = run_frame(
+            &mut drag_world,
+            vec![
+                cursor_moved(250.0, 50.0),
+                mouse_input(ElementState::Released),
+            ],
+        );
+
+        // Dropping isn't a click.
+        assert!(has_kind(&events, WidgetEventKind::Dropped { target }));
+        assert!(!has_kind(&events, WidgetEventKind::Released));
+
+        // The widget returns to its placement, for the receiver to move.
+        let world = &drag_world.world;
+        assert!(!world.read_resource::<DraggedWidget>().has_widget());
+        assert!(!world.read_storage::<AlwaysOnTop>().contains(widget));
+        let placements = world.read_storage::<Placement>();
+        assert_eq!(Vector2::zeros(), *placements.get(widget).unwrap().offset());
+    }
+
+    #[test]
+    fn test_drag_cancel() {
+        let mut drag_world = create_world();
+        start_drag(&mut drag_world);
+
+        let events = run_frame(
+            &mut drag_world,
+            vec![
+                cursor_moved(600.0, 500.0),
+                mouse_input(ElementState::Released),
+            ],
+        );
+        assert!(has_kind(&events, WidgetEventKind::DragCancelled));
+        assert!(!drag_world
+            .world
+            .read_resource::<DraggedWidget>()
+            .has_widget());
+    }
+
+    #[test]
+    fn test_drag_within_frame_is_not_click() {
+        let mut drag_world = create_world();
+        let widget = drag_world.widget;
+
+        run_frame(
+            &mut drag_world,
+            vec![cursor_moved(20.0, 20.0), mouse_input(ElementState::Pressed)],
+        );
+
+        // Crossing the threshold and releasing in the same frame.
+        let events = run_frame(
+            &mut drag_world,
+            vec![
+                cursor_moved(40.0, 40.0),
+                mouse_input(ElementState::Released),
+            ],
+        );
+        assert!(has_kind(&events, WidgetEventKind::DragStarted));
+        assert!(has_kind(&events, WidgetEventKind::DragCancelled));
+        assert!(!events.contains(&(widget, WidgetEventKind::Released)));
+
+        // A press and release without moving is still a click.
+        let events = run_frame(
+            &mut drag_world,
+            vec![
+                cursor_moved(20.0, 20.0),
+                mouse_input(ElementState::Pressed),
+                mouse_input(ElementState::Released),
+            ],
+        );
+        assert!(events.contains(&(widget, WidgetEventKind::Released)));
+        assert!(!has_kind(&events, WidgetEventKind::DragStarted));
+    }
+
+    #[test]
+    fn test_drag_despawn() {
+        let mut drag_world = create_world();
+        let widget = drag_world.widget;
+        start_drag(&mut drag_world);
+
+        drag_world.world.delete_entity(widget).unwrap();
+        drag_world.world.maintain();
+
+        // The drag ends quietly, and releasing the button does nothing.
+        let events = run_frame(&mut drag_world, vec![mouse_input(ElementState::Released)]);
+        assert!(events.is_empty());
+        assert!(!drag_world
+            .world
+            .read_resource::<DraggedWidget>()
+            .has_widget());
+    }
+}
@@ -1,16 +1,26 @@
-use super::{create_gui_proj_matrix, GuiMesh};
+use super::{create_gui_proj_matrix, DisabledTint, Enabled, GuiMesh, Hidden};
 use crate::camera::CameraProjection;
+use crate::colors::{Color, WHITE};
 use crate::comp::{GlTexture, Transform};
-use crate::draw2d::Canvas;
+use crate::draw2d::{Canvas2d, CanvasBuffers};
 use crate::gfx_types::{gui_pipe, DepthTarget, PipelineBundle, RenderTarget};
-use crate::render::ChannelPair;
-use crate::res::{DeviceDimensions, ViewPort};
-use gfx_device::{CommandBuffer, Resources};
-use specs::{Join, ReadExpect, ReadStorage, System};
+use crate::metrics::{builtin_metrics::*, MetricAggregate, MetricHub};
+use crate::render::{BatchBuffers, BatchBuilder, BatchRange, ChannelPair, MAX_BATCH_VERTICES};
+use crate::res::{upload_texture, DeviceDimensions, ViewPort};
+use gfx_device::{CommandBuffer, Factory, Resources};
+use nalgebra::Matrix4;
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, WriteExpect};
+use std::cmp::Ordering;
+use std::ops::Range;
 
 pub struct DrawGuiSystem {
     channel: ChannelPair<Resources, CommandBuffer>,
-    _canvas: Canvas,
+    factory: Factory,
+    batch: BatchBuilder,
+    batch_buffers: BatchBuffers,
+    canvas_buffers: CanvasBuffers,
+    /// Plain white texture sampled by untextured canvas primitives.
+    white: GlTexture,
     pub(crate) render_target: RenderTarget<gfx_device::Resources>,
     pub(crate) depth_target: DepthTarget<gfx_device::Resources>,
     camera: CameraProjection,
@@ -18,24 +28,52 @@ pub struct DrawGuiSystem {
 
 #[derive(SystemData)]
 pub struct DrawGuiSystemData<'a> {
+    metrics: Option<Read<'a, MetricHub>>,
     basic_pipe_bundle: ReadExpect<'a, PipelineBundle<gui_pipe::Meta>>,
     view_port: ReadExpect<'a, ViewPort>,
     device_dim: ReadExpect<'a, DeviceDimensions>,
+    disabled_tint: Read<'a, DisabledTint>,
+    canvas: WriteExpect<'a, Canvas2d>,
+    entities: Entities<'a>,
     textures: ReadStorage<'a, GlTexture>,
     transforms: ReadStorage<'a, Transform>,
     gui_meshes: ReadStorage<'a, GuiMesh>,
+    hidden: ReadStorage<'a, Hidden>,
+    enabled: ReadStorage<'a, Enabled>,
+}
+
+/// A single GUI draw call, planned before any geometry is sent to
+/// the graphics card.
+enum GuiDrawOp<'a> {
+    /// Widget drawn on its own, with its own model transform.
+    Single(&'a GuiMesh, &'a GlTexture, &'a Transform, Color),
+    /// Several widgets merged into the batch buffers.
+    Batch(&'a GlTexture, Color, BatchRange),
 }
 
 impl DrawGuiSystem {
     pub fn new(
         channel: ChannelPair<Resources, CommandBuffer>,
-        canvas: Canvas,
+        mut factory: Factory,
         render_target: RenderTarget<gfx_device::Resources>,
         depth_target: DepthTarget<gfx_device::Resources>,
     ) -> Self {
+        let batch_buffers = BatchBuffers::new(&mut factory);
+        let canvas_buffers = CanvasBuffers::new(&mut factory);
+        let white = GlTexture::from_bundle(upload_texture(
+            &mut factory,
+            1,
+            1,
+            &[&[0xFF, 0xFF, 0xFF, 0xFF]],
+        ));
+
         DrawGuiSystem {
             channel,
-            _canvas: canvas,
+            factory,
+            batch: BatchBuilder::new(),
+            batch_buffers,
+            canvas_buffers,
+            white,
             render_target,
             depth_target,
             camera: CameraProjection::default(),
@@ -48,13 +86,18 @@ impl<'a> System<'a> for DrawGuiSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let DrawGuiSystemData {
+            metrics,
             basic_pipe_bundle,
             view_port,
             device_dim,
+            disabled_tint,
+            mut canvas,
+            entities,
             textures,
             transforms,
             gui_meshes,
-            ..
+            hidden,
+            enabled,
         } = data;
 
         let device_physical_size = *device_dim.physical_size();
@@ -64,25 +107,174 @@ impl<'a> System<'a> for DrawGuiSystem {
             device_physical_size.height as u16,
         ));
 
-        let proj_matrix = create_gui_proj_matrix(device_physical_size, dpi_factor);
+        // The GUI is laid out inside the view port
+        let proj_matrix = view_port.clip_transform(device_physical_size)
+            * create_gui_proj_matrix(view_port.physical_size(), dpi_factor);
 
         match self.channel.recv_block() {
             Ok(mut encoder) => {
+                let mut render_timer = metrics
+                    .as_ref()
+                    .map(|metrics| metrics.timer(GRAPHICS_GUI_RENDER, MetricAggregate::Average));
+                let mut draw_calls = 0;
+
+                let mut items = vec![];
+                for (entity, mesh, tex, trans, _) in
+                    (&entities, &gui_meshes, &textures, &transforms, !&hidden).join()
+                {
+                    let tint = match enabled.get(entity) {
+                        Some(Enabled(false)) => disabled_tint.0,
+                        _ => WHITE,
+                    };
+
+                    items.push((trans.position().z, mesh, tex, trans, tint));
+                }
+
+                // Back to front, in the depth order assigned by `GuiSortSystem`
+                items.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+                // Merge consecutive widgets that share a texture and
+                // tint. Only neighbours are merged, which keeps the
+                // draw order intact.
+                let keys: Vec<_> = items
+                    .iter()
+                    .map(|(_, mesh, tex, _, tint)| {
+                        let vertex_count = mesh.local.as_ref().map(|local| local.vertices.len());
+                        (vertex_count, (&*tex.bundle as *const _, *tint))
+                    })
+                    .collect();
+
+                self.batch.clear();
+                let mut ops = vec![];
+                for run in plan_runs(&keys, MAX_BATCH_VERTICES) {
+                    let (_, mesh, tex, trans, tint) = items[run.start];
+
+                    // A run of one is drawn as is, to avoid copying its geometry.
+                    if run.len() == 1 {
+                        ops.push(GuiDrawOp::Single(mesh, tex, trans, tint));
+                        continue;
+                    }
+
+                    for &(_, mesh, _, trans, _) in &items[run] {
+                        if let Some(local) = &mesh.local {
+                            self.batch
+                                .push(&local.vertices, &local.indices, &trans.matrix());
+                        }
+                    }
+                    ops.push(GuiDrawOp::Batch(tex, tint, self.batch.finish()));
+                }
+
+                // Send batched geometry to graphics card
+                if !self.batch.vertices().is_empty() {
+                    self.batch_buffers.reserve(
+                        &mut self.factory,
+                        self.batch.vertices().len(),
+                        self.batch.indices().len(),
+                    );
+
+                    encoder
+                        .update_buffer(&self.batch_buffers.vbuf, self.batch.vertices(), 0)
+                        .expect("Failed to update buffer");
+                    encoder
+                        .update_buffer(&self.batch_buffers.ibuf, self.batch.indices(), 0)
+                        .expect("Failed to update buffer");
+                }
+
                 // Draw to screen
-                for (ref mesh, ref tex, ref trans) in (&gui_meshes, &textures, &transforms).join() {
+                for op in ops {
+                    let (vbuf, slice, tex, model, tint) = match op {
+                        GuiDrawOp::Single(mesh, tex, trans, tint) => (
+                            mesh.vbuf.clone(),
+                            mesh.slice.clone(),
+                            tex,
+                            trans.matrix(),
+                            tint,
+                        ),
+                        GuiDrawOp::Batch(tex, tint, range) => (
+                            self.batch_buffers.vbuf.clone(),
+                            gfx::Slice {
+                                start: range.start,
+                                end: range.end,
+                                base_vertex: range.base_vertex,
+                                instances: None,
+                                buffer: gfx::IndexBuffer::Index16(self.batch_buffers.ibuf.clone()),
+                            },
+                            tex,
+                            // Vertices are already positioned
+                            Matrix4::identity(),
+                            tint,
+                        ),
+                    };
+
                     // Prepare data
                     let data = gui_pipe::Data {
-                        vbuf: mesh.vbuf.clone(),
+                        vbuf,
                         sampler: (tex.bundle.view.clone(), tex.bundle.sampler.clone()),
-                        model: trans.matrix().into(),
+                        model: model.into(),
                         proj: proj_matrix.into(),
+                        tint,
                         // The rectangle to allow rendering within
                         scissor: view_port.rect,
                         render_target: self.render_target.clone(),
                         depth_target: self.depth_target.clone(),
                     };
 
-                    encoder.draw(&mesh.slice, &basic_pipe_bundle.pso, &data);
+                    encoder.draw(&slice, &basic_pipe_bundle.pso, &data);
+                    draw_calls += 1;
+                }
+
+                // Canvas primitives are drawn over the widgets
+                if !canvas.batch().is_empty() {
+                    let batch = canvas.batch();
+                    self.canvas_buffers.reserve(
+                        &mut self.factory,
+                        batch.vertices().len(),
+                        batch.indices().len(),
+                    );
+
+                    encoder
+                        .update_buffer(&self.canvas_buffers.vbuf, batch.vertices(), 0)
+                        .expect("Failed to update buffer");
+                    encoder
+                        .update_buffer(&self.canvas_buffers.ibuf, batch.indices(), 0)
+                        .expect("Failed to update buffer");
+
+                    for (texture, range) in batch.draws() {
+                        let tex = texture.as_ref().unwrap_or(&self.white);
+                        let data = gui_pipe::Data {
+                            vbuf: self.canvas_buffers.vbuf.clone(),
+                            sampler: (tex.bundle.view.clone(), tex.bundle.sampler.clone()),
+                            // Vertices are already positioned
+                            model: Matrix4::identity().into(),
+                            proj: proj_matrix.into(),
+                            tint: WHITE,
+                            scissor: view_port.rect,
+                            render_target: self.render_target.clone(),
+                            depth_target: self.depth_target.clone(),
+                        };
+                        let slice = gfx::Slice {
+                            start: range.start,
+                            end: range.end,
+                            base_vertex: 0,
+                            instances: None,
+                            buffer: gfx::IndexBuffer::Index32(self.canvas_buffers.ibuf.clone()),
+                        };
+
+                        encoder.draw(&slice, &basic_pipe_bundle.pso, &data);
+                        draw_calls += 1;
+                    }
+                }
+                canvas.clear();
+
+                // Adds to the draw calls recorded by `DrawSystem`
+                if let Some(ref metrics) = metrics {
+                    metrics
+                        .counter(GRAPHICS_DRAW_CALLS, MetricAggregate::Sum)
+                        .set(draw_calls);
+                }
+
+                if let Some(ref mut render_timer) = render_timer {
+                    render_timer.stop();
                 }
 
                 self.channel
@@ -93,3 +285,72 @@ impl<'a> System<'a> for DrawGuiSystem {
         }
     }
 }
+
+/// Splits widgets, in draw order, into runs that are drawn with one
+/// call each.
+///
+/// Each item is the vertex count of a widget's local geometry, or
+/// `None` when it has none and is drawn on its own, along with the key
+/// of the texture and tint it's drawn with. Neighbours with the same
+/// key are merged while their vertices fit in a batch, so a change of
+/// texture or tint starts a new run.
+fn plan_runs<K>(items: &[(Option<usize>, K)], max_vertices: usize) -> Vec<Range<usize>>
+where
+    K: PartialEq,
+{
+    let mut runs = vec![];
+    let mut start = 0;
+    let mut run_vertices = 0;
+
+    for (index, (vertex_count, key)) in items.iter().enumerate() {
+        let compatible = match (vertex_count, items.get(start)) {
+            (Some(count), Some((Some(_), run_key))) => {
+                index > start && key == run_key && run_vertices + count <= max_vertices
+            }
+            _ => false,
+        };
+
+        if !compatible && index > start {
+            runs.push(start..index);
+            start = index;
+            run_vertices = 0;
+        }
+
+        run_vertices += vertex_count.unwrap_or(0);
+    }
+
+    if start < items.len() {
+        runs.push(start..items.len());
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plan_runs() {
+        // Widgets sharing a texture are merged into fewer draw calls,
+        // and a change of texture splits the batch.
+        let items = [
+            (Some(4), "a"),
+            (Some(4), "a"),
+            (Some(4), "a"),
+            (Some(4), "b"),
+            (Some(4), "a"),
+        ];
+        assert_eq!(vec![0..3, 3..4, 4..5], plan_runs(&items, 1024));
+
+        // Widgets without local geometry are drawn on their own.
+        let items = [(Some(4), "a"), (None, "a"), (Some(4), "a"), (Some(4), "a")];
+        assert_eq!(vec![0..1, 1..2, 2..4], plan_runs(&items, 1024));
+
+        // Batches are split when they run out of vertices.
+        let items = [(Some(4), "a"), (Some(4), "a"), (Some(4), "a")];
+        assert_eq!(vec![0..2, 2..3], plan_runs(&items, 8));
+
+        assert!(plan_runs::<&str>(&[], 1024).is_empty());
+    }
+}
@@ -0,0 +1,341 @@
+//! Keyboard focus and navigation between widgets.
+use super::{Enabled, GuiGraph, Hidden, NodeId, WidgetEvent, WidgetEventKind};
+use glutin::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use log::warn;
+use shrev::EventChannel;
+use specs::prelude::*;
+
+/// Moves keyboard focus between widgets, and activates the
+/// focused widget from the keyboard.
+///
+/// - `Tab` focuses the next `Focusable` widget, in GUI graph pre-order.
+/// - `Shift+Tab` focuses the previous widget.
+/// - `Escape` clears focus.
+/// - `Enter` and `Space` press and release the focused widget, the
+///   same way a mouse click would.
+pub struct GuiKeyboardSystem {
+    /// Widget pressed by a key that has not been released yet.
+    pressed: Option<(Entity, NodeId)>,
+}
+
+impl GuiKeyboardSystem {
+    pub fn new() -> Self {
+        GuiKeyboardSystem { pressed: None }
+    }
+}
+
+impl Default for GuiKeyboardSystem {
+    fn default() -> Self {
+        GuiKeyboardSystem::new()
+    }
+}
+
+impl<'a> System<'a> for GuiKeyboardSystem {
+    type SystemData = GuiKeyboardData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let GuiKeyboardData {
+            events,
+            mut gui_events,
+            gui_graph,
+            mut focused,
+            focusables,
+            mut focused_tags,
+            hidden,
+            enabled,
+        } = data;
+
+        let can_focus = |entity: Entity| {
+            focusables.contains(entity)
+                && !hidden.contains(entity)
+                && enabled.get(entity).map_or(true, Enabled::is_enabled)
+        };
+
+        for ev in events.iter() {
+            let (event, input) = match ev {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::KeyboardInput { input, .. } => (event, *input),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let KeyboardInput {
+                state,
+                virtual_keycode,
+                modifiers,
+                ..
+            } = input;
+
+            match (state, virtual_keycode) {
+                (ElementState::Pressed, Some(VirtualKeyCode::Tab)) => {
+                    let next =
+                        next_focus(&gui_graph, focused.node_id(), &can_focus, modifiers.shift);
+                    if next.is_some() {
+                        set_focus(
+                            &mut focused,
+                            &mut focused_tags,
+                            &mut gui_events,
+                            next,
+                            event,
+                        );
+                    }
+                }
+                (ElementState::Pressed, Some(VirtualKeyCode::Escape)) => {
+                    set_focus(
+                        &mut focused,
+                        &mut focused_tags,
+                        &mut gui_events,
+                        None,
+                        event,
+                    );
+                }
+                (ElementState::Pressed, Some(VirtualKeyCode::Return))
+                | (ElementState::Pressed, Some(VirtualKeyCode::Space)) => {
+                    // Held keys repeat their press events
+                    if self.pressed.is_some() {
+                        continue;
+                    }
+
+                    if let Some((entity, node_id)) = focused.widget() {
+                        self.pressed = Some((entity, node_id));
+                        gui_events.single_write(WidgetEvent {
+                            entity,
+                            node_id,
+                            kind: WidgetEventKind::Pressed,
+                            window_event: event.clone(),
+                        });
+                    }
+                }
+                (ElementState::Released, Some(VirtualKeyCode::Return))
+                | (ElementState::Released, Some(VirtualKeyCode::Space)) => {
+                    if let Some((entity, node_id)) = self.pressed.take() {
+                        gui_events.single_write(WidgetEvent {
+                            entity,
+                            node_id,
+                            kind: WidgetEventKind::Released,
+                            window_event: event.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+pub struct GuiKeyboardData<'a> {
+    events: Read<'a, Vec<Event>>,
+    gui_events: Write<'a, EventChannel<WidgetEvent>>,
+    gui_graph: ReadExpect<'a, GuiGraph>,
+    focused: Write<'a, FocusedWidget>,
+    focusables: ReadStorage<'a, Focusable>,
+    focused_tags: WriteStorage<'a, Focused>,
+    hidden: ReadStorage<'a, Hidden>,
+    enabled: ReadStorage<'a, Enabled>,
+}
+
+/// Finds the focusable widget after the given node, in GUI graph
+/// pre-order, wrapping around at the end.
+///
+/// When no node is given, the first focusable widget is returned,
+/// or the last when searching backwards.
+pub fn next_focus<F>(
+    gui_graph: &GuiGraph,
+    current: Option<NodeId>,
+    can_focus: F,
+    backwards: bool,
+) -> Option<(Entity, NodeId)>
+where
+    F: Fn(Entity) -> bool,
+{
+    let mut candidates = vec![];
+    let mut current_index = None;
+
+    let mut walker = gui_graph.walk_dfs_pre_order(gui_graph.root_id());
+    while let Some(node_id) = walker.next(gui_graph) {
+        if let Some(entity) = gui_graph.get_entity(node_id) {
+            if Some(node_id) == current {
+                current_index = Some(candidates.len());
+            }
+
+            if can_focus(entity) {
+                candidates.push((entity, node_id));
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    // The current widget may no longer be focusable, in which case
+    // its index is where the next candidate would have been.
+    let len = candidates.len();
+    let index = match (current_index, backwards) {
+        (None, false) => 0,
+        (None, true) => len - 1,
+        (Some(index), false) => {
+            if candidates.get(index).map(|&(_, n)| n) == current {
+                (index + 1) % len
+            } else {
+                index % len
+            }
+        }
+        (Some(index), true) => (index + len - 1) % len,
+    };
+
+    candidates.get(index).cloned()
+}
+
+/// Moves focus to the given widget, or clears it when `None`.
+///
+/// Emits `FocusLost` for the previously focused widget, and
+/// `FocusGained` for the new one, and keeps the `Focused`
+/// component in sync.
+fn set_focus(
+    focused: &mut FocusedWidget,
+    focused_tags: &mut WriteStorage<Focused>,
+    gui_events: &mut EventChannel<WidgetEvent>,
+    widget: Option<(Entity, NodeId)>,
+    window_event: &WindowEvent,
+) {
+    if focused.widget() == widget {
+        return;
+    }
+
+    if let Some((entity, node_id)) = focused.clear() {
+        focused_tags.remove(entity);
+        gui_events.single_write(WidgetEvent {
+            entity,
+            node_id,
+            kind: WidgetEventKind::FocusLost,
+            window_event: window_event.clone(),
+        });
+    }
+
+    if let Some((entity, node_id)) = widget {
+        focused.set(entity, node_id);
+        if let Err(err) = focused_tags.insert(entity, Focused) {
+            warn!("Failed to focus widget {:?}: {}", entity, err);
+        }
+        gui_events.single_write(WidgetEvent {
+            entity,
+            node_id,
+            kind: WidgetEventKind::FocusGained,
+            window_event: window_event.clone(),
+        });
+    }
+}
+
+// --------- //
+// Resources //
+// --------- //
+
+/// Widget that receives keyboard input.
+#[derive(Debug, Default)]
+pub struct FocusedWidget(Option<(Entity, NodeId)>);
+
+impl FocusedWidget {
+    #[inline]
+    pub fn entity(&self) -> Option<Entity> {
+        self.0.map(|(e, _)| e)
+    }
+
+    #[inline]
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.0.map(|(_, n)| n)
+    }
+
+    #[inline]
+    pub fn widget(&self) -> Option<(Entity, NodeId)> {
+        self.0
+    }
+
+    #[inline]
+    pub fn set(&mut self, entity: Entity, node_id: NodeId) {
+        self.0 = Some((entity, node_id))
+    }
+
+    #[inline]
+    pub fn has_widget(&self) -> bool {
+        self.0.is_some()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) -> Option<(Entity, NodeId)> {
+        self.0.take()
+    }
+}
+
+// ---------- //
+// Components //
+// ---------- //
+
+/// Marks a widget that can receive keyboard focus.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Focusable;
+
+/// Marks the widget that currently has keyboard focus.
+///
+/// Maintained by `GuiKeyboardSystem`, so games can draw
+/// a focus ring around the widget.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Focused;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_focus() {
+        let mut world = World::new();
+        let root = world.create_entity().build();
+        let first = world.create_entity().build();
+        let skipped = world.create_entity().build();
+        let second = world.create_entity().build();
+        let third = world.create_entity().build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let first_id = gui_graph.insert_entity(first, None);
+        let skipped_id = gui_graph.insert_entity(skipped, Some(first_id));
+        let second_id = gui_graph.insert_entity(second, Some(skipped_id));
+        let third_id = gui_graph.insert_entity(third, None);
+
+        let can_focus = |entity| entity != root && entity != skipped;
+
+        assert_eq!(
+            Some((first, first_id)),
+            next_focus(&gui_graph, None, can_focus, false)
+        );
+        assert_eq!(
+            Some((third, third_id)),
+            next_focus(&gui_graph, None, can_focus, true)
+        );
+        assert_eq!(
+            Some((second, second_id)),
+            next_focus(&gui_graph, Some(first_id), can_focus, false)
+        );
+        assert_eq!(
+            Some((first, first_id)),
+            next_focus(&gui_graph, Some(third_id), can_focus, false)
+        );
+        assert_eq!(
+            Some((third, third_id)),
+            next_focus(&gui_graph, Some(first_id), can_focus, true)
+        );
+
+        // Traversal continues from a widget that can't be focused.
+        assert_eq!(
+            Some((second, second_id)),
+            next_focus(&gui_graph, Some(skipped_id), can_focus, false)
+        );
+        assert_eq!(
+            Some((first, first_id)),
+            next_focus(&gui_graph, Some(skipped_id), can_focus, true)
+        );
+    }
+}
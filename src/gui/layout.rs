@@ -1,12 +1,14 @@
 //! Layout engine.
-use super::{create_gui_proj_matrix, text, GuiGraph};
+use super::{create_gui_proj_matrix, text, update_hidden, GuiGraph, Hidden, Visibility};
 use crate::collections::ordered_dag::prelude::*;
 use crate::comp::Transform;
-use crate::res::DeviceDimensions;
+use crate::errors::GuiGraphError;
+use crate::res::{DeviceDimensions, ViewPort};
 use glutin::dpi::LogicalSize;
 use log::warn;
 use nalgebra::{Matrix4, Point2, Vector2, Vector3};
 use specs::prelude::*;
+use std::collections::HashSet;
 use std::fmt;
 
 // ------- //
@@ -22,22 +24,55 @@ impl<'a> System<'a> for GuiLayoutSystem {
         if let Some(node_id) = data.layout_dirty.take_node_id() {
             println!("processing layout");
 
-            // Set the root widget's dimensions to match the device to be rendered to.
-            let LogicalSize { width, height } = *data.device_dim.logical_size();
+            // Set the root widget's dimensions to match the view port
+            // being rendered to, which covers the device unless letter boxed.
+            let dpi_factor = data.device_dim.dpi_factor();
+            let (physical_size, logical_size) = match data.view_port {
+                Some(ref view_port) => (
+                    view_port.physical_size(),
+                    view_port.logical_size(dpi_factor),
+                ),
+                None => (
+                    *data.device_dim.physical_size(),
+                    *data.device_dim.logical_size(),
+                ),
+            };
+            let LogicalSize { width, height } = logical_size;
             data.bounds
                 .get_mut(data.gui_graph.root_entity())
                 .expect("GUI root entity has no bounds")
                 .set_size([width as f32, height as f32]);
-            let proj_matrix = create_gui_proj_matrix(
-                *data.device_dim.physical_size(),
-                data.device_dim.dpi_factor() as f32,
-            );
+            let proj_matrix = create_gui_proj_matrix(physical_size, dpi_factor as f32);
+
+            // Widget may have been removed since it was marked dirty.
+            let root_id = data.gui_graph.root_id();
+            let node_id = match data.gui_graph.get_entity(node_id) {
+                Some(_) => node_id,
+                None => root_id,
+            };
+
+            // A widget other than the root keeps its current position,
+            // and only its descendants are recalculated.
+            let suggested_pos = if node_id == root_id {
+                Point2::new(0.0, 0.0)
+            } else {
+                let entity = data.gui_graph.get_entity(node_id).unwrap();
+                let pos = data
+                    .global_positions
+                    .get(entity)
+                    .map(GlobalPosition::point)
+                    .unwrap_or_else(Point2::origin);
+                let offset = data
+                    .placements
+                    .get(entity)
+                    .map(|p| *p.offset())
+                    .unwrap_or_else(Vector2::zeros);
+                pos - offset
+            };
 
-            // TODO: Is it reasonable to use a node id in the dirty flag to start
-            //       the recalc from an arbitrary node?
             let parent_measure = ParentMeasurements {
                 bounds: BoundsRect::new(width as f32, height as f32),
-                suggested_pos: Point2::new(0.0, 0.0),
+                suggested_pos,
             };
             process_layout(&mut data, node_id, parent_measure, proj_matrix);
         }
@@ -91,6 +126,9 @@ pub fn process_layout(
         // Accumulated value of the widths and heights of the previous children, in logical pixels.
         let mut acc_pack = [0.0, 0.0];
 
+        // Extent of the children, relative to this widget's position.
+        let mut content_size = [0.0_f32, 0.0_f32];
+
         while let Some(child_node_id) = walker.next(&data.gui_graph) {
             println!("child node id {:?}", child_node_id);
 
@@ -103,34 +141,54 @@ pub fn process_layout(
             // Suggeted available space that the child may take up.
             let bounds = *data.bounds.get(entity).unwrap();
 
+            let child_entity = data.gui_graph.get_entity(child_node_id).unwrap();
+
+            // Collapsed children give up their space to their siblings.
+            let collapsed = data
+                .visibilities
+                .get(child_entity)
+                .map_or(false, Visibility::is_collapsed);
+
             if let Some(pack) = data.packs.get(entity) {
                 match pack.mode {
                     PackMode::Frame => {
-                        // TODO: Offset from anchor
+                        // Children without an anchor stay at the parent's origin.
+                        if let Some(anchor) = data.anchors.get(child_entity) {
+                            let child_bounds = data
+                                .bounds
+                                .get(child_entity)
+                                .cloned()
+                                .unwrap_or_else(|| BoundsRect::new(0.0, 0.0));
+                            pos += anchor.offset(&bounds, &child_bounds);
+                        }
                     }
                     PackMode::Horizontal => {
                         pos.x = acc_pack[0];
 
                         // Add bounds of current child to accumulator so the
                         // next child can be positioned by it.
-                        acc_pack[0] += pack.margin[0]
-                            + data
-                                .bounds
-                                .get(data.gui_graph.get_entity(child_node_id).unwrap())
-                                .map(|b| b.width)
-                                .unwrap_or_default();
+                        if !collapsed {
+                            acc_pack[0] += pack.margin[0]
+                                + data
+                                    .bounds
+                                    .get(child_entity)
+                                    .map(|b| b.width)
+                                    .unwrap_or_default();
+                        }
                     }
                     PackMode::Vertical => {
                         pos.y += acc_pack[1];
 
                         // Add bounds of current child to accumulator so the
                         // next child can be positioned by it.
-                        acc_pack[1] += pack.margin[1]
-                            + data
-                                .bounds
-                                .get(data.gui_graph.get_entity(child_node_id).unwrap())
-                                .map(|b| b.height)
-                                .unwrap_or_default();
+                        if !collapsed {
+                            acc_pack[1] += pack.margin[1]
+                                + data
+                                    .bounds
+                                    .get(child_entity)
+                                    .map(|b| b.height)
+                                    .unwrap_or_default();
+                        }
                     }
                     PackMode::Grid { .. } => unimplemented!(),
                 }
@@ -143,6 +201,40 @@ pub fn process_layout(
                 suggested_pos: pos,
             };
             process_layout(data, child_node_id, pm, proj);
+
+            if !collapsed {
+                let child_pos = data
+                    .global_positions
+                    .get(child_entity)
+                    .map(GlobalPosition::point)
+                    .unwrap_or(pos);
+                let [child_w, child_h] = data
+                    .bounds
+                    .get(child_entity)
+                    .map(BoundsRect::finite_size)
+                    .unwrap_or([0.0, 0.0]);
+                content_size[0] = content_size[0].max(child_pos.x - new_pos.x + child_w);
+                content_size[1] = content_size[1].max(child_pos.y - new_pos.y + child_h);
+            }
+        }
+
+        // Resize to fit the children within the widget's constraints.
+        if let Some(constraints) = data.packs.get(entity).and_then(|pack| pack.constraints) {
+            let bounds = data
+                .bounds
+                .get_mut(entity)
+                .unwrap_or_else(|| panic!("{:?} {:?} has no bounds for layout", node_id, entity));
+            let size = constraints.fit(bounds.finite_size(), content_size);
+
+            if size != bounds.size() {
+                bounds.set_size(size);
+
+                // The parent has already packed this widget using
+                // its previous size, so needs another pass.
+                let root_id = data.gui_graph.root_id();
+                let parent_id = data.gui_graph.parent_id(node_id).unwrap_or(root_id);
+                data.layout_dirty.mark(parent_id, root_id);
+            }
         }
     } else {
         warn!("Entity for {:?} not found during layout pass.", node_id);
@@ -153,10 +245,13 @@ pub fn process_layout(
 #[derive(SystemData)]
 pub struct LayoutData<'a> {
     device_dim: ReadExpect<'a, DeviceDimensions>,
+    view_port: Option<Read<'a, ViewPort>>,
     gui_graph: ReadExpect<'a, GuiGraph>,
     layout_dirty: Write<'a, LayoutDirty>,
     bounds: WriteStorage<'a, BoundsRect>,
     placements: ReadStorage<'a, Placement>,
+    anchors: ReadStorage<'a, Anchor>,
+    visibilities: ReadStorage<'a, Visibility>,
     global_positions: WriteStorage<'a, GlobalPosition>,
     zdepths: ReadStorage<'a, ZDepth>,
     packs: ReadStorage<'a, Pack>,
@@ -190,7 +285,23 @@ pub fn sort_widgets(data: SortData, node_id: NodeId) {
         gui_graph,
         mut zdepths,
         mut texts,
+        visibilities,
+        mut hidden,
+        always_on_top,
     } = data;
+
+    // Visibility is inherited from ancestors.
+    update_hidden(
+        &gui_graph,
+        node_id,
+        |entity| {
+            visibilities
+                .get(entity)
+                .map_or(true, Visibility::is_visible)
+        },
+        &mut hidden,
+    );
+
     let mut walker = gui_graph.walk_dfs_pre_order(node_id);
     let mut i = 0.0;
     // println!("----- sort -----");
@@ -206,22 +317,41 @@ pub fn sort_widgets(data: SortData, node_id: NodeId) {
     //     farz,
     // ));
 
+    // Widgets that are always on top, and their descendants, are
+    // sorted after the rest of the graph, so they are drawn last.
+    let mut in_order = vec![];
+    let mut on_top = vec![];
+    let mut on_top_ids = HashSet::new();
+
     while let Some(next_id) = walker.next(&gui_graph) {
         if let Some(entity) = gui_graph.get_entity(next_id) {
-            if let Some(zdepth) = zdepths.get_mut(entity) {
-                zdepth.set(i);
-
-                // let point = gui_matrix.transform_point(&nalgebra::Point3::new(0.0, 0.0, i as f32));
-                // println!("z_depth {} ({}, {}, {})", zdepth, point.x, point.y, point.z);
+            let parent_on_top = gui_graph
+                .parent_id(next_id)
+                .map_or(false, |parent_id| on_top_ids.contains(&parent_id));
+
+            if parent_on_top || always_on_top.contains(entity) {
+                on_top_ids.insert(next_id);
+                on_top.push(entity);
+            } else {
+                in_order.push(entity);
             }
+        }
+    }
 
-            if let Some(text) = texts.get_mut(entity) {
-                text.set_z_depth(i);
-                // let point = text_matrix.transform_point(&nalgebra::Point3::new(0.0, 0.0, i as f32));
-                // println!("text z_depth {} ({}, {}, {})", i, point.x, point.y, point.z);
-            }
-            i -= 1.0;
+    for entity in in_order.into_iter().chain(on_top) {
+        if let Some(zdepth) = zdepths.get_mut(entity) {
+            zdepth.set(i);
+
+            // let point = gui_matrix.transform_point(&nalgebra::Point3::new(0.0, 0.0, i as f32));
+            // println!("z_depth {} ({}, {}, {})", zdepth, point.x, point.y, point.z);
+        }
+
+        if let Some(text) = texts.get_mut(entity) {
+            text.set_z_depth(i);
+            // let point = text_matrix.transform_point(&nalgebra::Point3::new(0.0, 0.0, i as f32));
+            // println!("text z_depth {} ({}, {}, {})", i, point.x, point.y, point.z);
         }
+        i -= 1.0;
     }
 }
 
@@ -231,6 +361,9 @@ pub struct SortData<'a> {
     zdepths: WriteStorage<'a, ZDepth>,
     /// Text has its own z-depth
     texts: WriteStorage<'a, text::TextBatch>,
+    visibilities: ReadStorage<'a, Visibility>,
+    hidden: WriteStorage<'a, Hidden>,
+    always_on_top: ReadStorage<'a, AlwaysOnTop>,
 }
 
 // --------- //
@@ -241,6 +374,64 @@ pub struct SortData<'a> {
 #[derive(Debug, Default)]
 pub struct LayoutDirty(Option<NodeId>);
 
+/// Schedules a layout pass of the whole GUI graph, starting at the root.
+///
+/// Called when widgets are added to the graph, or when
+/// the device dimensions change.
+pub fn invalidate_layout(world: &World) {
+    let root_id = world.read_resource::<GuiGraph>().root_id();
+    world.write_resource::<LayoutDirty>().set_node_id(root_id);
+}
+
+/// Moves the widget, and its descendants, to the end of the given
+/// parent's children, and schedules a layout pass of the affected
+/// widgets.
+///
+/// Layout starts at the deepest widget containing both the old
+/// and new parents, since both lose or gain a child.
+pub fn reparent_widget(
+    world: &World,
+    node_id: NodeId,
+    new_parent: NodeId,
+) -> Result<(), GuiGraphError> {
+    let mut gui_graph = world.write_resource::<GuiGraph>();
+    let root_id = gui_graph.root_id();
+    let old_parent = gui_graph.parent_id(node_id);
+
+    gui_graph.reparent(node_id, new_parent)?;
+
+    let dirty_id = old_parent
+        .and_then(|old_parent| gui_graph.common_ancestor(old_parent, new_parent))
+        .unwrap_or(root_id);
+    world
+        .write_resource::<LayoutDirty>()
+        .mark(dirty_id, root_id);
+
+    Ok(())
+}
+
+/// Sets the sort key of the widget among its siblings, and
+/// schedules a layout pass of its parent.
+///
+/// Z-depths follow the new order after the next `GuiSortSystem` pass.
+pub fn reorder_widget(
+    world: &World,
+    node_id: NodeId,
+    order_index: u16,
+) -> Result<(), GuiGraphError> {
+    let mut gui_graph = world.write_resource::<GuiGraph>();
+    let root_id = gui_graph.root_id();
+
+    gui_graph.set_child_order(node_id, order_index)?;
+
+    let parent_id = gui_graph.parent_id(node_id).unwrap_or(root_id);
+    world
+        .write_resource::<LayoutDirty>()
+        .mark(parent_id, root_id);
+
+    Ok(())
+}
+
 impl LayoutDirty {
     pub fn with_node_id(node_id: NodeId) -> Self {
         LayoutDirty(Some(node_id))
@@ -250,10 +441,26 @@ impl LayoutDirty {
         self.0 = Some(node_id);
     }
 
+    /// Marks the given node as dirty.
+    ///
+    /// When a different node is already dirty, the layout
+    /// falls back to a full pass starting at the root.
+    pub fn mark(&mut self, node_id: NodeId, root_id: NodeId) {
+        self.0 = match self.0 {
+            Some(dirty_id) if dirty_id != node_id => Some(root_id),
+            _ => Some(node_id),
+        };
+    }
+
     pub fn node_id(&self) -> Option<NodeId> {
         self.0
     }
 
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.0.is_some()
+    }
+
     pub fn take_node_id(&mut self) -> Option<NodeId> {
         self.0.take()
     }
@@ -270,6 +477,9 @@ pub struct Pack {
     pub mode: PackMode,
     /// The vertical and horizontal spacing between child widgets in logical pixels.
     pub margin: [f32; 2],
+    /// Limits on the size of the widget when it grows to fit its
+    /// children. Without constraints, the widget keeps its size.
+    pub constraints: Option<SizeConstraints>,
 }
 
 impl Pack {
@@ -277,8 +487,62 @@ impl Pack {
         Pack {
             mode,
             margin: [0.0, 0.0],
+            constraints: None,
         }
     }
+
+    pub fn with_constraints(mut self, constraints: SizeConstraints) -> Self {
+        self.constraints = Some(constraints);
+        self
+    }
+}
+
+/// Limits on the size of a widget that grows to fit its children,
+/// set on its `Pack`.
+///
+/// After its children are laid out, the widget's `BoundsRect` is
+/// expanded to contain them, up to the maximum size, and is never
+/// smaller than the minimum size. Unbounded dimensions are sized
+/// to the children.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SizeConstraints {
+    pub min_size: Option<[f32; 2]>,
+    pub max_size: Option<[f32; 2]>,
+}
+
+impl SizeConstraints {
+    /// Calculates the size of a widget with the given current size,
+    /// containing children that take up the given content size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::gui::SizeConstraints;
+    ///
+    /// let constraints = SizeConstraints {
+    ///     min_size: Some([100.0, 100.0]),
+    ///     max_size: Some([200.0, 200.0]),
+    /// };
+    ///
+    /// assert_eq!(constraints.fit([0.0, 0.0], [50.0, 150.0]), [100.0, 150.0]);
+    /// assert_eq!(constraints.fit([120.0, 120.0], [50.0, 300.0]), [120.0, 200.0]);
+    /// ```
+    pub fn fit(&self, current: [f32; 2], content: [f32; 2]) -> [f32; 2] {
+        let fit_axis = |axis: usize| {
+            let size = current[axis];
+            let grown = if content[axis] > size {
+                self.max_size
+                    .map_or(content[axis], |max| content[axis].min(max[axis]))
+                    .max(size)
+            } else {
+                size
+            };
+
+            self.min_size.map_or(grown, |min| grown.max(min[axis]))
+        };
+
+        [fit_axis(0), fit_axis(1)]
+    }
 }
 
 #[derive(Debug)]
@@ -374,6 +638,14 @@ impl fmt::Display for ZDepth {
     }
 }
 
+/// Marks a widget that is drawn above all other widgets,
+/// regardless of its position in the GUI graph.
+///
+/// The widget's descendants are drawn above it as usual.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct AlwaysOnTop;
+
 /// Represents a relative position within a View.
 ///
 /// To support different sized Windows and Screens, a Placement
@@ -456,6 +728,86 @@ impl fmt::Display for Placement {
     }
 }
 
+/// Point within a parent Frame that a Widget is attached to.
+///
+/// During a layout pass the same normalised point on the Widget is
+/// lined up with the point on its parent, so a `TopRight` anchored
+/// Widget has its top right corner in the parent's top right corner.
+/// The Widget's `Placement` is applied afterwards.
+///
+/// Only has an effect when the parent is packed with `PackMode::Frame`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[storage(DenseVecStorage)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+
+    /// Normalised point, where (0.0, 0.0) is the top
+    /// left and (1.0, 1.0) is the bottom right.
+    Custom(Vector2<f32>),
+}
+
+impl Anchor {
+    /// Normalised point of the anchor.
+    pub fn point(&self) -> Vector2<f32> {
+        use Anchor::*;
+
+        match self {
+            TopLeft => Vector2::new(0.0, 0.0),
+            TopCenter => Vector2::new(0.5, 0.0),
+            TopRight => Vector2::new(1.0, 0.0),
+            CenterLeft => Vector2::new(0.0, 0.5),
+            Center => Vector2::new(0.5, 0.5),
+            CenterRight => Vector2::new(1.0, 0.5),
+            BottomLeft => Vector2::new(0.0, 1.0),
+            BottomCenter => Vector2::new(0.5, 1.0),
+            BottomRight => Vector2::new(1.0, 1.0),
+            Custom(point) => *point,
+        }
+    }
+
+    /// Calculates the offset, in logical pixels, from the parent's
+    /// position to the child's position.
+    ///
+    /// Unbounded dimensions are treated as zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::gui::{Anchor, BoundsRect};
+    /// use nalgebra::Vector2;
+    ///
+    /// let parent = BoundsRect::new(640.0, 480.0);
+    /// let child = BoundsRect::new(100.0, 20.0);
+    ///
+    /// assert_eq!(Anchor::TopRight.offset(&parent, &child), Vector2::new(540.0, 0.0));
+    /// assert_eq!(Anchor::Center.offset(&parent, &child), Vector2::new(270.0, 230.0));
+    /// ```
+    pub fn offset(&self, parent: &BoundsRect, child: &BoundsRect) -> Vector2<f32> {
+        let point = self.point();
+        let [parent_w, parent_h] = parent.finite_size();
+        let [child_w, child_h] = child.finite_size();
+
+        Vector2::new(
+            parent_w * point.x - child_w * point.x,
+            parent_h * point.y - child_h * point.y,
+        )
+    }
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Anchor::TopLeft
+    }
+}
+
 /// Axis-aligned bounding box in logical pixel size.
 #[derive(Component, Clone, Copy)]
 #[storage(DenseVecStorage)]
@@ -491,6 +843,13 @@ impl BoundsRect {
         [self.width, self.height]
     }
 
+    /// Size where unbounded dimensions are replaced with zero.
+    #[inline]
+    pub fn finite_size(&self) -> [f32; 2] {
+        let finite = |v: f32| if v.is_finite() { v } else { 0.0 };
+        [finite(self.width), finite(self.height)]
+    }
+
     /// Returns whether the given point is within the local
     /// bounds, in logical pixels.
     ///
@@ -519,3 +878,222 @@ impl Into<[f32; 2]> for BoundsRect {
         [self.width, self.height]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_widget(world: &mut World, bounds: BoundsRect) -> EntityBuilder {
+        world
+            .create_entity()
+            .with(bounds)
+            .with(GlobalPosition::default())
+            .with(ZDepth::default())
+            .with(Transform::default())
+    }
+
+    #[test]
+    fn test_anchor_resize() {
+        let mut world = World::new();
+        world.register::<BoundsRect>();
+        world.register::<Placement>();
+        world.register::<Anchor>();
+        world.register::<Visibility>();
+        world.register::<GlobalPosition>();
+        world.register::<ZDepth>();
+        world.register::<Pack>();
+        world.register::<Transform>();
+
+        let root = create_widget(&mut world, BoundsRect::new(0.0, 0.0))
+            .with(Pack::new(PackMode::Frame))
+            .build();
+        let child = create_widget(&mut world, BoundsRect::new(100.0, 20.0))
+            .with(Anchor::TopRight)
+            .with(Placement::new(-8.0, 8.0))
+            .build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        gui_graph.insert_entity(child, None);
+        world.add_resource(LayoutDirty::with_node_id(gui_graph.root_id()));
+        world.add_resource(gui_graph);
+        world.add_resource(DeviceDimensions::new(1.0, LogicalSize::new(640.0, 480.0)));
+
+        GuiLayoutSystem.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_storage::<GlobalPosition>()
+                .get(child)
+                .unwrap()
+                .point(),
+            Point2::new(532.0, 8.0)
+        );
+
+        // Simulate window resize.
+        world.add_resource(DeviceDimensions::new(1.0, LogicalSize::new(800.0, 600.0)));
+        let root_id = world.read_resource::<GuiGraph>().root_id();
+        world.write_resource::<LayoutDirty>().set_node_id(root_id);
+
+        GuiLayoutSystem.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_storage::<GlobalPosition>()
+                .get(child)
+                .unwrap()
+                .point(),
+            Point2::new(692.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn test_collapsed_pack() {
+        let mut world = World::new();
+        world.register::<BoundsRect>();
+        world.register::<Placement>();
+        world.register::<Anchor>();
+        world.register::<Visibility>();
+        world.register::<GlobalPosition>();
+        world.register::<ZDepth>();
+        world.register::<Pack>();
+        world.register::<Transform>();
+
+        let root = create_widget(&mut world, BoundsRect::new(0.0, 0.0))
+            .with(Pack::new(PackMode::Horizontal))
+            .build();
+        let first = create_widget(&mut world, BoundsRect::new(100.0, 20.0))
+            .with(Visibility::new(false).with_collapse(true))
+            .build();
+        let second = create_widget(&mut world, BoundsRect::new(100.0, 20.0)).build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        gui_graph.insert_entity(first, None);
+        gui_graph.insert_entity(second, None);
+        world.add_resource(LayoutDirty::with_node_id(gui_graph.root_id()));
+        world.add_resource(gui_graph);
+        world.add_resource(DeviceDimensions::new(1.0, LogicalSize::new(640.0, 480.0)));
+
+        GuiLayoutSystem.run_now(&world.res);
+        assert_eq!(
+            world
+                .read_storage::<GlobalPosition>()
+                .get(second)
+                .unwrap()
+                .point(),
+            Point2::new(0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_size_constraints() {
+        let mut world = World::new();
+        world.register::<BoundsRect>();
+        world.register::<Placement>();
+        world.register::<Anchor>();
+        world.register::<Visibility>();
+        world.register::<GlobalPosition>();
+        world.register::<ZDepth>();
+        world.register::<Pack>();
+        world.register::<Transform>();
+
+        let root = create_widget(&mut world, BoundsRect::new(0.0, 0.0))
+            .with(Pack::new(PackMode::Frame))
+            .build();
+        let vbox = create_widget(
+            &mut world,
+            BoundsRect::new(std::f32::INFINITY, std::f32::INFINITY),
+        )
+        .with(
+            Pack::new(PackMode::Vertical).with_constraints(SizeConstraints {
+                min_size: Some([100.0, 200.0]),
+                max_size: None,
+            }),
+        )
+        .build();
+        let child = create_widget(&mut world, BoundsRect::new(50.0, 50.0)).build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let vbox_id = gui_graph.insert_entity(vbox, None);
+        gui_graph.insert_entity(child, Some(vbox_id));
+        world.add_resource(LayoutDirty::with_node_id(gui_graph.root_id()));
+        world.add_resource(gui_graph);
+        world.add_resource(DeviceDimensions::new(1.0, LogicalSize::new(640.0, 480.0)));
+
+        GuiLayoutSystem.run_now(&world.res);
+        let [width, height] = world.read_storage::<BoundsRect>().get(vbox).unwrap().size();
+        assert!(width >= 100.0);
+        assert!(height >= 200.0);
+
+        // Size changed, so the parent is laid out again.
+        assert!(world.read_resource::<LayoutDirty>().is_dirty());
+        GuiLayoutSystem.run_now(&world.res);
+        assert!(!world.read_resource::<LayoutDirty>().is_dirty());
+    }
+
+    #[test]
+    fn test_always_on_top_sort() {
+        let mut world = World::new();
+        world.register::<ZDepth>();
+        world.register::<text::TextBatch>();
+        world.register::<Visibility>();
+        world.register::<Hidden>();
+        world.register::<AlwaysOnTop>();
+
+        let root = world.create_entity().with(ZDepth::default()).build();
+        let popup = world
+            .create_entity()
+            .with(ZDepth::default())
+            .with(AlwaysOnTop)
+            .build();
+        let popup_child = world.create_entity().with(ZDepth::default()).build();
+        let sibling = world.create_entity().with(ZDepth::default()).build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let popup_id = gui_graph.insert_entity(popup, None);
+        gui_graph.insert_entity(popup_child, Some(popup_id));
+        gui_graph.insert_entity(sibling, None);
+        world.add_resource(gui_graph);
+
+        GuiSortSystem.run_now(&world.res);
+        let zdepths = world.read_storage::<ZDepth>();
+        let depth = |entity| zdepths.get(entity).unwrap().inner();
+        assert!(depth(popup) < depth(sibling));
+        assert!(depth(popup_child) < depth(popup));
+    }
+
+    #[test]
+    fn test_reorder_widget() {
+        let mut world = World::new();
+        world.register::<ZDepth>();
+        world.register::<text::TextBatch>();
+        world.register::<Visibility>();
+        world.register::<Hidden>();
+        world.register::<AlwaysOnTop>();
+
+        let root = world.create_entity().with(ZDepth::default()).build();
+        let first = world.create_entity().with(ZDepth::default()).build();
+        let second = world.create_entity().with(ZDepth::default()).build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let root_id = gui_graph.root_id();
+        let first_id = gui_graph.insert_entity(first, None);
+        gui_graph.insert_entity(second, None);
+        world.add_resource(gui_graph);
+        world.add_resource(LayoutDirty::default());
+
+        GuiSortSystem.run_now(&world.res);
+        {
+            let zdepths = world.read_storage::<ZDepth>();
+            let depth = |entity| zdepths.get(entity).unwrap().inner();
+            assert!(depth(second) < depth(first));
+        }
+
+        // Bring the first widget to front.
+        reorder_widget(&world, first_id, 1).unwrap();
+        let dirty_id = world.read_resource::<LayoutDirty>().node_id();
+        assert_eq!(Some(root_id), dirty_id);
+
+        GuiSortSystem.run_now(&world.res);
+        let zdepths = world.read_storage::<ZDepth>();
+        let depth = |entity| zdepths.get(entity).unwrap().inner();
+        assert!(depth(first) < depth(second));
+    }
+}
@@ -1,8 +1,12 @@
-use super::{BoundsRect, GlobalPosition, GuiGraph, HoveredWidget, NodeId};
+use super::{
+    BoundsRect, DraggedWidget, Enabled, GlobalPosition, GuiGraph, Hidden, HoveredWidget, NodeId,
+};
 use crate::comp::Tag;
+use crate::res::{DeviceDimensions, ViewPort};
 use glutin::{ElementState, Event, WindowEvent};
-use shrev::EventChannel;
+use shrev::{EventChannel, ReaderId};
 use specs::prelude::*;
+use std::collections::HashMap;
 
 pub struct GuiMouseMoveSystem {
     /// Last known mouse cursor position on main window, in screen coordinates.
@@ -27,10 +31,15 @@ impl<'a> System<'a> for GuiMouseMoveSystem {
             gui_graph,
             mut hovered,
             mut pressed,
+            dragged,
             clickables,
             bounds_rects,
             global_positions,
+            hidden,
+            enabled,
             tags,
+            view_port,
+            device_dim,
         } = data;
 
         for ev in events.iter() {
@@ -45,9 +54,13 @@ impl<'a> System<'a> for GuiMouseMoveSystem {
                                 gui_graph: &gui_graph,
                                 global_positions: &global_positions,
                                 bounds_rects: &bounds_rects,
-                                clickables: &clickables,
+                                hidden: &hidden,
+                                enabled: &enabled,
+                                view_port: &view_port,
+                                device_dim: &device_dim,
                             },
                             self.mouse_pos,
+                            |entity| clickables.contains(entity),
                         ) {
                             if hovered.entity() != Some(entity) {
                                 let name: &str =
@@ -78,9 +91,13 @@ impl<'a> System<'a> for GuiMouseMoveSystem {
                                 gui_graph: &gui_graph,
                                 global_positions: &global_positions,
                                 bounds_rects: &bounds_rects,
-                                clickables: &clickables,
+                                hidden: &hidden,
+                                enabled: &enabled,
+                                view_port: &view_port,
+                                device_dim: &device_dim,
                             },
                             self.mouse_pos,
+                            |entity| clickables.contains(entity),
                         ) {
                             match state {
                                 ElementState::Pressed => {
@@ -94,7 +111,12 @@ impl<'a> System<'a> for GuiMouseMoveSystem {
                                 }
                                 ElementState::Released => {
                                     // Only a widget that has been pressed will receive a release event
-                                    if pressed.entity() == Some(entity) {
+                                    let is_pressed = pressed.entity() == Some(entity);
+
+                                    // A drag replaces the click
+                                    let is_dragged =
+                                        dragged.has_widget() || dragged.was_released(entity);
+                                    if is_pressed && !is_dragged {
                                         gui_events.single_write(WidgetEvent {
                                             entity,
                                             node_id,
@@ -120,6 +142,47 @@ impl<'a> System<'a> for GuiMouseMoveSystem {
     }
 }
 
+/// System that collects the widgets clicked during the
+/// frame into the `ButtonClicks` resource.
+///
+/// Should run after `GuiMouseMoveSystem`.
+pub struct GuiClickSystem {
+    reader: ReaderId<WidgetEvent>,
+}
+
+impl GuiClickSystem {
+    pub fn new(world: &World) -> Self {
+        GuiClickSystem {
+            reader: world.write_resource::<WidgetEvents>().register_reader(),
+        }
+    }
+}
+
+impl<'a> System<'a> for GuiClickSystem {
+    type SystemData = (
+        Read<'a, WidgetEvents>,
+        Write<'a, ButtonClicks>,
+        ReadStorage<'a, Clickable>,
+        ReadStorage<'a, Tag>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (gui_events, mut clicks, clickables, tags) = data;
+
+        // Clicks only last for a single frame.
+        clicks.0.clear();
+
+        for ev in gui_events.read(&mut self.reader) {
+            // A release is only sent to the widget that received the
+            // preceding press, so a release on a clickable is a click.
+            let is_click = ev.kind == WidgetEventKind::Released;
+            if is_click && clickables.contains(ev.entity) {
+                clicks.0.push((ev.entity, tags.get(ev.entity).cloned()));
+            }
+        }
+    }
+}
+
 #[derive(SystemData)]
 pub struct GuiMouseData<'a> {
     events: Read<'a, Vec<Event>>,
@@ -127,39 +190,73 @@ pub struct GuiMouseData<'a> {
     gui_graph: ReadExpect<'a, GuiGraph>,
     hovered: Write<'a, HoveredWidget>,
     pressed: Write<'a, PressedWidget>,
+    dragged: Read<'a, DraggedWidget>,
     clickables: ReadStorage<'a, Clickable>,
     bounds_rects: ReadStorage<'a, BoundsRect>,
     global_positions: ReadStorage<'a, GlobalPosition>,
+    hidden: ReadStorage<'a, Hidden>,
+    enabled: ReadStorage<'a, Enabled>,
     tags: ReadStorage<'a, Tag>,
+    view_port: ReadExpect<'a, ViewPort>,
+    device_dim: ReadExpect<'a, DeviceDimensions>,
 }
 
 #[derive(SystemData)]
-struct FindWidgetData<'run, 'res: 'run> {
-    gui_graph: &'run ReadExpect<'res, GuiGraph>,
-    global_positions: &'run ReadStorage<'res, GlobalPosition>,
-    bounds_rects: &'run ReadStorage<'res, BoundsRect>,
-    clickables: &'run ReadStorage<'res, Clickable>,
+pub(crate) struct FindWidgetData<'run, 'res: 'run> {
+    pub(crate) gui_graph: &'run ReadExpect<'res, GuiGraph>,
+    pub(crate) global_positions: &'run ReadStorage<'res, GlobalPosition>,
+    pub(crate) bounds_rects: &'run ReadStorage<'res, BoundsRect>,
+    pub(crate) hidden: &'run ReadStorage<'res, Hidden>,
+    pub(crate) enabled: &'run ReadStorage<'res, Enabled>,
+    pub(crate) view_port: &'run ReadExpect<'res, ViewPort>,
+    pub(crate) device_dim: &'run ReadExpect<'res, DeviceDimensions>,
 }
 
-fn find_widget(data: FindWidgetData, mouse_position: [f32; 2]) -> Option<(Entity, NodeId)> {
+/// Finds the top most widget under the mouse, out of
+/// the widgets accepted by the given predicate.
+///
+/// The mouse position is in logical pixels from the top left of the
+/// window, and is mapped into the view port that the GUI is laid out in.
+/// Hidden and disabled widgets are ignored, and so is the mouse when
+/// it's outside the view port.
+pub(crate) fn find_widget<F>(
+    data: FindWidgetData,
+    mouse_position: [f32; 2],
+    is_target: F,
+) -> Option<(Entity, NodeId)>
+where
+    F: Fn(Entity) -> bool,
+{
     let FindWidgetData {
         gui_graph,
         global_positions,
         bounds_rects,
-        clickables,
+        hidden,
+        enabled,
+        view_port,
+        device_dim,
     } = data;
-    let [mouse_x, mouse_y] = mouse_position;
+    // Widgets are drawn inside the view port, and clipped to it.
+    if !view_port.contains_logical(mouse_position, device_dim) {
+        return None;
+    }
+    let [mouse_x, mouse_y] = view_port.to_local_logical(mouse_position, device_dim);
 
     let mut walker = gui_graph.walk_dfs_post_order(gui_graph.root_id());
     while let Some(node_id) = walker.next(&gui_graph) {
         if let Some(entity) = gui_graph.get_entity(node_id) {
-            let maybe_components = (
-                bounds_rects.get(entity),
-                global_positions.get(entity),
-                clickables.get(entity),
-            );
+            let is_disabled = enabled.get(entity).map_or(false, |e| !e.is_enabled());
+            if hidden.contains(entity) || is_disabled {
+                continue;
+            }
 
-            if let (Some(bounds), Some(global_pos), Some(_)) = maybe_components {
+            if !is_target(entity) {
+                continue;
+            }
+
+            if let (Some(bounds), Some(global_pos)) =
+                (bounds_rects.get(entity), global_positions.get(entity))
+            {
                 // Bounds are in the widget's local space.
                 let global_point = global_pos.point();
                 let local_point = [mouse_x - global_point.x, mouse_y - global_point.y];
@@ -207,6 +304,60 @@ impl PressedWidget {
     }
 }
 
+/// Widgets that were clicked during the current frame.
+///
+/// Populated by `GuiClickSystem`, so scenes can check for
+/// clicks without keeping their own event reader.
+#[derive(Debug, Default)]
+pub struct ButtonClicks(Vec<(Entity, Option<Tag>)>);
+
+impl ButtonClicks {
+    /// Checks whether a widget, identified by either its entity
+    /// or its tag, was clicked this frame.
+    pub fn was_clicked<T>(&self, target: T) -> bool
+    where
+        T: ClickTarget,
+    {
+        self.0
+            .iter()
+            .any(|(entity, tag)| target.matches(*entity, tag.as_ref()))
+    }
+
+    /// Entities of the widgets clicked this frame.
+    pub fn entities<'a>(&'a self) -> impl Iterator<Item = Entity> + 'a {
+        self.0.iter().map(|(entity, _)| *entity)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Identifies a widget when querying `ButtonClicks`.
+pub trait ClickTarget {
+    fn matches(&self, entity: Entity, tag: Option<&Tag>) -> bool;
+}
+
+impl ClickTarget for Entity {
+    fn matches(&self, entity: Entity, _tag: Option<&Tag>) -> bool {
+        *self == entity
+    }
+}
+
+impl ClickTarget for &str {
+    fn matches(&self, _entity: Entity, tag: Option<&Tag>) -> bool {
+        tag.map_or(false, |tag| tag.as_ref() == *self)
+    }
+}
+
+impl ClickTarget for &Tag {
+    fn matches(&self, entity: Entity, tag: Option<&Tag>) -> bool {
+        let name: &str = (*self).as_ref();
+        ClickTarget::matches(&name, entity, tag)
+    }
+}
+
 // ---------- //
 // Components //
 // ---------- //
@@ -221,6 +372,50 @@ pub struct Clickable;
 
 pub type WidgetEvents = EventChannel<WidgetEvent>;
 
+/// Reader of `WidgetEvents`, created with `WidgetEvents::register_reader`.
+pub type WidgetEventReader = ReaderId<WidgetEvent>;
+
+/// Reads `WidgetEvents` grouped by widget, instead of
+/// matching the entity of each event.
+///
+/// Like `WidgetEvents::read`, every unread event is marked as
+/// read by the reader, including the events of other widgets.
+pub trait ReadWidgetEvents {
+    /// Kinds of the unread events of the given widget, in order.
+    fn read_for<'a>(
+        &'a self,
+        reader: &mut WidgetEventReader,
+        entity: Entity,
+    ) -> Box<dyn Iterator<Item = &'a WidgetEventKind> + 'a>;
+
+    /// Kinds of the unread events, in order, grouped by widget.
+    fn read_all(&self, reader: &mut WidgetEventReader) -> HashMap<Entity, Vec<WidgetEventKind>>;
+}
+
+impl ReadWidgetEvents for WidgetEvents {
+    fn read_for<'a>(
+        &'a self,
+        reader: &mut WidgetEventReader,
+        entity: Entity,
+    ) -> Box<dyn Iterator<Item = &'a WidgetEventKind> + 'a> {
+        Box::new(
+            self.read(reader)
+                .filter(move |ev| ev.entity == entity)
+                .map(|ev| &ev.kind),
+        )
+    }
+
+    fn read_all(&self, reader: &mut WidgetEventReader) -> HashMap<Entity, Vec<WidgetEventKind>> {
+        let mut grouped: HashMap<Entity, Vec<WidgetEventKind>> = HashMap::new();
+
+        for ev in self.read(reader) {
+            grouped.entry(ev.entity).or_default().push(ev.kind.clone());
+        }
+
+        grouped
+    }
+}
+
 #[derive(Debug)]
 pub struct WidgetEvent {
     /// Entity id of the widget that handled the event.
@@ -233,10 +428,119 @@ pub struct WidgetEvent {
     pub window_event: glutin::WindowEvent,
 }
 
+impl WidgetEvent {
+    /// Checks whether this event completes a click on the given
+    /// widget.
+    ///
+    /// A release is only sent to the widget that received the
+    /// preceding press, so a release on the widget is a click.
+    #[inline]
+    pub fn is_click_of(&self, entity: Entity) -> bool {
+        self.kind == WidgetEventKind::Released && self.entity == entity
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WidgetEventKind {
     HoverOver,
     HoverOut,
     Pressed,
     Released,
+    FocusGained,
+    FocusLost,
+    DragStarted,
+    /// Dragged widget was released over a drop target.
+    Dropped {
+        target: Entity,
+    },
+    /// Dragged widget was released away from any drop target.
+    DragCancelled,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_button_clicks() {
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.register::<Clickable>();
+        world.add_resource(WidgetEvents::new());
+        world.add_resource(ButtonClicks::default());
+
+        let button = world
+            .create_entity()
+            .with(Tag::new("Brush"))
+            .with(Clickable)
+            .build();
+        let other = world.create_entity().with(Tag::new("Other")).build();
+        let node_id = GuiGraph::with_root(button).root_id();
+        let mut click_sys = GuiClickSystem::new(&world);
+
+        let event = |kind| WidgetEvent {
+            entity: button,
+            node_id,
+            kind,
+            window_event: WindowEvent::Focused(true),
+        };
+        world.write_resource::<WidgetEvents>().iter_write(vec![
+            event(WidgetEventKind::HoverOver),
+            event(WidgetEventKind::Pressed),
+            event(WidgetEventKind::Released),
+            // Releases on widgets that aren't clickable aren't clicks.
+            WidgetEvent {
+                entity: other,
+                ..event(WidgetEventKind::Released)
+            },
+        ]);
+
+        click_sys.run_now(&world.res);
+        {
+            let clicks = world.read_resource::<ButtonClicks>();
+            assert!(clicks.was_clicked(button));
+            assert!(clicks.was_clicked("Brush"));
+            assert!(!clicks.was_clicked(other));
+            assert!(!clicks.was_clicked("Other"));
+        }
+
+        // Clicks are cleared on the next frame.
+        click_sys.run_now(&world.res);
+        assert!(world.read_resource::<ButtonClicks>().is_empty());
+    }
+
+    #[test]
+    fn test_read_widget_events() {
+        let mut world = World::new();
+        let button = world.create_entity().build();
+        let other = world.create_entity().build();
+        let node_id = GuiGraph::with_root(button).root_id();
+
+        let mut events = WidgetEvents::new();
+        let mut reader = events.register_reader();
+        let mut all_reader = events.register_reader();
+        let event = |entity, kind| WidgetEvent {
+            entity,
+            node_id,
+            kind,
+            window_event: WindowEvent::Focused(true),
+        };
+        events.iter_write(vec![
+            event(button, WidgetEventKind::Pressed),
+            event(other, WidgetEventKind::HoverOver),
+            event(button, WidgetEventKind::Released),
+        ]);
+
+        let kinds: Vec<&WidgetEventKind> = events.read_for(&mut reader, button).collect();
+        assert_eq!(
+            vec![&WidgetEventKind::Pressed, &WidgetEventKind::Released],
+            kinds
+        );
+        assert_eq!(0, events.read_for(&mut reader, button).count());
+
+        let grouped = events.read_all(&mut all_reader);
+        assert_eq!(2, grouped.len());
+        assert_eq!(vec![WidgetEventKind::HoverOver], grouped[&other]);
+        assert_eq!(2, grouped[&button].len());
+    }
 }
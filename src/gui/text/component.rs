@@ -1,15 +1,59 @@
+use super::FontHandle;
 use crate::colors::{self, Color};
 use gfx_glyph::{FontId, Layout, Section, Text};
 use specs::{Component, DenseVecStorage};
 
-#[derive(Component, Default)]
+/// Logical size of text when no scale is given.
+pub const DEFAULT_TEXT_SCALE: f32 = 16.0;
+
+/// Depth added to shadows and outlines, so they are drawn
+/// behind their text but in front of the widget below it.
+const EFFECT_Z_OFFSET: f32 = 0.5;
+
+/// Text drawn by a widget, made up of an ordered list of spans.
+///
+/// Spans are laid out one after the other as a single section, so
+/// each can have its own color, while alignment, wrapping and depth
+/// apply to the text as a whole.
+///
+/// ```
+/// use rengine::colors::{RED, WHITE};
+/// use rengine::gui::text::TextBatch;
+///
+/// let text_batch = TextBatch::new().with("HP: ", WHITE).with("42", RED);
+/// assert_eq!(vec![("HP: ", WHITE), ("42", RED)], text_batch.spans().collect::<Vec<_>>());
+/// ```
+#[derive(Component)]
 #[storage(DenseVecStorage)]
 pub struct TextBatch {
     fragments: Vec<TextFragment>,
     layout: LayoutSettings,
+    /// Font of all fragments in the batch.
+    font_id: FontId,
+    /// Logical size of all fragments in the batch.
+    scale: f32,
+    /// Logical width at which lines are wrapped.
+    max_width: Option<f32>,
+    shadow: Option<TextShadow>,
+    outline: Option<TextOutline>,
     pub z: f32,
 }
 
+impl Default for TextBatch {
+    fn default() -> Self {
+        TextBatch {
+            fragments: vec![],
+            layout: LayoutSettings::default(),
+            font_id: FontId::default(),
+            scale: DEFAULT_TEXT_SCALE,
+            max_width: None,
+            shadow: None,
+            outline: None,
+            z: 0.0,
+        }
+    }
+}
+
 impl TextBatch {
     pub fn new() -> Self {
         Default::default()
@@ -22,10 +66,32 @@ impl TextBatch {
         self.fragments.push(TextFragment {
             content: text.to_owned(),
             color: color.into(),
-            ..TextFragment::default()
+            scale: self.scale,
+            font_id: self.font_id,
         });
     }
 
+    /// Adds text with its own scale, so a batch can mix
+    /// different sizes.
+    pub fn add_span<C>(&mut self, text: &str, color: C, scale: f32)
+    where
+        C: Into<Color>,
+    {
+        self.fragments.push(TextFragment {
+            content: text.to_owned(),
+            color: color.into(),
+            scale,
+            font_id: self.font_id,
+        });
+    }
+
+    /// Text and color of each span, in order.
+    pub fn spans(&self) -> impl Iterator<Item = (&str, Color)> {
+        self.fragments
+            .iter()
+            .map(|fragment| (fragment.content.as_str(), fragment.color))
+    }
+
     #[inline]
     pub fn add_fragment(&mut self, fragment: TextFragment) {
         self.fragments.push(fragment);
@@ -37,6 +103,58 @@ impl TextBatch {
         self.layout.align_h = align_h;
     }
 
+    /// Changes the font of all text in the batch, including
+    /// text added later.
+    pub fn set_font(&mut self, font: FontHandle) {
+        self.font_id = font.font_id();
+        for fragment in &mut self.fragments {
+            fragment.font_id = self.font_id;
+        }
+    }
+
+    /// Changes the logical size of all text in the batch,
+    /// including text added later.
+    ///
+    /// Overrides the scale of spans.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+        for fragment in &mut self.fragments {
+            fragment.scale = scale;
+        }
+    }
+
+    /// Logical width at which lines are wrapped, when
+    /// narrower than the widget's bounds.
+    #[inline]
+    pub fn max_width(&self) -> Option<f32> {
+        self.max_width
+    }
+
+    #[inline]
+    pub fn set_max_width(&mut self, max_width: Option<f32>) {
+        self.max_width = max_width;
+    }
+
+    #[inline]
+    pub fn shadow(&self) -> Option<&TextShadow> {
+        self.shadow.as_ref()
+    }
+
+    #[inline]
+    pub fn set_shadow(&mut self, shadow: Option<TextShadow>) {
+        self.shadow = shadow;
+    }
+
+    #[inline]
+    pub fn outline(&self) -> Option<&TextOutline> {
+        self.outline.as_ref()
+    }
+
+    #[inline]
+    pub fn set_outline(&mut self, outline: Option<TextOutline>) {
+        self.outline = outline;
+    }
+
     #[inline]
     pub fn set_z_depth(&mut self, z_depth: f32) {
         self.z = z_depth;
@@ -52,6 +170,8 @@ impl TextBatch {
         self.add(text, color);
     }
 
+    /// Appends a span of text, drawn directly after the
+    /// previous span.
     pub fn with<C>(mut self, text: &str, color: C) -> Self
     where
         C: Into<Color>,
@@ -61,23 +181,159 @@ impl TextBatch {
         self
     }
 
+    pub fn with_span<C>(mut self, text: &str, color: C, scale: f32) -> Self
+    where
+        C: Into<Color>,
+    {
+        self.add_span(text, color, scale);
+        self
+    }
+
+    pub fn with_shadow(mut self, shadow: TextShadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    pub fn with_outline(mut self, outline: TextOutline) -> Self {
+        self.outline = Some(outline);
+        self
+    }
+
     pub fn with_z(mut self, z: f32) -> Self {
         self.z = z;
         self
     }
 
+    pub fn with_font(mut self, font: FontHandle) -> Self {
+        self.set_font(font);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.set_scale(scale);
+        self
+    }
+
+    /// Wraps lines at the given logical width, breaking words
+    /// that are wider than a whole line.
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.set_max_width(Some(max_width));
+        self
+    }
+
     pub fn with_align(mut self, align_v: TextAlignVertical, align_h: TextAlignHorizontal) -> Self {
         self.set_align(align_v, align_h);
         self
     }
 
     pub fn as_section(&self, dpi_factor: f32, bounds: [f32; 2]) -> Section {
+        self.build_section(
+            self.fragments
+                .iter()
+                .map(|fragment| fragment.content.as_str()),
+            dpi_factor,
+            bounds,
+        )
+    }
+
+    /// Creates a section using the given contents in place of the
+    /// fragments' own text, one for each fragment.
+    pub(crate) fn as_section_with<'a>(
+        &'a self,
+        contents: &'a [String],
+        dpi_factor: f32,
+        bounds: [f32; 2],
+    ) -> Section<'a> {
+        self.build_section(contents.iter().map(String::as_str), dpi_factor, bounds)
+    }
+
+    /// Copies of the given section, drawn behind it for the
+    /// batch's outline and shadow.
+    ///
+    /// The copies keep the section's layout, so follow its alignment,
+    /// and are offset from it in logical pixels.
+    pub(crate) fn effect_sections<'a>(
+        &self,
+        section: &Section<'a>,
+        dpi_factor: f32,
+    ) -> Vec<Section<'a>> {
+        let mut sections = vec![];
+
+        let mut push_copy = |offset: [f32; 2], color: Color, z: f32| {
+            let (x, y) = section.screen_position;
+            let mut copy = section.clone();
+            copy.screen_position = (x + offset[0] * dpi_factor, y + offset[1] * dpi_factor);
+            copy.text = copy
+                .text
+                .into_iter()
+                .map(|text| text.with_color(color).with_z(z))
+                .collect();
+            sections.push(copy);
+        };
+
+        if let Some(ref outline) = self.outline {
+            let t = outline.thickness;
+            let z = self.z + EFFECT_Z_OFFSET;
+            for offset in &[
+                [-t, -t],
+                [0.0, -t],
+                [t, -t],
+                [-t, 0.0],
+                [t, 0.0],
+                [-t, t],
+                [0.0, t],
+                [t, t],
+            ] {
+                push_copy(*offset, outline.color, z);
+            }
+        }
+
+        // Shadow is behind the outline.
+        if let Some(ref shadow) = self.shadow {
+            push_copy(shadow.offset, shadow.color, self.z + EFFECT_Z_OFFSET * 1.5);
+        }
+
+        sections
+    }
+
+    /// Copies the fragments' text, with line breaks inserted into
+    /// words that are wider than the maximum width.
+    ///
+    /// The measure function returns the physical width of the given
+    /// text, at the given physical scale and font.
+    ///
+    /// Returns `None` when the batch has no maximum width.
+    pub(crate) fn break_long_words<F>(&self, dpi_factor: f32, mut measure: F) -> Option<Vec<String>>
+    where
+        F: FnMut(&str, f32, FontId) -> f32,
+    {
+        let max_width = self.max_width? * dpi_factor;
+
+        let contents = self
+            .fragments
+            .iter()
+            .map(|fragment| {
+                let scale = fragment.scale * dpi_factor;
+                break_long_words(&fragment.content, max_width, |text| {
+                    measure(text, scale, fragment.font_id)
+                })
+            })
+            .collect();
+
+        Some(contents)
+    }
+
+    fn build_section<'a, I>(&'a self, contents: I, dpi_factor: f32, bounds: [f32; 2]) -> Section<'a>
+    where
+        I: Iterator<Item = &'a str>,
+    {
         // TODO: Specify either LogicalSize or PhysicalSize for bounds
         let texts: Vec<_> = self
             .fragments
             .iter()
-            .map(|fragment| {
-                Text::new(&fragment.content)
+            .zip(contents)
+            .map(|(fragment, content)| {
+                Text::new(content)
                     .with_color(fragment.color)
                     .with_scale(fragment.scale * dpi_factor)
                     .with_font_id(fragment.font_id)
@@ -90,7 +346,8 @@ impl TextBatch {
             section = section.add_text(text);
         }
         // TODO: Rather accept physical size
-        section.bounds = (bounds[0] * dpi_factor, bounds[1] * dpi_factor);
+        let width = self.max_width.map_or(bounds[0], |w| w.min(bounds[0]));
+        section.bounds = (width * dpi_factor, bounds[1] * dpi_factor);
         section.layout = Layout::default_wrap()
             .h_align(match self.layout.align_h {
                 TextAlignHorizontal::Left => gfx_glyph::HorizontalAlign::Left,
@@ -107,6 +364,47 @@ impl TextBatch {
     }
 }
 
+/// Copy of text drawn behind it, offset by a distance in
+/// logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextShadow {
+    pub offset: [f32; 2],
+    pub color: Color,
+}
+
+impl TextShadow {
+    pub fn new(offset: [f32; 2], color: Color) -> Self {
+        TextShadow { offset, color }
+    }
+}
+
+impl Default for TextShadow {
+    /// Black shadow one pixel down and to the right.
+    fn default() -> Self {
+        TextShadow::new([1.0, 1.0], colors::BLACK)
+    }
+}
+
+/// Border around the glyphs of text, with a thickness
+/// in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOutline {
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl TextOutline {
+    pub fn new(thickness: f32, color: Color) -> Self {
+        TextOutline { thickness, color }
+    }
+}
+
+impl Default for TextOutline {
+    fn default() -> Self {
+        TextOutline::new(1.0, colors::BLACK)
+    }
+}
+
 pub struct LayoutSettings {
     pub align_v: TextAlignVertical,
     pub align_h: TextAlignHorizontal,
@@ -152,8 +450,133 @@ impl Default for TextFragment {
         TextFragment {
             content: "".to_owned(),
             color: colors::WHITE,
-            scale: 16.0,
+            scale: DEFAULT_TEXT_SCALE,
             font_id: FontId::default(),
         }
     }
 }
+
+/// Inserts line breaks into words wider than the given width, so
+/// they are broken at the width instead of overflowing.
+///
+/// Words are separated by whitespace. The measure function
+/// returns the width of the given text.
+pub fn break_long_words<F>(text: &str, max_width: f32, mut measure: F) -> String
+where
+    F: FnMut(&str) -> f32,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let word_start = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        result.push_str(&rest[..word_start]);
+        rest = &rest[word_start..];
+
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let mut word = &rest[..word_end];
+        rest = &rest[word_end..];
+
+        while measure(word) > max_width {
+            // Longest piece that fits, but at least one character.
+            let mut split = word.chars().next().map_or(0, char::len_utf8);
+            for (index, c) in word.char_indices().skip(1) {
+                if measure(&word[..index + c.len_utf8()]) > max_width {
+                    break;
+                }
+                split = index + c.len_utf8();
+            }
+
+            if split >= word.len() {
+                break;
+            }
+
+            result.push_str(&word[..split]);
+            result.push('\n');
+            word = &word[split..];
+        }
+
+        result.push_str(word);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_break_long_words() {
+        // Every character is 10 pixels wide.
+        let measure = |text: &str| text.chars().count() as f32 * 10.0;
+
+        assert_eq!(
+            "the quick brown fox",
+            break_long_words("the quick brown fox", 50.0, measure)
+        );
+        assert_eq!(
+            "a abcde\nfghij\nk b",
+            break_long_words("a abcdefghijk b", 50.0, measure)
+        );
+
+        // Wider than a single character.
+        assert_eq!("a\nb\nc", break_long_words("abc", 5.0, measure));
+    }
+
+    #[test]
+    fn test_color_spans() {
+        let text_batch = TextBatch::new()
+            .with("HP: ", colors::WHITE)
+            .with("42", colors::RED)
+            .with_align(TextAlignVertical::Top, TextAlignHorizontal::Right)
+            .with_z(-7.0);
+
+        let section = text_batch.as_section(1.0, [200.0, 50.0]);
+
+        // Spans make up a single section, in order.
+        assert_eq!(2, section.text.len());
+        assert_eq!("HP: ", section.text[0].text);
+        assert_eq!(colors::WHITE, section.text[0].extra.color);
+        assert_eq!("42", section.text[1].text);
+        assert_eq!(colors::RED, section.text[1].extra.color);
+
+        // Depth and alignment are shared by all spans.
+        assert!(section.text.iter().all(|text| text.extra.z == -7.0));
+        assert_eq!(
+            Layout::default_wrap()
+                .h_align(gfx_glyph::HorizontalAlign::Right)
+                .v_align(gfx_glyph::VerticalAlign::Top),
+            section.layout
+        );
+    }
+
+    #[test]
+    fn test_effect_sections() {
+        let text_batch = TextBatch::new()
+            .with("Hello", colors::WHITE)
+            .with_span(", World", colors::RED, 24.0)
+            .with_shadow(TextShadow::default())
+            .with_z(-3.0);
+
+        let mut section = text_batch.as_section(2.0, [100.0, 100.0]);
+        section.screen_position = (10.0, 10.0);
+        let sections = text_batch.effect_sections(&section, 2.0);
+
+        assert_eq!(1, sections.len());
+        assert_eq!((12.0, 12.0), sections[0].screen_position);
+        assert_eq!(48.0, sections[0].text[1].scale.y);
+        for text in &sections[0].text {
+            assert_eq!(colors::BLACK, text.extra.color);
+            assert!(text.extra.z > -3.0 && text.extra.z < -2.0);
+        }
+
+        let outlined = TextBatch::new()
+            .with("Hello", colors::WHITE)
+            .with_outline(TextOutline::default());
+        let section = outlined.as_section(1.0, [100.0, 100.0]);
+        assert_eq!(8, outlined.effect_sections(&section, 1.0).len());
+    }
+}
@@ -1,26 +1,35 @@
-use super::super::layout;
-use super::TextBatch;
+use super::super::{layout, Hidden};
+use super::{FontAssets, TextBatch};
 use crate::gfx_types::{DepthTarget, RenderTarget};
+use crate::graphics::GraphicContext;
+use crate::metrics::{builtin_metrics::*, MetricAggregate, MetricHub};
 use crate::render::ChannelPair;
-use crate::res::DeviceDimensions;
+use crate::res::{DeviceDimensions, ViewPort};
 use gfx_device::{CommandBuffer, Resources};
-use gfx_glyph::{GlyphBrush, Section};
+use gfx_glyph::{FontId, GlyphBrush, GlyphCruncher, Layout, Section, Text};
 use glutin::dpi::PhysicalSize;
-use specs::{Join, ReadExpect, ReadStorage, System};
+use nalgebra::Matrix4;
+use specs::{Join, Read, ReadExpect, ReadStorage, System};
 
 pub struct DrawTextSystem {
     channel: ChannelPair<Resources, CommandBuffer>,
     pub(crate) render_target: RenderTarget<gfx_device::Resources>,
     pub(crate) depth_target: DepthTarget<gfx_device::Resources>,
     glyph_brush: GlyphBrush<gfx_device::Resources, gfx_device::Factory>,
+    /// Number of fonts from `FontAssets` added to the glyph brush.
+    font_count: usize,
 }
 
 #[derive(SystemData)]
 pub struct DrawTextSystemData<'a> {
+    metrics: Option<Read<'a, MetricHub>>,
     device_dim: ReadExpect<'a, DeviceDimensions>,
+    view_port: ReadExpect<'a, ViewPort>,
+    font_assets: ReadExpect<'a, FontAssets>,
     global_positions: ReadStorage<'a, layout::GlobalPosition>,
     bounds_rects: ReadStorage<'a, layout::BoundsRect>,
     text_batches: ReadStorage<'a, TextBatch>,
+    hidden: ReadStorage<'a, Hidden>,
 }
 
 impl DrawTextSystem {
@@ -35,6 +44,7 @@ impl DrawTextSystem {
             render_target,
             depth_target,
             glyph_brush,
+            font_count: 0,
         }
     }
 }
@@ -44,25 +54,69 @@ impl<'a> System<'a> for DrawTextSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let DrawTextSystemData {
+            metrics,
             device_dim,
+            view_port,
+            font_assets,
             global_positions,
             bounds_rects,
             text_batches,
+            hidden,
         } = data;
 
+        // Fonts loaded since the last draw
+        for font in font_assets.fonts_since(self.font_count) {
+            self.glyph_brush.add_font(font.clone());
+            self.font_count += 1;
+        }
+
         let dpi_factor = device_dim.dpi_factor() as f32;
         // z-axis is for depth and sorting
         let nearz = -65535.;
         let farz = 65535.;
-        let transform = create_text_matrix(*device_dim.physical_size(), nearz, farz);
+        // Text is positioned like the GUI, inside the view port
+        let text_matrix = create_text_matrix(view_port.physical_size(), nearz, farz);
+        let transform: [[f32; 4]; 4] = (view_port.clip_transform(*device_dim.physical_size())
+            * Matrix4::from(text_matrix))
+        .into();
 
         match self.channel.recv_block() {
             Ok(mut encoder) => {
-                // Project text batches to a form that GlyphBrush can use
-                let sections: Vec<Section> = (&text_batches, &global_positions, &bounds_rects)
+                let mut render_timer = metrics
+                    .as_ref()
+                    .map(|metrics| metrics.timer(GRAPHICS_TEXT_RENDER, MetricAggregate::Average));
+
+                let batches: Vec<_> = (&text_batches, &global_positions, &bounds_rects, !&hidden)
                     .join()
-                    .map(|(text_batch, pos, bounds)| {
-                        let mut section = text_batch.as_section(dpi_factor, (*bounds).into());
+                    .map(|(text_batch, pos, bounds, _)| (text_batch, pos, bounds))
+                    .collect();
+
+                // Words too long to be wrapped are broken up front.
+                let glyph_brush = &mut self.glyph_brush;
+                let mut measure = |text: &str, scale: f32, font_id: FontId| {
+                    let section = Section::default()
+                        .add_text(Text::new(text).with_scale(scale).with_font_id(font_id));
+                    glyph_brush
+                        .glyph_bounds(section)
+                        .map_or(0.0, |rect| rect.width())
+                };
+                let broken: Vec<_> = batches
+                    .iter()
+                    .map(|(text_batch, _, _)| text_batch.break_long_words(dpi_factor, &mut measure))
+                    .collect();
+
+                // Project text batches to a form that GlyphBrush can use
+                let sections: Vec<Section> = batches
+                    .iter()
+                    .zip(&broken)
+                    .map(|((text_batch, pos, bounds), contents)| {
+                        let bounds = (**bounds).into();
+                        let mut section = match contents {
+                            Some(contents) => {
+                                text_batch.as_section_with(contents, dpi_factor, bounds)
+                            }
+                            None => text_batch.as_section(dpi_factor, bounds),
+                        };
                         // TODO: Change to physical pixel position
                         let new_pos = pos.point() * dpi_factor;
                         section.screen_position = (new_pos.x, new_pos.y);
@@ -70,7 +124,12 @@ impl<'a> System<'a> for DrawTextSystem {
                     })
                     .collect();
 
-                for section in sections.into_iter() {
+                // Shadows and outlines are queued along with their text, and
+                // kept behind it by their depth.
+                for ((text_batch, _, _), section) in batches.iter().zip(sections) {
+                    for effect_section in text_batch.effect_sections(&section, dpi_factor) {
+                        self.glyph_brush.queue(effect_section);
+                    }
                     self.glyph_brush.queue(section);
                 }
 
@@ -81,6 +140,21 @@ impl<'a> System<'a> for DrawTextSystem {
                     .draw(&mut encoder, &self.render_target)
                     .expect("Failed drawing text queue");
 
+                // All queued sections are drawn in a single call, which
+                // adds to the draw calls recorded by `DrawSystem`.
+                if let Some(ref metrics) = metrics {
+                    metrics
+                        .counter(GRAPHICS_TEXT_SECTIONS, MetricAggregate::Sum)
+                        .set(batches.len() as u32);
+                    metrics
+                        .counter(GRAPHICS_DRAW_CALLS, MetricAggregate::Sum)
+                        .set(if batches.is_empty() { 0 } else { 1 });
+                }
+
+                if let Some(ref mut render_timer) = render_timer {
+                    render_timer.stop();
+                }
+
                 self.channel
                     .send_block(encoder)
                     .expect("Text render failed sending encoder back to main loop");
@@ -90,6 +164,23 @@ impl<'a> System<'a> for DrawTextSystem {
     }
 }
 
+/// Measures the size, in logical pixels, that the text will take up
+/// when drawn at the given scale, without drawing it.
+///
+/// Text is only broken into lines at explicit line breaks, in which
+/// case the bounds cover all lines.
+pub fn measure_text(graphics: &mut GraphicContext, text: &str, scale: f32) -> [f32; 2] {
+    let mut section = Section::default().add_text(Text::new(text).with_scale(scale));
+    section.bounds = (std::f32::INFINITY, std::f32::INFINITY);
+    section.layout = Layout::default_wrap();
+
+    graphics
+        .glyph_brush
+        .glyph_bounds(section)
+        .map(|rect| [rect.width(), rect.height()])
+        .unwrap_or([0.0, 0.0])
+}
+
 pub fn create_text_matrix<S>(device_size: S, nearz: f32, farz: f32) -> [[f32; 4]; 4]
 where
     S: Into<PhysicalSize>,
@@ -1 +1,90 @@
+use crate::graphics::GraphicContext;
+use gfx_glyph::ab_glyph::FontArc;
+use gfx_glyph::FontId;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
 
+/// Shared store for fonts loaded from disk.
+///
+/// Fonts are registered with the glyph brush of the graphics
+/// context, used for measuring text, and are picked up by
+/// `DrawTextSystem` the next time it draws. Both brushes receive
+/// fonts in the same order, so a `FontHandle` refers to the same
+/// font in each.
+pub struct FontAssets {
+    /// Handles of loaded fonts, keyed by file path.
+    cache: BTreeMap<String, FontHandle>,
+    /// Loaded fonts, in the order they were registered,
+    /// excluding the built in default font.
+    fonts: Vec<FontArc>,
+}
+
+impl FontAssets {
+    pub fn new() -> Self {
+        FontAssets {
+            cache: BTreeMap::new(),
+            fonts: vec![],
+        }
+    }
+
+    /// Handle to the font built into the engine.
+    #[inline]
+    pub fn default_font(&self) -> FontHandle {
+        FontHandle::default()
+    }
+
+    /// Loads a TrueType or OpenType font from disk.
+    ///
+    /// Paths are resolved the same way as texture paths. When the
+    /// file can't be read, or isn't a valid font, a warning is logged
+    /// and the default font is returned instead.
+    pub fn load_font(&mut self, graphics: &mut GraphicContext, path: &str) -> FontHandle {
+        if let Some(handle) = self.cache.get(path) {
+            return *handle;
+        }
+
+        let font = match fs::read(path) {
+            Ok(data) => match FontArc::try_from_vec(data) {
+                Ok(font) => font,
+                Err(err) => {
+                    warn!("Failed to parse font '{}': {}", path, err);
+                    return self.default_font();
+                }
+            },
+            Err(err) => {
+                warn!("Failed to load font '{}': {}", path, err);
+                return self.default_font();
+            }
+        };
+
+        let handle = FontHandle(graphics.glyph_brush.add_font(font.clone()));
+        self.fonts.push(font);
+        self.cache.insert(path.to_owned(), handle);
+
+        handle
+    }
+
+    /// Fonts loaded after the given number of fonts, for
+    /// registering with another glyph brush.
+    pub(crate) fn fonts_since(&self, count: usize) -> &[FontArc] {
+        &self.fonts[count.min(self.fonts.len())..]
+    }
+}
+
+impl Default for FontAssets {
+    fn default() -> Self {
+        FontAssets::new()
+    }
+}
+
+/// Handle to a font loaded by `FontAssets`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontHandle(FontId);
+
+impl FontHandle {
+    #[inline]
+    pub fn font_id(&self) -> FontId {
+        self.0
+    }
+}
@@ -0,0 +1,393 @@
+//! Text shown next to the mouse cursor while hovering a widget.
+use super::text::{TextAlignHorizontal, TextAlignVertical, TextBatch};
+use super::{
+    despawn_widget, invalidate_layout, next_widget_tag, AlwaysOnTop, BoundsRect, GlobalPosition,
+    GuiGraph, GuiMeshBuilder, HoveredWidget, NodeId, Pack, PackMode, Placement, ZDepth,
+};
+use crate::colors::{Color, WHITE};
+use crate::comp::{GlTexture, Transform};
+use crate::graphics::GraphicContext;
+use crate::res::{DeltaTime, DeviceDimensions, TextureAssets, ViewPort};
+use gfx_glyph::GlyphCruncher;
+use glutin::{ElementState, Event, WindowEvent};
+use log::warn;
+use specs::prelude::*;
+use std::time::Duration;
+
+/// Time a widget has to be hovered before its tooltip is shown.
+pub const DEFAULT_TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+
+/// Distance from the cursor to the top left of the tooltip,
+/// so the tooltip isn't covered by the cursor.
+const CURSOR_OFFSET: [f32; 2] = [12.0, 16.0];
+
+/// Space between the tooltip's edge and its text.
+const PADDING: f32 = 4.0;
+
+const BACKGROUND_COLOR: Color = [0.1, 0.1, 0.1, 0.9];
+
+/// Spawns a floating label for widgets with a `Tooltip`, once the
+/// mouse has hovered over them for long enough.
+///
+/// The label is removed as soon as the hover moves off the widget,
+/// or a mouse button is pressed.
+///
+/// Needs access to the graphics context to build the label, so it
+/// is maintained by the main loop instead of being run as a system.
+pub struct GuiTooltipSystem {
+    delay: Duration,
+    /// Currently hovered widget with a tooltip, and for how long.
+    hover: Option<(Entity, Duration)>,
+    /// Set when a button is pressed, so the tooltip isn't shown
+    /// again until the hover changes.
+    suppressed: bool,
+    /// Last known mouse cursor position, in logical coordinates.
+    cursor_pos: [f32; 2],
+    tooltip: Option<NodeId>,
+}
+
+impl GuiTooltipSystem {
+    pub fn new() -> Self {
+        GuiTooltipSystem {
+            delay: DEFAULT_TOOLTIP_DELAY,
+            hover: None,
+            suppressed: false,
+            cursor_pos: [0.0, 0.0],
+            tooltip: None,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Node of the currently shown tooltip widget.
+    #[inline]
+    pub fn tooltip_id(&self) -> Option<NodeId> {
+        self.tooltip
+    }
+
+    pub fn maintain(&mut self, world: &mut World, graphics: &mut GraphicContext) {
+        if let Some(text) = self.update_hover(world) {
+            self.show(world, graphics, &text);
+        }
+    }
+
+    /// Tracks how long the current widget has been hovered, hiding the
+    /// tooltip when the hover changes.
+    ///
+    /// Returns the text of the tooltip to show, once the hover has
+    /// lasted longer than the widget's delay.
+    fn update_hover(&mut self, world: &mut World) -> Option<String> {
+        let mut pressed = false;
+        for ev in world.read_resource::<Vec<Event>>().iter() {
+            if let Event::WindowEvent { event, .. } = ev {
+                match event {
+                    WindowEvent::CursorMoved { position, .. } => {
+                        self.cursor_pos = [position.x as f32, position.y as f32];
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        ..
+                    } => {
+                        pressed = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let hovered = world
+            .read_resource::<HoveredWidget>()
+            .entity()
+            .filter(|entity| world.read_storage::<Tooltip>().contains(*entity));
+
+        let is_same_hover = match (self.hover, hovered) {
+            (Some((current, _)), Some(entity)) => current == entity,
+            _ => false,
+        };
+
+        if !is_same_hover {
+            self.hide(world);
+            self.hover = hovered.map(|entity| (entity, Duration::from_secs(0)));
+            self.suppressed = false;
+        }
+
+        if pressed {
+            self.hide(world);
+            self.suppressed = true;
+        }
+
+        if self.suppressed || self.tooltip.is_some() {
+            return None;
+        }
+
+        let (entity, elapsed) = match self.hover {
+            Some((entity, ref mut elapsed)) => {
+                // Tooltips keep working while the game is paused.
+                *elapsed += *world.read_resource::<DeltaTime>().unscaled();
+                (entity, *elapsed)
+            }
+            None => return None,
+        };
+
+        let tooltips = world.read_storage::<Tooltip>();
+        let tooltip = tooltips.get(entity)?;
+        let delay = world
+            .read_storage::<TooltipDelay>()
+            .get(entity)
+            .map(|delay| delay.0)
+            .unwrap_or(self.delay);
+        if elapsed >= delay {
+            Some(tooltip.0.clone())
+        } else {
+            None
+        }
+    }
+
+    fn show(&mut self, world: &mut World, graphics: &mut GraphicContext, text: &str) {
+        // The GUI is laid out inside the view port.
+        let (device_size, cursor_pos) = {
+            let device_dim = world.read_resource::<DeviceDimensions>();
+            let view_port = world.read_resource::<ViewPort>();
+            let size = view_port.logical_size(device_dim.dpi_factor());
+            (
+                [size.width as f32, size.height as f32],
+                view_port.to_local_logical(self.cursor_pos, &device_dim),
+            )
+        };
+
+        let text_batch = TextBatch::default()
+            .with(text, WHITE)
+            .with_align(TextAlignVertical::Top, TextAlignHorizontal::Left);
+
+        // Measured in logical size, wrapping at the device width.
+        let text_size = graphics
+            .glyph_brush
+            .glyph_bounds(text_batch.as_section(1.0, device_size))
+            .map(|rect| [rect.width(), rect.height()])
+            .unwrap_or([0.0, 0.0]);
+        let size = [text_size[0] + PADDING * 2.0, text_size[1] + PADDING * 2.0];
+        let [x, y] = tooltip_position(cursor_pos, size, device_size);
+
+        let texture = GlTexture::from_bundle(
+            world
+                .write_resource::<TextureAssets>()
+                .default_texture(graphics.factory_mut()),
+        );
+
+        let background_entity = world
+            .create_entity()
+            .with(next_widget_tag())
+            .with(AlwaysOnTop)
+            .with(Pack::new(PackMode::Frame))
+            .with(Placement::new(x, y))
+            .with(GlobalPosition::new(0., 0.))
+            .with(ZDepth::default())
+            .with(Transform::default())
+            .with(BoundsRect::new(size[0], size[1]))
+            .with(texture)
+            .with(
+                GuiMeshBuilder::new()
+                    .quad(
+                        [0.0, 0.0],
+                        size,
+                        [BACKGROUND_COLOR; 4],
+                        [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+                    )
+                    .build(graphics),
+            )
+            .build();
+
+        let background_node_id = world
+            .write_resource::<GuiGraph>()
+            .insert_entity(background_entity, None);
+
+        let text_entity = world
+            .create_entity()
+            .with(next_widget_tag())
+            .with(Placement::new(PADDING, PADDING))
+            .with(GlobalPosition::default())
+            .with(ZDepth::default())
+            .with(Transform::default())
+            .with(BoundsRect::new(text_size[0], text_size[1]))
+            .with(text_batch)
+            .build();
+
+        world
+            .write_resource::<GuiGraph>()
+            .insert_entity(text_entity, Some(background_node_id));
+
+        invalidate_layout(world);
+
+        self.tooltip = Some(background_node_id);
+    }
+
+    fn hide(&mut self, world: &mut World) {
+        if let Some(node_id) = self.tooltip.take() {
+            if let Err(err) = despawn_widget(world, node_id) {
+                warn!("Failed to despawn tooltip: {}", err);
+            }
+        }
+    }
+}
+
+impl Default for GuiTooltipSystem {
+    fn default() -> Self {
+        GuiTooltipSystem::new()
+    }
+}
+
+/// Calculates the top left position of a tooltip with the given
+/// size, near the cursor, clamped to stay within the device.
+///
+/// Positions and sizes are in logical pixels, relative to the view port.
+pub fn tooltip_position(cursor: [f32; 2], size: [f32; 2], device_size: [f32; 2]) -> [f32; 2] {
+    let clamp = |pos: f32, size: f32, max: f32| pos.min(max - size).max(0.0);
+
+    [
+        clamp(cursor[0] + CURSOR_OFFSET[0], size[0], device_size[0]),
+        clamp(cursor[1] + CURSOR_OFFSET[1], size[1], device_size[1]),
+    ]
+}
+
+// ---------- //
+// Components //
+// ---------- //
+
+/// Text shown in a floating label when the mouse hovers over
+/// the widget.
+///
+/// Only widgets that can be hovered, which are those that are
+/// `Clickable`, show their tooltip.
+#[derive(Component, Debug, Clone)]
+#[storage(DenseVecStorage)]
+pub struct Tooltip(pub String);
+
+impl Tooltip {
+    pub fn new<S>(text: S) -> Self
+    where
+        S: ToString,
+    {
+        Tooltip(text.to_string())
+    }
+}
+
+/// Time a widget with a `Tooltip` has to be hovered before the tooltip
+/// is shown, overriding the delay of the `GuiTooltipSystem`.
+#[derive(Component, Debug, Clone, Copy)]
+#[storage(DenseVecStorage)]
+pub struct TooltipDelay(pub Duration);
+
+impl From<&str> for Tooltip {
+    fn from(text: &str) -> Self {
+        Tooltip::new(text)
+    }
+}
+
+impl From<String> for Tooltip {
+    fn from(text: String) -> Self {
+        Tooltip::new(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::comp::Tag;
+    use crate::gui::text::TextBatch;
+    use crate::gui::widgets::Button;
+    use crate::gui::{Clickable, Focusable, LayoutDirty, WidgetBuilder};
+    use specs::Builder;
+
+    fn create_world() -> World {
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.register::<Button>();
+        world.register::<Pack>();
+        world.register::<Placement>();
+        world.register::<GlobalPosition>();
+        world.register::<ZDepth>();
+        world.register::<Transform>();
+        world.register::<BoundsRect>();
+        world.register::<Clickable>();
+        world.register::<Focusable>();
+        world.register::<TextBatch>();
+        world.register::<Tooltip>();
+        world.register::<TooltipDelay>();
+
+        let root = world.create_entity().build();
+        let gui_graph = GuiGraph::with_root(root);
+        world.add_resource(LayoutDirty::with_node_id(gui_graph.root_id()));
+        world.add_resource(gui_graph);
+        world.add_resource(HoveredWidget::default());
+        world.add_resource(Vec::<Event>::new());
+        world
+    }
+
+    fn hover(world: &World, entity: Entity, node_id: NodeId) {
+        world.write_resource::<HoveredWidget>().set(entity, node_id);
+    }
+
+    fn advance(world: &mut World, millis: u64) {
+        world.add_resource(DeltaTime::new(Duration::from_millis(millis)));
+    }
+
+    #[test]
+    fn test_hover_delay() {
+        let mut world = create_world();
+        let (button, button_id) = Button::text("Save")
+            .size(100.0, 20.0)
+            .tooltip("Saves the game")
+            .build_headless(&mut world);
+        let (quick, quick_id) = Button::text("Quick")
+            .size(100.0, 20.0)
+            .tooltip("Quick save")
+            .tooltip_delay(Duration::from_millis(100))
+            .build_headless(&mut world);
+
+        let mut tooltip_system = GuiTooltipSystem::new();
+        hover(&world, button, button_id);
+
+        advance(&mut world, 300);
+        assert_eq!(None, tooltip_system.update_hover(&mut world));
+        let text = tooltip_system.update_hover(&mut world);
+        assert_eq!(Some("Saves the game"), text.as_ref().map(String::as_str));
+
+        // Hovering another widget restarts the timer.
+        hover(&world, quick, quick_id);
+        advance(&mut world, 50);
+        assert_eq!(None, tooltip_system.update_hover(&mut world));
+        let text = tooltip_system.update_hover(&mut world);
+        assert_eq!(Some("Quick save"), text.as_ref().map(String::as_str));
+
+        // Cleared hover shows nothing.
+        world.write_resource::<HoveredWidget>().clear();
+        advance(&mut world, 1000);
+        assert_eq!(None, tooltip_system.update_hover(&mut world));
+    }
+
+    #[test]
+    fn test_tooltip_position() {
+        let device_size = [640.0, 480.0];
+
+        // Offset from cursor
+        assert_eq!(
+            [112.0, 116.0],
+            tooltip_position([100.0, 100.0], [50.0, 20.0], device_size)
+        );
+
+        // Clamped to bottom right of device
+        assert_eq!(
+            [590.0, 460.0],
+            tooltip_position([620.0, 470.0], [50.0, 20.0], device_size)
+        );
+
+        // Larger than device
+        assert_eq!(
+            [0.0, 0.0],
+            tooltip_position([10.0, 10.0], [800.0, 600.0], device_size)
+        );
+    }
+}
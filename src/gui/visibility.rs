@@ -0,0 +1,296 @@
+//! Showing, hiding, enabling and disabling widgets.
+use super::{GuiGraph, LayoutDirty, NodeId};
+use crate::colors::Color;
+use log::warn;
+use specs::prelude::*;
+
+/// Shows or hides the widget and all its descendants.
+///
+/// Layout is only invalidated when a widget in the subtree collapses
+/// when hidden. Other widgets keep their space in the layout, so only
+/// drawing and hit testing are affected.
+pub fn set_visible(world: &World, node_id: NodeId, visible: bool) {
+    let gui_graph = world.read_resource::<GuiGraph>();
+    let mut visibilities = world.write_storage::<Visibility>();
+    let mut hidden = world.write_storage::<Hidden>();
+    let mut resized = false;
+
+    let mut walker = gui_graph.walk_dfs_pre_order(node_id);
+    while let Some(next_id) = walker.next(&gui_graph) {
+        if let Some(entity) = gui_graph.get_entity(next_id) {
+            match visibilities.get_mut(entity) {
+                Some(visibility) => {
+                    resized |= visibility.visible != visible && visibility.collapse_when_hidden;
+                    visibility.visible = visible;
+                }
+                None if !visible => {
+                    if let Err(err) = visibilities.insert(entity, Visibility::new(visible)) {
+                        warn!("Failed to hide widget {:?}: {}", entity, err);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    update_hidden(
+        &gui_graph,
+        node_id,
+        |entity| {
+            visibilities
+                .get(entity)
+                .map_or(true, Visibility::is_visible)
+        },
+        &mut hidden,
+    );
+
+    if resized {
+        let root_id = gui_graph.root_id();
+        world
+            .write_resource::<LayoutDirty>()
+            .mark(gui_graph.parent_id(node_id).unwrap_or(root_id), root_id);
+    }
+}
+
+/// Recalculates which widgets in the subtree starting at the
+/// given node are hidden, taking the node's ancestors into account.
+///
+/// The closure decides whether a single widget is visible on its own.
+pub fn update_hidden<F>(
+    gui_graph: &GuiGraph,
+    node_id: NodeId,
+    is_visible: F,
+    hidden: &mut WriteStorage<Hidden>,
+) where
+    F: Fn(Entity) -> bool,
+{
+    let mut parent_hidden = false;
+    let mut cursor = gui_graph.parent_id(node_id);
+    while let Some(ancestor_id) = cursor {
+        if gui_graph
+            .get_entity(ancestor_id)
+            .map_or(false, |entity| !is_visible(entity))
+        {
+            parent_hidden = true;
+            break;
+        }
+        cursor = gui_graph.parent_id(ancestor_id);
+    }
+
+    update_hidden_subtree(gui_graph, node_id, parent_hidden, &is_visible, hidden);
+}
+
+fn update_hidden_subtree<F>(
+    gui_graph: &GuiGraph,
+    node_id: NodeId,
+    parent_hidden: bool,
+    is_visible: &F,
+    hidden: &mut WriteStorage<Hidden>,
+) where
+    F: Fn(Entity) -> bool,
+{
+    if let Some(entity) = gui_graph.get_entity(node_id) {
+        let is_hidden = parent_hidden || !is_visible(entity);
+
+        if is_hidden {
+            if let Err(err) = hidden.insert(entity, Hidden) {
+                warn!("Failed to hide widget {:?}: {}", entity, err);
+            }
+        } else {
+            hidden.remove(entity);
+        }
+
+        // Using Walker because an iterator borrows the graph.
+        let mut walker = gui_graph.walk_children(node_id);
+        while let Some(child_id) = walker.next(gui_graph) {
+            update_hidden_subtree(gui_graph, child_id, is_hidden, is_visible, hidden);
+        }
+    }
+}
+
+// --------- //
+// Resources //
+// --------- //
+
+/// Colour multiplied with disabled widgets when they are drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct DisabledTint(pub Color);
+
+impl Default for DisabledTint {
+    fn default() -> Self {
+        DisabledTint([0.5, 0.5, 0.5, 1.0])
+    }
+}
+
+// ---------- //
+// Components //
+// ---------- //
+
+/// Controls whether a widget, and its descendants, are drawn
+/// and can be hit by the mouse.
+///
+/// Widgets without this component are visible.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(DenseVecStorage)]
+pub struct Visibility {
+    visible: bool,
+    collapse_when_hidden: bool,
+}
+
+impl Visibility {
+    pub fn new(visible: bool) -> Self {
+        Visibility {
+            visible,
+            collapse_when_hidden: false,
+        }
+    }
+
+    /// When set, a hidden widget gives up its space in its
+    /// parent's packing, instead of leaving a gap.
+    pub fn with_collapse(mut self, collapse_when_hidden: bool) -> Self {
+        self.collapse_when_hidden = collapse_when_hidden;
+        self
+    }
+
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    #[inline]
+    pub fn collapse_when_hidden(&self) -> bool {
+        self.collapse_when_hidden
+    }
+
+    /// Indicates whether the widget currently takes up no space
+    /// in the layout.
+    #[inline]
+    pub fn is_collapsed(&self) -> bool {
+        !self.visible && self.collapse_when_hidden
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::new(true)
+    }
+}
+
+/// Controls whether a widget responds to user input.
+///
+/// Widgets without this component are enabled. Disabled
+/// widgets are drawn with the `DisabledTint`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(DenseVecStorage)]
+pub struct Enabled(pub bool);
+
+impl Enabled {
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+}
+
+impl Default for Enabled {
+    fn default() -> Self {
+        Enabled(true)
+    }
+}
+
+/// Marks a widget that is not drawn, because either it or
+/// one of its ancestors is invisible.
+///
+/// Maintained from `Visibility` by `GuiSortSystem` and `set_visible`.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Hidden;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_world() -> World {
+        let mut world = World::new();
+        world.register::<Visibility>();
+        world.register::<Hidden>();
+        world.add_resource(LayoutDirty::default());
+        world
+    }
+
+    #[test]
+    fn test_set_visible_subtree() {
+        let mut world = create_world();
+        let root = world.create_entity().build();
+        let parent = world.create_entity().build();
+        let child = world.create_entity().build();
+        let sibling = world.create_entity().build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let parent_id = gui_graph.insert_entity(parent, None);
+        let child_id = gui_graph.insert_entity(child, Some(parent_id));
+        gui_graph.insert_entity(sibling, None);
+        world.add_resource(gui_graph);
+
+        set_visible(&world, parent_id, false);
+        {
+            let hidden = world.read_storage::<Hidden>();
+            assert!(hidden.get(parent).is_some());
+            assert!(hidden.get(child).is_some());
+            assert!(hidden.get(sibling).is_none());
+            assert!(hidden.get(root).is_none());
+        }
+
+        // Non-collapsing widgets keep their space.
+        assert!(!world.read_resource::<LayoutDirty>().is_dirty());
+
+        // Child stays hidden while its parent is invisible.
+        world
+            .write_storage::<Visibility>()
+            .get_mut(child)
+            .unwrap()
+            .visible = true;
+        update_hidden(
+            &world.read_resource::<GuiGraph>(),
+            child_id,
+            |entity| {
+                world
+                    .read_storage::<Visibility>()
+                    .get(entity)
+                    .map_or(true, Visibility::is_visible)
+            },
+            &mut world.write_storage::<Hidden>(),
+        );
+        assert!(world.read_storage::<Hidden>().get(child).is_some());
+
+        set_visible(&world, parent_id, true);
+        let hidden = world.read_storage::<Hidden>();
+        assert!(hidden.get(parent).is_none());
+        assert!(hidden.get(child).is_none());
+    }
+
+    #[test]
+    fn test_set_visible_collapse() {
+        let mut world = create_world();
+        let root = world.create_entity().build();
+        let widget = world
+            .create_entity()
+            .with(Visibility::new(true).with_collapse(true))
+            .build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let widget_id = gui_graph.insert_entity(widget, None);
+        let root_id = gui_graph.root_id();
+        world.add_resource(gui_graph);
+
+        set_visible(&world, widget_id, false);
+        assert!(world
+            .read_storage::<Visibility>()
+            .get(widget)
+            .unwrap()
+            .is_collapsed());
+        assert_eq!(
+            world.read_resource::<LayoutDirty>().node_id(),
+            Some(root_id)
+        );
+    }
+}
@@ -1,4 +1,9 @@
+use super::{
+    DraggedWidget, FocusedWidget, GuiGraph, HoveredWidget, LayoutDirty, NodeId, PressedWidget,
+};
 use crate::comp::Tag;
+use specs::error::WrongGeneration;
+use specs::{Entity, World};
 use std::sync::RwLock;
 
 lazy_static! {
@@ -24,3 +29,104 @@ pub fn next_widget_tag() -> Tag {
     counter.incr();
     Tag::new(format!("Widget {}", counter.inner()))
 }
+
+/// Removes the widget and its descendants from the GUI graph,
+/// and deletes their entities from the world.
+///
+/// Widget resources referring to a removed widget are cleared,
+/// and the layout of the widget's parent is marked dirty.
+pub fn despawn_widget(world: &mut World, node_id: NodeId) -> Result<(), WrongGeneration> {
+    let (entities, parent_id, root_id) = {
+        let mut gui_graph = world.write_resource::<GuiGraph>();
+        let parent_id = gui_graph.parent_id(node_id);
+        let entities = gui_graph.remove_subtree(node_id);
+        (entities, parent_id, gui_graph.root_id())
+    };
+
+    if entities.is_empty() {
+        return Ok(());
+    }
+
+    let is_removed = |entity: Option<Entity>| entity.map_or(false, |e| entities.contains(&e));
+
+    {
+        let mut hovered = world.write_resource::<HoveredWidget>();
+        if is_removed(hovered.entity()) {
+            hovered.clear();
+        }
+    }
+
+    {
+        let mut pressed = world.write_resource::<PressedWidget>();
+        if is_removed(pressed.entity()) {
+            pressed.clear();
+        }
+    }
+
+    {
+        let mut focused = world.write_resource::<FocusedWidget>();
+        if is_removed(focused.entity()) {
+            focused.clear();
+        }
+    }
+
+    {
+        let mut dragged = world.write_resource::<DraggedWidget>();
+        if is_removed(dragged.entity()) {
+            dragged.clear();
+        }
+    }
+
+    world
+        .write_resource::<LayoutDirty>()
+        .mark(parent_id.unwrap_or(root_id), root_id);
+
+    world.delete_entities(&entities)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::Builder;
+
+    #[test]
+    fn test_despawn_widget() {
+        let mut world = World::new();
+        let root = world.create_entity().build();
+        let parent = world.create_entity().build();
+        let child = world.create_entity().build();
+        let sibling = world.create_entity().build();
+
+        let mut gui_graph = GuiGraph::with_root(root);
+        let parent_id = gui_graph.insert_entity(parent, None);
+        let child_id = gui_graph.insert_entity(child, Some(parent_id));
+        let sibling_id = gui_graph.insert_entity(sibling, None);
+
+        let mut hovered = HoveredWidget::default();
+        hovered.set(child, child_id);
+        world.add_resource(hovered);
+        world.add_resource(PressedWidget::default());
+        world.add_resource(FocusedWidget::default());
+        let mut dragged = DraggedWidget::default();
+        dragged.set(child, child_id);
+        world.add_resource(dragged);
+        world.add_resource(LayoutDirty::default());
+        world.add_resource(gui_graph);
+
+        despawn_widget(&mut world, parent_id).unwrap();
+        world.maintain();
+
+        let gui_graph = world.read_resource::<GuiGraph>();
+        assert_eq!(gui_graph.get_entity(parent_id), None);
+        assert_eq!(gui_graph.get_entity(child_id), None);
+        assert_eq!(gui_graph.get_entity(sibling_id), Some(sibling));
+        assert!(!world.is_alive(parent));
+        assert!(!world.is_alive(child));
+        assert!(!world.read_resource::<HoveredWidget>().has_widget());
+        assert!(!world.read_resource::<DraggedWidget>().has_widget());
+        assert_eq!(
+            world.read_resource::<LayoutDirty>().node_id(),
+            Some(gui_graph.root_id())
+        );
+    }
+}
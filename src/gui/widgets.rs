@@ -1,5 +1,7 @@
 mod button;
 mod container;
+mod progress_bar;
 
 pub use button::*;
 pub use container::*;
+pub use progress_bar::*;
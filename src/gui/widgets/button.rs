@@ -1,7 +1,10 @@
-use super::super::text::{TextAlignHorizontal, TextAlignVertical, TextBatch};
+use super::super::text::{
+    measure_text, TextAlignHorizontal, TextAlignVertical, TextBatch, TextShadow, DEFAULT_TEXT_SCALE,
+};
 use super::super::{
-    next_widget_tag, BoundsRect, Clickable, GlobalPosition, GuiGraph, GuiMeshBuilder, Pack,
-    PackMode, Placement, WidgetBuilder, ZDepth,
+    invalidate_layout, next_widget_tag, BoundsRect, Clickable, Focusable, GlobalPosition, GuiGraph,
+    GuiMesh, GuiMeshBuilder, Pack, PackMode, Placement, Tooltip, TooltipDelay, WidgetBuilder,
+    ZDepth,
 };
 use crate::collections::ordered_dag::NodeId;
 use crate::colors::*;
@@ -12,6 +15,14 @@ use crate::res::TextureAssets;
 use nalgebra::Vector2;
 use specs::prelude::*;
 use std::string::ToString;
+use std::time::Duration;
+
+/// Size of buttons without text, when none is given.
+const DEFAULT_BUTTON_SIZE: [f32; 2] = [100.0, 100.0];
+
+/// Space between the edge of a text button and its text, when
+/// the button is sized to fit the text.
+const TEXT_PADDING: [f32; 2] = [8.0, 4.0];
 
 #[derive(Component)]
 #[storage(DenseVecStorage)]
@@ -26,10 +37,13 @@ impl Button {
             parent: None,
             tag: None,
             button_type: ButtonType::Text(text.to_string()),
-            size: [100.0, 100.0],
+            size: None,
             background: None,
             background_uv: [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
             background_src_rect: None,
+            text_shadow: None,
+            tooltip: None,
+            tooltip_delay: None,
         }
     }
 }
@@ -39,10 +53,13 @@ pub struct ButtonBuilder {
     parent: Option<NodeId>,
     tag: Option<Tag>,
     button_type: ButtonType,
-    size: [f32; 2],
+    size: Option<[f32; 2]>,
     background: Option<String>,
     background_uv: [[f32; 2]; 4],
     background_src_rect: Option<[Vector2<u32>; 2]>,
+    text_shadow: Option<TextShadow>,
+    tooltip: Option<Tooltip>,
+    tooltip_delay: Option<TooltipDelay>,
 }
 
 impl ButtonBuilder {
@@ -59,8 +76,11 @@ impl ButtonBuilder {
         self
     }
 
+    /// Fixed size of the button.
+    ///
+    /// When not given, text buttons are sized to fit their text.
     pub fn size(mut self, x: f32, y: f32) -> Self {
-        self.size = [x, y];
+        self.size = Some([x, y]);
         self
     }
 
@@ -81,41 +101,50 @@ impl ButtonBuilder {
         self.background_src_rect = Some([pos.into(), size.into()]);
         self
     }
+
+    /// Draws a shadow behind the button's text.
+    pub fn text_shadow(mut self, shadow: TextShadow) -> Self {
+        self.text_shadow = Some(shadow);
+        self
+    }
+
+    /// Text shown next to the cursor while hovering the button.
+    pub fn tooltip<T>(mut self, tooltip: T) -> Self
+    where
+        T: Into<Tooltip>,
+    {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    /// Time the button has to be hovered before its tooltip is shown.
+    pub fn tooltip_delay(mut self, delay: Duration) -> Self {
+        self.tooltip_delay = Some(TooltipDelay(delay));
+        self
+    }
 }
 
-impl WidgetBuilder for ButtonBuilder {
-    fn build(self, world: &mut World, graphics: &mut GraphicContext) -> (Entity, NodeId) {
+impl ButtonBuilder {
+    /// Creates the button and its text, adding the texture and mesh
+    /// when the button is drawn.
+    fn create_entities(
+        self,
+        world: &mut World,
+        size: [f32; 2],
+        visuals: Option<(GlTexture, GuiMesh)>,
+    ) -> (Entity, NodeId) {
         let ButtonBuilder {
             parent,
             tag,
             button_type,
-            size,
-            background,
-            background_uv,
-            background_src_rect,
+            text_shadow,
+            tooltip,
+            tooltip_delay,
+            ..
         } = self;
 
-        let texture = match background {
-            Some(file_path) => GlTexture::from_bundle(
-                world
-                    .write_resource::<TextureAssets>()
-                    .load_texture(graphics.factory_mut(), &file_path),
-            ),
-            None => GlTexture::from_bundle(
-                world
-                    .write_resource::<TextureAssets>()
-                    .default_texture(graphics.factory_mut()),
-            ),
-        };
-
-        let uvs = if let Some([pos, size]) = background_src_rect {
-            texture.source_rect().sub_rect(pos, size).into()
-        } else {
-            background_uv
-        };
-
         // Create Sprite
-        let sprite_entity = world
+        let mut entity_builder = world
             .create_entity()
             .with(tag.unwrap_or_else(next_widget_tag))
             .with(Button)
@@ -127,15 +156,22 @@ impl WidgetBuilder for ButtonBuilder {
             .with(Transform::default())
             .with(BoundsRect::new(size[0], size[1]))
             .with(Clickable)
+            .with(Focusable);
+
+        if let Some((texture, mesh)) = visuals {
             // .with(Material::Basic { texture })
-            .with(texture)
-            .with(
-                // TODO: replace with 9-patch
-                GuiMeshBuilder::new()
-                    .quad([0.0, 0.0], size, [WHITE, WHITE, WHITE, WHITE], uvs)
-                    .build(graphics),
-            )
-            .build();
+            entity_builder = entity_builder.with(texture).with(mesh);
+        }
+
+        if let Some(tooltip) = tooltip {
+            entity_builder = entity_builder.with(tooltip);
+        }
+
+        if let Some(tooltip_delay) = tooltip_delay {
+            entity_builder = entity_builder.with(tooltip_delay);
+        }
+
+        let sprite_entity = entity_builder.build();
 
         let sprite_node_id = world
             .write_resource::<GuiGraph>()
@@ -147,6 +183,12 @@ impl WidgetBuilder for ButtonBuilder {
             let center = Vector2::from(size) / 2.0;
             // let center = Vector2::from(size) / 1.5;
 
+            let mut text_batch = TextBatch::default()
+                .with(&text, WHITE)
+                .with_z(0.0)
+                .with_align(TextAlignVertical::Center, TextAlignHorizontal::Center);
+            text_batch.set_shadow(text_shadow);
+
             let text_entity = world
                 .create_entity()
                 .with(next_widget_tag())
@@ -154,12 +196,7 @@ impl WidgetBuilder for ButtonBuilder {
                 .with(GlobalPosition::default())
                 .with(Transform::default())
                 .with(BoundsRect::new(size[0], size[1]))
-                .with(
-                    TextBatch::default()
-                        .with(&text, WHITE)
-                        .with_z(0.0)
-                        .with_align(TextAlignVertical::Center, TextAlignHorizontal::Center),
-                )
+                .with(text_batch)
                 .build();
 
             let _text_node_id = world
@@ -167,10 +204,58 @@ impl WidgetBuilder for ButtonBuilder {
                 .insert_entity(text_entity, Some(sprite_node_id));
         }
 
+        invalidate_layout(world);
+
         (sprite_entity, sprite_node_id)
     }
 }
 
+impl WidgetBuilder for ButtonBuilder {
+    fn build(self, world: &mut World, graphics: &mut GraphicContext) -> (Entity, NodeId) {
+        let size = self.size.unwrap_or_else(|| match self.button_type {
+            ButtonType::Text(ref text) => {
+                let [w, h] = measure_text(graphics, text, DEFAULT_TEXT_SCALE);
+                [w + TEXT_PADDING[0] * 2.0, h + TEXT_PADDING[1] * 2.0]
+            }
+            ButtonType::Image(_) => DEFAULT_BUTTON_SIZE,
+        });
+
+        let texture = match self.background {
+            Some(ref file_path) => GlTexture::from_bundle(
+                world
+                    .write_resource::<TextureAssets>()
+                    .load_texture(graphics.factory_mut(), file_path),
+            ),
+            None => GlTexture::from_bundle(
+                world
+                    .write_resource::<TextureAssets>()
+                    .default_texture(graphics.factory_mut()),
+            ),
+        };
+
+        let uvs = if let Some([pos, src_size]) = self.background_src_rect {
+            texture.source_rect().sub_rect(pos, src_size).into()
+        } else {
+            self.background_uv
+        };
+
+        // TODO: replace with 9-patch
+        let mesh = GuiMeshBuilder::new()
+            .quad([0.0, 0.0], size, [WHITE, WHITE, WHITE, WHITE], uvs)
+            .build(graphics);
+
+        self.create_entities(world, size, Some((texture, mesh)))
+    }
+
+    /// Text can't be measured without a graphics context, so
+    /// text buttons without a fixed size get the default size.
+    fn build_headless(self, world: &mut World) -> (Entity, NodeId) {
+        let size = self.size.unwrap_or(DEFAULT_BUTTON_SIZE);
+
+        self.create_entities(world, size, None)
+    }
+}
+
 #[allow(dead_code)]
 enum ButtonType {
     Text(String),
@@ -60,6 +60,8 @@ pub struct ContainerBuilder {
     pack_mode: layout::PackMode,
     margin: [f32; 2],
     size: [f32; 2],
+    min_size: Option<[f32; 2]>,
+    max_size: Option<[f32; 2]>,
 }
 
 impl Default for ContainerBuilder {
@@ -71,6 +73,8 @@ impl Default for ContainerBuilder {
             pack_mode: layout::PackMode::Frame,
             margin: [0.0, 0.0],
             size: [::std::f32::INFINITY, ::std::f32::INFINITY],
+            min_size: None,
+            max_size: None,
         }
     }
 }
@@ -103,10 +107,30 @@ impl ContainerBuilder {
         self.size = size;
         self
     }
+
+    /// Smallest size the container takes up, regardless of
+    /// its children.
+    pub fn min_size(mut self, width: f32, height: f32) -> Self {
+        self.min_size = Some([width, height]);
+        self
+    }
+
+    /// Largest size the container grows to when its
+    /// children don't fit.
+    pub fn max_size(mut self, width: f32, height: f32) -> Self {
+        self.max_size = Some([width, height]);
+        self
+    }
 }
 
 impl WidgetBuilder for ContainerBuilder {
+    /// Containers aren't drawn, so they're the same with or
+    /// without graphics.
     fn build(self, world: &mut World, _graphics: &mut GraphicContext) -> (Entity, NodeId) {
+        self.build_headless(world)
+    }
+
+    fn build_headless(self, world: &mut World) -> (Entity, NodeId) {
         let ContainerBuilder {
             parent_id,
             tag,
@@ -114,11 +138,18 @@ impl WidgetBuilder for ContainerBuilder {
             pack_mode,
             margin,
             size,
+            min_size,
+            max_size,
         } = self;
 
         let mut pack = layout::Pack::new(pack_mode);
         pack.margin = margin;
 
+        // Containers without constraints keep the size they were given.
+        if min_size.is_some() || max_size.is_some() {
+            pack.constraints = Some(layout::SizeConstraints { min_size, max_size });
+        }
+
         let entity_id = world
             .create_entity()
             .with(Container)
@@ -134,6 +165,7 @@ impl WidgetBuilder for ContainerBuilder {
         let node_id = world
             .write_resource::<GuiGraph>()
             .insert_entity(entity_id, parent_id);
+        layout::invalidate_layout(world);
 
         (entity_id, node_id)
     }
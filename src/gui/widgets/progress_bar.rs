@@ -0,0 +1,319 @@
+use super::super::{
+    invalidate_layout, next_widget_tag, BoundsRect, GlobalPosition, GuiGraph, GuiMesh,
+    GuiMeshBuilder, Pack, PackMode, Placement, WidgetBuilder, ZDepth,
+};
+use crate::collections::ordered_dag::NodeId;
+use crate::colors::*;
+use crate::comp::{GlTexture, MeshCmd, MeshCommandBuffer, Tag, Transform};
+use crate::graphics::GraphicContext;
+use crate::res::TextureAssets;
+use specs::prelude::*;
+use std::string::ToString;
+
+/// UVs of the plain white default texture.
+const FULL_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+/// Bar that fills up as its value goes from zero to one, such as
+/// a health or loading bar.
+#[derive(Component, Debug)]
+#[storage(DenseVecStorage)]
+pub struct ProgressBar {
+    value: f32,
+    direction: FillDirection,
+    background_color: Color,
+    fill_color: Color,
+    /// Set when the value changed, and the mesh has
+    /// to be rebuilt.
+    dirty: bool,
+}
+
+impl ProgressBar {
+    pub fn builder() -> ProgressBarBuilder {
+        ProgressBarBuilder {
+            parent: None,
+            tag: None,
+            size: [100.0, 16.0],
+            value: 0.0,
+            direction: FillDirection::LeftToRight,
+            background_color: [0.1, 0.1, 0.1, 1.0],
+            fill_color: GREEN,
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Changes how full the bar is, clamped between zero and one.
+    ///
+    /// The widget's mesh is rebuilt by `ProgressBarSystem`.
+    pub fn set_value(&mut self, value: f32) {
+        let value = value.max(0.0).min(1.0);
+
+        if (self.value - value).abs() > std::f32::EPSILON {
+            self.value = value;
+            self.dirty = true;
+        }
+    }
+
+    #[inline]
+    pub fn direction(&self) -> FillDirection {
+        self.direction
+    }
+
+    /// Position and size of the filled part of the bar, within
+    /// a bar of the given size.
+    pub fn fill_rect(&self, size: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let [w, h] = size;
+        let (fill_w, fill_h) = (w * self.value, h * self.value);
+
+        match self.direction {
+            FillDirection::LeftToRight => ([0.0, 0.0], [fill_w, h]),
+            FillDirection::RightToLeft => ([w - fill_w, 0.0], [fill_w, h]),
+            FillDirection::TopToBottom => ([0.0, 0.0], [w, fill_h]),
+            FillDirection::BottomToTop => ([0.0, h - fill_h], [w, fill_h]),
+        }
+    }
+
+    /// Background spanning the whole bar, with the fill drawn over it.
+    fn mesh_builder(&self, size: [f32; 2]) -> GuiMeshBuilder {
+        let (fill_pos, fill_size) = self.fill_rect(size);
+
+        GuiMeshBuilder::new()
+            .quad([0.0, 0.0], size, [self.background_color; 4], FULL_UVS)
+            .quad(fill_pos, fill_size, [self.fill_color; 4], FULL_UVS)
+    }
+}
+
+/// Side of a progress bar that starts filling up first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillDirection {
+    LeftToRight,
+    RightToLeft,
+    BottomToTop,
+    TopToBottom,
+}
+
+#[must_use = "Call .build() on widget builder."]
+pub struct ProgressBarBuilder {
+    parent: Option<NodeId>,
+    tag: Option<Tag>,
+    size: [f32; 2],
+    value: f32,
+    direction: FillDirection,
+    background_color: Color,
+    fill_color: Color,
+}
+
+impl ProgressBarBuilder {
+    pub fn child_of(mut self, parent: NodeId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn tag<S>(mut self, name: S) -> Self
+    where
+        S: ToString,
+    {
+        self.tag = Some(Tag::new(name));
+        self
+    }
+
+    pub fn size(mut self, x: f32, y: f32) -> Self {
+        self.size = [x, y];
+        self
+    }
+
+    pub fn direction(mut self, direction: FillDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn fill_color(mut self, color: Color) -> Self {
+        self.fill_color = color;
+        self
+    }
+
+    pub fn background_color(mut self, color: Color) -> Self {
+        self.background_color = color;
+        self
+    }
+
+    /// Value the bar starts with, clamped between zero and one.
+    pub fn initial_value(mut self, value: f32) -> Self {
+        self.value = value.max(0.0).min(1.0);
+        self
+    }
+}
+
+impl ProgressBarBuilder {
+    /// Creates the bar, adding the texture and mesh when it's drawn.
+    fn create_entity(
+        self,
+        world: &mut World,
+        visuals: Option<(GlTexture, GuiMesh)>,
+    ) -> (Entity, NodeId) {
+        let progress_bar = self.progress_bar();
+        let ProgressBarBuilder {
+            parent, tag, size, ..
+        } = self;
+
+        let mut entity_builder = world
+            .create_entity()
+            .with(tag.unwrap_or_else(next_widget_tag))
+            .with(progress_bar)
+            .with(Pack::new(PackMode::Frame))
+            .with(Placement::new(0.0, 0.0))
+            .with(GlobalPosition::new(0., 0.))
+            .with(ZDepth::default())
+            .with(Transform::default())
+            .with(BoundsRect::new(size[0], size[1]));
+
+        if let Some((texture, mesh)) = visuals {
+            entity_builder = entity_builder.with(texture).with(mesh);
+        }
+
+        let entity = entity_builder.build();
+
+        let node_id = world
+            .write_resource::<GuiGraph>()
+            .insert_entity(entity, parent);
+
+        invalidate_layout(world);
+
+        (entity, node_id)
+    }
+
+    fn progress_bar(&self) -> ProgressBar {
+        ProgressBar {
+            value: self.value,
+            direction: self.direction,
+            background_color: self.background_color,
+            fill_color: self.fill_color,
+            dirty: false,
+        }
+    }
+}
+
+impl WidgetBuilder for ProgressBarBuilder {
+    fn build(self, world: &mut World, graphics: &mut GraphicContext) -> (Entity, NodeId) {
+        let texture = GlTexture::from_bundle(
+            world
+                .write_resource::<TextureAssets>()
+                .default_texture(graphics.factory_mut()),
+        );
+        let mesh = self.progress_bar().mesh_builder(self.size).build(graphics);
+
+        self.create_entity(world, Some((texture, mesh)))
+    }
+
+    fn build_headless(self, world: &mut World) -> (Entity, NodeId) {
+        self.create_entity(world, None)
+    }
+}
+
+/// Rebuilds the meshes of progress bars whose value has changed.
+///
+/// Meshes are allocated through the `MeshCommandBuffer`, so this
+/// should run before it is maintained.
+#[derive(Default)]
+pub struct ProgressBarSystem;
+
+impl<'a> System<'a> for ProgressBarSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, MeshCommandBuffer>,
+        WriteStorage<'a, ProgressBar>,
+        ReadStorage<'a, BoundsRect>,
+    );
+
+    fn run(&mut self, (entities, mut mesh_cmds, mut progress_bars, bounds): Self::SystemData) {
+        for (entity, progress_bar, bounds) in (&entities, &mut progress_bars, &bounds).join() {
+            if progress_bar.dirty {
+                let builder = progress_bar.mesh_builder(bounds.finite_size());
+                mesh_cmds.submit(MeshCmd::AllocateGuiMesh(entity, builder));
+                progress_bar.dirty = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn progress_bar(value: f32, direction: FillDirection) -> ProgressBar {
+        let mut progress_bar = ProgressBar {
+            value: 0.0,
+            direction,
+            background_color: BLACK,
+            fill_color: GREEN,
+            dirty: false,
+        };
+        progress_bar.set_value(value);
+        progress_bar
+    }
+
+    #[test]
+    fn test_fill_rect() {
+        let size = [200.0, 20.0];
+
+        let (pos, fill_size) = progress_bar(0.5, FillDirection::LeftToRight).fill_rect(size);
+        assert_eq!(size[0] * 0.5, pos[0] + fill_size[0]);
+        assert_eq!(size[1], fill_size[1]);
+
+        let (pos, fill_size) = progress_bar(0.25, FillDirection::BottomToTop).fill_rect(size);
+        assert_eq!([0.0, 15.0], pos);
+        assert_eq!([200.0, 5.0], fill_size);
+    }
+
+    #[test]
+    fn test_set_value() {
+        let mut progress_bar = progress_bar(1.5, FillDirection::RightToLeft);
+        assert_eq!(1.0, progress_bar.value());
+        assert!(progress_bar.dirty);
+
+        progress_bar.set_value(-1.0);
+        assert_eq!(0.0, progress_bar.value());
+    }
+
+    #[test]
+    fn test_build_headless() {
+        use crate::gui::LayoutDirty;
+
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.register::<ProgressBar>();
+        world.register::<Pack>();
+        world.register::<Placement>();
+        world.register::<GlobalPosition>();
+        world.register::<ZDepth>();
+        world.register::<Transform>();
+        world.register::<BoundsRect>();
+        world.register::<GuiMesh>();
+
+        let root = world.create_entity().build();
+        world.add_resource(GuiGraph::with_root(root));
+        world.add_resource(LayoutDirty::default());
+
+        // Built without a graphics context.
+        let (entity, node_id) = ProgressBar::builder()
+            .size(100.0, 10.0)
+            .initial_value(0.5)
+            .build_headless(&mut world);
+
+        assert_eq!(
+            Some(entity),
+            world.read_resource::<GuiGraph>().get_entity(node_id)
+        );
+        assert!(world.read_resource::<LayoutDirty>().is_dirty());
+        let progress_bars = world.read_storage::<ProgressBar>();
+        assert_eq!(0.5, progress_bars.get(entity).unwrap().value());
+        let bounds = world.read_storage::<BoundsRect>();
+        assert_eq!([100.0, 10.0], bounds.get(entity).unwrap().size());
+
+        // Nothing to draw without a graphics context.
+        assert!(!world.read_storage::<GuiMesh>().contains(entity));
+    }
+}
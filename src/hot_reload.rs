@@ -0,0 +1,185 @@
+//! Reloading of textures and shaders when their files change,
+//! enabled by the `debug-hot-reload` feature.
+
+use crate::gfx_types::GraphicsEncoder;
+use crate::render::{Pipeline, SHADER_DIR};
+use crate::res::TextureAssets;
+use gfx_device::Factory;
+use log::{error, trace, warn};
+use specs::World;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between checks of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches texture and shader files, and reloads them
+/// when they're modified.
+///
+/// Textures are reloaded in place, so existing `GlTexture`
+/// components keep working. Shaders are linked into new
+/// pipelines, which replace the pipeline resources in the
+/// world. When linking fails, the error is logged and the
+/// previous pipeline is kept.
+pub(crate) struct HotReload {
+    watcher: FileWatcher,
+    /// Pipelines using each shader file.
+    shaders: BTreeMap<PathBuf, Vec<Pipeline>>,
+    last_poll: Instant,
+}
+
+impl HotReload {
+    pub(crate) fn new() -> Self {
+        let mut watcher = FileWatcher::new();
+        let mut shaders: BTreeMap<PathBuf, Vec<Pipeline>> = BTreeMap::new();
+
+        for pipeline in Pipeline::ALL.iter() {
+            for files in pipeline.shader_files() {
+                for file_name in &[files.vertex, files.fragment] {
+                    let path = Path::new(SHADER_DIR).join(file_name);
+                    watcher.watch(&path);
+
+                    let pipelines = shaders.entry(path).or_insert_with(Vec::new);
+                    if !pipelines.contains(pipeline) {
+                        pipelines.push(*pipeline);
+                    }
+                }
+            }
+        }
+
+        HotReload {
+            watcher,
+            shaders,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Reloads the textures and shaders that changed since the
+    /// last poll.
+    ///
+    /// Texture updates are recorded to the encoder, so this must be
+    /// called before the encoder is flushed.
+    pub(crate) fn update(
+        &mut self,
+        world: &mut World,
+        factory: &mut Factory,
+        encoder: &mut GraphicsEncoder,
+    ) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        // Textures loaded since the last poll
+        for path in world.read_resource::<TextureAssets>().texture_paths() {
+            self.watcher.watch(Path::new(path));
+        }
+
+        let mut pipelines = vec![];
+
+        for path in self.watcher.poll() {
+            if let Some(shader_pipelines) = self.shaders.get(&path) {
+                trace!("Shader changed {:?}", path);
+
+                for pipeline in shader_pipelines {
+                    if !pipelines.contains(pipeline) {
+                        pipelines.push(*pipeline);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(key) = path.to_str() {
+                trace!("Texture changed '{}'", key);
+
+                let textures = world.read_resource::<TextureAssets>();
+                if let Err(err) = textures.reload_texture(encoder, key) {
+                    warn!("Failed to reload texture '{}': {}", key, err);
+                }
+            }
+        }
+
+        for pipeline in pipelines {
+            if let Err(err) = pipeline.create(factory, world) {
+                error!(
+                    "Failed to reload {:?} shaders, keeping previous pipeline: {}",
+                    pipeline, err
+                );
+            }
+        }
+    }
+}
+
+/// Polls the modification times of files.
+struct FileWatcher {
+    /// Last known modification time of each file, or `None`
+    /// if the file couldn't be read.
+    modified: BTreeMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    fn new() -> Self {
+        FileWatcher {
+            modified: BTreeMap::new(),
+        }
+    }
+
+    /// Starts watching the file, if it's not watched already.
+    fn watch(&mut self, path: &Path) {
+        if !self.modified.contains_key(path) {
+            self.modified.insert(path.to_owned(), modified_time(path));
+        }
+    }
+
+    /// Files that were modified, or created, since the last poll.
+    ///
+    /// Removed files aren't reported, so editors that replace a
+    /// file by deleting it don't trigger a reload of a missing file.
+    fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+
+        for (path, last_modified) in self.modified.iter_mut() {
+            let modified = modified_time(path);
+
+            if modified.is_some() && modified != *last_modified {
+                changed.push(path.clone());
+            }
+
+            *last_modified = modified;
+        }
+
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_watcher() {
+        let path = std::env::temp_dir().join("rengine_test_file_watcher.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut watcher = FileWatcher::new();
+        watcher.watch(&path);
+        assert!(watcher.poll().is_empty());
+
+        // Created after it started being watched
+        fs::write(&path, "a").unwrap();
+        assert_eq!(vec![path.clone()], watcher.poll());
+        assert!(watcher.poll().is_empty());
+
+        // Removed files aren't reported
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! Mapping of keyboard and mouse input to game actions.
+use crate::errors::InputMapError;
+use glutin::{MouseButton, VirtualKeyCode};
+use serde::de::value::StrDeserializer;
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Physical input that can be bound to an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UserInput {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl From<VirtualKeyCode> for UserInput {
+    fn from(key: VirtualKeyCode) -> Self {
+        UserInput::Key(key)
+    }
+}
+
+impl From<MouseButton> for UserInput {
+    fn from(button: MouseButton) -> Self {
+        UserInput::Mouse(button)
+    }
+}
+
+/// Formats the input as it's stored in a saved input
+/// map, like `Key:W`, `Mouse:Left` or `Mouse:4`.
+impl fmt::Display for UserInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UserInput::Key(key) => write!(f, "Key:{:?}", key),
+            UserInput::Mouse(MouseButton::Other(button)) => write!(f, "Mouse:{}", button),
+            UserInput::Mouse(button) => write!(f, "Mouse:{:?}", button),
+        }
+    }
+}
+
+impl FromStr for UserInput {
+    type Err = InputMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || InputMapError::UnknownInput(s.to_owned());
+        let mut parts = s.splitn(2, ':');
+
+        match (parts.next(), parts.next()) {
+            (Some("Key"), Some(name)) => {
+                parse_variant(name).map(UserInput::Key).ok_or_else(unknown)
+            }
+            (Some("Mouse"), Some(name)) => match name.parse::<u8>() {
+                Ok(button) => Ok(UserInput::Mouse(MouseButton::Other(button))),
+                Err(_) => parse_variant(name)
+                    .map(UserInput::Mouse)
+                    .ok_or_else(unknown),
+            },
+            _ => Err(unknown()),
+        }
+    }
+}
+
+/// Parses the name of a unit enum variant, like `W` or `Left`.
+fn parse_variant<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let deserializer: StrDeserializer<'_, serde::de::value::Error> = name.into_deserializer();
+    T::deserialize(deserializer).ok()
+}
+
+/// Bindings of keys and mouse buttons to actions.
+///
+/// Each input triggers at most one action, while an action
+/// can be bound to several inputs.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::glutin::{MouseButton, VirtualKeyCode};
+/// # use rengine::input::{InputMap, UserInput};
+/// let mut input_map = InputMap::new();
+/// input_map.bind(VirtualKeyCode::Space, "jump");
+/// input_map.bind_mouse(MouseButton::Left, "shoot");
+///
+/// assert_eq!(Some(&"jump"), input_map.lookup(&UserInput::Key(VirtualKeyCode::Space)));
+/// assert_eq!(Some(&"shoot"), input_map.lookup(&UserInput::Mouse(MouseButton::Left)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct InputMap<T> {
+    mapping: HashMap<UserInput, T>,
+}
+
+impl<T> InputMap<T> {
+    pub fn new() -> Self {
+        InputMap {
+            mapping: HashMap::new(),
+        }
+    }
+
+    /// Binds a key to the action, replacing the key's
+    /// previous binding.
+    pub fn bind(&mut self, key: VirtualKeyCode, action: T) {
+        self.bind_input(UserInput::Key(key), action);
+    }
+
+    /// Binds a mouse button to the action, replacing the
+    /// button's previous binding.
+    pub fn bind_mouse(&mut self, button: MouseButton, action: T) {
+        self.bind_input(UserInput::Mouse(button), action);
+    }
+
+    /// Binds any input to the action, replacing the input's
+    /// previous binding.
+    pub fn bind_input(&mut self, input: UserInput, action: T) {
+        self.mapping.insert(input, action);
+    }
+
+    /// Removes the input's binding, returning the action
+    /// it was bound to.
+    pub fn unbind(&mut self, input: &UserInput) -> Option<T> {
+        self.mapping.remove(input)
+    }
+
+    /// Action bound to the input.
+    #[inline]
+    pub fn lookup(&self, input: &UserInput) -> Option<&T> {
+        self.mapping.get(input)
+    }
+
+    /// All current bindings, in no particular order, for
+    /// displaying in a settings screen.
+    pub fn bound_actions(&self) -> impl Iterator<Item = (&UserInput, &T)> {
+        self.mapping.iter()
+    }
+}
+
+impl<T> InputMap<T>
+where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    /// Loads bindings from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, InputMapError> {
+        let contents = fs::read_to_string(path)?;
+
+        Self::from_toml(&contents)
+    }
+
+    /// Saves the bindings to a TOML file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), InputMapError> {
+        fs::write(path, self.to_toml()?)?;
+
+        Ok(())
+    }
+
+    fn from_toml(contents: &str) -> Result<Self, InputMapError> {
+        let file: InputMapFile<T> = toml::from_str(contents)?;
+        let mut mapping = HashMap::new();
+        for binding in file.bindings {
+            mapping.insert(binding.input.parse()?, binding.action);
+        }
+
+        Ok(InputMap { mapping })
+    }
+
+    fn to_toml(&self) -> Result<String, InputMapError> {
+        let file = InputMapFile {
+            bindings: self
+                .mapping
+                .iter()
+                .map(|(input, action)| Binding {
+                    input: input.to_string(),
+                    action: action.clone(),
+                })
+                .collect(),
+        };
+
+        Ok(toml::to_string(&file)?)
+    }
+}
+
+impl<T> Default for InputMap<T> {
+    fn default() -> Self {
+        InputMap::new()
+    }
+}
+
+/// Layout of a saved input map.
+///
+/// TOML tables can only have string keys, so bindings are
+/// stored as a list. TOML also can't store enum variants with
+/// data, so inputs are stored in their `Display` format.
+#[derive(Serialize, Deserialize)]
+struct InputMapFile<T> {
+    #[serde(default = "Vec::new", rename = "binding")]
+    bindings: Vec<Binding<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Binding<T> {
+    input: String,
+    action: T,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    enum Action {
+        Jump,
+        Shoot,
+    }
+
+    #[test]
+    fn test_input_map() {
+        let mut input_map = InputMap::new();
+        input_map.bind(VirtualKeyCode::Space, Action::Jump);
+        input_map.bind_mouse(MouseButton::Left, Action::Shoot);
+        input_map.bind_mouse(MouseButton::Right, Action::Jump);
+
+        // Keys and mouse buttons share a single lookup.
+        let space = UserInput::Key(VirtualKeyCode::Space);
+        let left = UserInput::Mouse(MouseButton::Left);
+        let right = UserInput::Mouse(MouseButton::Right);
+        assert_eq!(Some(&Action::Jump), input_map.lookup(&space));
+        assert_eq!(Some(&Action::Shoot), input_map.lookup(&left));
+        assert_eq!(Some(&Action::Jump), input_map.lookup(&right));
+        assert_eq!(
+            None,
+            input_map.lookup(&UserInput::Mouse(MouseButton::Middle))
+        );
+        assert_eq!(3, input_map.bound_actions().count());
+
+        // Rebinding replaces the action.
+        input_map.bind_mouse(MouseButton::Left, Action::Jump);
+        assert_eq!(Some(&Action::Jump), input_map.lookup(&left));
+
+        assert_eq!(Some(Action::Jump), input_map.unbind(&space));
+        assert_eq!(None, input_map.lookup(&space));
+    }
+
+    #[test]
+    fn test_user_input_string() {
+        let inputs = [
+            UserInput::Key(VirtualKeyCode::Key1),
+            UserInput::Key(VirtualKeyCode::LShift),
+            UserInput::Mouse(MouseButton::Right),
+            UserInput::Mouse(MouseButton::Other(4)),
+        ];
+        for input in inputs.iter() {
+            assert_eq!(*input, input.to_string().parse::<UserInput>().unwrap());
+        }
+
+        assert_eq!(
+            "Mouse:4",
+            UserInput::Mouse(MouseButton::Other(4)).to_string()
+        );
+        assert!("Key:NotAKey".parse::<UserInput>().is_err());
+        assert!("Joystick:A".parse::<UserInput>().is_err());
+        assert!("W".parse::<UserInput>().is_err());
+    }
+
+    #[test]
+    fn test_input_map_toml() {
+        let mut input_map = InputMap::new();
+        input_map.bind(VirtualKeyCode::W, Action::Jump);
+        input_map.bind_mouse(MouseButton::Left, Action::Shoot);
+
+        let contents = input_map.to_toml().unwrap();
+        let loaded = InputMap::<Action>::from_toml(&contents).unwrap();
+
+        assert_eq!(2, loaded.bound_actions().count());
+        for (input, action) in input_map.bound_actions() {
+            assert_eq!(Some(action), loaded.lookup(input));
+        }
+
+        assert!(contents.contains("input = \"Key:W\""));
+        assert!(contents.contains("input = \"Mouse:Left\""));
+
+        // Empty files have no bindings.
+        assert_eq!(
+            0,
+            InputMap::<Action>::from_toml("")
+                .unwrap()
+                .bound_actions()
+                .count()
+        );
+    }
+}
@@ -1,8 +1,6 @@
 extern crate chrono;
 extern crate daggy;
 #[macro_use]
-extern crate error_chain;
-#[macro_use]
 extern crate gfx;
 extern crate gfx_core;
 extern crate gfx_device_gl as gfx_device;
@@ -17,6 +15,7 @@ extern crate mopa;
 pub extern crate nalgebra;
 pub extern crate nalgebra_glm as glm;
 extern crate num_traits;
+extern crate rayon;
 extern crate regex;
 pub extern crate rlua;
 extern crate serde;
@@ -29,6 +28,7 @@ pub extern crate specs;
 extern crate specs_derive;
 #[macro_use]
 extern crate slotmap;
+extern crate thiserror;
 extern crate toml;
 extern crate walkdir;
 
@@ -44,6 +44,9 @@ mod float;
 mod gfx_types;
 mod graphics;
 pub mod gui;
+#[cfg(feature = "debug-hot-reload")]
+mod hot_reload;
+pub mod input;
 pub mod intern;
 pub mod metrics;
 pub mod modding;
@@ -54,6 +57,7 @@ pub mod render;
 pub mod res;
 mod scene;
 pub mod scripting;
+pub mod spatial;
 pub mod sprite;
 pub mod sync;
 pub mod sys;
@@ -24,18 +24,55 @@
 //!
 //! // Retrieve aggregated time series from metric hub.
 //! let mut timeseries: Vec<DataPoint> = vec![DataPoint::default(); 64];
-//! metrics.make_time_series(EXAMPLE_METRIC, MetricAggregate::Maximum, &mut timeseries, 0, 64);
+//! let aggregate = MetricAggregate::Maximum;
+//! metrics.make_time_series(EXAMPLE_METRIC, aggregate, None, &mut timeseries, 0, 64);
 //! ```
 //!
+//! Metrics can be given a label, so subsystems can record the same metric
+//! without mixing their measurements. Each label has its own time series.
+//!
+//! ```
+//! # use rengine::metrics::{MetricAggregate, MetricHub, MetricSettings};
+//! # let metrics = MetricHub::new(MetricSettings::default());
+//! const LOADED: u16 = 1;
+//! metrics.gauge(LOADED, MetricAggregate::Last).with_label("chunks").set(12.0);
+//! metrics.gauge(LOADED, MetricAggregate::Last).with_label("textures").set(3.0);
+//! ```
+//!
+//! # Export
+//!
+//! Stored time series can be written to a file with `MetricHub::export_to_file`,
+//! for graphing in external tools. Each row, or line, is one data point:
+//!
+//! | Field       | Description                                           |
+//! |-------------|-------------------------------------------------------|
+//! | `metric_id` | Id the metric was recorded with.                      |
+//! | `aggregate` | Aggregation method, like `Maximum` or `P95`.          |
+//! | `label`     | Label the metric was recorded with. Empty, or `null`. |
+//! | `timestamp` | Start of the aggregated interval, in Unix millis.     |
+//! | `value`     | Aggregated value.                                     |
+//!
+//! CSV files start with a header row naming the fields. JSON lines files
+//! contain one object per line, with the fields as keys.
+//!
+//! Only the last data points, as many as the `MetricSettings` keep, are
+//! stored. For longer sessions, `MetricSettings::with_drain` appends every
+//! data point to a file as it's aggregated.
+//!
 //! # Implementation
 //!
 //! TODO: Explain implementation
+use crate::errors;
 use crate::number::NonNan;
 use chrono::prelude::*;
 use crossbeam::{bounded, select, tick, unbounded, Receiver, Sender};
-use log::{trace, warn};
+use log::{error, trace, warn};
 use std::cmp::Ord;
 use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -49,6 +86,20 @@ pub mod builtin_metrics {
     pub const GRAPHICS_RENDER: u16 = 2000;
     /// Number of calls to encoder draw function.
     pub const GRAPHICS_DRAW_CALLS: u16 = 2010;
+    /// Time taken drawing GUI widgets.
+    pub const GRAPHICS_GUI_RENDER: u16 = 2020;
+    /// Time taken drawing GUI text.
+    pub const GRAPHICS_TEXT_RENDER: u16 = 2030;
+    /// Number of text sections queued for drawing.
+    pub const GRAPHICS_TEXT_SECTIONS: u16 = 2040;
+    /// Number of textures cached in `TextureAssets`.
+    pub const TEXTURE_COUNT: u16 = 3000;
+    /// Estimated graphics memory used by cached textures, in kilobytes.
+    pub const TEXTURE_MEMORY: u16 = 3010;
+    /// Number of dirty voxel chunks remeshed.
+    pub const CHUNK_REMESH_PROCESSED: u16 = 4000;
+    /// Number of dirty voxel chunks deferred to a later frame.
+    pub const CHUNK_REMESH_DEFERRED: u16 = 4010;
 }
 
 /// Central hub for recording and aggregating metrics.
@@ -97,6 +148,15 @@ impl MetricHub {
     ) -> thread::JoinHandle<()> {
         thread::spawn(move || {
             let ticker = tick(settings.aggregate_interval);
+            let mut drain = settings.drain.as_ref().and_then(|(path, format)| {
+                match MetricDrain::open(path, *format) {
+                    Ok(drain) => Some(drain),
+                    Err(err) => {
+                        warn!("Failed to open metric drain {}: {}", path.display(), err);
+                        None
+                    }
+                }
+            });
 
             'message_pump: loop {
                 select! {
@@ -105,27 +165,14 @@ impl MetricHub {
                             let mut ts_map = timeseries_map
                                 .lock()
                                 .expect("Metric worker mutex poisoned");
-                            let timeseries = ts_map
-                                .entry(msg.key)
-                                .or_insert_with(|| {
-                                    TimeSeries::new(settings.aggregate_interval, settings.data_point_count)
-                                });
-                            // Convert metrics into raw measurements.
-                            timeseries
-                                .measurements
-                                .entry(msg.slot(timeseries.interval)
-                                          .expect("divide by zero"))
-                                .or_insert_with(Vec::new)
-                                .push(msg.into());
+                            record_message(&mut ts_map, msg, &settings);
                         }
                     }
                     recv(ticker) -> _instant => {
                         let mut ts_map = timeseries_map
                             .lock()
                             .expect("Metric worker mutex poisoned");
-                        for (key, timeseries) in ts_map.iter_mut() {
-                            process_timeseries(key.aggregate, timeseries, settings.aggregate_interval);
-                        }
+                        process_all(&mut ts_map, settings.aggregate_interval, &mut drain);
                     }
                     recv(cancel_recv) -> _msg => {
                         break 'message_pump;
@@ -143,9 +190,8 @@ impl MetricHub {
     pub fn timer(&self, metric_id: u16, aggregate: MetricAggregate) -> TimerMetric {
         TimerMetric {
             sender: self.message_sender.clone(),
-            metric_id,
+            key: MetricKey::new(metric_id, aggregate),
             start_at: Instant::now(),
-            aggregate,
             stopped: false,
         }
     }
@@ -153,17 +199,75 @@ impl MetricHub {
     pub fn counter(&self, metric_id: u16, aggregate: MetricAggregate) -> CounterMetric {
         CounterMetric {
             sender: self.message_sender.clone(),
-            metric_id,
-            aggregate,
+            key: MetricKey::new(metric_id, aggregate),
             value: 0,
         }
     }
 
+    /// Records the current value of a quantity, like the
+    /// number of live entities.
+    ///
+    /// Intended to be used with `MetricAggregate::Last`, so each
+    /// data point holds the latest value set in its interval.
+    pub fn gauge(&self, metric_id: u16, aggregate: MetricAggregate) -> GaugeMetric {
+        GaugeMetric {
+            sender: self.message_sender.clone(),
+            key: MetricKey::new(metric_id, aggregate),
+            value: 0.0,
+        }
+    }
+
+    /// Writes every stored data point, of all time series.
+    ///
+    /// The stored data points are copied first, so the worker
+    /// thread isn't blocked while writing. See the module
+    /// documentation for the fields written.
+    pub fn export<W>(&self, writer: &mut W, format: ExportFormat) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let series: Vec<(MetricKey, Vec<DataPoint>)> = {
+            let timeseries_map = self
+                .timeseries_map
+                .lock()
+                .expect("Metric hub mutex has been poisoned");
+            timeseries_map
+                .iter()
+                .map(|(key, timeseries)| (*key, timeseries.data_points.iter().cloned().collect()))
+                .collect()
+        };
+
+        format.write_header(writer)?;
+        for (key, data_points) in series {
+            for data_point in data_points {
+                format.write_data_point(writer, &key, &data_point)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every stored data point to a file, replacing its contents.
+    pub fn export_to_file<P>(&self, path: P, format: ExportFormat) -> errors::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.export(&mut writer, format)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
     /// Builds a time series, containing aggregated datapoints.
+    ///
+    /// Only measurements recorded with the given label are included.
+    /// Metrics recorded without a label are retrieved with `None`.
     pub fn make_time_series(
         &self,
         metric_id: u16,
         aggregate: MetricAggregate,
+        label: Option<&'static str>,
         out: &mut [DataPoint],
         start: usize,
         length: usize,
@@ -175,6 +279,7 @@ impl MetricHub {
         let timeseries = timeseries_map
             .entry(MetricKey {
                 metric_id,
+                label,
                 aggregate,
             })
             .or_insert_with(|| {
@@ -205,52 +310,107 @@ impl Drop for MetricHub {
     }
 }
 
+/// Converts a message into a raw measurement of its time series.
+fn record_message(
+    ts_map: &mut BTreeMap<MetricKey, TimeSeries>,
+    msg: MetricMessage,
+    settings: &MetricSettings,
+) {
+    let timeseries = ts_map
+        .entry(msg.key)
+        .or_insert_with(|| TimeSeries::new(settings.aggregate_interval, settings.data_point_count));
+
+    match msg.slot(timeseries.interval) {
+        Some(slot) => timeseries
+            .measurements
+            .entry(slot)
+            .or_insert_with(Vec::new)
+            .push(msg.into()),
+        None => warn!("Metric {:?} dropped, aggregate interval is zero", msg.key),
+    }
+}
+
+/// Processes all time series, appending the new data points to the drain.
+fn process_all(
+    ts_map: &mut BTreeMap<MetricKey, TimeSeries>,
+    interval: Duration,
+    drain: &mut Option<MetricDrain>,
+) {
+    for (key, timeseries) in ts_map.iter_mut() {
+        // A broken metric must not stop the worker, which
+        // would end all other metrics as well.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            process_timeseries(key.aggregate, timeseries, interval)
+        }));
+
+        let data_points = match result {
+            Ok(data_points) => data_points,
+            Err(_) => {
+                error!("Metric {:?} failed to aggregate", key);
+                timeseries.measurements.clear();
+                continue;
+            }
+        };
+
+        let failed = match drain {
+            Some(drain) => drain.append(key, &data_points).is_err(),
+            None => false,
+        };
+        if failed {
+            warn!("Failed to write metric drain, no longer draining");
+            *drain = None;
+        }
+    }
+
+    if let Some(drain) = drain {
+        if let Err(err) = drain.writer.flush() {
+            warn!("Failed to flush metric drain: {}", err);
+        }
+    }
+}
+
 /// Process the raw measurements of the given time series into aggregated data points.
-fn process_timeseries(aggregate: MetricAggregate, timeseries: &mut TimeSeries, interval: Duration) {
+///
+/// Returns the new data points.
+fn process_timeseries(
+    aggregate: MetricAggregate,
+    timeseries: &mut TimeSeries,
+    interval: Duration,
+) -> Vec<DataPoint> {
     // Expensive call; keep outside loop.
     let now = Utc::now();
 
-    while let Some(slot) = timeseries.measurements.iter().map(|(key, _)| *key).next() {
-        // Don't aggregate the current slot.
-        if datetime_to_slot(&now, &interval) == Some(slot) {
-            continue;
-        }
+    // Don't aggregate the current slot, which is still being measured.
+    let current_slot = match datetime_to_slot(&now, &interval) {
+        Some(slot) => slot,
+        None => return vec![],
+    };
+    let slots: Vec<i64> = timeseries
+        .measurements
+        .range(..current_slot)
+        .map(|(slot, _)| *slot)
+        .collect();
+    let mut new_data_points = vec![];
+
+    for slot in slots {
         // Important: remove element to cleanup memory.
         if let Some(measurements) = timeseries.measurements.remove(&slot) {
             let naive = NaiveDateTime::from_timestamp(slot, 0);
             let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
 
-            let value: f64 = match aggregate {
-                MetricAggregate::Maximum => measurements
-                    .into_iter()
-                    .map(|raw| NonNan::new(raw.value).expect("Metric value was NaN"))
-                    .max()
-                    .unwrap()
-                    .into_inner(),
-                MetricAggregate::Sum => measurements
-                    .into_iter()
-                    .map(|raw| {
-                        NonNan::new(raw.value)
-                            .expect("Metric value was NaN")
-                            .into_inner()
-                    })
-                    .sum(),
-                // TODO:
-                //   - Minimum
-                //   - Average
-                //   - Count
-                //   - P95
-                //   - P99
-                _ => unimplemented!(),
-            };
-
-            timeseries.data_points.push_back(DataPoint {
-                datetime: datetime.into(),
-                value,
-            });
+            if let Some(value) = aggregate_measurements(aggregate, measurements) {
+                new_data_points.push(DataPoint {
+                    datetime: datetime.into(),
+                    value,
+                });
+            }
         }
     }
 
+    timeseries
+        .data_points
+        .extend(new_data_points.iter().cloned());
+
     // Important: Limit the size of the time series for memory usage.
     if timeseries.data_points.len() > timeseries.max_data_points {
         let overflow = timeseries.data_points.len() - timeseries.max_data_points;
@@ -258,6 +418,55 @@ fn process_timeseries(aggregate: MetricAggregate, timeseries: &mut TimeSeries, i
             timeseries.data_points.pop_front();
         }
     }
+
+    new_data_points
+}
+
+/// Aggregates the raw measurements of a slot into a single value.
+///
+/// Measurements that are NaN are skipped. Returns `None` when
+/// there are no measurements left.
+fn aggregate_measurements(
+    aggregate: MetricAggregate,
+    measurements: Vec<RawMeasurement>,
+) -> Option<f64> {
+    let mut values: Vec<NonNan<f64>> = measurements
+        .into_iter()
+        .filter_map(|raw| NonNan::new(raw.value))
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    let count = values.len() as f64;
+    let sum: f64 = values.iter().map(|value| value.into_inner()).sum();
+
+    let value = match aggregate {
+        MetricAggregate::Minimum => values.iter().min()?.into_inner(),
+        MetricAggregate::Maximum => values.iter().max()?.into_inner(),
+        MetricAggregate::Average => sum / count,
+        MetricAggregate::Sum => sum,
+        MetricAggregate::Count => count,
+        MetricAggregate::P95 => percentile(&mut values, 0.95),
+        MetricAggregate::P99 => percentile(&mut values, 0.99),
+        MetricAggregate::Last => values.last()?.into_inner(),
+    };
+
+    Some(value)
+}
+
+/// Value below which the given fraction of the values fall,
+/// interpolated between the two nearest values.
+///
+/// The values must not be empty.
+fn percentile(values: &mut [NonNan<f64>], fraction: f64) -> f64 {
+    values.sort();
+
+    let rank = fraction * (values.len() - 1) as f64;
+    let lower = values[rank.floor() as usize].into_inner();
+    let upper = values[rank.ceil() as usize].into_inner();
+
+    lower + (upper - lower) * rank.fract()
 }
 
 #[derive(Debug, Clone)]
@@ -269,6 +478,23 @@ pub struct MetricSettings {
     aggregate_interval: Duration,
     /// Duration to sleep the worker thread when no messages are left to consume.
     sleep_duration: Duration,
+    /// File that every aggregated data point is appended to.
+    drain: Option<(PathBuf, ExportFormat)>,
+}
+
+impl MetricSettings {
+    /// Appends every data point to the file as it's aggregated, so
+    /// history isn't limited to the data points kept in memory.
+    ///
+    /// The file is created when it doesn't exist. Writing stops,
+    /// with a warning, when the file can't be written.
+    pub fn with_drain<P>(mut self, path: P, format: ExportFormat) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.drain = Some((path.into(), format));
+        self
+    }
 }
 
 impl Default for MetricSettings {
@@ -277,10 +503,118 @@ impl Default for MetricSettings {
             data_point_count: 64,
             aggregate_interval: Duration::from_secs(1),
             sleep_duration: Duration::from_millis(32),
+            drain: None,
         }
     }
 }
 
+/// File format of exported metrics.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// Comma separated values, with a header row.
+    Csv,
+    /// A JSON object per line.
+    JsonLines,
+}
+
+impl ExportFormat {
+    fn write_header<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        match self {
+            ExportFormat::Csv => writeln!(writer, "metric_id,aggregate,label,timestamp,value"),
+            ExportFormat::JsonLines => Ok(()),
+        }
+    }
+
+    fn write_data_point<W: Write>(
+        self,
+        writer: &mut W,
+        key: &MetricKey,
+        data_point: &DataPoint,
+    ) -> io::Result<()> {
+        let timestamp = data_point.datetime.timestamp_millis();
+
+        match self {
+            ExportFormat::Csv => {
+                let label = key
+                    .label
+                    .map(|label| format!("\"{}\"", label.replace('"', "\"\"")))
+                    .unwrap_or_default();
+                writeln!(
+                    writer,
+                    "{},{:?},{},{},{}",
+                    key.metric_id, key.aggregate, label, timestamp, data_point.value
+                )
+            }
+            ExportFormat::JsonLines => {
+                let label = key
+                    .label
+                    .map(json_string)
+                    .unwrap_or_else(|| "null".to_owned());
+                // JSON has no infinity.
+                let value = if data_point.value.is_finite() {
+                    data_point.value.to_string()
+                } else {
+                    "null".to_owned()
+                };
+                writeln!(
+                    writer,
+                    concat!(
+                        "{{\"metric_id\":{},\"aggregate\":\"{:?}\",\"label\":{},",
+                        "\"timestamp\":{},\"value\":{}}}"
+                    ),
+                    key.metric_id, key.aggregate, label, timestamp, value
+                )
+            }
+        }
+    }
+}
+
+/// Quotes the string as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// File that the worker thread appends aggregated data points to.
+struct MetricDrain {
+    writer: BufWriter<File>,
+    format: ExportFormat,
+}
+
+impl MetricDrain {
+    fn open(path: &Path, format: ExportFormat) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+
+        // Appending to an earlier session's file keeps its header.
+        if is_empty {
+            format.write_header(&mut writer)?;
+        }
+
+        Ok(MetricDrain { writer, format })
+    }
+
+    fn append(&mut self, key: &MetricKey, data_points: &[DataPoint]) -> io::Result<()> {
+        for data_point in data_points {
+            self.format
+                .write_data_point(&mut self.writer, key, data_point)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum MetricAggregate {
     Minimum,
@@ -290,6 +624,8 @@ pub enum MetricAggregate {
     Count,
     P95,
     P99,
+    /// Latest measurement of the interval.
+    Last,
 }
 
 /// Metric for measuring the time a block of code takes to execute.
@@ -313,13 +649,18 @@ pub enum MetricAggregate {
 /// ```
 pub struct TimerMetric {
     sender: Sender<MetricMessage>,
-    metric_id: u16,
+    key: MetricKey,
     start_at: Instant,
-    aggregate: MetricAggregate,
     stopped: bool,
 }
 
 impl TimerMetric {
+    /// Records the measurement in the time series of the label.
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.key.label = Some(label);
+        self
+    }
+
     #[inline]
     pub fn stopped(&self) -> bool {
         self.stopped
@@ -329,7 +670,7 @@ impl TimerMetric {
         if !self.stopped {
             // println!("Stop timer");
             let msg = MetricMessage {
-                key: MetricKey::new(self.metric_id, self.aggregate),
+                key: self.key,
                 datetime: Local::now(),
                 kind: MetricMessageKind::TimeMeasurement {
                     duration: self.start_at.elapsed(),
@@ -372,12 +713,17 @@ impl Drop for TimerMetric {
 /// ```
 pub struct CounterMetric {
     sender: Sender<MetricMessage>,
-    metric_id: u16,
-    aggregate: MetricAggregate,
+    key: MetricKey,
     value: u32,
 }
 
 impl CounterMetric {
+    /// Records the measurement in the time series of the label.
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.key.label = Some(label);
+        self
+    }
+
     #[inline]
     pub fn incr(&mut self, value: u32) {
         self.value += value;
@@ -402,7 +748,7 @@ impl CounterMetric {
 impl Drop for CounterMetric {
     fn drop(&mut self) {
         let msg = MetricMessage {
-            key: MetricKey::new(self.metric_id, self.aggregate),
+            key: self.key,
             datetime: Local::now(),
             kind: MetricMessageKind::UIntMeasurement { value: self.value },
         };
@@ -413,10 +759,68 @@ impl Drop for CounterMetric {
     }
 }
 
+/// Metric for recording the current value of a quantity.
+///
+/// Every value set is sent to the drain immediately.
+///
+/// # Examples
+///
+/// ```
+/// use rengine::metrics::{MetricHub, MetricSettings, MetricAggregate};
+///
+/// let metric_hub = MetricHub::new(MetricSettings::default());
+///
+/// const EXAMPLE_METRIC: u16 = 1;
+/// let mut gauge = metric_hub.gauge(EXAMPLE_METRIC, MetricAggregate::Last);
+/// gauge.set(250.0);
+/// gauge.set(300.0);
+///
+/// assert_eq!(300.0, gauge.value());
+/// ```
+pub struct GaugeMetric {
+    sender: Sender<MetricMessage>,
+    key: MetricKey,
+    value: f64,
+}
+
+impl GaugeMetric {
+    /// Records the measurement in the time series of the label.
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.key.label = Some(label);
+        self
+    }
+
+    pub fn set(&mut self, value: f64) {
+        self.value = value;
+
+        let msg = MetricMessage {
+            key: self.key,
+            datetime: Local::now(),
+            kind: MetricMessageKind::FloatMeasurement { value },
+        };
+
+        if let Err(err) = self.sender.send(msg) {
+            warn!("Gauge failed to record metric: {}", err);
+        }
+    }
+
+    /// Last value set.
+    #[inline]
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
 /// Identifier for a metric.
+///
+/// Labels are static strings rather than `InternedStr`, because
+/// keys are sent to the worker thread, and interned strings are
+/// only valid on the thread that interned them.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 struct MetricKey {
     metric_id: u16,
+    /// Dimension separating measurements of the same metric.
+    label: Option<&'static str>,
     aggregate: MetricAggregate,
 }
 
@@ -424,6 +828,7 @@ impl MetricKey {
     fn new(metric_id: u16, aggregate: MetricAggregate) -> Self {
         MetricKey {
             metric_id,
+            label: None,
             aggregate,
         }
     }
@@ -450,6 +855,7 @@ impl MetricMessage {
 enum MetricMessageKind {
     TimeMeasurement { duration: Duration },
     UIntMeasurement { value: u32 },
+    FloatMeasurement { value: f64 },
 }
 
 fn datetime_to_slot<Tz: TimeZone>(datetime: &DateTime<Tz>, interval: &Duration) -> Option<i64> {
@@ -502,6 +908,10 @@ impl From<MetricMessage> for RawMeasurement {
                 value: value.into(),
                 timestamp: m.datetime.timestamp(),
             },
+            MetricMessageKind::FloatMeasurement { value } => RawMeasurement {
+                value,
+                timestamp: m.datetime.timestamp(),
+            },
         }
     }
 }
@@ -521,3 +931,184 @@ impl Default for DataPoint {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Aggregates the values, measured in a slot that has passed.
+    fn aggregate_values(aggregate: MetricAggregate, values: &[u32]) -> f64 {
+        let settings = MetricSettings::default();
+        let mut ts_map = BTreeMap::new();
+        let key = MetricKey::new(1, aggregate);
+        let datetime = Local::now() - chrono::Duration::seconds(10);
+
+        for value in values {
+            let msg = MetricMessage {
+                key,
+                datetime,
+                kind: MetricMessageKind::UIntMeasurement { value: *value },
+            };
+            record_message(&mut ts_map, msg, &settings);
+        }
+
+        let timeseries = ts_map.get_mut(&key).unwrap();
+        process_timeseries(aggregate, timeseries, settings.aggregate_interval);
+        assert!(timeseries.measurements.is_empty());
+        assert_eq!(1, timeseries.data_points.len());
+
+        timeseries.data_points[0].value
+    }
+
+    #[test]
+    fn test_aggregates() {
+        // Shuffled, so percentiles have to sort.
+        let values: Vec<u32> = (1..=100).map(|i| (i * 37) % 100 + 1).collect();
+        let expected = [
+            (MetricAggregate::Minimum, 1.0),
+            (MetricAggregate::Maximum, 100.0),
+            (MetricAggregate::Average, 50.5),
+            (MetricAggregate::Sum, 5050.0),
+            (MetricAggregate::Count, 100.0),
+            (MetricAggregate::P95, 95.05),
+            (MetricAggregate::P99, 99.01),
+            (MetricAggregate::Last, 1.0),
+        ];
+
+        for (aggregate, value) in expected.iter() {
+            let actual = aggregate_values(*aggregate, &values);
+            assert!(
+                (value - actual).abs() < 1.0e-9,
+                "{:?} was {}",
+                aggregate,
+                actual
+            );
+        }
+
+        // Single measurement
+        assert_eq!(7.0, aggregate_values(MetricAggregate::P99, &[7]));
+    }
+
+    #[test]
+    fn test_pending_slot() {
+        let settings = MetricSettings::default();
+        let mut ts_map = BTreeMap::new();
+        let key = MetricKey::new(1, MetricAggregate::Sum);
+
+        // Slots that haven't passed are kept for later.
+        let msg = MetricMessage {
+            key,
+            datetime: Local::now() + chrono::Duration::seconds(10),
+            kind: MetricMessageKind::UIntMeasurement { value: 1 },
+        };
+        record_message(&mut ts_map, msg, &settings);
+
+        let timeseries = ts_map.get_mut(&key).unwrap();
+        process_timeseries(key.aggregate, timeseries, settings.aggregate_interval);
+        assert_eq!(1, timeseries.measurements.len());
+        assert!(timeseries.data_points.is_empty());
+    }
+
+    #[test]
+    fn test_labels() {
+        let settings = MetricSettings::default();
+        let mut ts_map = BTreeMap::new();
+        let datetime = Local::now() - chrono::Duration::seconds(10);
+
+        let mut record = |label, value| {
+            let mut key = MetricKey::new(1, MetricAggregate::Last);
+            key.label = label;
+            let kind = MetricMessageKind::FloatMeasurement { value };
+            let msg = MetricMessage {
+                key,
+                datetime,
+                kind,
+            };
+            record_message(&mut ts_map, msg, &settings);
+        };
+        record(Some("chunks"), 10.0);
+        record(Some("textures"), 3.0);
+        record(Some("chunks"), 12.0);
+        record(None, 1.0);
+
+        assert_eq!(3, ts_map.len());
+        for (key, timeseries) in ts_map.iter_mut() {
+            process_timeseries(key.aggregate, timeseries, settings.aggregate_interval);
+        }
+
+        let latest = |label| {
+            let mut key = MetricKey::new(1, MetricAggregate::Last);
+            key.label = label;
+            ts_map[&key].data_points[0].value
+        };
+        assert_eq!(12.0, latest(Some("chunks")));
+        assert_eq!(3.0, latest(Some("textures")));
+        assert_eq!(1.0, latest(None));
+    }
+
+    /// Adds a time series with the given values to the hub.
+    fn insert_series(metrics: &MetricHub, key: MetricKey, values: &[f64]) {
+        let mut timeseries = TimeSeries::new(Duration::from_secs(1), values.len());
+        for (index, value) in values.iter().enumerate() {
+            let naive = NaiveDateTime::from_timestamp(1_000 + index as i64, 0);
+            let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+            timeseries.data_points.push_back(DataPoint {
+                datetime: datetime.into(),
+                value: *value,
+            });
+        }
+
+        metrics
+            .timeseries_map
+            .lock()
+            .unwrap()
+            .insert(key, timeseries);
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let metrics = MetricHub::default();
+        let mut labelled = MetricKey::new(2, MetricAggregate::Last);
+        labelled.label = Some("chunks");
+        let p95 = MetricKey::new(1, MetricAggregate::P95);
+        insert_series(&metrics, p95, &[0.1, 2.5]);
+        insert_series(&metrics, labelled, &[12.0]);
+
+        let mut out = vec![];
+        metrics.export(&mut out, ExportFormat::Csv).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            Some("metric_id,aggregate,label,timestamp,value"),
+            lines.next()
+        );
+
+        let rows: Vec<(u16, &str, &str, i64, f64)> = lines
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                (
+                    fields[0].parse().unwrap(),
+                    fields[1],
+                    fields[2],
+                    fields[3].parse().unwrap(),
+                    fields[4].parse().unwrap(),
+                )
+            })
+            .collect();
+        let expected = vec![
+            (1, "P95", "", 1_000_000, 0.1),
+            (1, "P95", "", 1_001_000, 2.5),
+            (2, "Last", "\"chunks\"", 1_000_000, 12.0),
+        ];
+        assert_eq!(expected, rows);
+
+        let mut out = vec![];
+        metrics.export(&mut out, ExportFormat::JsonLines).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        let expected = concat!(
+            r#"{"metric_id":2,"aggregate":"Last","label":"chunks","#,
+            r#""timestamp":1000000,"value":12}"#
+        );
+        assert_eq!(Some(expected), json.lines().nth(2));
+    }
+}
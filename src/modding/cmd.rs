@@ -20,3 +20,15 @@ pub enum SceneHook {
     /// Scene has stopped.
     Stop,
 }
+
+/// Command sent by a script runner, requesting the
+/// engine to perform an action on the main thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCmd {
+    /// Spawn an entity from the named prototype
+    /// at the given world position.
+    SpawnEntity { proto: String, pos: [f32; 3] },
+
+    /// Play the sound found at the given path.
+    PlaySound(String),
+}
@@ -1,7 +1,7 @@
 use crate::errors;
 use crate::intern::{intern, InternedStr};
 use crate::sync::ChannelPair;
-use crossbeam::{channel, channel::SendError};
+use crossbeam::{channel, channel::TrySendError};
 use log::{error, trace, warn};
 use rlua::Lua;
 use serde::Deserialize;
@@ -26,6 +26,10 @@ pub const DEFAULT_MOD_PATH: &str = "./mods";
 pub const DEFAULT_MOD_DEF: &str = "mod.toml";
 pub const DEFAULT_ENTRY_FILE: &str = "init.lua";
 
+/// Number of command buffers that can wait in a script
+/// runner's channel before dispatching is skipped.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1;
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// World level resource that contains a mapping of
@@ -44,6 +48,10 @@ pub struct Mods {
 
     /// Path to the mod folder.
     mod_path: PathBuf,
+
+    /// Capacity of the command channel created
+    /// for each loaded mod.
+    channel_capacity: usize,
 }
 
 #[allow(dead_code)]
@@ -107,7 +115,7 @@ pub struct ModMeta {
     // TODO: Currently unimplemented.
     /// Stream of outgoing commands that have been sent by script runners
     /// during script execution.
-    script_cmds: (channel::Sender<u32>, channel::Receiver<u32>),
+    script_cmds: (channel::Sender<ScriptCmd>, channel::Receiver<ScriptCmd>),
 }
 
 /// Meta file found at the top level of a mod's folder.
@@ -122,6 +130,26 @@ struct ModMetaModel {
 
 type ScriptRunnerHandle = thread::JoinHandle<errors::Result<()>>;
 
+/// Outcome of dispatching a command buffer to the script runners.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DispatchStatus {
+    /// Every script runner received the command buffer.
+    Delivered,
+
+    /// The command buffer could not be sent to the contained
+    /// mods, because their channels were full or disconnected.
+    Skipped(Vec<InternedStr>),
+}
+
+impl DispatchStatus {
+    pub fn is_delivered(&self) -> bool {
+        match self {
+            DispatchStatus::Delivered => true,
+            DispatchStatus::Skipped(_) => false,
+        }
+    }
+}
+
 impl Mods {
     pub fn new(lib_name: &'static str, mod_path: &Path) -> Self {
         Mods {
@@ -129,9 +157,22 @@ impl Mods {
             order: Vec::new(),
             lib_name: intern(lib_name),
             mod_path: mod_path.to_path_buf(),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
         }
     }
 
+    /// Sets the number of command buffers that can be queued for
+    /// a script runner before a dispatch to it is skipped.
+    ///
+    /// Only affects mods loaded after the call.
+    pub fn set_channel_capacity(&mut self, capacity: usize) {
+        self.channel_capacity = capacity;
+    }
+
+    pub fn channel_capacity(&self) -> usize {
+        self.channel_capacity
+    }
+
     /// Walks the target mod path and loads the metadata files.
     ///
     /// Fails if the mod folder does not exist, if a mod meta data
@@ -157,7 +198,9 @@ impl Mods {
                 // TODO: Validate string values
                 if !validate::mod_name(mod_name.as_ref()) {
                     error!("Invalid mod name '{}'", mod_name.as_ref());
-                    return Err(errors::ErrorKind::ModLoad.into());
+                    return Err(
+                        errors::ModLoadError::InvalidName(mod_name.as_ref().to_owned()).into(),
+                    );
                 }
 
                 if !file_path.is_file() {
@@ -178,7 +221,7 @@ impl Mods {
                 let id = intern(&format!("{}:{}", mod_name.as_ref(), meta.version));
 
                 if let Entry::Vacant(e) = self.mods.entry(id) {
-                    let (hub_chan, mod_chan) = ChannelPair::create();
+                    let (hub_chan, mod_chan) = ChannelPair::with_capacity(self.channel_capacity);
                     let error_chan = channel::unbounded();
                     let script_cmds_chan = channel::unbounded();
 
@@ -245,7 +288,10 @@ impl Mods {
             );
         }
 
-        let (_in_cmds, out_cmds) = self.dispatch(vec![cmd::ModCmd::Init])?;
+        let (_in_cmds, status, out_cmds) = self.dispatch(vec![cmd::ModCmd::Init])?;
+        if let DispatchStatus::Skipped(ref ids) = status {
+            warn!("Mod initialization skipped for {:?}", ids);
+        }
         if out_cmds.is_some() {
             warn!("Dispatching commands during initialization is not supported.");
             if let Some(mut cmds) = out_cmds {
@@ -293,7 +339,7 @@ impl Mods {
     /// panic.
     pub fn shutdown(&mut self) -> errors::Result<()> {
         let result = self.dispatch(vec![cmd::ModCmd::Shutdown]);
-        if let Ok((_, Some(_))) = result {
+        if let Ok((_, _, Some(_))) = result {
             warn!("Dispatching commands during shutdown is not supported.");
         }
 
@@ -309,7 +355,7 @@ impl Mods {
                     }
                     Err(_) => errors
                         .get_or_insert_with(|| vec![])
-                        .push(errors::ErrorKind::ModScriptThread.into()),
+                        .push(errors::ScriptError::Thread.into()),
                 }
             }
         }
@@ -321,37 +367,56 @@ impl Mods {
     }
 
     /// Dispatches a scene lifetime hook to all mods.
-    pub fn scene_hook(&mut self, hook: SceneHook) -> errors::Result<Option<Vec<u32>>> {
+    ///
+    /// The returned status reports whether any mod was
+    /// skipped due to back-pressure on its channel.
+    pub fn scene_hook(
+        &mut self,
+        hook: SceneHook,
+    ) -> errors::Result<(DispatchStatus, Option<Vec<ScriptCmd>>)> {
         self.dispatch(vec![ModCmd::Scene(hook)])
             // Discard in command buffer.
-            .map(|(_in_cmds, out_cmds)| out_cmds)
+            .map(|(_in_cmds, status, out_cmds)| (status, out_cmds))
     }
 
     /// Executes all mods, passing the given command buffer
     /// to all script runners. Blocks on each script runner
     /// waiting for the buffer to be returned.
+    ///
+    /// Mods whose channel can not accept the buffer are
+    /// skipped, and reported in the returned status.
     fn dispatch(
         &mut self,
         mut in_cmds: Vec<cmd::ModCmd>,
-    ) -> errors::Result<(Vec<cmd::ModCmd>, Option<Vec<u32>>)> {
+    ) -> errors::Result<(Vec<cmd::ModCmd>, DispatchStatus, Option<Vec<ScriptCmd>>)> {
         // Lazy instantiated vectors
         let mut errors: Option<Vec<errors::Error>> = None;
-        let mut out_cmds: Option<Vec<u32>> = None;
+        let mut out_cmds: Option<Vec<ScriptCmd>> = None;
+        let mut skipped: Option<Vec<InternedStr>> = None;
 
-        for (_id, meta) in self.mods.iter_mut() {
+        for (id, meta) in self.mods.iter_mut() {
             // Ownership of the command buffer is passed
             // to script runner thread and returned on
             // each iteration.
-            in_cmds = match meta.hub.send(in_cmds) {
+            in_cmds = match meta.hub.try_send(in_cmds) {
                 Ok(_) => match meta.hub.receive() {
                     Ok(v) => v,
                     // If the receiver is closed, then
                     // we've lost the command buffer.
-                    Err(_) => return Err(errors::ErrorKind::ModDispatch.into()),
+                    Err(_) => return Err(errors::ScriptError::Dispatch.into()),
                 },
-                // If the channel is full, the command
-                // buffer is returned.
-                Err(SendError(v)) => v,
+                // If the channel is full or closed, the
+                // command buffer is returned undelivered.
+                Err(TrySendError::Full(v)) => {
+                    warn!("Mod {} channel is full, dispatch skipped", id);
+                    skipped.get_or_insert_with(|| vec![]).push(*id);
+                    v
+                }
+                Err(TrySendError::Disconnected(v)) => {
+                    warn!("Mod {} channel is disconnected, dispatch skipped", id);
+                    skipped.get_or_insert_with(|| vec![]).push(*id);
+                    v
+                }
             };
 
             // Gather possible errors
@@ -367,10 +432,15 @@ impl Mods {
 
         in_cmds.clear();
 
+        let status = match skipped {
+            Some(ids) => DispatchStatus::Skipped(ids),
+            None => DispatchStatus::Delivered,
+        };
+
         if let Some(e) = errors {
-            Err(errors::ErrorKind::ModComposite(e).into())
+            Err(errors::ScriptError::Composite(e).into())
         } else {
-            Ok((in_cmds, out_cmds))
+            Ok((in_cmds, status, out_cmds))
         }
     }
 }
@@ -382,6 +452,7 @@ impl Default for Mods {
             order: Vec::new(),
             lib_name: intern(DEFAULT_LIB_NAME),
             mod_path: PathBuf::from(DEFAULT_MOD_PATH),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
         }
     }
 }
@@ -405,16 +476,38 @@ impl Drop for ModMeta {
     }
 }
 
+/// Sending end of a script runner's outgoing commands,
+/// gathered by the engine after each dispatch.
 #[derive(Clone)]
-pub struct ScriptChannel(pub(crate) channel::Sender<u32>);
+pub struct ScriptChannel(pub(crate) channel::Sender<ScriptCmd>);
 
 impl ScriptChannel {
-    pub fn send(&mut self, message: u32) {
+    pub fn send(&mut self, message: ScriptCmd) {
         // TODO: Properly handle error.
         self.0.send(message).expect("Script channel send failure");
     }
 }
 
+/// Interned strings are exposed to Lua as handles, which
+/// compare equal when they refer to the same string.
+///
+/// The interner is thread local, and each mod's Lua state runs
+/// on its own script runner thread. A handle is only meaningful
+/// within the Lua state that created it, and must not be passed
+/// to another mod or back to the engine's main thread.
+impl rlua::UserData for InternedStr {
+    fn add_methods<'lua, M: rlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        use rlua::MetaMethod;
+
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: InternedStr| {
+            Ok(*this == other)
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(this.as_ref().to_owned())
+        });
+    }
+}
+
 fn create_interface(lib_name: &str) -> errors::Result<Lua> {
     use rlua::Table;
     let lua = Lua::new();
@@ -427,9 +520,16 @@ fn create_interface(lib_name: &str) -> errors::Result<Lua> {
                 Ok(())
             })?;
 
+        // Handles for cheap comparison of string keys in hot loops.
+        let intern_str = lua_ctx.create_function(|_, s: rlua::String| Ok(intern(s.to_str()?)))?;
+        let interned_eq =
+            lua_ctx.create_function(|_, (a, b): (InternedStr, InternedStr)| Ok(a == b))?;
+
         let lib = lua_ctx.create_table()?;
         lib.set("version", VERSION)?;
         lib.set("register_entity", register_entity)?;
+        lib.set("intern", intern_str)?;
+        lib.set("eq", interned_eq)?;
 
         let globals = lua_ctx.globals();
         globals.set(lib_name, lib)?;
@@ -439,7 +539,7 @@ fn create_interface(lib_name: &str) -> errors::Result<Lua> {
 
     match result {
         Ok(_) => Ok(lua),
-        Err(err) => Err(errors::ErrorKind::Lua(err).into()),
+        Err(err) => Err(errors::ScriptError::Lua(err).into()),
     }
 }
 
@@ -451,3 +551,101 @@ fn is_hidden(entry: &DirEntry) -> bool {
         .map(|s| s.starts_with('.'))
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_meta(id: InternedStr, capacity: usize) -> ModMeta {
+        let (hub, chan) = ChannelPair::with_capacity(capacity);
+
+        ModMeta {
+            id,
+            path: PathBuf::from(DEFAULT_MOD_PATH),
+            name: id,
+            version: intern("0.0.0"),
+            author: intern("test"),
+            email: None,
+            website: None,
+            entry: intern(DEFAULT_ENTRY_FILE),
+            depends_on: Vec::new(),
+            enabled: false,
+            hub,
+            chan,
+            join: None,
+            errors: channel::unbounded(),
+            script_cmds: channel::unbounded(),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_full_channel() {
+        let mut mods = Mods::default();
+        let id = intern("test:0.0.0");
+        let mut meta = create_meta(id, 1);
+
+        // Buffer that the script runner never picked up.
+        meta.hub.try_send(vec![ModCmd::Game]).unwrap();
+        mods.mods.insert(id, meta);
+
+        let (in_cmds, status, out_cmds) = mods.dispatch(vec![ModCmd::Init]).unwrap();
+        assert_eq!(status, DispatchStatus::Skipped(vec![id]));
+        assert!(!status.is_delivered());
+        assert!(in_cmds.is_empty());
+        assert!(out_cmds.is_none());
+
+        // Only the original buffer is waiting in the channel.
+        let chan = &mut mods.mods.get_mut(&id).unwrap().chan;
+        match chan.try_receive().unwrap().as_slice() {
+            [ModCmd::Game] => {}
+            _ => panic!("unexpected command buffer"),
+        }
+        assert!(chan.try_receive().is_err());
+    }
+
+    #[test]
+    fn test_dispatch_script_cmds() {
+        let mut mods = Mods::default();
+        let id = intern("test:0.0.0");
+        let meta = create_meta(id, 1);
+
+        // Stand-in for a script runner.
+        let mut chan = meta.chan.clone();
+        let mut script_channel = ScriptChannel(meta.script_cmds.0.clone());
+        let handle = thread::spawn(move || {
+            let cmds = chan.receive().unwrap();
+            script_channel.send(ScriptCmd::PlaySound("boom.ogg".to_string()));
+            chan.send(cmds).unwrap();
+        });
+        mods.mods.insert(id, meta);
+
+        let (_in_cmds, status, out_cmds) = mods.dispatch(vec![ModCmd::Game]).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(status, DispatchStatus::Delivered);
+        assert_eq!(
+            out_cmds,
+            Some(vec![ScriptCmd::PlaySound("boom.ogg".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_script_intern() {
+        let lua = create_interface("core").unwrap();
+
+        let result: rlua::Result<bool> = lua.context(|lua_ctx| {
+            lua_ctx
+                .load(
+                    r#"
+                    local a = core.intern("foo")
+                    local b = core.intern("foo")
+                    local c = core.intern("bar")
+                    return core.eq(a, b) and a == b and not core.eq(a, c)
+                    "#,
+                )
+                .eval()
+        });
+
+        assert!(result.unwrap());
+    }
+}
@@ -1,3 +1,5 @@
+use image::{DynamicImage, GrayImage};
+
 const PERMUTATION_LIST: [u8; 255] = [
     12, 208, 115, 24, 3, 23, 151, 244, 253, 75, 118, 17, 249, 18, 191, 179, 195, 148, 235, 92, 120,
     40, 103, 226, 15, 101, 209, 194, 218, 204, 109, 182, 143, 42, 147, 79, 163, 52, 90, 213, 185,
@@ -49,3 +51,197 @@ pub fn sample_value_noise(position: f32, octaves: u8) -> f32 {
 
     sum
 }
+
+/// Source of two-dimensional noise.
+pub trait NoiseGen2D {
+    /// Samples the noise at the given position, in the range 0 to 1.
+    fn sample_2d(&self, x: f32, y: f32) -> f32;
+
+    /// Greyscale image of the noise sampled at each pixel, with
+    /// 0 mapped to black and 1 to white.
+    ///
+    /// Useful for previewing noise by saving it to disk.
+    fn generate_image(&self, size: [usize; 2]) -> DynamicImage {
+        let [width, height] = size;
+        let pixels = (0..width * height)
+            .map(|index| self.sample_2d((index % width) as f32, (index / width) as f32))
+            .map(|value| (normalize(value) * 255.0).round() as u8)
+            .collect();
+        let image = GrayImage::from_raw(width as u32, height as u32, pixels)
+            .expect("Noise image buffer too small");
+
+        DynamicImage::ImageLuma8(image)
+    }
+}
+
+/// Source of three-dimensional noise.
+pub trait NoiseGen3D {
+    /// Samples the noise at the given position, in the range 0 to 1.
+    fn sample_3d(&self, x: f32, y: f32, z: f32) -> f32;
+
+    /// Samples the noise at each integer point of a grid with the
+    /// given dimensions, for uploading as a volume texture.
+    ///
+    /// Values are in the range 0 to 1, and ordered by x first,
+    /// then y, then z.
+    fn generate_volume(&self, size: [usize; 3]) -> Vec<f32> {
+        let [width, height, depth] = size;
+        let mut values = Vec::with_capacity(width * height * depth);
+
+        for z in 0..depth {
+            for y in 0..height {
+                for x in 0..width {
+                    values.push(normalize(self.sample_3d(x as f32, y as f32, z as f32)));
+                }
+            }
+        }
+
+        values
+    }
+}
+
+/// Value noise, interpolating random values at the corners of a
+/// lattice, summed over octaves of increasing frequency.
+#[derive(Debug, Clone)]
+pub struct ValueNoise {
+    seed: u8,
+    /// Lattice cells per unit of the first octave.
+    frequency: f32,
+    octaves: u8,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u8) -> Self {
+        ValueNoise {
+            seed,
+            frequency: 0.25,
+            octaves: 1,
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if octaves is zero.
+    pub fn with_octaves(mut self, octaves: u8) -> Self {
+        assert!(octaves > 0, "Octaves must be greater than 0");
+        self.octaves = octaves;
+        self
+    }
+
+    /// Random value, from 0 to 1, at the corner of a lattice cell.
+    fn lattice(&self, i: i32, j: i32, k: i32) -> f32 {
+        // Negative coordinates wrap around, like the permutation list.
+        let permute = |hash: usize, n: i32| {
+            let index = (hash + n as u32 as usize) % PERMUTATION_LIST.len();
+            PERMUTATION_LIST[index] as usize
+        };
+        let hash = permute(permute(permute(self.seed as usize, i), j), k);
+
+        hash as f32 / 254.0
+    }
+
+    /// Single octave of noise, interpolated between the
+    /// corners of the lattice cell containing the position.
+    fn octave(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (i, j, k) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+
+        // Eases out the creases along the lattice.
+        let fade = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty, tz) = (
+            fade(x - x.floor()),
+            fade(y - y.floor()),
+            fade(z - z.floor()),
+        );
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let face = |k: i32| {
+            let bottom = lerp(self.lattice(i, j, k), self.lattice(i + 1, j, k), tx);
+            let top = lerp(self.lattice(i, j + 1, k), self.lattice(i + 1, j + 1, k), tx);
+            lerp(bottom, top, ty)
+        };
+
+        lerp(face(k), face(k + 1), tz)
+    }
+}
+
+impl NoiseGen2D for ValueNoise {
+    fn sample_2d(&self, x: f32, y: f32) -> f32 {
+        self.sample_3d(x, y, 0.0)
+    }
+}
+
+impl NoiseGen3D for ValueNoise {
+    fn sample_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+
+        for oct in 0..self.octaves {
+            let frequency = self.frequency * 2_f32.powi(i32::from(oct));
+            let amplitude = 1.0 / 2_f32.powi(i32::from(oct));
+
+            sum += self.octave(x * frequency, y * frequency, z * frequency) * amplitude;
+            total_amplitude += amplitude;
+        }
+
+        sum / total_amplitude
+    }
+}
+
+/// Keeps a sample within the range 0 to 1, in case
+/// rounding pushed it just outside.
+fn normalize(value: f32) -> f32 {
+    value.max(0.0).min(1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noise_image() {
+        let noise = ValueNoise::new(5).with_octaves(3);
+
+        // The noise itself varies, rather than being
+        // stretched to fill the image.
+        let samples: Vec<f32> = (0..16 * 16)
+            .map(|index| noise.sample_2d((index % 16) as f32, (index / 16) as f32))
+            .collect();
+        let min = samples.iter().cloned().fold(std::f32::INFINITY, f32::min);
+        let max = samples
+            .iter()
+            .cloned()
+            .fold(std::f32::NEG_INFINITY, f32::max);
+        assert!(min >= 0.0 && max <= 1.0);
+        assert!(max - min >= 0.8, "{}..{}", min, max);
+
+        let image = noise.generate_image([16, 16]).to_luma();
+        let same = noise.clone().generate_image([16, 16]).to_luma();
+        assert_eq!((16, 16), image.dimensions());
+        for (pixel, sample) in image.pixels().zip(samples.iter()) {
+            assert_eq!((sample * 255.0).round() as u8, pixel[0]);
+        }
+
+        // Same seed gives the same noise.
+        assert_eq!(image, same);
+    }
+
+    #[test]
+    fn test_noise_volume() {
+        let noise = ValueNoise::new(1);
+        let volume = noise.generate_volume([4, 5, 6]);
+        assert_eq!(4 * 5 * 6, volume.len());
+        assert!(volume.iter().all(|value| *value >= 0.0 && *value <= 1.0));
+        assert_eq!(
+            noise.sample_3d(1.0, 2.0, 3.0),
+            volume[1 + 2 * 4 + 3 * 4 * 5]
+        );
+
+        let sample = noise.sample_3d(1.5, -2.25, 3.0);
+        assert!(sample >= 0.0 && sample <= 1.0);
+    }
+}
@@ -1,14 +1,24 @@
 //! Utilities for working with generic numbers.
+use crate::errors::NanError;
 use num_traits::Float;
 use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 /// Wrapper for a floating point number that's not NaN.
 ///
+/// Unlike floats, it's totally ordered and hashable, so it can be
+/// used in sorted collections and as a map key.
+///
 /// ```
 /// use rengine::number::NonNan;
+/// use std::convert::TryFrom;
 ///
 /// assert_eq!(NonNan::new(3.0).unwrap().into_inner(), 3.0);
 /// assert_eq!(NonNan::new(::std::f64::NAN), None);
+/// assert!(NonNan::try_from(::std::f32::NAN).is_err());
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct NonNan<F: Float>(F);
@@ -43,3 +53,154 @@ where
         self.0.partial_cmp(&rhs.0).unwrap()
     }
 }
+
+impl<F> Hash for NonNan<F>
+where
+    F: Float,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Zero and negative zero are equal, so they must hash the same.
+        let value = if self.0 == F::zero() {
+            F::zero()
+        } else {
+            self.0
+        };
+        value.integer_decode().hash(state);
+    }
+}
+
+impl<F> fmt::Display for NonNan<F>
+where
+    F: Float + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// There's no `From` conversion, since NaN can't be converted.
+macro_rules! impl_try_from {
+    ($($float:ty),*) => {
+        $(
+            impl TryFrom<$float> for NonNan<$float> {
+                type Error = NanError;
+
+                #[inline]
+                fn try_from(val: $float) -> Result<Self, Self::Error> {
+                    NonNan::new(val).ok_or(NanError)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from!(f32, f64);
+
+/// Sorts the numbers in ascending order.
+///
+/// Returns an error, leaving the slice unchanged, when
+/// any of the numbers are NaN.
+///
+/// ```
+/// use rengine::number::sort_floats;
+///
+/// let mut distances = [3.0, -1.0, 2.5];
+/// sort_floats(&mut distances).unwrap();
+/// assert_eq!([-1.0, 2.5, 3.0], distances);
+///
+/// assert!(sort_floats(&mut [1.0, ::std::f32::NAN]).is_err());
+/// ```
+pub fn sort_floats<F>(values: &mut [F]) -> Result<(), NanError>
+where
+    F: Float,
+{
+    if values.iter().any(|val| val.is_nan()) {
+        return Err(NanError);
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    Ok(())
+}
+
+/// Collects the numbers into a max-heap, for popping the
+/// largest number first.
+///
+/// Wrap the numbers in `std::cmp::Reverse` to pop the
+/// smallest number first instead.
+///
+/// ```
+/// use rengine::number::float_heap;
+///
+/// let mut heap = float_heap(vec![0.5, 4.0, 2.0]).unwrap();
+/// assert_eq!(Some(4.0), heap.pop().map(|val| val.into_inner()));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error when any of the numbers are NaN.
+pub fn float_heap<F, I>(values: I) -> Result<BinaryHeap<NonNan<F>>, NanError>
+where
+    F: Float,
+    I: IntoIterator<Item = F>,
+{
+    values
+        .into_iter()
+        .map(|val| NonNan::new(val).ok_or(NanError))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_nan_rejected() {
+        assert!(NonNan::try_from(::std::f32::NAN).is_err());
+        assert!(NonNan::try_from(::std::f64::NAN).is_err());
+        assert_eq!(Ok(2.0), NonNan::try_from(2.0_f32).map(NonNan::into_inner));
+
+        assert!(float_heap(vec![1.0, ::std::f32::NAN]).is_err());
+
+        let mut values = [2.0, ::std::f32::NAN, 1.0];
+        assert!(sort_floats(&mut values).is_err());
+        assert_eq!(1.0, values[2]);
+    }
+
+    #[test]
+    fn test_sort_floats() {
+        let mut values = [
+            4.5,
+            -2.0,
+            0.0,
+            100.0,
+            -0.5,
+            3.25,
+            ::std::f32::INFINITY,
+            -7.0,
+        ];
+        let mut expected = values;
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        sort_floats(&mut values).unwrap();
+        assert_eq!(expected, values);
+    }
+
+    #[test]
+    fn test_hash_display() {
+        let zero = NonNan::new(0.0_f32).unwrap();
+        let negative_zero = NonNan::new(-0.0_f32).unwrap();
+        assert_eq!(zero, negative_zero);
+        assert_eq!(hash_of(&zero), hash_of(&negative_zero));
+        assert_ne!(hash_of(&zero), hash_of(&NonNan::new(1.0_f32).unwrap()));
+
+        assert_eq!("1.5", NonNan::new(1.5_f64).unwrap().to_string());
+    }
+}
@@ -0,0 +1,269 @@
+//! Merging of small meshes into shared draw calls.
+//!
+//! Meshes that use the same pipeline and texture are transformed
+//! into world space on the CPU, and their geometry concatenated,
+//! so they can be drawn with a single call and an identity model
+//! matrix.
+use gfx::handle::Buffer;
+use gfx::traits::FactoryExt;
+use gfx::Factory;
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::gfx_types::{Transform, Vertex};
+
+/// Maximum number of vertices a single batch can address
+/// with 16-bit indices.
+pub const MAX_BATCH_VERTICES: usize = u16::max_value() as usize + 1;
+
+/// Number of elements allocated for the batch buffers up front.
+const INITIAL_BUFFER_LEN: usize = 1024;
+
+/// Location of a finished batch within the geometry of a `BatchBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchRange {
+    /// Offset added to each index when fetching vertices.
+    pub base_vertex: u32,
+    /// First index of the batch.
+    pub start: u32,
+    /// One past the last index of the batch.
+    pub end: u32,
+    /// Number of meshes merged into the batch.
+    pub meshes: usize,
+}
+
+/// Accumulates the world space geometry of meshes that can
+/// share a draw call.
+///
+/// Geometry of every batch in a frame is kept in one list, so it
+/// can be uploaded to graphics memory with a single update. Each
+/// batch is addressed by the `BatchRange` returned from `finish`.
+#[derive(Default)]
+pub struct BatchBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+    base_vertex: usize,
+    start: usize,
+    meshes: usize,
+}
+
+impl BatchBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Discards all geometry, ready for the next frame.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.base_vertex = 0;
+        self.start = 0;
+        self.meshes = 0;
+    }
+
+    /// Number of meshes in the batch that is currently open.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.meshes
+    }
+
+    /// Checks whether the open batch has room for the given number
+    /// of additional vertices.
+    #[inline]
+    pub fn fits(&self, vertex_count: usize) -> bool {
+        self.vertices.len() - self.base_vertex + vertex_count <= MAX_BATCH_VERTICES
+    }
+
+    /// Transforms the given model space geometry into world space,
+    /// and appends it to the open batch.
+    ///
+    /// The caller is responsible for checking `fits` beforehand.
+    pub fn push(&mut self, vertices: &[Vertex], indices: &[u16], model: &Matrix4<f32>) {
+        debug_assert!(self.fits(vertices.len()));
+
+        let offset = (self.vertices.len() - self.base_vertex) as u16;
+        let normal_matrix = model
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .transpose();
+
+        self.vertices.extend(vertices.iter().map(|vertex| {
+            let pos = model.transform_point(&Point3::from(vertex.pos));
+            let normal = normal_matrix
+                .transform_vector(&Vector3::from(vertex.normal))
+                .normalize();
+
+            Vertex {
+                pos: pos.coords.into(),
+                normal: normal.into(),
+                ..*vertex
+            }
+        }));
+        self.indices
+            .extend(indices.iter().map(|index| index + offset));
+        self.meshes += 1;
+    }
+
+    /// Closes the open batch, and starts a new one.
+    pub fn finish(&mut self) -> BatchRange {
+        let range = BatchRange {
+            base_vertex: self.base_vertex as u32,
+            start: self.start as u32,
+            end: self.indices.len() as u32,
+            meshes: self.meshes,
+        };
+
+        self.base_vertex = self.vertices.len();
+        self.start = self.indices.len();
+        self.meshes = 0;
+
+        range
+    }
+
+    #[inline]
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    #[inline]
+    pub fn indices(&self) -> &[u16] {
+        &self.indices
+    }
+}
+
+/// Dynamic graphics memory that batched geometry is uploaded to
+/// each frame.
+pub(crate) struct BatchBuffers {
+    pub(crate) vbuf: Buffer<gfx_device::Resources, Vertex>,
+    pub(crate) ibuf: Buffer<gfx_device::Resources, u16>,
+    /// Identity model transform shared by all batches, since
+    /// their vertices are already in world space.
+    pub(crate) transbuf: Buffer<gfx_device::Resources, Transform>,
+}
+
+impl BatchBuffers {
+    pub(crate) fn new(factory: &mut gfx_device::Factory) -> Self {
+        BatchBuffers {
+            vbuf: Self::create_vertex_buffer(factory, INITIAL_BUFFER_LEN),
+            ibuf: Self::create_index_buffer(factory, INITIAL_BUFFER_LEN),
+            transbuf: factory.create_constant_buffer(1),
+        }
+    }
+
+    /// Grows the buffers, if required, to hold the given number
+    /// of vertices and indices.
+    ///
+    /// Replaced buffers do not keep their previous contents.
+    pub(crate) fn reserve(
+        &mut self,
+        factory: &mut gfx_device::Factory,
+        vertex_count: usize,
+        index_count: usize,
+    ) {
+        if self.vbuf.len() < vertex_count {
+            self.vbuf = Self::create_vertex_buffer(factory, vertex_count.next_power_of_two());
+        }
+
+        if self.ibuf.len() < index_count {
+            self.ibuf = Self::create_index_buffer(factory, index_count.next_power_of_two());
+        }
+    }
+
+    fn create_vertex_buffer(
+        factory: &mut gfx_device::Factory,
+        len: usize,
+    ) -> Buffer<gfx_device::Resources, Vertex> {
+        factory
+            .create_buffer(
+                len,
+                gfx::buffer::Role::Vertex,
+                gfx::memory::Usage::Dynamic,
+                gfx::memory::Bind::empty(),
+            )
+            .expect("Failed to create batch vertex buffer")
+    }
+
+    fn create_index_buffer(
+        factory: &mut gfx_device::Factory,
+        len: usize,
+    ) -> Buffer<gfx_device::Resources, u16> {
+        factory
+            .create_buffer(
+                len,
+                gfx::buffer::Role::Index,
+                gfx::memory::Usage::Dynamic,
+                gfx::memory::Bind::empty(),
+            )
+            .expect("Failed to create batch index buffer")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::colors::WHITE;
+
+    fn triangle() -> (Vec<Vertex>, Vec<u16>) {
+        let vertex = |x: f32, y: f32| Vertex {
+            pos: [x, y, 0.0],
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            color: WHITE,
+            layer: 0.0,
+        };
+
+        (
+            vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)],
+            vec![0, 1, 2],
+        )
+    }
+
+    #[test]
+    fn test_batch_push() {
+        let (vertices, indices) = triangle();
+        let mut batch = BatchBuilder::new();
+
+        batch.push(&vertices, &indices, &Matrix4::identity());
+        batch.push(
+            &vertices,
+            &indices,
+            &Matrix4::new_translation(&Vector3::new(2.0, 0.0, -1.0)),
+        );
+        assert_eq!(2, batch.pending());
+
+        let first = batch.finish();
+        assert_eq!(0, batch.pending());
+        assert_eq!(
+            BatchRange {
+                base_vertex: 0,
+                start: 0,
+                end: 6,
+                meshes: 2,
+            },
+            first
+        );
+        assert_eq!(&[0, 1, 2, 3, 4, 5], batch.indices());
+        assert_eq!([3.0, 0.0, -1.0], batch.vertices()[4].pos);
+        assert_eq!([0.0, 0.0, 1.0], batch.vertices()[4].normal);
+
+        // Indices of the next batch are relative to its base vertex
+        batch.push(&vertices, &indices, &Matrix4::identity());
+        let second = batch.finish();
+        assert_eq!(6, second.base_vertex);
+        assert_eq!((6, 9), (second.start, second.end));
+        assert_eq!(&[0, 1, 2], &batch.indices()[6..]);
+    }
+
+    #[test]
+    fn test_batch_fits() {
+        let (vertices, indices) = triangle();
+        let mut batch = BatchBuilder::new();
+
+        assert!(batch.fits(MAX_BATCH_VERTICES));
+        batch.push(&vertices, &indices, &Matrix4::identity());
+        assert!(!batch.fits(MAX_BATCH_VERTICES));
+
+        // A new batch starts with full capacity
+        batch.finish();
+        assert!(batch.fits(MAX_BATCH_VERTICES));
+    }
+}
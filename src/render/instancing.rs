@@ -0,0 +1,181 @@
+//! Drawing many copies of the same mesh with a single call.
+use gfx::handle::Buffer;
+use gfx::Factory;
+use specs::{Component, DenseVecStorage};
+use std::sync::Arc;
+
+use crate::comp::Mesh;
+use crate::gfx_types::Instance;
+
+/// Largest number of instances uploaded for a single draw call.
+///
+/// Groups with more instances are split into multiple calls.
+pub const MAX_INSTANCES_PER_DRAW: usize = 4096;
+
+/// Number of instances allocated for the instance buffer up front.
+const INITIAL_INSTANCE_LEN: usize = 64;
+
+/// Mesh in graphics memory that can be shared by many entities.
+#[derive(Clone)]
+pub struct SharedMesh(Arc<Mesh>);
+
+impl SharedMesh {
+    pub fn new(mesh: Mesh) -> Self {
+        SharedMesh(Arc::new(mesh))
+    }
+
+    #[inline]
+    pub(crate) fn mesh(&self) -> &Mesh {
+        &self.0
+    }
+
+    /// Checks whether both handles refer to the same mesh.
+    #[inline]
+    pub fn ptr_eq(&self, other: &SharedMesh) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Marks an entity as an instance of a shared mesh.
+///
+/// Instances of the same mesh that use the same `Material::Basic`
+/// texture are drawn together with one instanced draw call, using
+/// each entity's `Transform`.
+#[derive(Component, Clone)]
+#[storage(DenseVecStorage)]
+pub struct MeshInstance {
+    mesh: SharedMesh,
+}
+
+impl MeshInstance {
+    pub fn new(mesh: SharedMesh) -> Self {
+        MeshInstance { mesh }
+    }
+
+    #[inline]
+    pub fn shared_mesh(&self) -> &SharedMesh {
+        &self.mesh
+    }
+}
+
+/// Per instance data gathered for each distinct key, in the order
+/// the keys were first encountered.
+pub struct InstanceGroups<K> {
+    groups: Vec<(K, Vec<Instance>)>,
+}
+
+impl<K> InstanceGroups<K> {
+    pub fn new() -> Self {
+        InstanceGroups { groups: vec![] }
+    }
+
+    /// Adds an instance to the group matching the key, creating
+    /// a new group when none matches.
+    pub fn push<F>(&mut self, key: K, instance: Instance, matches: F)
+    where
+        F: Fn(&K, &K) -> bool,
+    {
+        match self
+            .groups
+            .iter_mut()
+            .find(|(other, _)| matches(other, &key))
+        {
+            Some((_, instances)) => instances.push(instance),
+            None => self.groups.push((key, vec![instance])),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Iterates the groups, splitting their instances into chunks
+    /// no larger than `MAX_INSTANCES_PER_DRAW`.
+    pub fn chunks<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a [Instance])> + 'a {
+        self.groups.iter().flat_map(|(key, instances)| {
+            instances
+                .chunks(MAX_INSTANCES_PER_DRAW)
+                .map(move |chunk| (key, chunk))
+        })
+    }
+}
+
+impl<K> Default for InstanceGroups<K> {
+    fn default() -> Self {
+        InstanceGroups::new()
+    }
+}
+
+/// Dynamic graphics memory that instance data is uploaded to
+/// before each instanced draw call.
+pub(crate) struct InstanceBuffer {
+    pub(crate) buf: Buffer<gfx_device::Resources, Instance>,
+}
+
+impl InstanceBuffer {
+    pub(crate) fn new(factory: &mut gfx_device::Factory) -> Self {
+        InstanceBuffer {
+            buf: Self::create_buffer(factory, INITIAL_INSTANCE_LEN),
+        }
+    }
+
+    /// Grows the buffer, if required, to hold the given number of
+    /// instances, up to `MAX_INSTANCES_PER_DRAW`.
+    pub(crate) fn reserve(&mut self, factory: &mut gfx_device::Factory, count: usize) {
+        let count = count.min(MAX_INSTANCES_PER_DRAW);
+
+        if self.buf.len() < count {
+            self.buf = Self::create_buffer(factory, count.next_power_of_two());
+        }
+    }
+
+    fn create_buffer(
+        factory: &mut gfx_device::Factory,
+        len: usize,
+    ) -> Buffer<gfx_device::Resources, Instance> {
+        factory
+            .create_buffer(
+                len,
+                gfx::buffer::Role::Vertex,
+                gfx::memory::Usage::Dynamic,
+                gfx::memory::Bind::empty(),
+            )
+            .expect("Failed to create instance buffer")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::{Matrix4, Vector3};
+
+    fn instance(x: f32) -> Instance {
+        Matrix4::new_translation(&Vector3::new(x, 0.0, 0.0)).into()
+    }
+
+    #[test]
+    fn test_instance_groups() {
+        let mut groups = InstanceGroups::new();
+        groups.push(1, instance(0.0), |a, b| a == b);
+        groups.push(2, instance(1.0), |a, b| a == b);
+        groups.push(1, instance(2.0), |a, b| a == b);
+
+        let chunks: Vec<_> = groups.chunks().collect();
+        assert_eq!(2, chunks.len());
+        assert_eq!((&1, 2), (chunks[0].0, chunks[0].1.len()));
+        assert_eq!((&2, 1), (chunks[1].0, chunks[1].1.len()));
+        assert_eq!([2.0, 0.0, 0.0, 1.0], chunks[0].1[1].model_3);
+    }
+
+    #[test]
+    fn test_instance_chunks() {
+        let mut groups = InstanceGroups::new();
+        for _ in 0..MAX_INSTANCES_PER_DRAW + 1 {
+            groups.push((), instance(0.0), |_, _| true);
+        }
+
+        let sizes: Vec<usize> = groups.chunks().map(|(_, chunk)| chunk.len()).collect();
+        assert_eq!(vec![MAX_INSTANCES_PER_DRAW, 1], sizes);
+    }
+}
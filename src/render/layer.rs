@@ -0,0 +1,91 @@
+use specs::prelude::*;
+use std::cmp::Ordering;
+
+/// Coarse group of draw calls that an entity's mesh is drawn in.
+///
+/// `DrawSystem` draws lower layers first, for example a skybox in a
+/// low layer before the scene, or gizmos in a high layer after it.
+/// Within a layer, opaque meshes are drawn before transparent ones,
+/// as usual. Layers only order the draw calls, meshes are still
+/// depth tested against each other.
+///
+/// Entities without the component are drawn in the middle layer,
+/// `RenderLayer::DEFAULT`, leaving room for layers below and above.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[storage(DenseVecStorage)]
+pub struct RenderLayer(pub u16);
+
+impl RenderLayer {
+    pub const DEFAULT: RenderLayer = RenderLayer(0x8000);
+}
+
+impl Default for RenderLayer {
+    fn default() -> Self {
+        RenderLayer::DEFAULT
+    }
+}
+
+/// Position of a mesh in the draw order of `DrawSystem`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DrawKey {
+    pub(crate) layer: RenderLayer,
+    pub(crate) transparent: bool,
+    /// Distance to the camera of opaque meshes, drawn nearest first,
+    /// or depth in view space of transparent meshes, drawn furthest first.
+    pub(crate) order: f32,
+}
+
+impl DrawKey {
+    /// Orders by layer, then opaque before transparent, then by `order`.
+    pub(crate) fn cmp(&self, other: &DrawKey) -> Ordering {
+        self.layer
+            .cmp(&other.layer)
+            .then(self.transparent.cmp(&other.transparent))
+            .then_with(|| {
+                self.order
+                    .partial_cmp(&other.order)
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(layer: u16, transparent: bool, order: f32) -> DrawKey {
+        DrawKey {
+            layer: RenderLayer(layer),
+            transparent,
+            order,
+        }
+    }
+
+    #[test]
+    fn test_draw_order() {
+        let middle = RenderLayer::DEFAULT.0;
+        let mut keys = vec![
+            (key(middle + 1, false, 1.0), "gizmo"),
+            (key(middle, true, -1.0), "near sprite"),
+            (key(middle, false, 9.0), "far wall"),
+            (key(0, false, 100.0), "skybox"),
+            (key(middle, true, -8.0), "far sprite"),
+            (key(middle, false, 2.0), "near wall"),
+        ];
+        keys.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let names: Vec<&str> = keys.iter().map(|(_, name)| *name).collect();
+        assert_eq!(
+            vec![
+                "skybox",
+                "near wall",
+                "far wall",
+                "far sprite",
+                "near sprite",
+                "gizmo"
+            ],
+            names
+        );
+        assert_eq!(RenderLayer::DEFAULT, RenderLayer::default());
+    }
+}
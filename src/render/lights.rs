@@ -1,4 +1,5 @@
 use gfx::traits::FactoryExt;
+use log::warn;
 use nalgebra::Vector3;
 use specs::prelude::*;
 
@@ -15,6 +16,10 @@ use crate::{
 /// Default maximum number of lights.
 pub const MAX_NUM_LIGHTS: usize = 4;
 
+/// Creates an entity with a point light that follows its `Transform`.
+///
+/// When all light slots are taken, the entity is created
+/// without a light.
 pub fn create_light<V>(
     world: &mut World,
     mut graphics: &mut GraphicContext,
@@ -24,23 +29,28 @@ pub fn create_light<V>(
 where
     V: Into<Vector3<f32>>,
 {
-    // TODO: Move buffer to global resource
-    let lights_buf = graphics.factory.create_constant_buffer(1);
+    let pos: Vector3<f32> = pos.into();
     let texture = GlTexture::from_bundle(
         world
             .write_resource::<TextureAssets>()
             .default_texture(graphics.factory_mut()),
     );
 
+    let maybe_handle = world
+        .write_resource::<Lights>()
+        .add_point_light(PointLightParams::new([pos.x, pos.y, pos.z]));
+
     let mut builder = world
         .create_entity()
-        .with(Transform::default().with_position(pos))
-        .with(PointLight {
-            buf: lights_buf,
-            ambient: [0.6, 0.6, 1.0, 1.0],
-            diffuse: [0.6, 0.8, 0.8, 1.0],
-            specular: [1.0, 1.0, 1.0, 1.0],
-        });
+        .with(Transform::default().with_position(pos));
+
+    builder = match maybe_handle {
+        Some(handle) => builder.with(PointLight::new(handle)),
+        None => {
+            warn!("Failed to create point light, all light slots are taken");
+            builder
+        }
+    };
 
     builder = if debug {
         let tex_rect = texture.source_rect();
@@ -69,28 +79,104 @@ where
     builder.build()
 }
 
-#[derive(Component, Debug)]
-#[storage(DenseVecStorage)]
-pub struct PointLight {
-    pub buf: gfx::handle::Buffer<gfx_device::Resources, gfx_types::LightParams>,
+/// Attenuation of a light at its range, where its
+/// contribution is small enough to be cut off.
+pub const RANGE_CUTOFF: f32 = 1.0 / 256.0;
+
+/// Parameters of a single point light.
+///
+/// The diffuse and specular contributions fall off with the
+/// distance `d` from the light, by the attenuation
+/// `1.0 / (constant + linear * d + quadratic * d * d)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLightParams {
+    pub position: [f32; 3],
     pub ambient: Color,
     pub diffuse: Color,
     pub specular: Color,
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl PointLightParams {
+    /// Creates a light that doesn't fall off with distance.
+    pub fn new(position: [f32; 3]) -> Self {
+        PointLightParams {
+            position,
+            ambient: [0.6, 0.6, 1.0, 1.0],
+            diffuse: [0.6, 0.8, 0.8, 1.0],
+            specular: [1.0, 1.0, 1.0, 1.0],
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        }
+    }
+
+    /// Sets the attenuation coefficients so the light falls off
+    /// quadratically, down to `RANGE_CUTOFF` at the given range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rengine::render::{PointLightParams, RANGE_CUTOFF};
+    ///
+    /// let light = PointLightParams::new([0.0, 0.0, 0.0]).with_range(10.0);
+    /// assert_eq!(1.0, light.attenuation(0.0));
+    /// assert!((light.attenuation(10.0) - RANGE_CUTOFF).abs() < 1.0e-6);
+    /// ```
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.constant = 1.0;
+        self.linear = 0.0;
+        self.quadratic = (1.0 / RANGE_CUTOFF - 1.0) / (range * range);
+        self
+    }
+
+    /// Attenuation of the light at the given distance.
+    ///
+    /// Matches the falloff applied by the gloss shaders.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
 }
 
+impl Into<gfx_types::LightParams> for PointLightParams {
+    fn into(self) -> gfx_types::LightParams {
+        let [x, y, z] = self.position;
+
+        gfx_types::LightParams {
+            pos: [x, y, z, 1.0],
+            ambient: self.ambient,
+            diffuse: self.diffuse,
+            specular: self.specular,
+            attenuation: [self.constant, self.linear, self.quadratic, 0.0],
+        }
+    }
+}
+
+/// Opaque handle to a point light added to `Lights`.
+///
+/// A handle is invalidated when its light is removed, even
+/// if the slot is later reused by another light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightHandle {
+    index: usize,
+    generation: u32,
+}
+
+/// Point lights in the scene, and their buffer in graphics memory.
 pub struct Lights {
     /// Handle to light buffer in graphics memory.
     buf: gfx::handle::Buffer<gfx_device::Resources, gfx_types::LightParams>,
 
-    /// Maximum number of allowed lights
-    max_num: usize,
+    slots: LightSlots,
 }
 
 impl Lights {
     pub fn new(graphics: &mut GraphicContext, max_num: usize) -> Self {
         Lights {
             buf: graphics.factory.create_constant_buffer(max_num),
-            max_num,
+            slots: LightSlots::new(max_num),
         }
     }
 
@@ -103,6 +189,264 @@ impl Lights {
     /// Maximum number of allowed lights.
     #[inline]
     pub fn max_num(&self) -> usize {
-        self.max_num
+        self.slots.capacity()
+    }
+
+    /// Number of lights currently added.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.len() == 0
+    }
+
+    /// Reserves a slot for the light.
+    ///
+    /// Returns `None` when all `max_num` slots are taken.
+    pub fn add_point_light(&mut self, light: PointLightParams) -> Option<LightHandle> {
+        self.slots.insert(light)
+    }
+
+    /// Frees the light's slot, to be reused by the next light added.
+    ///
+    /// Returns the removed light, or `None` if the handle
+    /// no longer refers to a light.
+    pub fn remove_point_light(&mut self, handle: LightHandle) -> Option<PointLightParams> {
+        self.slots.remove(handle)
+    }
+
+    #[inline]
+    pub fn point_light(&self, handle: LightHandle) -> Option<&PointLightParams> {
+        self.slots.get(handle)
+    }
+
+    #[inline]
+    pub fn point_light_mut(&mut self, handle: LightHandle) -> Option<&mut PointLightParams> {
+        self.slots.get_mut(handle)
+    }
+
+    /// Parameters of all added lights, packed together in
+    /// slot order, for uploading to graphics memory.
+    pub(crate) fn light_params(&self) -> Vec<gfx_types::LightParams> {
+        self.slots.iter().map(|light| (*light).into()).collect()
+    }
+}
+
+/// Fixed number of light slots, managed as a free-list.
+struct LightSlots {
+    slots: Vec<LightSlot>,
+
+    /// Indices of unoccupied slots. The most recently
+    /// freed slot is at the end, and is reused first.
+    free: Vec<usize>,
+}
+
+struct LightSlot {
+    /// Incremented each time the slot is freed, to
+    /// invalidate the handles pointing to it.
+    generation: u32,
+    light: Option<PointLightParams>,
+}
+
+impl LightSlots {
+    fn new(capacity: usize) -> Self {
+        LightSlots {
+            slots: (0..capacity)
+                .map(|_| LightSlot {
+                    generation: 0,
+                    light: None,
+                })
+                .collect(),
+            free: (0..capacity).rev().collect(),
+        }
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    fn insert(&mut self, light: PointLightParams) -> Option<LightHandle> {
+        let index = self.free.pop()?;
+        let slot = &mut self.slots[index];
+        slot.light = Some(light);
+
+        Some(LightHandle {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    fn remove(&mut self, handle: LightHandle) -> Option<PointLightParams> {
+        let slot = self
+            .slots
+            .get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)?;
+        let light = slot.light.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+
+        Some(light)
+    }
+
+    fn get(&self, handle: LightHandle) -> Option<&PointLightParams> {
+        self.slots
+            .get(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.light.as_ref())
+    }
+
+    fn get_mut(&mut self, handle: LightHandle) -> Option<&mut PointLightParams> {
+        self.slots
+            .get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.light.as_mut())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &PointLightParams> {
+        self.slots.iter().filter_map(|slot| slot.light.as_ref())
+    }
+}
+
+/// Moves the point lights of entities to their `Transform`, and
+/// frees the lights of entities that no longer have a `PointLight`.
+pub struct PointLightSystem {
+    /// Lights owned by entities during the last run.
+    tracked: Vec<(Entity, LightHandle)>,
+}
+
+impl PointLightSystem {
+    pub fn new() -> Self {
+        PointLightSystem { tracked: vec![] }
+    }
+}
+
+impl Default for PointLightSystem {
+    fn default() -> Self {
+        PointLightSystem::new()
+    }
+}
+
+impl<'a> System<'a> for PointLightSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Lights>,
+        ReadStorage<'a, PointLight>,
+        ReadStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (entities, mut lights, point_lights, transforms): Self::SystemData) {
+        let mut tracked = Vec::with_capacity(self.tracked.len());
+
+        for (entity, point_light) in (&entities, &point_lights).join() {
+            let handle = point_light.handle();
+
+            if let (Some(light), Some(trans)) =
+                (lights.point_light_mut(handle), transforms.get(entity))
+            {
+                let pos = trans.position();
+                light.position = [pos.x, pos.y, pos.z];
+            }
+
+            tracked.push((entity, handle));
+        }
+
+        for entry in self.tracked.drain(..) {
+            if !tracked.contains(&entry) {
+                lights.remove_point_light(entry.1);
+            }
+        }
+
+        self.tracked = tracked;
+    }
+}
+
+// ---------- //
+// Components //
+// ---------- //
+
+/// Attaches a point light in `Lights` to an entity.
+///
+/// The light follows the entity's `Transform`, and is removed
+/// along with the component.
+#[derive(Component, Debug)]
+#[storage(DenseVecStorage)]
+pub struct PointLight {
+    handle: LightHandle,
+}
+
+impl PointLight {
+    pub fn new(handle: LightHandle) -> Self {
+        PointLight { handle }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> LightHandle {
+        self.handle
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_light_slots_reuse() {
+        let mut slots = LightSlots::new(MAX_NUM_LIGHTS);
+        let handles: Vec<LightHandle> = (0..MAX_NUM_LIGHTS)
+            .map(|i| slots.insert(PointLightParams::new([i as f32, 0.0, 0.0])))
+            .collect::<Option<_>>()
+            .unwrap();
+        assert_eq!(MAX_NUM_LIGHTS, slots.len());
+
+        // Full
+        assert_eq!(None, slots.insert(PointLightParams::new([0.0, 0.0, 0.0])));
+
+        let removed = handles[1];
+        assert_eq!(
+            Some(PointLightParams::new([1.0, 0.0, 0.0])),
+            slots.remove(removed)
+        );
+        assert_eq!(None, slots.remove(removed));
+        assert_eq!(MAX_NUM_LIGHTS - 1, slots.len());
+
+        let added = slots
+            .insert(PointLightParams::new([8.0, 0.0, 0.0]))
+            .unwrap();
+        assert_eq!(removed.index, added.index);
+        assert_eq!(MAX_NUM_LIGHTS, slots.len());
+
+        // Old handle doesn't refer to the new light.
+        assert_eq!(None, slots.get(removed));
+        assert_eq!(
+            Some(&PointLightParams::new([8.0, 0.0, 0.0])),
+            slots.get(added)
+        );
+
+        // Lights are kept in slot order.
+        let positions: Vec<f32> = slots.iter().map(|light| light.position[0]).collect();
+        assert_eq!(vec![0.0, 8.0, 2.0, 3.0], positions);
+    }
+
+    #[test]
+    fn test_light_range() {
+        let light = PointLightParams::new([0.0, 0.0, 0.0]);
+        assert_eq!(1.0, light.attenuation(100.0));
+
+        for range in &[1.0, 7.5, 50.0] {
+            let light = light.with_range(*range);
+            let cutoff = light.attenuation(*range);
+            assert!((cutoff - 1.0 / 256.0).abs() < 1.0e-6, "range {}", range);
+            assert!(light.attenuation(range * 0.5) > cutoff);
+            assert!(light.attenuation(range * 2.0) < cutoff);
+        }
     }
 }
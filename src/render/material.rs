@@ -1,7 +1,12 @@
 use gfx::traits::FactoryExt;
 use specs::prelude::*;
 
-use crate::{colors::Color, comp::GlTexture, gfx_types, graphics::GraphicContext};
+use crate::{
+    colors::Color,
+    comp::{GlTexture, GlTextureArray},
+    gfx_types,
+    graphics::GraphicContext,
+};
 
 #[derive(Component)]
 #[storage(DenseVecStorage)]
@@ -14,9 +19,30 @@ pub enum Material {
         texture: GlTexture,
         material: GlossMaterial,
     },
+    /// Gloss material sampling the layers of a texture array,
+    /// selected by the `layer` of each vertex.
+    GlossArray {
+        textures: GlTextureArray,
+        material: GlossMaterial,
+    },
     Gizmo,
 }
 
+impl Material {
+    /// Indicates whether meshes with this material are expected to
+    /// fully cover what is behind them.
+    ///
+    /// Basic materials are used for sprites, which may have
+    /// transparent pixels.
+    #[inline]
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            Material::Lambert | Material::Gloss { .. } | Material::GlossArray { .. } => true,
+            Material::Basic { .. } | Material::Gizmo => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GlossMaterial {
     /// Handle to material buffer in graphics memory.
@@ -1,9 +1,17 @@
+mod batch;
 mod channel;
 mod draw;
+mod instancing;
+mod layer;
 mod lights;
 mod material;
+mod pipelines;
 
+pub use batch::*;
 pub use channel::*;
 pub use draw::*;
+pub use instancing::*;
+pub use layer::*;
 pub use lights::*;
 pub use material::*;
+pub use pipelines::*;
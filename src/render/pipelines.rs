@@ -0,0 +1,273 @@
+use crate::errors::RenderError;
+use crate::gfx_types::*;
+use gfx::traits::FactoryExt;
+use specs::World;
+use std::borrow::Cow;
+
+/// Folder containing the shader sources, read at runtime
+/// when hot reloading is enabled.
+#[cfg(feature = "debug-hot-reload")]
+pub(crate) const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+/// Vertex and fragment shader files of a program, and their
+/// sources embedded in the binary.
+pub(crate) struct ShaderFiles {
+    pub(crate) vertex: &'static str,
+    pub(crate) fragment: &'static str,
+    vertex_src: &'static [u8],
+    fragment_src: &'static [u8],
+}
+
+macro_rules! shader_files {
+    ($vertex:tt, $fragment:tt) => {
+        ShaderFiles {
+            vertex: $vertex,
+            fragment: $fragment,
+            vertex_src: include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/shaders/",
+                $vertex
+            )),
+            fragment_src: include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/shaders/",
+                $fragment
+            )),
+        }
+    };
+}
+
+impl ShaderFiles {
+    /// Sources of the vertex and fragment shaders.
+    ///
+    /// With hot reloading enabled, the sources are read from the
+    /// shader folder, falling back to the embedded sources when
+    /// a file can't be read.
+    fn sources(&self) -> (Cow<'static, [u8]>, Cow<'static, [u8]>) {
+        (
+            read_shader(self.vertex, self.vertex_src),
+            read_shader(self.fragment, self.fragment_src),
+        )
+    }
+
+    fn link(&self, factory: &mut gfx_device::Factory) -> Result<ShaderProgram, RenderError> {
+        let (vertex_src, fragment_src) = self.sources();
+
+        factory
+            .link_program(&vertex_src, &fragment_src)
+            .map_err(|err| {
+                RenderError::Pipeline(format!("{} and {}: {}", self.vertex, self.fragment, err))
+            })
+    }
+}
+
+#[cfg(feature = "debug-hot-reload")]
+fn read_shader(file_name: &str, embedded: &'static [u8]) -> Cow<'static, [u8]> {
+    let path = ::std::path::Path::new(SHADER_DIR).join(file_name);
+
+    match ::std::fs::read(&path) {
+        Ok(source) => Cow::Owned(source),
+        Err(err) => {
+            log::warn!(
+                "Failed to read shader {:?}, using embedded source: {}",
+                path,
+                err
+            );
+            Cow::Borrowed(embedded)
+        }
+    }
+}
+
+#[cfg(not(feature = "debug-hot-reload"))]
+#[inline]
+fn read_shader(_file_name: &str, embedded: &'static [u8]) -> Cow<'static, [u8]> {
+    Cow::Borrowed(embedded)
+}
+
+/// Render pipelines created by the application, each
+/// stored as a world resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pipeline {
+    Basic,
+    Instanced,
+    /// Gloss material, along with the depth prepass.
+    Gloss,
+    /// Gloss material sampling a texture array.
+    GlossArray,
+    Gizmo,
+    Gui,
+}
+
+impl Pipeline {
+    pub(crate) const ALL: [Pipeline; 6] = [
+        Pipeline::Basic,
+        Pipeline::Instanced,
+        Pipeline::Gloss,
+        Pipeline::GlossArray,
+        Pipeline::Gizmo,
+        Pipeline::Gui,
+    ];
+
+    /// Shader programs used by the pipeline.
+    pub(crate) fn shader_files(self) -> Vec<ShaderFiles> {
+        match self {
+            Pipeline::Basic => vec![shader_files!("basic_150.glslv", "basic_150.glslf")],
+            Pipeline::Instanced => vec![shader_files!("instanced_150.glslv", "basic_150.glslf")],
+            Pipeline::Gloss => vec![
+                shader_files!("gloss_150.glslv", "gloss_150.glslf"),
+                shader_files!("depth_150.glslv", "depth_150.glslf"),
+            ],
+            Pipeline::GlossArray => vec![shader_files!(
+                "gloss_array_150.glslv",
+                "gloss_array_150.glslf"
+            )],
+            Pipeline::Gizmo => vec![shader_files!("gizmo_150.glslv", "gizmo_150.glslf")],
+            Pipeline::Gui => vec![shader_files!("gui_150.glslv", "gui_150.glslf")],
+        }
+    }
+
+    /// Links the shader programs and creates the pipeline state
+    /// objects, adding them to the world.
+    ///
+    /// Replaces the pipeline's existing resources, which are
+    /// left untouched when linking fails.
+    pub(crate) fn create(
+        self,
+        factory: &mut gfx_device::Factory,
+        world: &mut World,
+    ) -> Result<(), RenderError> {
+        let files = self.shader_files();
+        let program = files[0].link(factory)?;
+        let fill = gfx::state::Rasterizer::new_fill().with_cull_back();
+
+        // Bundle program and pipeline state object together to avoid
+        // lifetime issues with world resources borrowing each other.
+        match self {
+            Pipeline::Basic => {
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("basic", err))?;
+
+                world.add_resource(PipelineBundle::new(pso, program));
+            }
+            Pipeline::Instanced => {
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        instanced_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("instanced material", err))?;
+
+                world.add_resource(PipelineBundle::new(pso, program));
+            }
+            Pipeline::Gloss => {
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        gloss_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("gloss material", err))?;
+
+                // Main pass after a depth prepass, which only draws fragments
+                // that are at the depth already written.
+                let prepass_pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        gloss_pipe::Init {
+                            depth_target: gfx::preset::depth::LESS_EQUAL_TEST,
+                            ..gloss_pipe::new()
+                        },
+                    )
+                    .map_err(|err| pipeline_error("gloss material", err))?;
+
+                // Depth only PSO
+                let depth_shader = files[1].link(factory)?;
+                let depth_pso = factory
+                    .create_pipeline_from_program(
+                        &depth_shader,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        depth_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("depth prepass", err))?;
+
+                world.add_resource(DepthPrepassBundle::new(
+                    PipelineBundle::new(depth_pso, depth_shader),
+                    PipelineBundle::new(prepass_pso, program.clone()),
+                ));
+                world.add_resource(PipelineBundle::new(pso, program));
+            }
+            Pipeline::GlossArray => {
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        gloss_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("gloss array material", err))?;
+
+                let prepass_pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fill,
+                        gloss_pipe::Init {
+                            depth_target: gfx::preset::depth::LESS_EQUAL_TEST,
+                            ..gloss_pipe::new()
+                        },
+                    )
+                    .map_err(|err| pipeline_error("gloss array material", err))?;
+
+                world.add_resource(GlossArrayBundle::new(
+                    PipelineBundle::new(pso, program.clone()),
+                    PipelineBundle::new(prepass_pso, program),
+                ));
+            }
+            Pipeline::Gizmo => {
+                let mut fillmode = gfx::state::Rasterizer::new_fill();
+                fillmode.method = gfx::state::RasterMethod::Line(1); // Render lines
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        fillmode,
+                        gizmo_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("wireframe material", err))?;
+
+                world.add_resource(PipelineBundle::new(pso, program));
+            }
+            Pipeline::Gui => {
+                let pso = factory
+                    .create_pipeline_from_program(
+                        &program,
+                        gfx::Primitive::TriangleList,
+                        // TODO: Currently we're drawing quads backwards
+                        fill,
+                        gui_pipe::new(),
+                    )
+                    .map_err(|err| pipeline_error("gui", err))?;
+
+                world.add_resource(PipelineBundle::new(pso, program));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn pipeline_error(name: &str, err: gfx::PipelineStateError<String>) -> RenderError {
+    RenderError::Pipeline(format!("{} pipeline: {}", name, err))
+}
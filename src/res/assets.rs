@@ -1,11 +1,31 @@
+use crate::comp::GlTexture;
+#[cfg(feature = "debug-hot-reload")]
+use crate::errors::TextureError;
 use crate::gfx_types::ColorFormat;
+use crate::gfx_types::GraphicsEncoder;
+use crate::noise::NoiseGen2D;
+use crossbeam::channel::{self, Receiver, Sender};
 use gfx::texture::{FilterMethod, SamplerInfo, WrapMode};
 use gfx_device::{Factory, Resources};
+use image::RgbaImage;
+use log::{trace, warn};
+use shrev::EventChannel;
+use specs::{Component, DenseVecStorage};
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 const DEFAULT_TEXTURE_KEY: &str = "#";
 
+/// Number of background threads decoding images for `async_load`.
+const LOADER_THREADS: usize = 2;
+
+/// Number of frames a texture stays cached after it stops
+/// being used, so textures aren't reloaded when a scene is
+/// replaced by one using the same textures.
+const DEFAULT_GRACE_PERIOD: u32 = 300;
+
 /// Shared store for caching Textures
 ///
 /// Inner values are protected by Arc, but the container
@@ -13,24 +33,73 @@ const DEFAULT_TEXTURE_KEY: &str = "#";
 /// are immutable, can be sent across thread boundries to
 /// systems, but access to the cache itself must occur from
 /// a single thread.
+///
+/// Textures are reference counted. Every `Arc<AssetBundle>` returned
+/// by the cache, and every `GlTexture` wrapping one, keeps its texture
+/// alive. Once only the cache holds a texture, and it hasn't been used
+/// for the grace period, it is evicted by `maintain`, which is called
+/// each frame by `TextureUpkeepSystem`.
 pub struct TextureAssets {
     /// Reference counted shared textures.
-    cache: BTreeMap<String, Arc<AssetBundle>>,
+    cache: BTreeMap<String, CachedTexture>,
+    /// Number of frames an unused texture is kept before
+    /// it is evicted.
+    grace_period: u32,
+    /// Queue of images to be decoded by the loader threads.
+    ///
+    /// The threads stop when the sender is dropped.
+    jobs: Sender<(String, Arc<Mutex<LoadState>>)>,
+    /// Asynchronous loads not yet added to the cache.
+    pending: Mutex<Vec<LoadHandle>>,
+    /// Finished asynchronous loads, waiting to be published
+    /// by `TextureLoadSystem`.
+    events: Vec<TextureEvent>,
+    /// Textures waiting for their mipmaps to be generated.
+    mipmap_queue: Vec<gfx::handle::ShaderResourceView<Resources, [f32; 4]>>,
 }
 
 impl TextureAssets {
     pub fn new() -> Self {
+        let (jobs, receiver) = channel::unbounded();
+
+        for index in 0..LOADER_THREADS {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("texture-loader-{}", index))
+                .spawn(move || decode_images(receiver))
+                .expect("Failed to spawn texture loader thread");
+        }
+
         TextureAssets {
             cache: BTreeMap::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+            jobs,
+            pending: Mutex::new(vec![]),
+            events: vec![],
+            mipmap_queue: vec![],
         }
     }
 
+    /// Number of frames an unused texture is kept in the
+    /// cache before it is evicted.
+    #[inline]
+    pub fn grace_period(&self) -> u32 {
+        self.grace_period
+    }
+
+    #[inline]
+    pub fn set_grace_period(&mut self, frames: u32) {
+        self.grace_period = frames;
+    }
+
     /// Retrieve the special default texture.
     ///
     /// The default texture is a 1x1 white pixel, so a mesh with no texture
     /// can be drawn using a shader that expects a texture to be loaded.
     ///
     /// Sampling an empty texture would be undefined behaviour.
+    ///
+    /// The default texture is never evicted.
     pub fn default_texture(&mut self, factory: &mut Factory) -> Arc<AssetBundle> {
         // Constant image
         let data: &[&[u8]] = &[&[0xFF, 0xFF, 0xFF, 0xFF]];
@@ -39,6 +108,11 @@ impl TextureAssets {
         self.create_texture(factory, DEFAULT_TEXTURE_KEY, width, height, data)
     }
 
+    /// Loads a texture from disk, or retrieves it from the cache.
+    ///
+    /// The texture stays cached for as long as the returned bundle,
+    /// or any `GlTexture` wrapping it, is alive.
+    ///
     /// TODO: Normalise path to something common, like absolute, or relative to CWD; for cache so we don't load same texture twice under differnet looking paths
     pub fn load_texture(&mut self, factory: &mut Factory, path: &str) -> Arc<AssetBundle> {
         // Load from disk
@@ -48,13 +122,186 @@ impl TextureAssets {
         self.create_texture(factory, path, width, height, &[&img])
     }
 
+    /// Loads a texture from disk, or retrieves it from the cache,
+    /// sampled with the given filtering and wrapping.
+    ///
+    /// Textures loaded from the same path share their pixels on the
+    /// graphics card, and differ only in their sampler. The default
+    /// `SamplerDesc` gives the same texture as `load_texture`.
+    ///
+    /// When the sampler uses mipmaps, they are generated on the
+    /// graphics card by `generate_mipmaps` before the next frame is
+    /// drawn. Textures of any size, including non-power-of-two, have
+    /// a full mipmap chain down to one texel.
+    pub fn load_texture_with(
+        &mut self,
+        factory: &mut Factory,
+        path: &str,
+        sampler: SamplerDesc,
+    ) -> Arc<AssetBundle> {
+        let bundle = self.load_texture(factory, path);
+        if sampler == SamplerDesc::default() {
+            return bundle;
+        }
+
+        match self.cache.get_mut(path) {
+            Some(entry) => {
+                if sampler.mipmaps && !entry.mipmapped {
+                    entry.mipmapped = true;
+                    self.mipmap_queue.push(entry.bundle.view.clone());
+                }
+
+                entry.variant(factory, sampler)
+            }
+            None => bundle,
+        }
+    }
+
+    /// Generates the mipmaps of textures loaded with a mipmapped
+    /// sampler since the last call.
+    ///
+    /// Mipmap storage is allocated with every texture, so only the
+    /// levels below the full size image are filled in. Called each
+    /// frame by the application, before anything is drawn.
+    pub fn generate_mipmaps(&mut self, encoder: &mut GraphicsEncoder) {
+        for view in self.mipmap_queue.drain(..) {
+            encoder.generate_mipmap(&view);
+        }
+    }
+
+    /// Creates a greyscale texture of the noise, sampled at each pixel.
+    ///
+    /// Generated textures aren't cached, and are released
+    /// when the returned bundle is dropped.
+    pub fn load_noise_texture(
+        &mut self,
+        factory: &mut Factory,
+        noise: &dyn NoiseGen2D,
+        size: [u32; 2],
+    ) -> Arc<AssetBundle> {
+        let [width, height] = size;
+        let img = noise
+            .generate_image([width as usize, height as usize])
+            .to_rgba();
+
+        upload_texture(factory, width, height, &[&img])
+    }
+
+    /// Starts loading a texture from disk without blocking.
+    ///
+    /// The image is decoded on a background thread. Graphics memory
+    /// can only be allocated on the main thread, so the texture is
+    /// uploaded by `LoadHandle::poll`, or by `process_pending`, which
+    /// also adds it to the cache.
+    pub fn async_load(&self, path: &str) -> LoadHandle {
+        if let Some(bundle) = self.cache.get(path) {
+            return LoadHandle::new(path, LoadState::Uploaded(bundle.bundle.clone()));
+        }
+
+        let handle = LoadHandle::new(path, LoadState::Decoding);
+
+        if self
+            .jobs
+            .send((path.to_owned(), handle.state.clone()))
+            .is_err()
+        {
+            warn!("Texture loader threads have stopped, can't load '{}'", path);
+            *handle.state.lock().unwrap() = LoadState::Failed;
+        }
+
+        self.pending.lock().unwrap().push(handle.clone());
+
+        handle
+    }
+
+    /// Starts loading a texture from disk without blocking, and
+    /// returns a handle that stands in for it until it's ready.
+    ///
+    /// Until the texture is uploaded, the handle's texture is the
+    /// default texture. Entities with both a `TextureHandle` and a
+    /// `GlTexture` have the placeholder swapped for the loaded
+    /// texture by `TextureLoadSystem`, which also publishes a
+    /// `TextureEvent` once the load finishes or fails.
+    ///
+    /// The image dimensions are read from the file header up
+    /// front, so texture rectangles can be calculated before the
+    /// texture is ready.
+    pub fn load_texture_async(&mut self, factory: &mut Factory, path: &str) -> TextureHandle {
+        let placeholder = self.default_texture(factory);
+        let size = image::image_dimensions(path).ok();
+        let load = self.async_load(path);
+
+        // Cached textures are ready immediately.
+        if let Some(bundle) = load.uploaded() {
+            self.events.push(TextureEvent::Ready {
+                path: path.to_owned(),
+                size: bundle.tex_size,
+            });
+        }
+
+        TextureHandle {
+            load,
+            placeholder,
+            size,
+        }
+    }
+
+    /// Uploads the textures of finished asynchronous loads, and
+    /// adds them to the cache.
+    ///
+    /// Intended to be called once per frame.
+    pub fn process_pending(&mut self, factory: &mut Factory) {
+        self.drain_failed();
+        let pending = mem::replace(self.pending.get_mut().unwrap(), vec![]);
+
+        for handle in pending {
+            match handle.poll(factory) {
+                Some(texture) => {
+                    self.events.push(TextureEvent::Ready {
+                        path: handle.path.clone(),
+                        size: texture.bundle.tex_size,
+                    });
+                    self.cache
+                        .entry(handle.path.clone())
+                        .or_insert_with(|| CachedTexture::new(texture.bundle.clone()));
+                }
+                None => self.pending.get_mut().unwrap().push(handle),
+            }
+        }
+    }
+
+    /// Stops tracking asynchronous loads that failed, and
+    /// publishes their failure.
+    ///
+    /// Their handles keep drawing the placeholder.
+    fn drain_failed(&mut self) {
+        let events = &mut self.events;
+
+        self.pending.get_mut().unwrap().retain(|handle| {
+            if handle.is_failed() {
+                events.push(TextureEvent::Failed {
+                    path: handle.path.clone(),
+                });
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Takes the events of asynchronous loads that finished
+    /// since the last call.
+    pub(crate) fn drain_events(&mut self) -> Vec<TextureEvent> {
+        mem::replace(&mut self.events, vec![])
+    }
+
     /// Creates a texture in the cache.
     ///
     /// The key is the unique identifier of the texture.
     ///
     /// The width and height are the dimensions of the image, and the data
     /// is a slice of pixels, represented as slices.
-    fn create_texture(
+    pub(crate) fn create_texture(
         &mut self,
         factory: &mut Factory,
         key: &str,
@@ -62,49 +309,117 @@ impl TextureAssets {
         height: u32,
         data: &[&[u8]],
     ) -> Arc<AssetBundle> {
-        self.cache
+        let entry = self
+            .cache
             .entry(key.to_owned())
-            .or_insert_with(|| {
-                let kind = gfx::texture::Kind::D2(
-                    width as u16,
-                    height as u16,
-                    gfx::texture::AaMode::Single,
-                );
-
-                // Mipmap data is allocated now, generated later
-                let mipmap = gfx::texture::Mipmap::Allocated;
-
-                // Allocate texture on graphics card
-                let (tex, view) = gfx::Factory::create_texture_immutable_u8::<ColorFormat>(
-                    factory, kind, mipmap, data,
-                )
-                .unwrap();
-
-                // Texture Sampler
-                // let sampler = factory.create_sampler_linear();
-                let sampler = gfx::Factory::create_sampler(
-                    factory,
-                    SamplerInfo::new(FilterMethod::Scale, WrapMode::Clamp),
-                );
-
-                // Cache
-                Arc::new(AssetBundle {
-                    tex_size: (width, height),
-                    _tex: tex,
-                    view,
-                    sampler,
-                })
-            })
-            .clone()
+            .or_insert_with(|| CachedTexture::new(upload_texture(factory, width, height, data)));
+
+        entry.usage.reset();
+        entry.bundle.clone()
     }
 
-    /// Remove the given texture from the cache.
+    /// Evicts the given texture from the cache once it's unused.
+    ///
+    /// When no entities or systems use the texture, it is removed
+    /// immediately. Otherwise it is removed by `maintain` as soon as
+    /// it's no longer used, without waiting for the grace period.
     ///
-    /// Will not be deallocated immediately if it is
-    /// still used. Only reduces the reference count
-    /// on the `Arc`.
+    /// Until then the texture stays in the cache, so loading it again
+    /// reuses it and cancels the removal, rather than uploading a
+    /// second copy.
     pub fn remove_texture(&mut self, key: &str) {
-        self.cache.remove(key);
+        let is_unused = match self.cache.get_mut(key) {
+            Some(entry) => {
+                entry.usage.evict = true;
+                entry.is_unused()
+            }
+            None => false,
+        };
+
+        if is_unused {
+            self.cache.remove(key);
+        }
+    }
+
+    /// Evicts textures that are only held by the cache, and that
+    /// haven't been used for the grace period or were removed.
+    ///
+    /// Graphics memory is released when the device is cleaned up at
+    /// the end of the frame. Intended to be called once per frame.
+    pub fn maintain(&mut self) {
+        let grace_period = self.grace_period;
+        let mut evicted = vec![];
+
+        for (key, entry) in self.cache.iter_mut() {
+            if key == DEFAULT_TEXTURE_KEY {
+                continue;
+            }
+
+            let is_unused = entry.is_unused();
+            if entry.usage.tick(is_unused, grace_period) {
+                evicted.push(key.clone());
+            }
+        }
+
+        for key in evicted {
+            trace!("Evicting unused texture '{}'", key);
+            self.cache.remove(&key);
+        }
+    }
+
+    /// Paths of the cached textures loaded from disk.
+    #[cfg(feature = "debug-hot-reload")]
+    pub(crate) fn texture_paths(&self) -> impl Iterator<Item = &str> {
+        self.cache
+            .keys()
+            .map(|key| key.as_str())
+            .filter(|key| *key != DEFAULT_TEXTURE_KEY)
+    }
+
+    /// Decodes a cached texture from disk again, and replaces its
+    /// pixels in place.
+    ///
+    /// Every `GlTexture` sharing the texture sees the new image. Cached
+    /// textures are allocated as dynamic by `upload_texture`, which lets
+    /// the encoder update them. The size of the texture is fixed when
+    /// it's allocated, so images that changed size can't be reloaded.
+    #[cfg(feature = "debug-hot-reload")]
+    pub(crate) fn reload_texture(
+        &self,
+        encoder: &mut GraphicsEncoder,
+        path: &str,
+    ) -> Result<(), TextureError> {
+        let bundle = match self.cache.get(path) {
+            Some(entry) => entry.bundle.clone(),
+            None => return Ok(()),
+        };
+
+        let img = decode_reload(path, bundle.tex_size)?;
+        let (width, height) = img.dimensions();
+        GlTexture::from_bundle(bundle.clone()).sub_update(
+            encoder,
+            [0, 0],
+            [width as u16, height as u16],
+            &img,
+        )?;
+        encoder.generate_mipmap(&bundle.view);
+
+        Ok(())
+    }
+
+    /// Number of cached textures, and an estimate of the
+    /// graphics memory they use.
+    pub fn stats(&self) -> TextureStats {
+        self.cache
+            .values()
+            .fold(TextureStats::default(), |acc, entry| {
+                let (width, height) = entry.bundle.tex_size;
+
+                TextureStats {
+                    count: acc.count + 1,
+                    bytes: acc.bytes + texture_bytes(width, height),
+                }
+            })
     }
 }
 
@@ -114,9 +429,607 @@ impl Default for TextureAssets {
     }
 }
 
+/// Texture held by the cache.
+struct CachedTexture {
+    bundle: Arc<AssetBundle>,
+    /// Bundles sharing the texture, with samplers other than the default.
+    variants: Vec<(SamplerDesc, Arc<AssetBundle>)>,
+    /// Set once the texture's mipmaps are queued for generation.
+    mipmapped: bool,
+    usage: Usage,
+}
+
+impl CachedTexture {
+    fn new(bundle: Arc<AssetBundle>) -> Self {
+        CachedTexture {
+            bundle,
+            variants: vec![],
+            mipmapped: false,
+            usage: Usage::default(),
+        }
+    }
+
+    /// Checks whether the cache holds the only references to the texture.
+    #[inline]
+    fn is_unused(&self) -> bool {
+        Arc::strong_count(&self.bundle) == 1
+            && self
+                .variants
+                .iter()
+                .all(|(_, bundle)| Arc::strong_count(bundle) == 1)
+    }
+
+    /// Bundle of the texture with the given sampler, creating
+    /// the sampler on first use.
+    fn variant(&mut self, factory: &mut Factory, sampler: SamplerDesc) -> Arc<AssetBundle> {
+        if let Some((_, bundle)) = self.variants.iter().find(|(desc, _)| *desc == sampler) {
+            return bundle.clone();
+        }
+
+        let bundle = Arc::new(AssetBundle {
+            tex_size: self.bundle.tex_size,
+            tex: self.bundle.tex.clone(),
+            view: self.bundle.view.clone(),
+            sampler: gfx::Factory::create_sampler(factory, sampler.sampler_info()),
+        });
+        self.variants.push((sampler, bundle.clone()));
+
+        bundle
+    }
+}
+
+/// Tracks how long a cached texture went unused, to decide
+/// when it's evicted.
+#[derive(Debug, Default)]
+struct Usage {
+    /// Number of consecutive frames only the cache held the texture.
+    unused_frames: u32,
+    /// Set when the texture was removed, so it's evicted as
+    /// soon as it's unused.
+    evict: bool,
+}
+
+impl Usage {
+    /// Loading the texture again cancels its removal.
+    fn reset(&mut self) {
+        self.unused_frames = 0;
+        self.evict = false;
+    }
+
+    /// Counts a frame, and checks whether the texture
+    /// should be evicted.
+    fn tick(&mut self, is_unused: bool, grace_period: u32) -> bool {
+        if !is_unused {
+            self.unused_frames = 0;
+            return false;
+        }
+
+        self.unused_frames = self.unused_frames.saturating_add(1);
+        self.evict || self.unused_frames > grace_period
+    }
+}
+
+/// Filtering of a texture when it's scaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /// Takes the nearest texel, keeping pixel art crisp.
+    ///
+    /// Maps to `FilterMethod::Scale`.
+    Nearest,
+    /// Blends the four nearest texels.
+    ///
+    /// Maps to `FilterMethod::Bilinear`.
+    Linear,
+}
+
+/// Sampling of a texture outside of the 0 to 1 range
+/// of texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    /// Repeats the texels at the edge.
+    ///
+    /// Maps to `WrapMode::Clamp`.
+    Clamp,
+    /// Tiles the texture.
+    ///
+    /// Maps to `WrapMode::Tile`.
+    Repeat,
+}
+
+/// Sampler settings of a texture loaded with
+/// `TextureAssets::load_texture_with`.
+///
+/// Defaults to nearest filtering and clamped wrapping, without
+/// mipmaps, which is what `TextureAssets::load_texture` uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// Samples from smaller copies of the texture when it's drawn
+    /// smaller than its size, reducing shimmering in the distance.
+    ///
+    /// Linear filtering blends between mipmap levels, which is
+    /// trilinear filtering. Nearest filtering takes the nearest level.
+    pub mipmaps: bool,
+}
+
+impl SamplerDesc {
+    pub fn new(filter: TextureFilter, wrap: TextureWrap) -> Self {
+        SamplerDesc {
+            filter,
+            wrap,
+            mipmaps: false,
+        }
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// The gfx sampler state for these settings.
+    pub fn sampler_info(&self) -> SamplerInfo {
+        let filter = match (self.filter, self.mipmaps) {
+            (TextureFilter::Nearest, false) => FilterMethod::Scale,
+            (TextureFilter::Linear, false) => FilterMethod::Bilinear,
+            (TextureFilter::Nearest, true) => FilterMethod::Mipmap,
+            (TextureFilter::Linear, true) => FilterMethod::Trilinear,
+        };
+        let wrap = match self.wrap {
+            TextureWrap::Clamp => WrapMode::Clamp,
+            TextureWrap::Repeat => WrapMode::Tile,
+        };
+
+        SamplerInfo::new(filter, wrap)
+    }
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc::new(TextureFilter::Nearest, TextureWrap::Clamp)
+    }
+}
+
+/// Statistics of the textures in `TextureAssets`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextureStats {
+    /// Number of cached textures.
+    pub count: usize,
+    /// Estimated graphics memory used by the cached textures,
+    /// including their mipmaps.
+    pub bytes: usize,
+}
+
+/// Estimated size in bytes of an `RGBA8` texture with a full mipmap chain.
+fn texture_bytes(width: u32, height: u32) -> usize {
+    let (mut width, mut height) = (width.max(1) as usize, height.max(1) as usize);
+    let mut bytes = 0;
+
+    loop {
+        bytes += width * height * 4;
+
+        if width == 1 && height == 1 {
+            break;
+        }
+
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+
+    bytes
+}
+
+/// Decodes the image of a texture being reloaded, which must
+/// keep the size the texture was allocated with.
+#[cfg(feature = "debug-hot-reload")]
+fn decode_reload(path: &str, tex_size: (u32, u32)) -> Result<RgbaImage, TextureError> {
+    let img = image::open(path)
+        .map_err(|err| TextureError::Decode(err.to_string()))?
+        .to_rgba();
+
+    if img.dimensions() != tex_size {
+        return Err(TextureError::SizeChanged {
+            old: tex_size,
+            new: img.dimensions(),
+        });
+    }
+
+    Ok(img)
+}
+
+/// Allocates a texture on the graphics card.
+///
+/// Textures are dynamic, so their pixels can be replaced with
+/// `GlTexture::sub_update`. The encoder refuses to update
+/// immutable textures.
+pub(crate) fn upload_texture(
+    factory: &mut Factory,
+    width: u32,
+    height: u32,
+    data: &[&[u8]],
+) -> Arc<AssetBundle> {
+    let kind = gfx::texture::Kind::D2(width as u16, height as u16, gfx::texture::AaMode::Single);
+    let gfx::format::Format(_, channel) = <ColorFormat as gfx::format::Formatted>::get_format();
+
+    // Mipmap data is allocated now, generated later
+    let mipmap = gfx::texture::Mipmap::Allocated;
+
+    // Allocate texture on graphics card
+    let raw = gfx::Factory::create_texture_raw(
+        factory,
+        texture_info(kind),
+        Some(channel),
+        Some((data, mipmap)),
+    )
+    .unwrap();
+    let levels = (0, raw.get_info().levels - 1);
+    let tex = gfx::memory::Typed::new(raw);
+    let view = gfx::Factory::view_texture_as_shader_resource::<ColorFormat>(
+        factory,
+        &tex,
+        levels,
+        gfx::format::Swizzle::new(),
+    )
+    .unwrap();
+
+    // Texture Sampler
+    // let sampler = factory.create_sampler_linear();
+    let sampler = gfx::Factory::create_sampler(factory, SamplerDesc::default().sampler_info());
+
+    Arc::new(AssetBundle {
+        tex_size: (width, height),
+        tex,
+        view,
+        sampler,
+    })
+}
+
+/// Description of a texture allocated by `upload_texture`,
+/// with a full chain of mipmap levels.
+fn texture_info(kind: gfx::texture::Kind) -> gfx::texture::Info {
+    let gfx::format::Format(surface, _) = <ColorFormat as gfx::format::Formatted>::get_format();
+
+    gfx::texture::Info {
+        kind,
+        levels: kind.get_num_levels(),
+        format: surface,
+        bind: gfx::memory::Bind::SHADER_RESOURCE,
+        usage: gfx::memory::Usage::Dynamic,
+    }
+}
+
+/// Loop of a loader thread, decoding images until the
+/// job queue is closed.
+fn decode_images(jobs: Receiver<(String, Arc<Mutex<LoadState>>)>) {
+    for (path, state) in jobs.iter() {
+        let result = match image::open(&path) {
+            Ok(img) => LoadState::Decoded(img.to_rgba()),
+            Err(err) => {
+                warn!("Failed to load texture '{}': {}", path, err);
+                LoadState::Failed
+            }
+        };
+
+        *state.lock().unwrap() = result;
+    }
+}
+
+/// Progress of an asynchronous texture load.
+enum LoadState {
+    Decoding,
+    Decoded(RgbaImage),
+    Uploaded(Arc<AssetBundle>),
+    Failed,
+}
+
+/// Handle to a texture being loaded by `TextureAssets::async_load`.
+#[derive(Clone)]
+pub struct LoadHandle {
+    path: String,
+    state: Arc<Mutex<LoadState>>,
+}
+
+impl LoadHandle {
+    fn new(path: &str, state: LoadState) -> Self {
+        LoadHandle {
+            path: path.to_owned(),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Checks whether the image has been decoded, without blocking.
+    pub fn is_ready(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            LoadState::Decoded(_) | LoadState::Uploaded(_) => true,
+            LoadState::Decoding | LoadState::Failed => false,
+        }
+    }
+
+    /// Checks whether the image could not be read or decoded.
+    pub fn is_failed(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            LoadState::Failed => true,
+            _ => false,
+        }
+    }
+
+    /// Texture of the load, if it has been uploaded.
+    fn uploaded(&self) -> Option<Arc<AssetBundle>> {
+        match *self.state.lock().unwrap() {
+            LoadState::Uploaded(ref bundle) => Some(bundle.clone()),
+            _ => None,
+        }
+    }
+
+    /// Retrieves the texture if the image has been decoded.
+    ///
+    /// The first successful poll uploads the texture to the graphics
+    /// card, so must be called from the main thread.
+    pub fn poll(&self, factory: &mut Factory) -> Option<GlTexture> {
+        let mut state = self.state.lock().unwrap();
+
+        *state = match mem::replace(&mut *state, LoadState::Decoding) {
+            LoadState::Decoded(img) => {
+                let (width, height) = img.dimensions();
+                LoadState::Uploaded(upload_texture(factory, width, height, &[&img]))
+            }
+            other => other,
+        };
+
+        match *state {
+            LoadState::Uploaded(ref bundle) => Some(GlTexture::from_bundle(bundle.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Texture loaded in the background by `TextureAssets::load_texture_async`,
+/// standing in with a placeholder until it's ready.
+///
+/// As a component, it marks the entity's `GlTexture` to be replaced
+/// by `TextureLoadSystem` once the texture is uploaded. The component
+/// is removed when the load finishes or fails.
+#[derive(Component, Clone)]
+#[storage(DenseVecStorage)]
+pub struct TextureHandle {
+    load: LoadHandle,
+    placeholder: Arc<AssetBundle>,
+    /// Dimensions read from the image header.
+    size: Option<(u32, u32)>,
+}
+
+impl TextureHandle {
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.load.path()
+    }
+
+    /// Dimensions of the image in pixels, read from the file header
+    /// before the image is decoded.
+    ///
+    /// Returns `None` when the header couldn't be read, in which case
+    /// the load will fail.
+    #[inline]
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.size
+    }
+
+    /// Checks whether the texture has been uploaded, and
+    /// replaces the placeholder.
+    pub fn is_loaded(&self) -> bool {
+        self.load.uploaded().is_some()
+    }
+
+    /// Checks whether the image could not be read or decoded. The
+    /// placeholder is kept in its place.
+    pub fn is_failed(&self) -> bool {
+        self.load.is_failed()
+    }
+
+    /// The loaded texture, or the placeholder while loading or
+    /// when the load failed.
+    ///
+    /// Does not upload the texture, that happens in
+    /// `TextureAssets::process_pending`.
+    pub fn texture(&self) -> GlTexture {
+        GlTexture::from_bundle(
+            self.load
+                .uploaded()
+                .unwrap_or_else(|| self.placeholder.clone()),
+        )
+    }
+}
+
+pub type TextureEvents = EventChannel<TextureEvent>;
+
+/// Outcome of an asynchronous texture load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureEvent {
+    /// The texture was uploaded, and replaced its placeholder.
+    ///
+    /// Texture rectangles calculated from the placeholder's
+    /// size should be recalculated.
+    Ready { path: String, size: (u32, u32) },
+    /// The image could not be read or decoded, so the
+    /// placeholder is kept.
+    Failed { path: String },
+}
+
 pub struct AssetBundle {
     pub(crate) tex_size: (u32, u32),
-    _tex: gfx::handle::Texture<Resources, gfx::format::R8_G8_B8_A8>,
+    pub(crate) tex: gfx::handle::Texture<Resources, gfx::format::R8_G8_B8_A8>,
     pub(crate) view: gfx::handle::ShaderResourceView<Resources, [f32; 4]>,
     pub(crate) sampler: gfx::handle::Sampler<Resources>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// Polls the handle until it is no longer decoding.
+    fn wait_for(handle: &LoadHandle) {
+        for _ in 0..500 {
+            if handle.is_ready() || handle.is_failed() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_async_load() {
+        let path = std::env::temp_dir().join("rengine_test_async_load.png");
+        RgbaImage::from_pixel(4, 4, image::Rgba([0xFF, 0xFF, 0xFF, 0xFF]))
+            .save(&path)
+            .unwrap();
+
+        let textures = TextureAssets::new();
+        let handle = textures.async_load(path.to_str().unwrap());
+        wait_for(&handle);
+
+        assert!(handle.is_ready());
+        assert!(!handle.is_failed());
+        match *handle.state.lock().unwrap() {
+            LoadState::Decoded(ref img) => assert_eq!((4, 4), img.dimensions()),
+            _ => panic!("Expected decoded image"),
+        }
+        assert_eq!(1, textures.pending.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_texture_bytes() {
+        // 4x4, 2x2 and 1x1 mipmap levels.
+        assert_eq!((16 + 4 + 1) * 4, texture_bytes(4, 4));
+        assert_eq!((8 + 2 + 1) * 4, texture_bytes(4, 2));
+        assert_eq!(4, texture_bytes(1, 1));
+    }
+
+    #[test]
+    fn test_texture_info() {
+        let kind = gfx::texture::Kind::D2(4, 2, gfx::texture::AaMode::Single);
+        let info = texture_info(kind);
+
+        // Dynamic textures can be updated by the encoder.
+        assert_eq!(gfx::memory::Usage::Dynamic, info.usage);
+        assert!(info.bind.contains(gfx::memory::Bind::SHADER_RESOURCE));
+        // Mipmap levels are allocated, to be generated later.
+        assert_eq!(kind.get_num_levels(), info.levels);
+    }
+
+    #[cfg(feature = "debug-hot-reload")]
+    #[test]
+    fn test_decode_reload() {
+        let path = std::env::temp_dir().join("rengine_test_decode_reload.png");
+        RgbaImage::from_pixel(4, 2, image::Rgba([0xFF, 0x00, 0x00, 0xFF]))
+            .save(&path)
+            .unwrap();
+        let path = path.to_str().unwrap();
+
+        let img = decode_reload(path, (4, 2)).unwrap();
+        assert_eq!(&[0xFF, 0x00, 0x00, 0xFF], &img.get_pixel(3, 1).0);
+
+        // The texture can't be resized in place.
+        assert_eq!(
+            Err(TextureError::SizeChanged {
+                old: (2, 2),
+                new: (4, 2),
+            }),
+            decode_reload(path, (2, 2)).map(|_| ())
+        );
+        assert!(decode_reload("does/not/exist.png", (4, 2)).is_err());
+    }
+
+    #[test]
+    fn test_sampler_desc() {
+        let nearest = SamplerDesc::default().sampler_info();
+        assert_eq!(FilterMethod::Scale, nearest.filter);
+        assert_eq!(WrapMode::Clamp, nearest.wrap_mode.0);
+
+        let linear = SamplerDesc::new(TextureFilter::Linear, TextureWrap::Repeat).sampler_info();
+        assert_eq!(FilterMethod::Bilinear, linear.filter);
+        assert_eq!(WrapMode::Tile, linear.wrap_mode.0);
+    }
+
+    #[test]
+    fn test_sampler_mipmaps() {
+        let desc = SamplerDesc::new(TextureFilter::Linear, TextureWrap::Clamp).with_mipmaps(true);
+        assert_eq!(FilterMethod::Trilinear, desc.sampler_info().filter);
+
+        let desc = SamplerDesc::default().with_mipmaps(true);
+        assert_eq!(FilterMethod::Mipmap, desc.sampler_info().filter);
+        assert_ne!(SamplerDesc::default(), desc);
+
+        // Non-power-of-two levels are rounded down: 5x3, 2x1 and 1x1.
+        assert_eq!((15 + 2 + 1) * 4, texture_bytes(5, 3));
+    }
+
+    #[test]
+    fn test_usage_grace_period() {
+        let mut usage = Usage::default();
+
+        // Kept for the grace period, then evicted.
+        assert!(!usage.tick(true, 2));
+        assert!(!usage.tick(true, 2));
+        assert!(usage.tick(true, 2));
+
+        // Using the texture restarts the count.
+        let mut usage = Usage::default();
+        assert!(!usage.tick(true, 2));
+        assert!(!usage.tick(true, 2));
+        assert!(!usage.tick(false, 2));
+        assert!(!usage.tick(true, 2));
+        assert!(!usage.tick(true, 2));
+        assert!(usage.tick(true, 2));
+    }
+
+    #[test]
+    fn test_usage_removed() {
+        // A removed texture is kept while in use, and evicted
+        // as soon as it's released.
+        let mut usage = Usage::default();
+        usage.evict = true;
+        assert!(!usage.tick(false, 10));
+        assert!(!usage.tick(false, 10));
+        assert!(usage.tick(true, 10));
+
+        // Loading it again cancels the removal.
+        let mut usage = Usage::default();
+        usage.evict = true;
+        usage.reset();
+        assert!(!usage.tick(true, 10));
+        assert!(!usage.evict);
+    }
+
+    #[test]
+    fn test_async_load_missing() {
+        let textures = TextureAssets::new();
+        let handle = textures.async_load("does/not/exist.png");
+        wait_for(&handle);
+
+        assert!(handle.is_failed());
+        assert!(!handle.is_ready());
+    }
+
+    #[test]
+    fn test_async_load_failed_event() {
+        let mut textures = TextureAssets::new();
+        let handle = textures.async_load("does/not/exist.png");
+        wait_for(&handle);
+
+        textures.drain_failed();
+        assert!(textures.pending.lock().unwrap().is_empty());
+        assert_eq!(
+            vec![TextureEvent::Failed {
+                path: "does/not/exist.png".to_owned()
+            }],
+            textures.drain_events()
+        );
+        assert!(textures.drain_events().is_empty());
+    }
+}
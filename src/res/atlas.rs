@@ -0,0 +1,405 @@
+use super::TextureAssets;
+use crate::comp::{GlTexture, TexRect};
+use crate::errors::AtlasError;
+use gfx_device::Factory;
+use image::RgbaImage;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Largest width and height of a packed atlas texture.
+pub const DEFAULT_MAX_ATLAS_SIZE: u32 = 2048;
+
+/// Texture containing many sprites, looked up by name.
+///
+/// Sprites sharing an atlas can be drawn without switching
+/// textures. An atlas is either loaded from an image with a
+/// descriptor file, using `TextureAssets::load_atlas`, or packed
+/// from individual images by an `AtlasBuilder`.
+///
+/// # Example
+///
+/// ```ignore
+/// let atlas = ctx
+///     .world
+///     .write_resource::<TextureAssets>()
+///     .load_atlas(ctx.graphics.factory_mut(), "block.png", "block.toml")?;
+/// let top = atlas.get("top").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct TextureAtlas {
+    texture: GlTexture,
+    rects: BTreeMap<String, TexRect>,
+}
+
+impl TextureAtlas {
+    /// Texture containing all the sprites of the atlas.
+    #[inline]
+    pub fn texture(&self) -> &GlTexture {
+        &self.texture
+    }
+
+    /// Texture coordinates of the sprite with the given name.
+    pub fn get(&self, name: &str) -> Option<TexRect> {
+        self.rects.get(name).cloned()
+    }
+
+    /// Texture coordinates of the six sprites covering the sides of a
+    /// pseudocube, in the order expected by `MeshBuilder::pseudocube`:
+    /// back, front, left, right, bottom and top.
+    pub fn cube_rects(&self, names: [&str; 6]) -> Option<[TexRect; 6]> {
+        Some([
+            self.get(names[0])?,
+            self.get(names[1])?,
+            self.get(names[2])?,
+            self.get(names[3])?,
+            self.get(names[4])?,
+            self.get(names[5])?,
+        ])
+    }
+
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.rects.contains_key(name)
+    }
+
+    /// Names of the sprites in the atlas, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rects.keys().map(String::as_str)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Creates the texture coordinates of the given pixel
+    /// rectangles within the texture.
+    fn new(texture: GlTexture, pixel_rects: &BTreeMap<String, [u32; 4]>) -> Self {
+        let source_rect = texture.source_rect();
+        let rects = pixel_rects
+            .iter()
+            .map(|(name, [x, y, w, h])| (name.clone(), source_rect.sub_rect([*x, *y], [*w, *h])))
+            .collect();
+
+        TextureAtlas { texture, rects }
+    }
+}
+
+/// Layout of an atlas descriptor file.
+///
+/// ```toml
+/// # Sprites as [x, y, width, height], in pixels.
+/// [rects]
+/// block_top = [32, 16, 16, 16]
+/// block_side = [0, 16, 16, 16]
+/// ```
+#[derive(Debug, Deserialize)]
+struct AtlasDescriptor {
+    rects: BTreeMap<String, [u32; 4]>,
+}
+
+impl TextureAssets {
+    /// Loads an atlas image from disk, with the sprite rectangles
+    /// listed in a TOML descriptor file.
+    ///
+    /// The image is cached like any other texture.
+    pub fn load_atlas(
+        &mut self,
+        factory: &mut Factory,
+        image_path: &str,
+        descriptor_path: &str,
+    ) -> Result<TextureAtlas, AtlasError> {
+        let descriptor: AtlasDescriptor = toml::from_str(&fs::read_to_string(descriptor_path)?)?;
+
+        // Checked up front, since loading the texture panics
+        // when the image is missing.
+        let tex_size = image::image_dimensions(image_path)?;
+        for (name, [x, y, w, h]) in &descriptor.rects {
+            if x + w > tex_size.0 || y + h > tex_size.1 {
+                return Err(AtlasError::OutOfBounds {
+                    name: name.clone(),
+                    tex_size,
+                });
+            }
+        }
+
+        let texture = GlTexture::from_bundle(self.load_texture(factory, image_path));
+
+        Ok(TextureAtlas::new(texture, &descriptor.rects))
+    }
+
+    /// Packs the images of the builder into a single texture.
+    ///
+    /// The key identifies the atlas texture in the cache, so
+    /// packing again with the same key returns the texture
+    /// packed the first time.
+    pub fn pack_atlas(
+        &mut self,
+        factory: &mut Factory,
+        key: &str,
+        builder: &AtlasBuilder,
+    ) -> Result<TextureAtlas, AtlasError> {
+        let packed = builder.pack()?;
+        let (width, height) = packed.size;
+
+        let mut image = RgbaImage::new(width, height);
+        for (name, sprite) in &builder.images {
+            let [x, y, _, _] = packed.rects[name];
+            image::imageops::replace(&mut image, sprite, x, y);
+        }
+
+        let bundle = self.create_texture(factory, key, width, height, &[&image]);
+
+        Ok(TextureAtlas::new(
+            GlTexture::from_bundle(bundle),
+            &packed.rects,
+        ))
+    }
+}
+
+/// Collects individual images to be packed into a
+/// `TextureAtlas` by `TextureAssets::pack_atlas`.
+pub struct AtlasBuilder {
+    images: BTreeMap<String, RgbaImage>,
+    packer: ShelfPacker,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Largest width and height of the packed texture.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.packer.max_size = (max_size, max_size);
+        self
+    }
+
+    /// Space left between sprites, in pixels, so texture filtering
+    /// doesn't bleed neighbouring sprites into each other.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.packer.padding = padding;
+        self
+    }
+
+    /// Adds an image to the atlas, replacing any image
+    /// previously added with the same name.
+    pub fn add_image<S>(&mut self, name: S, image: RgbaImage)
+    where
+        S: ToString,
+    {
+        self.images.insert(name.to_string(), image);
+    }
+
+    /// Loads an image from disk, and adds it to the atlas.
+    pub fn load_image<S>(&mut self, name: S, path: &str) -> Result<(), AtlasError>
+    where
+        S: ToString,
+    {
+        let image = image::open(path)?.to_rgba();
+        self.add_image(name, image);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.images.contains_key(name)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Positions of the images within the atlas texture.
+    fn pack(&self) -> Result<PackedAtlas, AtlasError> {
+        self.packer.pack(
+            self.images
+                .iter()
+                .map(|(name, image)| (name.as_str(), image.dimensions())),
+        )
+    }
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        AtlasBuilder {
+            images: BTreeMap::new(),
+            packer: ShelfPacker::new((DEFAULT_MAX_ATLAS_SIZE, DEFAULT_MAX_ATLAS_SIZE)),
+        }
+    }
+}
+
+/// Packs rectangles into rows, called shelves, from the
+/// top left of the atlas.
+///
+/// Rectangles are placed tallest first, so each shelf wastes little
+/// space above its shorter rectangles. Ties are broken by width and
+/// then name, so the same rectangles always produce the same layout,
+/// regardless of the order they're given in.
+#[derive(Debug, Clone)]
+pub struct ShelfPacker {
+    max_size: (u32, u32),
+    padding: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(max_size: (u32, u32)) -> Self {
+        ShelfPacker {
+            max_size,
+            padding: 0,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Positions the named rectangles of the given sizes.
+    ///
+    /// Fails when a rectangle doesn't fit within the maximum size.
+    pub fn pack<'a, I>(&self, sizes: I) -> Result<PackedAtlas, AtlasError>
+    where
+        I: IntoIterator<Item = (&'a str, (u32, u32))>,
+    {
+        let mut sizes: Vec<(&str, (u32, u32))> = sizes.into_iter().collect();
+        sizes.sort_by(|(a_name, (a_w, a_h)), (b_name, (b_w, b_h))| {
+            b_h.cmp(a_h)
+                .then_with(|| b_w.cmp(a_w))
+                .then_with(|| a_name.cmp(b_name))
+        });
+
+        let (max_w, max_h) = self.max_size;
+        let (mut x, mut y, mut shelf_h) = (0, 0, 0);
+        let mut used_w = 0;
+        let mut rects = BTreeMap::new();
+
+        for (name, (w, h)) in sizes {
+            let does_not_fit = || AtlasError::DoesNotFit {
+                name: name.to_owned(),
+                size: (w, h),
+                max_size: self.max_size,
+            };
+
+            if w > max_w {
+                return Err(does_not_fit());
+            }
+
+            // Start a new shelf below the current one.
+            if x + w > max_w {
+                x = 0;
+                y += shelf_h + self.padding;
+                shelf_h = 0;
+            }
+
+            if y + h > max_h {
+                return Err(does_not_fit());
+            }
+
+            rects.insert(name.to_owned(), [x, y, w, h]);
+            used_w = used_w.max(x + w);
+            shelf_h = shelf_h.max(h);
+            x += w + self.padding;
+        }
+
+        // Power of two textures have complete mipmaps.
+        let size = (
+            used_w.max(1).next_power_of_two().min(max_w),
+            (y + shelf_h).max(1).next_power_of_two().min(max_h),
+        );
+
+        Ok(PackedAtlas { size, rects })
+    }
+}
+
+/// Layout of rectangles produced by `ShelfPacker`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedAtlas {
+    /// Width and height of the texture containing all the rectangles.
+    pub size: (u32, u32),
+    /// Pixel rectangles, as `[x, y, width, height]`, by name.
+    pub rects: BTreeMap<String, [u32; 4]>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sizes() -> Vec<(String, (u32, u32))> {
+        (0..20)
+            .map(|i| {
+                (
+                    format!("sprite_{}", i),
+                    (8 + (i * 7) % 25, 4 + (i * 13) % 29),
+                )
+            })
+            .collect()
+    }
+
+    fn pack(packer: &ShelfPacker, sizes: &[(String, (u32, u32))]) -> PackedAtlas {
+        packer
+            .pack(sizes.iter().map(|(name, size)| (name.as_str(), *size)))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_shelf_packer() {
+        let packer = ShelfPacker::new((128, 128)).with_padding(1);
+        let packed = pack(&packer, &sizes());
+        assert_eq!(20, packed.rects.len());
+
+        // Fits within the maximum size.
+        assert!(packed.size.0 <= 128 && packed.size.1 <= 128);
+        for [x, y, w, h] in packed.rects.values() {
+            assert!(x + w <= packed.size.0);
+            assert!(y + h <= packed.size.1);
+        }
+
+        // No overlaps.
+        let rects: Vec<[u32; 4]> = packed.rects.values().cloned().collect();
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                let separate = a[0] + a[2] <= b[0]
+                    || b[0] + b[2] <= a[0]
+                    || a[1] + a[3] <= b[1]
+                    || b[1] + b[3] <= a[1];
+                assert!(separate, "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        // Deterministic regardless of input order.
+        let mut reversed = sizes();
+        reversed.reverse();
+        assert_eq!(packed, pack(&packer, &reversed));
+    }
+
+    #[test]
+    fn test_shelf_packer_does_not_fit() {
+        let packer = ShelfPacker::new((32, 32));
+
+        match packer.pack(vec![("wide", (33, 8))]) {
+            Err(AtlasError::DoesNotFit { name, .. }) => assert_eq!("wide", name),
+            _ => panic!("Expected image to not fit"),
+        }
+
+        match packer.pack(vec![("a", (32, 20)), ("b", (32, 20))]) {
+            Err(AtlasError::DoesNotFit { name, .. }) => assert_eq!("b", name),
+            _ => panic!("Expected second shelf to not fit"),
+        }
+
+        let packed = packer.pack(vec![("a", (16, 16)), ("b", (16, 16))]).unwrap();
+        assert_eq!((32, 16), packed.size);
+    }
+}
@@ -1,17 +1,199 @@
+use std::collections::VecDeque;
 use std::time::Duration;
 
 /// The time it took for the last frame to elapse
+///
+/// The duration given to systems is capped, smoothed and scaled
+/// by `TimeScale`, so pausing or slowing down the game pauses or
+/// slows down every system driven by it. The wall clock time of
+/// the frame is kept as `unscaled`.
 #[derive(Default, Clone)]
-pub struct DeltaTime(pub(crate) Duration);
+pub struct DeltaTime {
+    duration: Duration,
+    unscaled: Duration,
+}
 
 impl DeltaTime {
+    /// Creates a delta time where the scaled and
+    /// unscaled durations are the same.
+    pub fn new(duration: Duration) -> Self {
+        DeltaTime {
+            duration,
+            unscaled: duration,
+        }
+    }
+
     #[inline]
     pub fn duration(&self) -> &Duration {
-        &self.0
+        &self.duration
     }
 
     #[inline]
     pub fn as_secs_float(&self) -> f32 {
-        self.0.as_millis() as f32 / 1000.
+        self.duration.as_millis() as f32 / 1000.
+    }
+
+    /// Wall clock duration of the frame, without capping,
+    /// smoothing or scaling.
+    ///
+    /// For systems that measure real time, like frame rate
+    /// counters, or user interface that keeps working while
+    /// the game is paused.
+    #[inline]
+    pub fn unscaled(&self) -> &Duration {
+        &self.unscaled
+    }
+
+    /// The frame's duration, limited to the given maximum.
+    ///
+    /// Protects systems from the spikes caused by the
+    /// application losing focus, or resuming from sleep.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use rengine::res::DeltaTime;
+    /// # use std::time::Duration;
+    /// let delta_time = DeltaTime::default();
+    /// assert_eq!(Duration::from_secs(0), delta_time.capped(Duration::from_millis(100)));
+    /// ```
+    #[inline]
+    pub fn capped(&self, max: Duration) -> Duration {
+        self.duration.min(max)
+    }
+
+    /// The frame's duration in seconds, limited to the
+    /// given maximum.
+    #[inline]
+    pub fn as_secs_f32_capped(&self, max_secs: f32) -> f32 {
+        self.as_secs_float().min(max_secs)
+    }
+}
+
+/// Multiplier applied to `DeltaTime` before it's given to systems.
+///
+/// Zero pauses all systems driven by delta time, and 0.5 runs
+/// them in slow motion. Negative scales are treated as zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale(pub f32);
+
+impl TimeScale {
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.0 <= 0.0
+    }
+
+    /// Scales the duration, rounded down to the nanosecond.
+    pub fn scale(&self, duration: Duration) -> Duration {
+        let nanos = duration.as_nanos() as f64 * f64::from(self.0.max(0.0));
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
+/// Turns the wall clock duration of each frame
+/// into the `DeltaTime` given to systems.
+pub(crate) struct FrameTimer {
+    /// Longest frame duration, before smoothing.
+    max_delta: Option<Duration>,
+    /// Number of frames averaged by smoothing.
+    window_size: usize,
+    /// Capped durations of the most recent frames, oldest first.
+    window: VecDeque<Duration>,
+}
+
+impl FrameTimer {
+    /// A window size of one frame turns smoothing off.
+    pub(crate) fn new(max_delta: Option<Duration>, window_size: usize) -> Self {
+        let window_size = window_size.max(1);
+
+        FrameTimer {
+            max_delta,
+            window_size,
+            window: VecDeque::with_capacity(window_size),
+        }
+    }
+
+    /// Records the frame's duration, capped to the maximum,
+    /// and averages it with the previous frames before scaling.
+    ///
+    /// Capping first keeps a single hitch from dragging the
+    /// average up for the whole window.
+    pub(crate) fn tick(&mut self, elapsed: Duration, time_scale: TimeScale) -> DeltaTime {
+        let capped = match self.max_delta {
+            Some(max) => elapsed.min(max),
+            None => elapsed,
+        };
+
+        if self.window.len() == self.window_size {
+            self.window.pop_front();
+        }
+        self.window.push_back(capped);
+
+        let smoothed = self.window.iter().sum::<Duration>() / self.window.len() as u32;
+
+        DeltaTime {
+            duration: time_scale.scale(smoothed),
+            unscaled: elapsed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capped() {
+        let delta_time = DeltaTime::new(Duration::from_secs(100));
+        assert_eq!(
+            Duration::from_millis(100),
+            delta_time.capped(Duration::from_millis(100))
+        );
+        assert!((delta_time.as_secs_f32_capped(0.1) - 0.1).abs() < ::std::f32::EPSILON);
+
+        // Short frames are left alone.
+        let delta_time = DeltaTime::new(Duration::from_millis(16));
+        assert_eq!(
+            Duration::from_millis(16),
+            delta_time.capped(Duration::from_millis(100))
+        );
+        assert!((delta_time.as_secs_f32_capped(0.1) - 0.016).abs() < ::std::f32::EPSILON);
+    }
+
+    #[test]
+    fn test_frame_timer() {
+        let ms = Duration::from_millis;
+        let mut timer = FrameTimer::new(Some(ms(100)), 4);
+
+        // Hitch is capped before it's averaged.
+        assert_eq!(ms(10), *timer.tick(ms(10), TimeScale::default()).duration());
+        let delta_time = timer.tick(ms(1000), TimeScale::default());
+        assert_eq!(ms(55), *delta_time.duration());
+        assert_eq!(ms(1000), *delta_time.unscaled());
+
+        // Hitch leaves the window.
+        for _ in 0..3 {
+            timer.tick(ms(20), TimeScale::default());
+        }
+        assert_eq!(ms(20), *timer.tick(ms(20), TimeScale::default()).duration());
+
+        // Paused and slow motion.
+        let delta_time = timer.tick(ms(20), TimeScale(0.0));
+        assert_eq!(ms(0), *delta_time.duration());
+        assert_eq!(ms(20), *delta_time.unscaled());
+        assert_eq!(ms(10), *timer.tick(ms(20), TimeScale(0.5)).duration());
+        assert_eq!(ms(0), *timer.tick(ms(20), TimeScale(-1.0)).duration());
+
+        // No smoothing.
+        let mut timer = FrameTimer::new(None, 0);
+        timer.tick(ms(10), TimeScale::default());
+        let delta_time = timer.tick(ms(1000), TimeScale::default());
+        assert_eq!(ms(1000), *delta_time.duration());
     }
 }
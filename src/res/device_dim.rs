@@ -1,5 +1,6 @@
 use glutin::dpi::{LogicalSize, PhysicalSize};
 use glutin::WindowedContext;
+use shrev::EventChannel;
 
 #[derive(Debug)]
 pub struct DeviceDimensions {
@@ -53,3 +54,33 @@ impl Default for DeviceDimensions {
         }
     }
 }
+
+/// Change to the window, published to `DeviceEvents` by the app
+/// whenever the `DeviceDimensions` resource is replaced.
+///
+/// A change of DPI factor is followed by a `Resized` event with the
+/// new physical size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceEvent {
+    Resized {
+        logical: LogicalSize,
+        physical: PhysicalSize,
+        dpi_factor: f64,
+    },
+    DpiChanged {
+        dpi_factor: f64,
+    },
+}
+
+impl DeviceEvent {
+    /// Event describing the given device dimensions.
+    pub fn resized(dim: &DeviceDimensions) -> Self {
+        DeviceEvent::Resized {
+            logical: dim.logical_size,
+            physical: dim.physical_size,
+            dpi_factor: dim.dpi_factor,
+        }
+    }
+}
+
+pub type DeviceEvents = EventChannel<DeviceEvent>;
@@ -0,0 +1,18 @@
+/// Number of frames the application has run.
+///
+/// Incremented at the start of each frame, so the
+/// first frame is number 1.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCounter(pub(crate) u64);
+
+impl FrameCounter {
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    pub(crate) fn incr(&mut self) {
+        self.0 += 1;
+    }
+}
@@ -1,11 +1,17 @@
 //! Single instance components, called *resources*.
 
 mod assets;
+mod atlas;
 mod delta_time;
 mod device_dim;
+mod frame_counter;
+mod rng;
 mod view_port;
 
 pub use assets::*;
+pub use atlas::*;
 pub use delta_time::*;
 pub use device_dim::*;
+pub use frame_counter::*;
+pub use rng::*;
 pub use view_port::*;
@@ -0,0 +1,276 @@
+use rlua::{UserData, UserDataMethods};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Seed used by the default generator, so runs are
+/// reproducible unless a seed is chosen.
+///
+/// Fits in a Lua integer, so scripts can reseed with it.
+pub const DEFAULT_RNG_SEED: u64 = 0x053c_49e6_748f_ea9b;
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/// Deterministic random number generator, shared by
+/// gameplay systems as a world resource.
+///
+/// A small PCG32 generator. The same seed always produces the
+/// same sequence, on every platform, so a run can be replayed
+/// by recording the seed.
+///
+/// Cloning copies the generator, so the copy continues the same
+/// sequence independently. To draw from the same sequence in
+/// several places, such as scripts, use `share`.
+///
+/// Not suitable for cryptography.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::res::Rng;
+/// let mut a = Rng::new(42);
+/// let mut b = Rng::new(42);
+///
+/// assert_eq!(a.next_u32(), b.next_u32());
+///
+/// let roll = a.range(1, 7);
+/// assert!(roll >= 1 && roll < 7);
+/// ```
+#[derive(Debug)]
+pub struct Rng {
+    pcg: Arc<Mutex<Pcg32>>,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut pcg = Pcg32 { seed, state: 0 };
+        pcg.reseed(seed);
+
+        Rng {
+            pcg: Arc::new(Mutex::new(pcg)),
+        }
+    }
+
+    /// Handle to the same generator, so numbers drawn through
+    /// either handle continue a single sequence.
+    ///
+    /// Used to give scripts the world's generator.
+    pub fn share(&self) -> Self {
+        Rng {
+            pcg: self.pcg.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pcg32> {
+        // The state is always valid, even after a panic.
+        self.pcg.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Seed the generator was last seeded with.
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.lock().seed
+    }
+
+    /// Restarts the generator's sequence from the given seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.lock().reseed(seed);
+    }
+
+    /// Next number in the sequence, uniformly distributed
+    /// over all `u32` values.
+    pub fn next_u32(&mut self) -> u32 {
+        self.lock().next_u32()
+    }
+
+    /// Uniformly distributed number in the range `[0.0, 1.0)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // Upper 24 bits fit exactly in the mantissa.
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Uniformly distributed integer in the range `[low, high)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low` isn't less than `high`.
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        assert!(
+            low < high,
+            "Range low {} must be less than high {}",
+            low,
+            high
+        );
+
+        let span = (i64::from(high) - i64::from(low)) as u64;
+        let offset = (u64::from(self.next_u32()) * span) >> 32;
+        (i64::from(low) + offset as i64) as i32
+    }
+
+    /// Uniformly distributed number in the range `[low, high)`.
+    pub fn range_f32(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.next_f32()
+    }
+}
+
+impl Clone for Rng {
+    fn clone(&self) -> Self {
+        Rng {
+            pcg: Arc::new(Mutex::new(*self.lock())),
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(DEFAULT_RNG_SEED)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pcg32 {
+    seed: u64,
+    state: u64,
+}
+
+impl Pcg32 {
+    fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.state = 0;
+        self.step();
+        self.state = self.state.wrapping_add(seed);
+        self.step();
+    }
+
+    #[inline]
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+}
+
+/// Scripts use the generator through methods, as in
+/// `rng:range(1, 7)`.
+///
+/// Lua integers are signed, so seeds above `i64::MAX`
+/// appear as negative numbers in scripts.
+impl UserData for Rng {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("seed", |_, rng, ()| Ok(rng.seed() as i64));
+        methods.add_method_mut("reseed", |_, rng, seed: i64| {
+            rng.reseed(seed as u64);
+            Ok(())
+        });
+        methods.add_method_mut("next_u32", |_, rng, ()| Ok(rng.next_u32()));
+        methods.add_method_mut("next_f32", |_, rng, ()| Ok(rng.next_f32()));
+        methods.add_method_mut("range", |_, rng, (low, high): (i32, i32)| {
+            if low >= high {
+                return Err(rlua::Error::RuntimeError(format!(
+                    "range low {} must be less than high {}",
+                    low, high
+                )));
+            }
+
+            Ok(rng.range(low, high))
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_known_sequence() {
+        let mut rng = Rng::new(42);
+        let sequence: Vec<u32> = (0..4).map(|_| rng.next_u32()).collect();
+        assert_eq!(
+            vec![3_270_867_926, 1_795_671_209, 1_924_641_435, 1_143_034_755],
+            sequence
+        );
+        assert_eq!(16_101_214, (rng.next_f32() * 16_777_216.0) as u32);
+
+        // Reseeding replays the sequence.
+        rng.reseed(42);
+        assert_eq!(3_270_867_926, rng.next_u32());
+        assert_eq!(42, rng.seed());
+    }
+
+    #[test]
+    fn test_share() {
+        let mut rng = Rng::new(42);
+        let mut shared = rng.share();
+        let mut copy = rng.clone();
+
+        let first = shared.next_u32();
+        assert_eq!(first, copy.next_u32());
+
+        // Handles continue the same sequence, copies don't.
+        let second = rng.next_u32();
+        assert_ne!(first, second);
+        assert_eq!(second, copy.next_u32());
+
+        shared.reseed(7);
+        assert_eq!(7, rng.seed());
+        assert_eq!(42, copy.seed());
+    }
+
+    #[test]
+    fn test_range() {
+        let mut rng = Rng::default();
+
+        for _ in 0..1000 {
+            let value = rng.range(-3, 3);
+            assert!(value >= -3 && value < 3);
+
+            let value = rng.range_f32(2.0, 4.0);
+            assert!(value >= 2.0 && value < 4.0);
+        }
+
+        // Full span doesn't overflow.
+        rng.range(::std::i32::MIN, ::std::i32::MAX);
+    }
+
+    #[test]
+    fn test_lua_rng() {
+        let lua = rlua::Lua::new();
+
+        let result: rlua::Result<()> = lua.context(|lua_ctx| {
+            lua_ctx.globals().set("rng", Rng::new(42))?;
+
+            let value: u32 = lua_ctx.load("return rng:next_u32()").eval()?;
+            assert_eq!(3_270_867_926, value);
+
+            let value: u32 = lua_ctx
+                .load("rng:reseed(42); return rng:next_u32()")
+                .eval()?;
+            assert_eq!(3_270_867_926, value);
+
+            assert!(lua_ctx.load("rng:range(3, 3)").exec().is_err());
+
+            // Seeds round trip through Lua integers.
+            let mut rng = Rng::new(::std::u64::MAX);
+            let expected = rng.next_u32();
+            lua_ctx.globals().set("rng", rng)?;
+            let value: u32 = lua_ctx
+                .load("rng:reseed(rng:seed()); return rng:next_u32()")
+                .eval()?;
+            assert_eq!(expected, value);
+            let seed: i64 = lua_ctx.load("return rng:seed()").eval()?;
+            assert_eq!(::std::u64::MAX, seed as u64);
+
+            Ok(())
+        });
+        result.unwrap();
+    }
+}
@@ -1,12 +1,29 @@
+use super::DeviceDimensions;
+use crate::colors::Color;
+use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use nalgebra::{Matrix4, Vector3};
+
 /// View port rectangle used for communicating target window size to
 /// rendering systems.
 ///
 /// The view port is concerned with the physical size of the device.
 ///
+/// The rectangle is in physical pixels, with its origin at the bottom
+/// left of the window, as expected by OpenGL. Rendering systems map
+/// their projection into the rectangle with `clip_transform`, and clip
+/// to it with the scissor test, so the scene and the GUI are drawn
+/// inside the view port. Picking with the mouse, like
+/// `raycast_from_camera` and GUI hit testing, maps the cursor into the
+/// view port the same way.
+///
 /// see [Gfx, windows, and resizing](https://falseidolfactory.com/2018/05/28/gfx-windows-and-resizing.html)
 #[derive(Debug)]
 pub struct ViewPort {
     pub(crate) rect: gfx::Rect,
+
+    /// Color of the window outside the view port, when
+    /// the active camera adds bars to keep its aspect ratio.
+    pub(crate) bar_color: Option<Color>,
 }
 
 impl ViewPort {
@@ -19,6 +36,333 @@ impl ViewPort {
                 w: device_size.0,
                 h: device_size.1,
             },
+            bar_color: None,
+        }
+    }
+
+    /// Create a view port rectangle covering part of the device target,
+    /// such as the scene view of an editor with docked panels.
+    pub fn with_rect(x: u16, y: u16, w: u16, h: u16) -> Self {
+        ViewPort {
+            rect: gfx::Rect { x, y, w, h },
+            bar_color: None,
+        }
+    }
+
+    /// Rectangle that rendering is clipped to, in physical pixels.
+    #[inline]
+    pub fn scissor_rect(&self) -> &gfx::Rect {
+        &self.rect
+    }
+
+    /// Color of the bars outside the view port, set by
+    /// `CameraResizeSystem` for letter boxed views.
+    #[inline]
+    pub fn bar_color(&self) -> Option<Color> {
+        self.bar_color
+    }
+
+    /// Size of the view port in physical pixels.
+    #[inline]
+    pub fn physical_size(&self) -> PhysicalSize {
+        PhysicalSize::new(f64::from(self.rect.w), f64::from(self.rect.h))
+    }
+
+    /// Size of the view port in logical pixels.
+    #[inline]
+    pub fn logical_size(&self, dpi_factor: f64) -> LogicalSize {
+        self.physical_size().to_logical(dpi_factor)
+    }
+
+    /// Matrix that maps the clip space of a projection covering the
+    /// whole device onto the view port.
+    ///
+    /// The graphics pipeline always renders to the whole target, so
+    /// projections made for the size of the view port are multiplied
+    /// by this matrix to be drawn inside it.
+    pub fn clip_transform(&self, device_size: PhysicalSize) -> Matrix4<f32> {
+        let (dev_w, dev_h) = (device_size.width as f32, device_size.height as f32);
+        if dev_w <= 0.0 || dev_h <= 0.0 {
+            return Matrix4::identity();
+        }
+
+        let (x, y) = (f32::from(self.rect.x), f32::from(self.rect.y));
+        let (w, h) = (f32::from(self.rect.w), f32::from(self.rect.h));
+
+        // Scales to the view port's share of the device, then moves the
+        // center. Translation in clip space is multiplied by w, so it
+        // survives the perspective division.
+        let scale = Vector3::new(w / dev_w, h / dev_h, 1.0);
+        let center = Vector3::new(
+            (2.0 * x + w) / dev_w - 1.0,
+            (2.0 * y + h) / dev_h - 1.0,
+            0.0,
+        );
+
+        Matrix4::new_translation(&center) * Matrix4::new_nonuniform_scaling(&scale)
+    }
+
+    /// Maps a cursor position, in physical pixels from the top left of
+    /// the window, to normalized device coordinates within the view port.
+    ///
+    /// Returns `None` when the position is outside the view port.
+    pub fn to_ndc(&self, screen_pos: PhysicalPosition, device_height: f64) -> Option<[f32; 2]> {
+        let gfx::Rect { x, y, w, h } = self.rect;
+        if w == 0 || h == 0 {
+            return None;
+        }
+
+        // Flip the cursor to the bottom left origin of the view port.
+        let local_x = screen_pos.x - f64::from(x);
+        let local_y = device_height - screen_pos.y - f64::from(y);
+
+        if local_x < 0.0 || local_y < 0.0 || local_x > f64::from(w) || local_y > f64::from(h) {
+            return None;
+        }
+
+        Some([
+            (local_x / f64::from(w) * 2.0 - 1.0) as f32,
+            (local_y / f64::from(h) * 2.0 - 1.0) as f32,
+        ])
+    }
+
+    /// Checks whether a cursor position, in logical pixels from the
+    /// top left of the window, is inside the view port.
+    pub fn contains_logical(&self, logical_pos: [f32; 2], device_dim: &DeviceDimensions) -> bool {
+        let dpi_factor = device_dim.dpi_factor();
+        let screen_pos = PhysicalPosition::new(
+            f64::from(logical_pos[0]) * dpi_factor,
+            f64::from(logical_pos[1]) * dpi_factor,
+        );
+
+        self.to_ndc(screen_pos, device_dim.physical_size().height)
+            .is_some()
+    }
+
+    /// Maps a cursor position, in logical pixels from the top left of
+    /// the window, to logical pixels from the top left of the view port.
+    ///
+    /// The GUI is laid out inside the view port, so widgets are hit
+    /// tested with the mapped position.
+    pub fn to_local_logical(
+        &self,
+        logical_pos: [f32; 2],
+        device_dim: &DeviceDimensions,
+    ) -> [f32; 2] {
+        let dpi_factor = device_dim.dpi_factor();
+        let device_h = device_dim.physical_size().height;
+
+        // The rectangle's origin is at the bottom left of the window.
+        let left = f64::from(self.rect.x) / dpi_factor;
+        let top = (device_h - f64::from(self.rect.y) - f64::from(self.rect.h)) / dpi_factor;
+
+        [logical_pos[0] - left as f32, logical_pos[1] - top as f32]
+    }
+}
+
+/// How the `ViewPort` fits a window that doesn't match the game's
+/// designed aspect ratio.
+///
+/// Applied by `CameraResizeSystem` when the window is resized. Policies
+/// other than `Stretch` take precedence over the aspect ratio policies
+/// of cameras, and leave black bars around the view port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewPortPolicy {
+    /// The view port covers the whole window.
+    Stretch,
+
+    /// The view port is the largest centered part of the window
+    /// with the given aspect ratio, width over height.
+    FixedAspect { ratio: f32 },
+
+    /// The view port is the largest whole multiple of the base size,
+    /// in physical pixels, that fits in the window, so pixel art
+    /// scales evenly. Windows smaller than the base size are fitted
+    /// by its aspect ratio instead.
+    IntegerScale { base_w: u16, base_h: u16 },
+}
+
+impl ViewPortPolicy {
+    /// Part of the device covered by the view port, in physical pixels.
+    pub fn view_rect(&self, device_size: (u16, u16)) -> gfx::Rect {
+        let (w, h) = device_size;
+        let full = gfx::Rect { x: 0, y: 0, w, h };
+        if w == 0 || h == 0 {
+            return full;
         }
+
+        match *self {
+            ViewPortPolicy::Stretch => full,
+            ViewPortPolicy::FixedAspect { ratio } if ratio > 0.0 => fit_rect(full, ratio),
+            ViewPortPolicy::FixedAspect { .. } => full,
+            ViewPortPolicy::IntegerScale { base_w, base_h } => {
+                integer_scale_rect(full, (base_w, base_h))
+            }
+        }
+    }
+}
+
+impl Default for ViewPortPolicy {
+    fn default() -> Self {
+        ViewPortPolicy::Stretch
+    }
+}
+
+/// Centered part of the device with the given aspect
+/// ratio, and bars above and below.
+pub(crate) fn letter_box_rect(full: gfx::Rect, aspect_ratio: f32) -> gfx::Rect {
+    let h = (f32::from(full.w) / aspect_ratio).round() as u16;
+    gfx::Rect {
+        y: (full.h - h) / 2,
+        h,
+        ..full
+    }
+}
+
+/// Centered part of the device with the given aspect
+/// ratio, and bars on the left and right.
+pub(crate) fn pillarbox_rect(full: gfx::Rect, aspect_ratio: f32) -> gfx::Rect {
+    let w = (f32::from(full.h) * aspect_ratio).round() as u16;
+    gfx::Rect {
+        x: (full.w - w) / 2,
+        w,
+        ..full
+    }
+}
+
+/// Largest centered part of the device with the given aspect ratio.
+pub(crate) fn fit_rect(full: gfx::Rect, aspect_ratio: f32) -> gfx::Rect {
+    let device_aspect = f32::from(full.w) / f32::from(full.h);
+
+    if device_aspect < aspect_ratio {
+        letter_box_rect(full, aspect_ratio)
+    } else if device_aspect > aspect_ratio {
+        pillarbox_rect(full, aspect_ratio)
+    } else {
+        full
+    }
+}
+
+/// Largest centered multiple of the base size that fits in the device.
+///
+/// Devices smaller than the base size are fitted by its
+/// aspect ratio instead, scaling the view down.
+pub(crate) fn integer_scale_rect(full: gfx::Rect, base_size: (u16, u16)) -> gfx::Rect {
+    let (base_w, base_h) = base_size;
+    if base_w == 0 || base_h == 0 {
+        return full;
+    }
+
+    let scale = (full.w / base_w).min(full.h / base_h);
+    if scale == 0 {
+        return fit_rect(full, f32::from(base_w) / f32::from(base_h));
+    }
+
+    let (w, h) = (base_w * scale, base_h * scale);
+    gfx::Rect {
+        x: (full.w - w) / 2,
+        y: (full.h - h) / 2,
+        w,
+        h,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_view_port_ndc() {
+        // Right half of a 800x600 window
+        let view_port = ViewPort::with_rect(400, 0, 400, 600);
+
+        assert_eq!(
+            Some([0.0, 0.0]),
+            view_port.to_ndc(PhysicalPosition::new(600.0, 300.0), 600.0)
+        );
+        assert_eq!(
+            Some([-1.0, 1.0]),
+            view_port.to_ndc(PhysicalPosition::new(400.0, 0.0), 600.0)
+        );
+        assert_eq!(
+            None,
+            view_port.to_ndc(PhysicalPosition::new(100.0, 300.0), 600.0)
+        );
+    }
+
+    #[test]
+    fn test_clip_transform() {
+        // Letter boxed 800x450 view in a 800x600 window
+        let view_port = ViewPort::with_rect(0, 75, 800, 450);
+        let m = view_port.clip_transform(PhysicalSize::new(800.0, 600.0));
+
+        // Corners of the projection land on the corners of the view port,
+        // before and after the perspective division.
+        for w in [1.0, 4.0].iter() {
+            let top_right = m * nalgebra::Vector4::new(*w, *w, 0.0, *w);
+            assert!((top_right.x / top_right.w - 1.0).abs() < 1.0e-5);
+            assert!((top_right.y / top_right.w - 0.75).abs() < 1.0e-5);
+
+            let bottom_left = m * nalgebra::Vector4::new(-*w, -*w, 0.0, *w);
+            assert!((bottom_left.x / bottom_left.w + 1.0).abs() < 1.0e-5);
+            assert!((bottom_left.y / bottom_left.w + 0.75).abs() < 1.0e-5);
+        }
+
+        // Covering the whole window changes nothing.
+        let view_port = ViewPort::new((800, 600));
+        let m = view_port.clip_transform(PhysicalSize::new(800.0, 600.0));
+        assert_eq!(Matrix4::identity(), m);
+    }
+
+    #[test]
+    fn test_to_local_logical() {
+        // Pillarboxed 1280x800 view in a 1600x800 window, at 2x scale
+        let view_port = ViewPort::with_rect(160, 0, 1280, 800);
+        let device_dim = DeviceDimensions::new(2.0, LogicalSize::new(800.0, 400.0));
+
+        assert_eq!(
+            [20.0, 10.0],
+            view_port.to_local_logical([100.0, 10.0], &device_dim)
+        );
+        assert_eq!(
+            LogicalSize::new(640.0, 400.0),
+            view_port.logical_size(device_dim.dpi_factor())
+        );
+
+        // Letter boxed 800x450 view in a 800x600 window
+        let view_port = ViewPort::with_rect(0, 75, 800, 450);
+        let device_dim = DeviceDimensions::new(1.0, LogicalSize::new(800.0, 600.0));
+        assert_eq!(
+            [0.0, 0.0],
+            view_port.to_local_logical([0.0, 75.0], &device_dim)
+        );
+    }
+
+    #[test]
+    fn test_view_port_policy() {
+        let rect_of = |policy: ViewPortPolicy, size: (u16, u16)| {
+            let rect = policy.view_rect(size);
+            (rect.x, rect.y, rect.w, rect.h)
+        };
+
+        let stretch = ViewPortPolicy::Stretch;
+        assert_eq!((0, 0, 800, 600), rect_of(stretch, (800, 600)));
+
+        let fixed = ViewPortPolicy::FixedAspect { ratio: 16.0 / 9.0 };
+        assert_eq!((0, 0, 1280, 720), rect_of(fixed, (1280, 720)));
+        assert_eq!((0, 75, 800, 450), rect_of(fixed, (800, 600)));
+        assert_eq!((200, 0, 1280, 720), rect_of(fixed, (1680, 720)));
+
+        let pixel_art = ViewPortPolicy::IntegerScale {
+            base_w: 320,
+            base_h: 180,
+        };
+        assert_eq!((0, 0, 1280, 720), rect_of(pixel_art, (1280, 720)));
+        assert_eq!((20, 30, 960, 540), rect_of(pixel_art, (1000, 600)));
+        assert_eq!((640, 20, 640, 360), rect_of(pixel_art, (1920, 400)));
+        assert_eq!((0, 35, 160, 90), rect_of(pixel_art, (160, 160)));
+
+        // Minimised windows have no size to fit.
+        assert_eq!((0, 0, 0, 0), rect_of(fixed, (0, 0)));
     }
 }
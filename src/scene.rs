@@ -23,6 +23,13 @@ pub trait Scene {
         None
     }
 
+    /// Called after the window was resized, with its new physical size.
+    ///
+    /// The `DeviceDimensions` resource is already updated, and
+    /// `CameraResizeSystem` has resized the camera projections and
+    /// fitted the `ViewPort` to the aspect ratio policies.
+    fn on_resize(&mut self, _ctx: &mut Context<'_>, _new_size: (u32, u32)) {}
+
     fn on_message(&mut self) {}
 }
 
@@ -226,6 +233,18 @@ impl SceneStack {
         }
     }
 
+    pub fn dispatch_resize(
+        &mut self,
+        world: &mut World,
+        graphics: &mut GraphicContext,
+        new_size: (u32, u32),
+    ) {
+        if let Some(ref mut scene) = self.current_mut() {
+            let mut ctx = Context { world, graphics };
+            scene.on_resize(&mut ctx, new_size);
+        }
+    }
+
     pub fn dispatch_event(
         &mut self,
         world: &mut World,
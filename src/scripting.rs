@@ -2,7 +2,7 @@
 use std::{
     borrow::Borrow,
     collections::HashSet,
-    fs::{canonicalize, File},
+    fs::{self, canonicalize, File},
     io::prelude::*,
     path::{Path, PathBuf},
 };
@@ -11,7 +11,10 @@ use walkdir::{DirEntry, WalkDir};
 use log::{trace, warn, Level};
 use regex::Regex;
 use rlua::Lua;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AtlasError;
+use crate::res::{AtlasBuilder, Rng};
 
 mod data_definer;
 pub mod errors;
@@ -25,7 +28,9 @@ use prototype::{Prototype, PrototypeTable};
 const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const DEFAULT_MOD_META_FILENAME: &str = "mod.toml";
+pub const DEFAULT_LOAD_ORDER_FILENAME: &str = "load_order.toml";
 pub const DEFAULT_DATA_FILENAME: &str = "data.lua";
+pub const DEFAULT_SPRITE_DIR: &str = "sprites";
 pub const DEFAULT_MOD_NAME_REGEX: &str = "^[a-zA-Z][a-zA-Z0-9_]+$";
 pub const DEFAULT_PROTO_KEY_FIELD: &str = "name";
 
@@ -35,6 +40,9 @@ pub struct Mods {
     mods: Vec<ModBundle>,
     prototypes: PrototypeTable,
     settings: ModSettings,
+
+    /// Generator given to scripts as `rng`.
+    rng: Rng,
 }
 
 impl Mods {
@@ -61,9 +69,12 @@ impl Mods {
                 max_search_depth: 2,
                 mod_meta_filename: DEFAULT_MOD_META_FILENAME.to_string(),
                 mod_data_filename: DEFAULT_DATA_FILENAME.to_string(),
+                mod_sprite_dir: DEFAULT_SPRITE_DIR.to_string(),
                 mod_name_re: Regex::new(DEFAULT_MOD_NAME_REGEX).unwrap(),
                 prototype_key_field: DEFAULT_PROTO_KEY_FIELD.to_string(),
+                force_order: false,
             },
+            rng: Rng::default(),
         })
     }
 
@@ -72,6 +83,19 @@ impl Mods {
         &self.settings
     }
 
+    #[inline]
+    pub fn settings_mut(&mut self) -> &mut ModSettings {
+        &mut self.settings
+    }
+
+    /// Shares the generator with scripts, so they draw from
+    /// the same sequence, usually the world's `Rng` resource.
+    ///
+    /// Applies to Lua states created by later data stages.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.share();
+    }
+
     /// Access to the inner [`PrototypeTable`](struct.PrototypeTable.html).
     #[inline]
     pub fn prototypes(&self) -> &PrototypeTable {
@@ -158,17 +182,79 @@ impl Mods {
         }
 
         // TODO: Load order and IDs by dependencies.
-        for (index, mod_bundle) in mods.iter_mut().enumerate() {
-            mod_bundle.meta.id = ModId(index);
-        }
-
         self.mods = mods;
+        self.assign_ids();
 
         trace!("Loading mods Done");
 
         Ok(())
     }
 
+    /// Loads all mods, in the order listed in a load order file.
+    ///
+    /// Mods in the file that aren't found are skipped. Mods that
+    /// aren't in the file are loaded after the listed mods, sorted
+    /// by name, unless [`ModSettings::force_order`] is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ModError::LoadOrderUnknownMod`](enum.ModError.html) when the order is
+    /// forced and a mod isn't in the file. Mods are left unloaded on error.
+    pub fn load_mods_with_order(&mut self, path: &Path) -> self::errors::Result<()> {
+        let contents = fs::read(path).map_err(ModError::IoError)?;
+        let load_order: LoadOrderModel =
+            toml::from_slice(&contents).map_err(ModError::LoadOrderParse)?;
+
+        self.load_mods()?;
+
+        let mut unordered = ::std::mem::replace(&mut self.mods, vec![]);
+        let mut ordered = Vec::with_capacity(unordered.len());
+
+        for name in load_order.order.iter() {
+            match unordered.iter().position(|m| &m.meta.name == name) {
+                Some(index) => ordered.push(unordered.remove(index)),
+                None => warn!("Mod '{}' in load order not found, skipping", name),
+            }
+        }
+
+        // Filesystem walk order varies, so unlisted mods are sorted.
+        unordered.sort_by(|a, b| a.meta.name.cmp(&b.meta.name));
+        for mod_bundle in unordered {
+            if self.settings.force_order {
+                return Err(ModError::LoadOrderUnknownMod(mod_bundle.meta.name));
+            }
+
+            warn!(
+                "Mod '{}' not in load order, loading last",
+                mod_bundle.meta.name
+            );
+            ordered.push(mod_bundle);
+        }
+
+        self.mods = ordered;
+        self.assign_ids();
+
+        Ok(())
+    }
+
+    /// Writes the current order of the loaded mods to a
+    /// load order file, to be loaded with [`Mods::load_mods_with_order`].
+    pub fn save_load_order(&self, path: &Path) -> self::errors::Result<()> {
+        let load_order = LoadOrderModel {
+            order: self.mods.iter().map(|m| m.meta.name.clone()).collect(),
+        };
+        let contents = toml::to_string(&load_order).map_err(ModError::LoadOrderWrite)?;
+
+        fs::write(path, contents).map_err(ModError::IoError)
+    }
+
+    /// Identifies mods by their position in the load order.
+    fn assign_ids(&mut self) {
+        for (index, mod_bundle) in self.mods.iter_mut().enumerate() {
+            mod_bundle.meta.id = ModId(index);
+        }
+    }
+
     /// Retrieve a reference to a mod.
     pub fn get<K>(&self, id: &K) -> Option<&ModBundle>
     where
@@ -197,7 +283,7 @@ impl Mods {
     pub fn data_stage(&mut self) -> self::errors::Result<()> {
         trace!("Mod data define stage pass start");
         let lua = Mods::create_lua();
-        Mods::load_builtins(&lua)?;
+        Mods::load_builtins(&lua, &self.rng)?;
 
         // Buffer for file contents.
         let mut buf = vec![];
@@ -248,35 +334,7 @@ impl Mods {
 
                 for mod_bundle in &self.mods {
                     println!("load data definitions for {}", mod_bundle.meta.name);
-                    let maybe_cat: Option<rlua::Table> =
-                        mod_table.get(mod_bundle.meta.name.clone())?;
-
-                    if let Some(categories) = maybe_cat {
-                        println!("mod_name {}", mod_bundle.meta.name);
-
-                        for pair in categories.pairs() {
-                            let (category_name, proto_definitions): (String, rlua::Table) = pair?;
-                            println!("category_name {}", category_name);
-
-                            for pair in proto_definitions.pairs() {
-                                let (proto_name, proto_value): (String, rlua::Value) = pair?;
-
-                                let key = format!(
-                                    "{}:{}:{}",
-                                    mod_bundle.meta.name, category_name, proto_name
-                                );
-                                println!("Registering prototype {}", key);
-                                self.prototypes.insert(
-                                    mod_bundle.meta.id,
-                                    category_name.as_str(),
-                                    key.as_str(),
-                                    proto_value,
-                                );
-                            }
-                        }
-                    } else {
-                        trace!("Mod {} has no data definitions", mod_bundle.meta.name);
-                    }
+                    Mods::insert_definitions(&mut self.prototypes, &mod_bundle.meta, &mod_table)?;
                 }
 
                 Ok(())
@@ -289,6 +347,150 @@ impl Mods {
         Ok(())
     }
 
+    /// Re-runs the data stage of a single mod, replacing its prototype
+    /// definitions.
+    ///
+    /// The mod's previous definitions are removed, and its `data.lua`
+    /// is executed in a fresh Lua state, which replaces the mod's
+    /// state. Other mods, and their Lua states, are left untouched.
+    ///
+    /// Intended for reloading a mod after its files changed, without
+    /// restarting the engine.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ModError::ModNotFound`](enum.ModError.html) if no mod has the id. When
+    /// the script fails, the mod is left without definitions.
+    pub fn reload_mod(&mut self, id: ModId) -> self::errors::Result<()> {
+        let Mods {
+            ref mut mods,
+            ref mut prototypes,
+            ref settings,
+            ref rng,
+        } = self;
+        let mod_bundle = mods
+            .get_mut(id.inner())
+            .ok_or_else(|| ModError::ModNotFound(id))?;
+
+        trace!("Reloading mod {}", mod_bundle.meta.name);
+        prototypes.clear_mod(id);
+
+        // Read the data files up front, so file errors don't
+        // leave the mod with a new, empty Lua state.
+        let mut sources = vec![];
+        for entry in WalkDir::new(&mod_bundle.meta.path) {
+            let entry = entry.map_err(|err| ModError::IoError(err.into()))?;
+            if entry.file_name() == settings.mod_data_filename.as_str() {
+                let mut file = File::open(entry.path()).map_err(ModError::IoError)?;
+                let mut buf = vec![];
+                file.read_to_end(&mut buf).map_err(ModError::IoError)?;
+                sources.push(buf);
+            }
+        }
+
+        let lua = Mods::create_lua();
+        Mods::load_builtins(&lua, rng)?;
+
+        let mut data_definer_rc = LuaDataDefinerRc::new(LuaDataDefiner::new(
+            &lua,
+            settings.prototype_key_field.clone(),
+        )?);
+        data_definer_rc.borrow_mut().prime_mod(&mod_bundle.meta);
+
+        let result: rlua::Result<()> = lua.context(|lua_ctx| {
+            lua_ctx.scope(|scope| {
+                let user_data = scope.create_nonstatic_userdata(data_definer_rc.clone())?;
+                lua_ctx.globals().set("data", user_data)?;
+
+                for source in &sources {
+                    lua_ctx.load(source).exec()?;
+                }
+
+                let mod_table: rlua::Table =
+                    lua_ctx.registry_value(&data_definer_rc.borrow().table_key)?;
+                Mods::insert_definitions(prototypes, &mod_bundle.meta, &mod_table)
+            })
+        });
+        result?;
+
+        mod_bundle.lua = lua;
+
+        trace!("Reloading mod {} done", mod_bundle.meta.name);
+
+        Ok(())
+    }
+
+    /// Deserializes a mod's definitions, from the data table filled
+    /// by the data definer, into the prototype table.
+    fn insert_definitions(
+        prototypes: &mut PrototypeTable,
+        mod_meta: &ModMeta,
+        mod_table: &rlua::Table<'_>,
+    ) -> rlua::Result<()> {
+        let maybe_cat: Option<rlua::Table> = mod_table.get(mod_meta.name.clone())?;
+
+        if let Some(categories) = maybe_cat {
+            println!("mod_name {}", mod_meta.name);
+
+            for pair in categories.pairs() {
+                let (category_name, proto_definitions): (String, rlua::Table) = pair?;
+                println!("category_name {}", category_name);
+
+                for pair in proto_definitions.pairs() {
+                    let (proto_name, proto_value): (String, rlua::Value) = pair?;
+
+                    let key = format!("{}:{}:{}", mod_meta.name, category_name, proto_name);
+                    println!("Registering prototype {}", key);
+                    prototypes.insert(
+                        mod_meta.id,
+                        category_name.as_str(),
+                        key.as_str(),
+                        proto_value,
+                    );
+                }
+            }
+        } else {
+            trace!("Mod {} has no data definitions", mod_meta.name);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the PNG images in each mod's sprite folder to the atlas,
+    /// so sprites from all mods can share a single texture.
+    ///
+    /// Sprites are named after their mod and file, as
+    /// `mod_name:file_stem`. Intended to be called after the data
+    /// stage, before the atlas is packed with
+    /// `TextureAssets::pack_atlas`.
+    pub fn add_sprites(&self, atlas: &mut AtlasBuilder) -> Result<(), AtlasError> {
+        for mod_bundle in &self.mods {
+            let sprite_path = mod_bundle.meta.path.join(&self.settings.mod_sprite_dir);
+            if !sprite_path.is_dir() {
+                continue;
+            }
+
+            for entry in WalkDir::new(&sprite_path) {
+                let entry = entry.map_err(|err| AtlasError::Io(err.into()))?;
+                let path = entry.path();
+                let is_png = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("png"));
+
+                if !entry.file_type().is_file() || !is_png {
+                    continue;
+                }
+
+                let stem = path.file_stem().unwrap().to_string_lossy();
+                let image = image::open(path)?.to_rgba();
+                atlas.add_image(format!("{}:{}", mod_bundle.meta.name, stem), image);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Unload all mods in this registry.
     pub fn clear(&mut self) {
         self.mods.clear();
@@ -318,10 +520,15 @@ impl Mods {
         rlua::Lua::new()
     }
 
-    pub fn load_builtins(lua: &rlua::Lua) -> rlua::Result<()> {
+    /// Adds the engine's globals to a Lua state.
+    ///
+    /// Includes `rng`, a handle to the given generator, so scripts
+    /// and systems draw from one seeded sequence.
+    pub fn load_builtins(lua: &rlua::Lua, rng: &Rng) -> rlua::Result<()> {
         lua.context(|lua_ctx| {
             let globals = lua_ctx.globals();
             globals.set("ENGINE_VERSION", ENGINE_VERSION)?;
+            globals.set("rng", rng.share())?;
 
             let deep_copy_src: &[u8] = include_bytes!("scripting/builtins/deepcopy.lua");
             lua_ctx.load(&deep_copy_src).exec()?;
@@ -339,6 +546,7 @@ impl Mods {
             ref mods,
             ref prototypes,
             ref settings,
+            ..
         } = self;
 
         for mod_bundle in mods {
@@ -373,11 +581,19 @@ pub struct ModSettings {
     /// Filename for mod data definition script file.
     pub mod_data_filename: String,
 
+    /// Folder within a mod containing images to be
+    /// packed into a shared texture atlas.
+    pub mod_sprite_dir: String,
+
     /// Regular expression used for validating mod names.
     pub mod_name_re: Regex,
 
     /// Name of the table field to use when extracting prototype identifiers.
     pub prototype_key_field: String,
+
+    /// Fail loading with a load order when a mod is missing from
+    /// the order, instead of loading it last.
+    pub force_order: bool,
 }
 
 /// Information describing a mod.
@@ -407,6 +623,12 @@ pub struct ModMetaModel {
     dependencies: Vec<String>,
 }
 
+/// Load order file, listing mod names in the order they're loaded.
+#[derive(Serialize, Deserialize)]
+struct LoadOrderModel {
+    order: Vec<String>,
+}
+
 pub struct ModBundle {
     pub meta: ModMeta,
     pub lua: rlua::Lua,
@@ -449,3 +671,130 @@ pub struct ModContext<'a> {
     pub prototypes: &'a PrototypeTable,
     pub mod_bundle: &'a ModBundle,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::borrow::Cow;
+    use std::fs;
+
+    #[derive(Deserialize)]
+    struct Soldier {
+        health: i32,
+    }
+
+    impl Prototype for Soldier {
+        fn type_name<'a>() -> Cow<'a, str> {
+            "soldier".into()
+        }
+    }
+
+    fn write_mod(mod_path: &Path, name: &str, health: i32) {
+        let dir_path = mod_path.join(name);
+        fs::create_dir_all(&dir_path).unwrap();
+        fs::write(
+            dir_path.join(DEFAULT_MOD_META_FILENAME),
+            format!(
+                "name = '{}'\nversion = '0.1.0'\nauthor = 'test'\ndependencies = []\n",
+                name
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir_path.join(DEFAULT_DATA_FILENAME),
+            format!(
+                "data:extend('soldier', {{ {{ name = 'grunt', health = {} }} }})",
+                health
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reload_mod() {
+        let mod_path = std::env::temp_dir().join("rengine_test_reload_mod");
+        let _ = fs::remove_dir_all(&mod_path);
+        write_mod(&mod_path, "alpha", 10);
+        write_mod(&mod_path, "beta", 20);
+
+        let mut mods = Mods::from_path(&mod_path).unwrap();
+        mods.register_prototype::<Soldier>();
+        mods.load_mods().unwrap();
+        mods.data_stage().unwrap();
+
+        let health = |mods: &Mods, key: &str| mods.prototypes().get::<Soldier>(key).unwrap().health;
+        assert_eq!(10, health(&mods, "alpha:soldier:grunt"));
+        assert_eq!(20, health(&mods, "beta:soldier:grunt"));
+
+        let alpha_id = mods
+            .iter()
+            .find(|m| m.meta.name == "alpha")
+            .unwrap()
+            .meta
+            .id;
+        write_mod(&mod_path, "alpha", 50);
+        mods.reload_mod(alpha_id).unwrap();
+
+        // Only the reloaded mod's definitions change.
+        assert_eq!(50, health(&mods, "alpha:soldier:grunt"));
+        assert_eq!(20, health(&mods, "beta:soldier:grunt"));
+        assert_eq!(
+            Some(alpha_id),
+            mods.prototypes()
+                .get_mod_id::<Soldier>("alpha:soldier:grunt")
+        );
+
+        match mods.reload_mod(ModId(99)) {
+            Err(ModError::ModNotFound(id)) => assert_eq!(ModId(99), id),
+            _ => panic!("expected missing mod error"),
+        }
+
+        fs::remove_dir_all(&mod_path).unwrap();
+    }
+
+    #[test]
+    fn test_load_order() {
+        let root_path = std::env::temp_dir().join("rengine_test_load_order");
+        let mod_path = root_path.join("mods");
+        let order_path = root_path.join(DEFAULT_LOAD_ORDER_FILENAME);
+        let _ = fs::remove_dir_all(&root_path);
+        write_mod(&mod_path, "gamma", 1);
+        write_mod(&mod_path, "alpha", 2);
+        write_mod(&mod_path, "beta", 3);
+
+        let names =
+            |mods: &Mods| -> Vec<String> { mods.iter().map(|m| m.meta.name.clone()).collect() };
+
+        // Chosen order, which unlikely matches the directory walk.
+        let order = "order = ['beta', 'gamma', 'missing', 'alpha']\n";
+        fs::write(&order_path, order).unwrap();
+        let mut mods = Mods::from_path(&mod_path).unwrap();
+        mods.load_mods_with_order(&order_path).unwrap();
+        assert_eq!(vec!["beta", "gamma", "alpha"], names(&mods));
+        mods.save_load_order(&order_path).unwrap();
+
+        // Recreate the mods in another order, with one new mod.
+        fs::remove_dir_all(&mod_path).unwrap();
+        write_mod(&mod_path, "delta", 4);
+        write_mod(&mod_path, "alpha", 2);
+        write_mod(&mod_path, "beta", 3);
+        write_mod(&mod_path, "gamma", 1);
+
+        let mut mods = Mods::from_path(&mod_path).unwrap();
+        mods.load_mods_with_order(&order_path).unwrap();
+        assert_eq!(vec!["beta", "gamma", "alpha", "delta"], names(&mods));
+        for (index, mod_bundle) in mods.iter().enumerate() {
+            assert_eq!(ModId(index), mod_bundle.meta.id);
+        }
+
+        let mut mods = Mods::from_path(&mod_path).unwrap();
+        mods.settings_mut().force_order = true;
+        match mods.load_mods_with_order(&order_path) {
+            Err(ModError::LoadOrderUnknownMod(name)) => assert_eq!("delta", name),
+            _ => panic!("expected unknown mod error"),
+        }
+        assert_eq!(0, mods.iter().count());
+
+        fs::remove_dir_all(&root_path).unwrap();
+    }
+}
@@ -1,56 +1,70 @@
 //! Mod and scripting errors.
+use crate::errors::join_lines;
+use crate::scripting::ModId;
 use std::path::PathBuf;
+use thiserror::Error;
 
 /// Specialised `Result` for the scripting API.
 pub type Result<T> = std::result::Result<T, ModError>;
 
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum ModError {
     /// IO error accessing mod directory.
-    ModDirectory(PathBuf, std::io::Error),
+    #[error("error accessing mod folder {}", .0.to_string_lossy())]
+    ModDirectory(PathBuf, #[source] std::io::Error),
 
     /// Mod name is not unique in the mod registry.
+    #[error("mod with name '{0}' already exists")]
     ModNameTaken(String),
 
+    /// No mod is registered with the id.
+    #[error("mod {0:?} does not exist")]
+    ModNotFound(ModId),
+
     /// Mod name failed validation check.
+    #[error("mod name '{0}' is invalid")]
     ModNameInvalid(String),
 
     /// Failure operating on file.
-    IoError(std::io::Error),
+    #[error("mod file error")]
+    IoError(#[source] std::io::Error),
+
+    /// Mod load order file could not be written.
+    #[error("failed to write mod load order")]
+    LoadOrderWrite(#[source] toml::ser::Error),
+
+    /// Mod load order file is misformed.
+    #[error("failed to parse mod load order")]
+    LoadOrderParse(#[source] toml::de::Error),
+
+    /// Discovered mod is missing from the load order,
+    /// when the order is forced.
+    #[error("mod '{0}' is not in the load order")]
+    LoadOrderUnknownMod(String),
 
     /// Error in Lua state or script.
-    LuaError(rlua::Error),
-}
+    #[error("error in Lua script")]
+    LuaError(#[from] rlua::Error),
 
-impl ::std::fmt::Display for ModError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> ::std::fmt::Result {
-        use ModError::*;
-        match self {
-            ModDirectory(path, _) => {
-                write!(f, "error accessing mod folder {}", path.to_string_lossy())
-            }
-            ModNameTaken(name) => write!(f, "mod with name '{}' already exists", name),
-            ModNameInvalid(name) => write!(f, "mod name '{}' is invalid", name),
-            IoError(_) => write!(f, "mod file error"),
-            LuaError(_) => write!(f, "error in Lua script"),
-        }
-    }
+    /// Multiple errors, for example from several mods.
+    #[error("{}", join_lines(.0))]
+    Composite(Vec<ModError>),
 }
 
-impl std::error::Error for ModError {
-    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
-        use ModError::*;
-        match self {
-            ModDirectory(_, err) => Some(err),
-            IoError(err) => Some(err),
-            LuaError(err) => Some(err),
-            _ => None,
-        }
-    }
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_composite_display() {
+        let err = ModError::Composite(vec![
+            ModError::ModNameTaken("foo".to_string()),
+            ModError::ModNameInvalid("b@r".to_string()),
+        ]);
 
-impl From<rlua::Error> for ModError {
-    fn from(lua_err: rlua::Error) -> Self {
-        ModError::LuaError(lua_err)
+        assert_eq!(
+            err.to_string(),
+            "mod with name 'foo' already exists\nmod name 'b@r' is invalid"
+        );
     }
 }
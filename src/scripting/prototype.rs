@@ -88,7 +88,10 @@ pub trait Prototype {
 /// Trait for a container that maps prototype keys to definition intances.
 ///
 /// Used for upcasting and boxing a concrete storage type in the [`PrototypeTable`](struct.PrototypeTable.html).
-trait Storage: mopa::Any {}
+trait Storage: mopa::Any {
+    /// Removes the prototypes defined by the given mod.
+    fn remove_mod(&mut self, mod_id: ModId);
+}
 mopafy!(Storage);
 
 /// Concrete storage implementation of prototype storage.
@@ -122,7 +125,15 @@ where
     }
 }
 
-impl<T> Storage for PrototypeMapStorage<T> where T: 'static + Prototype {}
+impl<T> Storage for PrototypeMapStorage<T>
+where
+    T: 'static + Prototype,
+{
+    fn remove_mod(&mut self, mod_id: ModId) {
+        self.data
+            .retain(|_, proto_meta| proto_meta.mod_id != mod_id);
+    }
+}
 
 /// Meta data describing the prototype.
 struct PrototypeMeta<T> {
@@ -312,6 +323,17 @@ impl PrototypeTable {
         factory.insert_value(storage, mod_id, key.to_string(), value);
     }
 
+    /// Removes all prototypes defined by the given mod, of every
+    /// registered type.
+    ///
+    /// Registered types are kept, so the mod's definitions can be
+    /// inserted again when it's reloaded.
+    pub fn clear_mod(&mut self, mod_id: ModId) {
+        for (_, storage) in self.prototypes2.values_mut() {
+            storage.remove_mod(mod_id);
+        }
+    }
+
     /// Retrieve an immutable reference to a prototype if it exists.
     pub fn get<T>(&self, key: &str) -> Option<&T>
     where
@@ -469,4 +491,31 @@ mod test {
 
         assert_eq!(count, 3, "Unexpected number of iterations");
     }
+
+    #[test]
+    fn test_clear_mod() {
+        let mut table: PrototypeTable = PrototypeTable::new();
+        let lua = rlua::Lua::new();
+
+        table.register::<Foo>();
+
+        let result: rlua::Result<()> = lua.context(|lua_ctx| {
+            for (mod_id, key) in &[(ModId(0), "a:foo:first"), (ModId(1), "b:foo:second")] {
+                let value: rlua::Value = lua_ctx
+                    .load(r#"{ name = 'foo', position = { 1, 2 } }"#)
+                    .eval()?;
+
+                table.insert(*mod_id, Foo::type_name().as_ref(), key, value);
+            }
+
+            Ok(())
+        });
+        result.unwrap();
+
+        table.clear_mod(ModId(0));
+
+        assert!(table.get::<Foo>("a:foo:first").is_none());
+        assert!(table.get::<Foo>("b:foo:second").is_some());
+        assert_eq!(Some(ModId(1)), table.get_mod_id::<Foo>("b:foo:second"));
+    }
 }
@@ -0,0 +1,426 @@
+//! Broad-phase spatial queries.
+//!
+//! Entities with a `Transform` and `Bounds` are indexed into a uniform
+//! grid of cubic cells, so proximity queries only visit the entities
+//! in nearby cells instead of every entity in the world.
+//!
+//! ## Cell Size
+//!
+//! The cell size trades memory and maintenance time against query time.
+//! Small cells keep each cell's list short, but large entities are stored
+//! in many cells, and queries covering a large region visit many cells.
+//! Large cells are cheap to maintain, but queries have to filter out more
+//! entities that happen to share a cell. A cell size around the size of
+//! a typical entity, or the typical query region, is a good start.
+//!
+//! ## Rays
+//!
+//! `ray_aabb_intersect` tests rays, like the ones cast from the camera
+//! for picking, against the boxes of entities.
+use crate::comp::Transform;
+use glm::Vec3;
+use nalgebra::{Point3, Unit, Vector3};
+use specs::{Component, DenseVecStorage, Entities, Entity, Join, ReadStorage, System, Write};
+use std::collections::HashMap;
+use std::f32::MAX;
+
+/// Cell size of the default grid, in world units.
+pub const DEFAULT_CELL_SIZE: f32 = 4.0;
+
+/// Axis aligned bounding box of an entity, relative
+/// to the position of its `Transform`.
+///
+/// The box is not rotated or scaled with the transform.
+#[derive(Component, Debug, Clone)]
+#[storage(DenseVecStorage)]
+pub struct Bounds {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Bounds {
+    pub fn new<V>(min: V, max: V) -> Self
+    where
+        V: Into<Vec3>,
+    {
+        let (min, max) = (min.into(), max.into());
+        Bounds {
+            min: glm::min2(&min, &max),
+            max: glm::max2(&min, &max),
+        }
+    }
+
+    /// Box extending the given distance from the
+    /// position in each direction.
+    pub fn with_half_extents<V>(half_extents: V) -> Self
+    where
+        V: Into<Vec3>,
+    {
+        let half_extents = half_extents.into();
+        Bounds::new(-half_extents, half_extents)
+    }
+
+    #[inline]
+    pub fn min(&self) -> &Vec3 {
+        &self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> &Vec3 {
+        &self.max
+    }
+}
+
+/// Uniform grid indexing entities by their bounding boxes.
+///
+/// Maintained each frame by `SpatialGridSystem`. To use a different
+/// cell size, add a grid to the world before the application runs.
+///
+/// ## Example
+///
+/// ```
+/// # use rengine::spatial::SpatialGrid;
+/// # use rengine::specs::{Builder, World};
+/// let mut world = World::new();
+/// let entity = world.create_entity().build();
+///
+/// let mut grid = SpatialGrid::new(2.0);
+/// grid.insert(entity, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+///
+/// assert_eq!(vec![entity], grid.query_radius([3.0, 0.5, 0.5], 2.5));
+/// assert!(grid.query_aabb([5.0, 5.0, 5.0], [6.0, 6.0, 6.0]).is_empty());
+/// ```
+#[derive(Debug)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<Entity>>,
+    /// World space bounding box of each entity in the grid.
+    boxes: HashMap<Entity, (Vec3, Vec3)>,
+}
+
+impl SpatialGrid {
+    /// Creates an empty grid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size isn't positive.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "Cell size {} must be positive", cell_size);
+
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            boxes: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Number of entities in the grid.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.boxes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Removes all entities from the grid.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.boxes.clear();
+    }
+
+    /// Adds an entity covering the given world space box to the grid,
+    /// replacing its previous box if it was already in the grid.
+    pub fn insert<V>(&mut self, entity: Entity, min: V, max: V)
+    where
+        V: Into<Vec3>,
+    {
+        self.remove(entity);
+
+        let (min, max) = (min.into(), max.into());
+        let (min, max) = (glm::min2(&min, &max), glm::max2(&min, &max));
+
+        for key in self.cell_range(&min, &max) {
+            self.cells.entry(key).or_insert_with(Vec::new).push(entity);
+        }
+        self.boxes.insert(entity, (min, max));
+    }
+
+    /// Removes an entity from the grid, returning whether it was in it.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let (min, max) = match self.boxes.remove(&entity) {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+
+        for key in self.cell_range(&min, &max) {
+            if let Some(cell) = self.cells.get_mut(&key) {
+                cell.retain(|other| *other != entity);
+
+                if cell.is_empty() {
+                    self.cells.remove(&key);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Entities with boxes overlapping the given world space box,
+    /// ordered by entity.
+    pub fn query_aabb<V>(&self, min: V, max: V) -> Vec<Entity>
+    where
+        V: Into<Vec3>,
+    {
+        let (min, max) = (min.into(), max.into());
+        let (min, max) = (glm::min2(&min, &max), glm::max2(&min, &max));
+
+        self.query(&min, &max, |(other_min, other_max)| {
+            (0..3).all(|i| other_min[i] <= max[i] && other_max[i] >= min[i])
+        })
+    }
+
+    /// Entities with boxes overlapping the given world space
+    /// sphere, ordered by entity.
+    pub fn query_radius<V>(&self, center: V, radius: f32) -> Vec<Entity>
+    where
+        V: Into<Vec3>,
+    {
+        let center = center.into();
+        let extents = glm::vec3(radius, radius, radius);
+
+        self.query(&(center - extents), &(center + extents), |(min, max)| {
+            // Closest point of the box to the center.
+            let closest = glm::clamp_vec(&center, min, max);
+            glm::distance2(&closest, &center) <= radius * radius
+        })
+    }
+
+    fn query<F>(&self, min: &Vec3, max: &Vec3, overlaps: F) -> Vec<Entity>
+    where
+        F: Fn((&Vec3, &Vec3)) -> bool,
+    {
+        let mut found: Vec<Entity> = self
+            .cell_range(min, max)
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .cloned()
+            .collect();
+
+        // Entities covering multiple cells are found more than once.
+        found.sort();
+        found.dedup();
+        found.retain(|entity| {
+            let (min, max) = &self.boxes[entity];
+            overlaps((min, max))
+        });
+
+        found
+    }
+
+    fn cell_key(&self, point: &Vec3) -> [i32; 3] {
+        [
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        ]
+    }
+
+    /// Keys of the cells covered by a box.
+    fn cell_range(&self, min: &Vec3, max: &Vec3) -> impl Iterator<Item = [i32; 3]> {
+        let [x0, y0, z0] = self.cell_key(min);
+        let [x1, y1, z1] = self.cell_key(max);
+
+        (x0..=x1).flat_map(move |x| (y0..=y1).flat_map(move |y| (z0..=z1).map(move |z| [x, y, z])))
+    }
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        SpatialGrid::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+/// Distance along a ray to where it enters an axis aligned box,
+/// using the slab method.
+///
+/// Rays starting inside the box hit it at a distance of zero. Boxes
+/// behind the origin aren't hit.
+///
+/// ## Example
+///
+/// ```
+/// # use rengine::nalgebra::{Point3, Unit, Vector3};
+/// # use rengine::spatial::ray_aabb_intersect;
+/// let t = ray_aabb_intersect(
+///     Point3::new(0.0, 0.5, 0.5),
+///     Unit::new_normalize(Vector3::new(1.0, 0.0, 0.0)),
+///     Point3::new(2.0, 0.0, 0.0),
+///     Point3::new(3.0, 1.0, 1.0),
+/// );
+/// assert_eq!(Some(2.0), t);
+/// ```
+pub fn ray_aabb_intersect(
+    origin: Point3<f32>,
+    direction: Unit<Vector3<f32>>,
+    min: Point3<f32>,
+    max: Point3<f32>,
+) -> Option<f32> {
+    let mut t_enter = 0.0_f32;
+    let mut t_exit = MAX;
+
+    for axis in 0..3 {
+        if direction[axis] != 0.0 {
+            let delta = 1.0 / direction[axis];
+            let t_min = (min[axis] - origin[axis]) * delta;
+            let t_max = (max[axis] - origin[axis]) * delta;
+
+            t_enter = t_enter.max(t_min.min(t_max));
+            t_exit = t_exit.min(t_min.max(t_max));
+
+            if t_enter > t_exit {
+                return None;
+            }
+        } else if origin[axis] < min[axis] || origin[axis] > max[axis] {
+            // Parallel to the slab, and outside of it.
+            return None;
+        }
+    }
+
+    Some(t_enter)
+}
+
+/// Rebuilds the `SpatialGrid` from the entities
+/// with a `Transform` and `Bounds`.
+#[derive(Default)]
+pub struct SpatialGridSystem;
+
+impl<'a> System<'a> for SpatialGridSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, SpatialGrid>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Bounds>,
+    );
+
+    fn run(&mut self, (entities, mut grid, transforms, bounds): Self::SystemData) {
+        grid.clear();
+
+        for (entity, transform, bounds) in (&entities, &transforms, &bounds).join() {
+            let pos = transform.position();
+            grid.insert(entity, pos + bounds.min, pos + bounds.max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use specs::{Builder, RunNow, World};
+
+    #[test]
+    fn test_spatial_grid_query() {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Bounds>();
+        world.add_resource(SpatialGrid::new(1.0));
+
+        let mut create = |pos: [f32; 3], half_extents: f32| {
+            world
+                .create_entity()
+                .with(Transform::new().with_position(pos))
+                .with(Bounds::with_half_extents([half_extents; 3]))
+                .build()
+        };
+        let near = create([0.5, 0.5, 0.5], 0.25);
+        let far = create([10.5, 0.5, 0.5], 0.25);
+        let negative = create([-2.5, 0.5, 0.5], 0.25);
+        // Covers many cells, but is found once.
+        let large = create([6.0, 0.0, 0.0], 3.0);
+        // No bounds, so not indexed.
+        world
+            .create_entity()
+            .with(Transform::new().with_position([0.5, 0.5, 0.5]))
+            .build();
+
+        SpatialGridSystem.run_now(&world.res);
+        let grid = world.read_resource::<SpatialGrid>();
+        assert_eq!(4, grid.len());
+
+        assert_eq!(
+            vec![near],
+            grid.query_aabb([0.0, 0.0, 0.0], [1.0, 1.0, 1.0])
+        );
+        assert_eq!(
+            vec![negative],
+            grid.query_aabb([-3.0, 0.0, 0.0], [-2.0, 1.0, 1.0])
+        );
+        assert_eq!(
+            vec![far, large],
+            grid.query_aabb([7.5, 0.0, 0.0], [10.5, 1.0, 1.0])
+        );
+
+        // Same cell, but outside the box.
+        assert!(grid.query_aabb([0.0, 0.0, 0.0], [0.2, 0.2, 0.2]).is_empty());
+
+        // Box corner is outside the sphere, but its face is inside.
+        assert_eq!(vec![near], grid.query_radius([1.5, 0.5, 0.5], 0.8));
+        assert!(grid.query_radius([1.5, 1.5, 1.5], 0.8).is_empty());
+    }
+
+    #[test]
+    fn test_ray_aabb_intersect() {
+        let (min, max) = (Point3::new(2.0, 0.0, 0.0), Point3::new(3.0, 1.0, 1.0));
+        let ray = |x: f32, y: f32, z: f32| Unit::new_normalize(Vector3::new(x, y, z));
+
+        // Hit
+        let t = ray_aabb_intersect(Point3::new(0.0, 0.0, 0.0), ray(1.0, 0.25, 0.25), min, max);
+        assert!((t.unwrap() - 2.0 * 1.125_f32.sqrt()).abs() < 1.0e-5);
+
+        // Miss
+        let t = ray_aabb_intersect(Point3::new(0.0, 0.0, 0.0), ray(1.0, 1.0, 0.0), min, max);
+        assert_eq!(None, t);
+
+        // Inside
+        let t = ray_aabb_intersect(Point3::new(2.5, 0.5, 0.5), ray(0.0, -1.0, 0.0), min, max);
+        assert_eq!(Some(0.0), t);
+
+        // Behind
+        let t = ray_aabb_intersect(Point3::new(4.0, 0.5, 0.5), ray(1.0, 0.0, 0.0), min, max);
+        assert_eq!(None, t);
+
+        // Parallel to an axis, inside and outside of its slab
+        let t = ray_aabb_intersect(Point3::new(0.0, 0.5, 0.5), ray(1.0, 0.0, 0.0), min, max);
+        assert_eq!(Some(2.0), t);
+        let t = ray_aabb_intersect(Point3::new(0.0, 1.5, 0.5), ray(1.0, 0.0, 0.0), min, max);
+        assert_eq!(None, t);
+    }
+
+    #[test]
+    fn test_spatial_grid_update() {
+        let mut world = World::new();
+        let entity = world.create_entity().build();
+
+        let mut grid = SpatialGrid::new(1.0);
+        grid.insert(entity, [0.0, 0.0, 0.0], [0.5, 0.5, 0.5]);
+        grid.insert(entity, [5.0, 0.0, 0.0], [5.5, 0.5, 0.5]);
+        assert_eq!(1, grid.len());
+        assert!(grid.query_aabb([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]).is_empty());
+        assert_eq!(
+            vec![entity],
+            grid.query_aabb([5.0, 0.0, 0.0], [6.0, 1.0, 1.0])
+        );
+
+        assert!(grid.remove(entity));
+        assert!(!grid.remove(entity));
+        assert!(grid.is_empty());
+        assert!(grid.cells.is_empty());
+    }
+}
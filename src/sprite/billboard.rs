@@ -1,14 +1,115 @@
 use crate::camera::{ActiveCamera, CameraView};
-use crate::comp::Transform;
+use crate::comp::{GlobalTransform, Transform, Y_AXIS};
+use crate::res::FrameCounter;
+use log::error;
 use nalgebra::Unit;
-use specs::{Component, FlaggedStorage, Join, ReadExpect, ReadStorage, System, WriteStorage};
+use specs::{
+    Component, DenseVecStorage, Entities, FlaggedStorage, Join, Read, ReadExpect, ReadStorage,
+    System, WriteStorage,
+};
 
-#[derive(Component)]
+#[derive(Component, Debug, Default)]
 #[storage(FlaggedStorage)]
-pub struct Billboard;
+pub struct Billboard {
+    pub mode: BillboardMode,
+    /// Point of the sprite that it turns around, which is placed at the
+    /// position of the `Transform`. When `None`, the transform's own
+    /// anchor is kept.
+    pub anchor: Option<glm::Vec3>,
+}
+
+impl Billboard {
+    pub fn new(mode: BillboardMode) -> Self {
+        Billboard { mode, anchor: None }
+    }
+
+    /// Sets the point the sprite turns around, in model space.
+    ///
+    /// Sprites standing on the ground, like trees and characters,
+    /// turn around their bottom so they stay rooted in place.
+    ///
+    /// ```
+    /// # use rengine::sprite::{Billboard, BillboardMode};
+    /// // Unit quad centered on the origin, turning around its bottom edge
+    /// let billboard = Billboard::new(BillboardMode::Cylindrical).with_anchor([0.0, -0.5, 0.0]);
+    /// ```
+    pub fn with_anchor<V>(mut self, anchor: V) -> Self
+    where
+        V: Into<glm::Vec3>,
+    {
+        self.anchor = Some(anchor.into());
+        self
+    }
+}
+
+/// Axes around which a billboard turns to face the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillboardMode {
+    /// Faces the camera on all axes.
+    Spherical,
+    /// Only rotates around the Y axis, keeping the sprite
+    /// upright when the camera is above or below it.
+    Cylindrical,
+}
+
+impl Default for BillboardMode {
+    fn default() -> Self {
+        BillboardMode::Spherical
+    }
+}
+
+/// Distances from the camera beyond which billboards are
+/// oriented less often, to save work when many are visible.
+///
+/// Billboards further than the first distance are updated every
+/// other frame, further than the second every 4 frames, and so on,
+/// doubling for each distance. Distances must be in increasing order.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::sprite::BillboardLod;
+/// let lod = BillboardLod::new(vec![20.0, 40.0]);
+///
+/// assert_eq!(1, lod.interval(10.0));
+/// assert_eq!(2, lod.interval(30.0));
+/// assert_eq!(4, lod.interval(50.0));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct BillboardLod {
+    pub distances: Vec<f32>,
+}
+
+impl BillboardLod {
+    pub fn new(distances: Vec<f32>) -> Self {
+        BillboardLod { distances }
+    }
+
+    /// Number of frames between updates of a
+    /// billboard at the given distance.
+    pub fn interval(&self, distance: f32) -> u64 {
+        let level = self
+            .distances
+            .iter()
+            .take_while(|cutoff| distance > **cutoff)
+            .count();
+        1 << level.min(63)
+    }
+}
+
+/// Book keeping of `BillboardSystem`, added to
+/// billboards when they're first oriented.
+#[derive(Component, Debug, Clone)]
+#[storage(DenseVecStorage)]
+pub struct BillboardState {
+    pub last_updated_frame: u64,
+}
 
 /// System that orients entities marked as billboards
 /// towards the currently active camera.
+///
+/// When the world has both a `BillboardLod` and a `FrameCounter`,
+/// distant billboards are updated less often.
 #[derive(Default)]
 pub struct BillboardSystem;
 
@@ -20,14 +121,30 @@ impl BillboardSystem {
 
 impl<'a> System<'a> for BillboardSystem {
     type SystemData = (
+        Entities<'a>,
         ReadExpect<'a, ActiveCamera>,
+        Option<Read<'a, BillboardLod>>,
+        Option<Read<'a, FrameCounter>>,
         ReadStorage<'a, CameraView>,
         ReadStorage<'a, Billboard>,
+        ReadStorage<'a, GlobalTransform>,
+        WriteStorage<'a, BillboardState>,
         WriteStorage<'a, Transform>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (active_camera, camera_views, billboards, mut transforms) = data;
+        let (
+            entities,
+            active_camera,
+            lod,
+            frame_counter,
+            camera_views,
+            billboards,
+            globals,
+            mut states,
+            mut transforms,
+        ) = data;
+        let frame = frame_counter.map(|counter| counter.frame());
 
         // Determine active camera
         let maybe_camera_view = active_camera
@@ -35,19 +152,196 @@ impl<'a> System<'a> for BillboardSystem {
             .and_then(|entity| camera_views.get(entity));
 
         if let Some(camera_view) = maybe_camera_view {
-            for (ref _billboard, ref mut transform) in (&billboards, &mut transforms).join() {
-                // TODO: Decide on either nalgebra or nalgebra_glm, but not both.
-
-                // Convert up from Vector3 to Vector4
-                let up = {
-                    let cam_up = camera_view.up();
-                    glm::Vec3::new(cam_up.x, cam_up.y, cam_up.z)
-                };
-                let diff = (camera_view.eye() - transform.pos).to_homogeneous();
-                let dir = Unit::new_normalize(diff);
-
-                transform.look_at(glm::Vec3::new(dir.x, dir.y, dir.z), up);
+            for (entity, ref billboard, global, ref mut transform) in
+                (&entities, &billboards, globals.maybe(), &mut transforms).join()
+            {
+                if let Some(anchor) = billboard.anchor {
+                    transform.set_anchor(anchor);
+                }
+
+                // Parented billboards face the camera from their world position
+                let pos = global
+                    .map(GlobalTransform::position)
+                    .unwrap_or(transform.pos);
+
+                if let (Some(lod), Some(frame)) = (lod.as_ref(), frame) {
+                    let distance = (camera_view.eye().coords - pos).norm();
+                    let is_due = states.get(entity).map_or(true, |state| {
+                        frame.saturating_sub(state.last_updated_frame) >= lod.interval(distance)
+                    });
+                    if !is_due {
+                        continue;
+                    }
+
+                    let state = BillboardState {
+                        last_updated_frame: frame,
+                    };
+                    if let Err(err) = states.insert(entity, state) {
+                        error!("Failed to insert billboard state: {}", err);
+                    }
+                }
+
+                match billboard.mode {
+                    BillboardMode::Spherical => {
+                        // TODO: Decide on either nalgebra or nalgebra_glm, but not both.
+
+                        // Convert up from Vector3 to Vector4
+                        let up = {
+                            let cam_up = camera_view.up();
+                            glm::Vec3::new(cam_up.x, cam_up.y, cam_up.z)
+                        };
+                        let diff = (camera_view.eye() - pos).to_homogeneous();
+                        let dir = Unit::new_normalize(diff);
+
+                        transform.look_at(glm::Vec3::new(dir.x, dir.y, dir.z), up);
+                    }
+                    BillboardMode::Cylindrical => {
+                        // Direction to the camera projected onto the XZ plane.
+                        let diff = camera_view.eye().coords - pos;
+
+                        // Sprites face the positive Z axis when unrotated.
+                        // When the camera is directly above, the angle is
+                        // undefined and the current rotation is kept.
+                        if diff.x != 0.0 || diff.z != 0.0 {
+                            transform.set_rotation(diff.x.atan2(diff.z), Y_AXIS);
+                        }
+                    }
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::Point3;
+    use specs::{Builder, RunNow, World};
+
+    #[test]
+    fn test_cylindrical_billboard() {
+        let mut world = World::new();
+        world.register::<CameraView>();
+        world.register::<Billboard>();
+        world.register::<BillboardState>();
+        world.register::<Transform>();
+
+        let mut camera_view = CameraView::new();
+        camera_view.set_position(Point3::new(5.0, 10.0, 5.0));
+        let camera = world.create_entity().with(camera_view).build();
+        world.add_resource(ActiveCamera::new(camera));
+
+        let sprite = world
+            .create_entity()
+            .with(Billboard::new(BillboardMode::Cylindrical))
+            .with(Transform::default())
+            .build();
+
+        BillboardSystem::new().run_now(&world.res);
+
+        let transforms = world.read_storage::<Transform>();
+        let rot = transforms.get(sprite).unwrap().rotation();
+        assert!(rot.j.abs() > 0.0);
+        assert!(rot.i.abs() < 1e-6);
+        assert!(rot.k.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_billboard_anchor() {
+        let mut world = World::new();
+        world.register::<CameraView>();
+        world.register::<Billboard>();
+        world.register::<BillboardState>();
+        world.register::<Transform>();
+        world.register::<GlobalTransform>();
+
+        let mut camera_view = CameraView::new();
+        camera_view.set_position(Point3::new(5.0, 10.0, 5.0));
+        let camera = world.create_entity().with(camera_view).build();
+        world.add_resource(ActiveCamera::new(camera));
+
+        let pos = glm::vec3(1.0, 2.0, 3.0);
+        let create = |world: &mut World, mode| {
+            world
+                .create_entity()
+                .with(Billboard::new(mode).with_anchor([0.0, -0.5, 0.0]))
+                .with(Transform::default().with_position(pos))
+                .build()
+        };
+        let cylindrical = create(&mut world, BillboardMode::Cylindrical);
+        let spherical = create(&mut world, BillboardMode::Spherical);
+
+        BillboardSystem::new().run_now(&world.res);
+
+        let transforms = world.read_storage::<Transform>();
+        let model_point = |entity, point: glm::Vec3| {
+            let matrix = transforms.get(entity).unwrap().matrix();
+            (matrix * glm::vec4(point.x, point.y, point.z, 1.0)).xyz()
+        };
+
+        for entity in &[cylindrical, spherical] {
+            // Bottom of the quad stays at the position
+            let bottom = model_point(*entity, glm::vec3(0.0, -0.5, 0.0));
+            assert!((bottom - pos).norm() < 1.0e-5);
+
+            // The rest of the quad turns around it
+            let center = model_point(*entity, glm::vec3(0.0, 0.0, 0.0));
+            assert!(((center - pos).norm() - 0.5).abs() < 1.0e-5);
+        }
+
+        // Upright sprites are shifted straight up
+        let center = model_point(cylindrical, glm::vec3(0.0, 0.0, 0.0));
+        assert!((center - (pos + glm::vec3(0.0, 0.5, 0.0))).norm() < 1.0e-5);
+
+        // Sprites facing the camera above lean back
+        let top = model_point(spherical, glm::vec3(0.0, 0.5, 0.0));
+        assert!(top.y < pos.y + 1.0 - 1.0e-3);
+    }
+
+    #[test]
+    fn test_billboard_lod() {
+        let mut world = World::new();
+        world.register::<CameraView>();
+        world.register::<Billboard>();
+        world.register::<BillboardState>();
+        world.register::<Transform>();
+        world.register::<GlobalTransform>();
+        world.add_resource(BillboardLod::new(vec![20.0, 40.0]));
+        world.add_resource(FrameCounter::default());
+
+        let mut camera_view = CameraView::new();
+        camera_view.set_position(Point3::new(0.0, 0.0, 0.0));
+        let camera = world.create_entity().with(camera_view).build();
+        world.add_resource(ActiveCamera::new(camera));
+
+        let sprites: Vec<_> = [5.0, 30.0, 50.0]
+            .iter()
+            .map(|distance| {
+                world
+                    .create_entity()
+                    .with(Billboard::new(BillboardMode::Cylindrical))
+                    .with(Transform::default().with_position([1.0, 0.0, *distance]))
+                    .build()
+            })
+            .collect();
+
+        let mut updates = [0; 3];
+        let mut system = BillboardSystem::new();
+        for _ in 0..4 {
+            world.write_resource::<FrameCounter>().incr();
+            system.run_now(&world.res);
+
+            // Count the sprites that were turned, and turn them back.
+            let mut transforms = world.write_storage::<Transform>();
+            for (count, sprite) in updates.iter_mut().zip(&sprites) {
+                let transform = transforms.get_mut(*sprite).unwrap();
+                if transform.rotation().j.abs() > 1e-6 {
+                    *count += 1;
+                }
+                transform.set_rotation(0.0_f32, Y_AXIS);
+            }
+        }
+
+        assert_eq!([4, 2, 1], updates);
+    }
+}
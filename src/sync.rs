@@ -1,5 +1,5 @@
 /// Tools for inter-thread communication.
-pub use channel::{RecvError, SendError};
+pub use channel::{RecvError, SendError, TryRecvError, TrySendError};
 use crossbeam::channel;
 
 /// A pair of multiple-producer-multiple-consumer channels
@@ -61,8 +61,19 @@ impl<T: Send> ChannelPair<T> {
     pub fn create() -> (Self, Self) {
         // Bounded channel with no capacity  will block on both
         // send and receive, until the other end is ready.
-        let (a_send, b_recv): (channel::Sender<T>, channel::Receiver<T>) = channel::bounded(0);
-        let (b_send, a_recv): (channel::Sender<T>, channel::Receiver<T>) = channel::bounded(0);
+        Self::with_capacity(0)
+    }
+
+    /// Creates two linked channel pairs, where each direction can
+    /// hold up to `capacity` values before sending blocks.
+    ///
+    /// A capacity of zero results in a rendezvous channel, where
+    /// a send waits for the other end to receive.
+    pub fn with_capacity(capacity: usize) -> (Self, Self) {
+        let (a_send, b_recv): (channel::Sender<T>, channel::Receiver<T>) =
+            channel::bounded(capacity);
+        let (b_send, a_recv): (channel::Sender<T>, channel::Receiver<T>) =
+            channel::bounded(capacity);
 
         let a = ChannelPair {
             sender: a_send,
@@ -80,9 +91,22 @@ impl<T: Send> ChannelPair<T> {
         self.sender.send(val)
     }
 
+    /// Attempts to send a value without blocking.
+    ///
+    /// Fails with `TrySendError::Full` when the channel is at
+    /// capacity, returning ownership of the value.
+    pub fn try_send(&mut self, val: T) -> Result<(), TrySendError<T>> {
+        self.sender.try_send(val)
+    }
+
     pub fn receive(&mut self) -> Result<T, RecvError> {
         self.receiver.recv()
     }
+
+    /// Attempts to receive a value without blocking.
+    pub fn try_receive(&mut self) -> Result<T, TryRecvError> {
+        self.receiver.try_recv()
+    }
 }
 
 /// Implicit implementation via derive doesn't work.
@@ -1,49 +1,140 @@
 use crate::camera::{ActiveCamera, CameraProjection, CameraView};
-use crate::comp::{GlTexture, Mesh, Transform};
+use crate::comp::{GlTexture, GlobalTransform, Mesh, Transform};
 use crate::gfx_types::{
-    self, gizmo_pipe, gloss_pipe, pipe, DepthTarget, PipelineBundle, RenderTarget,
+    self, depth_pipe, gizmo_pipe, gloss_pipe, instanced_pipe, pipe, DepthPrepassBundle,
+    DepthTarget, GlossArrayBundle, PipelineBundle, RenderTarget,
 };
 use crate::metrics::{builtin_metrics::*, MetricAggregate, MetricHub};
 use crate::option::lift2;
-use crate::render::{ChannelPair, Gizmo, Lights, Material, PointLight};
-use crate::res::ViewPort;
+use crate::render::{
+    BatchBuffers, BatchBuilder, BatchRange, ChannelPair, DrawKey, Gizmo, InstanceBuffer,
+    InstanceGroups, Lights, Material, MeshInstance, RenderLayer, SharedMesh,
+};
+use crate::res::{DeviceDimensions, ViewPort};
 
 use nalgebra::{Matrix4, Vector4};
 use specs::{Join, Read, ReadExpect, ReadStorage, System};
+use std::sync::Arc;
 
 pub struct DrawSystem {
     channel: ChannelPair<gfx_device::Resources, gfx_device::CommandBuffer>,
+    factory: gfx_device::Factory,
+    batch: BatchBuilder,
+    batch_buffers: BatchBuffers,
+    instance_buffer: InstanceBuffer,
     pub(crate) render_target: RenderTarget<gfx_device::Resources>,
     pub(crate) depth_target: DepthTarget<gfx_device::Resources>,
 }
 
+/// Options for how `DrawSystem` orders its draw calls.
+#[derive(Debug, Clone)]
+pub struct DrawSettings {
+    /// Draw opaque meshes nearest to the camera first, so hidden
+    /// fragments are rejected by the depth test before shading.
+    ///
+    /// Reduces overdraw in fill heavy scenes, at the cost of
+    /// sorting on the CPU each frame.
+    pub sort_opaque: bool,
+
+    /// Draw opaque meshes to the depth buffer first, so the lighting
+    /// shader only runs for the fragments that end up visible.
+    ///
+    /// Opaque geometry is submitted twice, so this only pays off
+    /// when shading is expensive and meshes overlap heavily.
+    pub depth_prepass: bool,
+}
+
+impl Default for DrawSettings {
+    fn default() -> Self {
+        DrawSettings {
+            sort_opaque: true,
+            depth_prepass: false,
+        }
+    }
+}
+
+/// Toggles drawing of debug visuals, such as `Gizmo` wireframes.
+///
+/// Off by default, in which case no draw calls are issued for them.
+#[derive(Debug, Clone, Default)]
+pub struct DebugRender {
+    enabled: bool,
+}
+
+impl DebugRender {
+    pub fn new(enabled: bool) -> Self {
+        DebugRender { enabled }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    #[inline]
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Flips debug rendering on or off, returning the new state.
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.enabled
+    }
+}
+
 #[derive(SystemData)]
 pub struct DrawSystemData<'a> {
-    // metrics: Read<'a, MetricHub>,
+    metrics: Option<Read<'a, MetricHub>>,
+    settings: Read<'a, DrawSettings>,
+    debug_render: Read<'a, DebugRender>,
     basic_pipe_bundle: ReadExpect<'a, PipelineBundle<pipe::Meta>>,
+    instanced_pipe_bundle: ReadExpect<'a, PipelineBundle<instanced_pipe::Meta>>,
     gloss_pipe_bundle: ReadExpect<'a, PipelineBundle<gloss_pipe::Meta>>,
+    depth_prepass_bundle: ReadExpect<'a, DepthPrepassBundle>,
+    gloss_array_bundle: ReadExpect<'a, GlossArrayBundle>,
     gizmo_pipe_bundle: ReadExpect<'a, PipelineBundle<gizmo_pipe::Meta>>,
     view_port: ReadExpect<'a, ViewPort>,
+    device_dim: ReadExpect<'a, DeviceDimensions>,
     active_camera: Read<'a, ActiveCamera>,
     meshes: ReadStorage<'a, Mesh>,
+    mesh_instances: ReadStorage<'a, MeshInstance>,
     materials: ReadStorage<'a, Material>,
     textures: ReadStorage<'a, GlTexture>,
     transforms: ReadStorage<'a, Transform>,
+    globals: ReadStorage<'a, GlobalTransform>,
     cam_views: ReadStorage<'a, CameraView>,
     cam_projs: ReadStorage<'a, CameraProjection>,
     gizmos: ReadStorage<'a, Gizmo>,
+    render_layers: ReadStorage<'a, RenderLayer>,
     lights: ReadExpect<'a, Lights>,
-    point_lights: ReadStorage<'a, PointLight>,
+}
+
+/// A single draw call, planned before any geometry is sent to
+/// the graphics card.
+enum DrawOp<'a> {
+    /// Mesh drawn on its own, with its own model matrix.
+    Single(&'a Mesh, &'a Material, Matrix4<f32>),
+    /// Several meshes merged into the batch buffers.
+    Batch(&'a GlTexture, BatchRange),
 }
 
 impl DrawSystem {
     pub fn new(
         channel: ChannelPair<gfx_device::Resources, gfx_device::CommandBuffer>,
+        mut factory: gfx_device::Factory,
         render_target: RenderTarget<gfx_device::Resources>,
         depth_target: DepthTarget<gfx_device::Resources>,
     ) -> Self {
+        let batch_buffers = BatchBuffers::new(&mut factory);
+        let instance_buffer = InstanceBuffer::new(&mut factory);
+
         DrawSystem {
             channel,
+            factory,
+            batch: BatchBuilder::new(),
+            batch_buffers,
+            instance_buffer,
             render_target,
             depth_target,
         }
@@ -51,19 +142,81 @@ impl DrawSystem {
 }
 
 impl DrawSystem {
+    /// Closes the current run of compatible meshes.
+    ///
+    /// A run of one is drawn as is, to avoid copying its geometry.
+    fn flush_run<'a>(
+        batch: &mut BatchBuilder,
+        run: &mut Vec<(&'a Mesh, &'a Material, Matrix4<f32>)>,
+        ops: &mut Vec<DrawOp<'a>>,
+    ) {
+        match run.len() {
+            0 => {}
+            1 => {
+                let (mesh, mat, model) = run[0];
+                ops.push(DrawOp::Single(mesh, mat, model));
+            }
+            _ => {
+                let mut texture = None;
+
+                for &(mesh, mat, model) in run.iter() {
+                    if let (Some(local), Material::Basic { texture: tex }) = (&mesh.local, mat) {
+                        batch.push(&local.vertices, &local.indices, &model);
+                        texture = Some(tex);
+                    }
+                }
+
+                if let Some(texture) = texture {
+                    ops.push(DrawOp::Batch(texture, batch.finish()));
+                }
+            }
+        }
+
+        run.clear();
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_basic(
+        &self,
+        encoder: &mut gfx::Encoder<gfx_device::Resources, gfx_device::CommandBuffer>,
+        basic_pipe_bundle: &PipelineBundle<pipe::Meta>,
+        vbuf: &gfx::handle::Buffer<gfx_device::Resources, gfx_types::Vertex>,
+        slice: &gfx::Slice<gfx_device::Resources>,
+        transbuf: &gfx::handle::Buffer<gfx_device::Resources, gfx_types::Transform>,
+        texture: &GlTexture,
+        view_matrix: Matrix4<f32>,
+        proj_matrix: Matrix4<f32>,
+        view_port: &ViewPort,
+    ) {
+        // Prepare data
+        let data = pipe::Data {
+            vbuf: vbuf.clone(),
+            sampler: (texture.bundle.view.clone(), texture.bundle.sampler.clone()),
+            transforms: transbuf.clone(),
+            view: view_matrix.into(),
+            proj: proj_matrix.into(),
+            // The rectangle to allow rendering within
+            scissor: view_port.rect,
+            render_target: self.render_target.clone(),
+            depth_target: self.depth_target.clone(),
+        };
+
+        encoder.draw(slice, &basic_pipe_bundle.pso, &data);
+    }
+
     fn draw_gizmo(
         &self,
         encoder: &mut gfx::Encoder<gfx_device::Resources, gfx_device::CommandBuffer>,
         gizmo_pipe_bundle: &gfx_types::PipelineBundle<gizmo_pipe::Meta>,
         mesh: &Mesh,
-        transform: &Transform,
+        model: Matrix4<f32>,
         view_matrix: Matrix4<f32>,
         proj_matrix: Matrix4<f32>,
         view_port: &ViewPort,
     ) {
         let data = gizmo_pipe::Data {
             vbuf: mesh.vbuf.clone(),
-            model: transform.matrix().into(),
+            model: model.into(),
             view: view_matrix.into(),
             proj: proj_matrix.into(),
             // The rectangle to allow rendering within
@@ -81,27 +234,35 @@ impl<'a> System<'a> for DrawSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let DrawSystemData {
-            // metrics,
+            metrics,
+            settings,
+            debug_render,
             basic_pipe_bundle,
+            instanced_pipe_bundle,
             gloss_pipe_bundle,
+            depth_prepass_bundle,
+            gloss_array_bundle,
             gizmo_pipe_bundle,
             view_port,
+            device_dim,
             active_camera,
             meshes,
+            mesh_instances,
             materials,
             textures,
             transforms,
+            globals,
             cam_views,
             cam_projs,
             gizmos,
+            render_layers,
             lights,
-            point_lights,
         } = data;
         match self.channel.recv_block() {
             Ok(mut encoder) => {
-                // let mut render_timer = metrics.timer(GRAPHICS_RENDER, MetricAggregate::Maximum);
-                // let mut _draw_call_counter =
-                //     metrics.counter(GRAPHICS_DRAW_CALLS, MetricAggregate::Sum);
+                let mut render_timer = metrics
+                    .as_ref()
+                    .map(|metrics| metrics.timer(GRAPHICS_RENDER, MetricAggregate::Average));
 
                 // Without a camera, we draw according to the default OpenGL behaviour
                 let (proj_matrix, view_matrix, eye) = active_camera
@@ -122,37 +283,190 @@ impl<'a> System<'a> for DrawSystem {
                         Vector4::new(0.0, 0.0, 0.0, 1.0),
                     ));
 
+                // Projections are sized to the view port, and drawn inside it
+                let proj_matrix =
+                    view_port.clip_transform(*device_dim.physical_size()) * proj_matrix;
+
                 // Send lights to graphics card
-                let max_lights = lights.max_num();
-                let mut light_count = 0;
-                for (offset, (light_trans, point_light)) in (&transforms, &point_lights)
+                let light_params = lights.light_params();
+                if !light_params.is_empty() {
+                    encoder
+                        .update_buffer(&lights.buffer(), &light_params, 0)
+                        .expect("Failed to update buffer");
+                }
+                let light_count = light_params.len() as i32;
+
+                // Parented entities are drawn using their world matrix
+                let mut items: Vec<(DrawKey, &Mesh, &Material, Matrix4<f32>)> = vec![];
+                for (mesh, mat, trans, global, layer) in (
+                    &meshes,
+                    &materials,
+                    &transforms,
+                    globals.maybe(),
+                    render_layers.maybe(),
+                )
                     .join()
-                    .enumerate()
-                    .take(max_lights)
                 {
-                    let pos = light_trans.position();
-                    let light_params = gfx_types::LightParams {
-                        pos: [pos.x, pos.y, pos.z, 1.0],
-                        ambient: point_light.ambient,
-                        diffuse: point_light.diffuse,
-                        specular: point_light.specular,
+                    let model = GlobalTransform::or_local(global, trans);
+                    let layer = layer.cloned().unwrap_or_default();
+
+                    let key = if mat.is_opaque() {
+                        // Opaque meshes are drawn front to back, so the depth
+                        // test can discard fragments hidden behind them.
+                        let distance = if settings.sort_opaque {
+                            (model.column(3).xyz() - eye.xyz()).norm_squared()
+                        } else {
+                            0.0
+                        };
+                        DrawKey {
+                            layer,
+                            transparent: false,
+                            order: distance,
+                        }
+                    } else {
+                        // The rest are drawn back to front by depth in view
+                        // space, so meshes are composited in the same order
+                        // whether they end up batched or not.
+                        DrawKey {
+                            layer,
+                            transparent: true,
+                            order: (view_matrix * model)[(2, 3)],
+                        }
                     };
 
-                    // Send light to graphics card
+                    items.push((key, mesh, mat, model));
+                }
+
+                // Lower layers are drawn first, and within a layer,
+                // opaque meshes before the rest.
+                items.sort_by(|a, b| a.0.cmp(&b.0));
+
+                let mut draw_calls = 0;
+
+                // Fill the depth buffer with opaque meshes, so the main
+                // pass can skip shading fragments that are hidden.
+                let depth_prepass =
+                    settings.depth_prepass && items.iter().any(|item| !item.0.transparent);
+                if depth_prepass {
+                    for &(_, mesh, _, model) in items.iter().filter(|item| !item.0.transparent) {
+                        let data = depth_pipe::Data {
+                            vbuf: mesh.vbuf.clone(),
+                            model: model.into(),
+                            view: view_matrix.into(),
+                            proj: proj_matrix.into(),
+                            // The rectangle to allow rendering within
+                            scissor: view_port.rect,
+                            depth_target: self.depth_target.clone(),
+                        };
+
+                        encoder.draw(&mesh.slice, &depth_prepass_bundle.depth.pso, &data);
+                        draw_calls += 1;
+                    }
+                }
+
+                let mut ops = Vec::with_capacity(items.len());
+
+                // Merge consecutive meshes that share a pipeline and
+                // texture. Only neighbours are merged, which keeps
+                // the draw order intact.
+                self.batch.clear();
+                let mut run: Vec<(&Mesh, &Material, Matrix4<f32>)> = vec![];
+                let mut run_vertices = 0;
+
+                for (_, mesh, mat, model) in items {
+                    let batchable = match (&mesh.local, mat) {
+                        (Some(local), Material::Basic { texture }) => Some((local, texture)),
+                        _ => None,
+                    };
+
+                    match batchable {
+                        Some((local, texture)) => {
+                            let compatible = match run.first() {
+                                Some((_, Material::Basic { texture: other }, _)) => {
+                                    Arc::ptr_eq(&texture.bundle, &other.bundle)
+                                        && self.batch.fits(run_vertices + local.vertices.len())
+                                }
+                                _ => false,
+                            };
+
+                            if !compatible {
+                                Self::flush_run(&mut self.batch, &mut run, &mut ops);
+                                run_vertices = 0;
+                            }
+
+                            run.push((mesh, mat, model));
+                            run_vertices += local.vertices.len();
+                        }
+                        None => {
+                            Self::flush_run(&mut self.batch, &mut run, &mut ops);
+                            run_vertices = 0;
+                            ops.push(DrawOp::Single(mesh, mat, model));
+                        }
+                    }
+                }
+                Self::flush_run(&mut self.batch, &mut run, &mut ops);
+
+                // Send batched geometry to graphics card
+                if !self.batch.vertices().is_empty() {
+                    self.batch_buffers.reserve(
+                        &mut self.factory,
+                        self.batch.vertices().len(),
+                        self.batch.indices().len(),
+                    );
+
+                    encoder
+                        .update_buffer(&self.batch_buffers.vbuf, self.batch.vertices(), 0)
+                        .expect("Failed to update buffer");
                     encoder
-                        .update_buffer(&lights.buffer(), &[light_params], offset)
+                        .update_buffer(&self.batch_buffers.ibuf, self.batch.indices(), 0)
                         .expect("Failed to update buffer");
 
-                    light_count += 1;
+                    let identity = gfx_types::Transform {
+                        transform: Matrix4::<f32>::identity().into(),
+                    };
+                    encoder
+                        .update_buffer(&self.batch_buffers.transbuf, &[identity], 0)
+                        .expect("Failed to update buffer");
                 }
 
-                for (ref mesh, ref mat, ref trans) in (&meshes, &materials, &transforms).join() {
+                // After a prepass, depth is tested but not written again
+                let (gloss_pso, gloss_array_pso) = if depth_prepass {
+                    (
+                        &depth_prepass_bundle.gloss.pso,
+                        &gloss_array_bundle.prepass.pso,
+                    )
+                } else {
+                    (&gloss_pipe_bundle.pso, &gloss_array_bundle.gloss.pso)
+                };
+
+                for op in ops {
                     // Choose pipeline based on material
-                    match mat {
-                        Material::Basic { texture } => {
+                    match op {
+                        DrawOp::Batch(texture, range) => {
+                            let slice = gfx::Slice {
+                                start: range.start,
+                                end: range.end,
+                                base_vertex: range.base_vertex,
+                                instances: None,
+                                buffer: gfx::IndexBuffer::Index16(self.batch_buffers.ibuf.clone()),
+                            };
+
+                            self.draw_basic(
+                                &mut encoder,
+                                &*basic_pipe_bundle,
+                                &self.batch_buffers.vbuf,
+                                &slice,
+                                &self.batch_buffers.transbuf,
+                                texture,
+                                view_matrix,
+                                proj_matrix,
+                                &*view_port,
+                            );
+                        }
+                        DrawOp::Single(mesh, Material::Basic { texture }, model) => {
                             // Convert to pipeline transform type
                             let trans = gfx_types::Transform {
-                                transform: trans.matrix().into(),
+                                transform: model.into(),
                             };
 
                             // Send transform to graphics card
@@ -160,25 +474,36 @@ impl<'a> System<'a> for DrawSystem {
                                 .update_buffer(&mesh.transbuf, &[trans], 0)
                                 .expect("Failed to update buffer");
 
-                            // Prepare data
-                            let data = pipe::Data {
-                                vbuf: mesh.vbuf.clone(),
-                                sampler: (
-                                    texture.bundle.view.clone(),
-                                    texture.bundle.sampler.clone(),
+                            self.draw_basic(
+                                &mut encoder,
+                                &*basic_pipe_bundle,
+                                &mesh.vbuf,
+                                &mesh.slice,
+                                &mesh.transbuf,
+                                texture,
+                                view_matrix,
+                                proj_matrix,
+                                &*view_port,
+                            );
+                        }
+                        DrawOp::Single(mesh, mat @ Material::Gloss { .. }, model)
+                        | DrawOp::Single(mesh, mat @ Material::GlossArray { .. }, model) => {
+                            // Texture arrays share the gloss pipeline layout,
+                            // with a shader sampling layers.
+                            let (sampler, material, pso) = match mat {
+                                Material::Gloss { texture, material } => (
+                                    (texture.bundle.view.clone(), texture.bundle.sampler.clone()),
+                                    material,
+                                    gloss_pso,
                                 ),
-                                transforms: mesh.transbuf.clone(),
-                                view: view_matrix.into(),
-                                proj: proj_matrix.into(),
-                                // The rectangle to allow rendering within
-                                scissor: view_port.rect,
-                                render_target: self.render_target.clone(),
-                                depth_target: self.depth_target.clone(),
+                                Material::GlossArray { textures, material } => (
+                                    (textures.view.clone(), textures.sampler.clone()),
+                                    material,
+                                    gloss_array_pso,
+                                ),
+                                _ => unreachable!(),
                             };
 
-                            encoder.draw(&mesh.slice, &basic_pipe_bundle.pso, &data);
-                        }
-                        Material::Gloss { texture, material } => {
                             // Send material to graphics card
                             encoder
                                 .update_buffer(
@@ -189,24 +514,20 @@ impl<'a> System<'a> for DrawSystem {
                                 .expect("Failed to update buffer");
 
                             // Surface Normal Matrix
-                            let model_matrix = trans.matrix();
-                            let mut normal_matrix = model_matrix;
+                            let mut normal_matrix = model;
                             normal_matrix.try_inverse_mut();
                             normal_matrix.transpose_mut();
 
                             // Prepare data
                             let data = gloss_pipe::Data {
                                 vbuf: mesh.vbuf.clone(),
-                                sampler: (
-                                    texture.bundle.view.clone(),
-                                    texture.bundle.sampler.clone(),
-                                ),
+                                sampler,
                                 material: material.material_buf.clone(),
                                 lights: lights.buffer().clone(),
                                 num_lights: light_count,
                                 eye: eye.into(),
                                 normal_matrix: normal_matrix.into(),
-                                model: model_matrix.into(),
+                                model: model.into(),
                                 view: view_matrix.into(),
                                 proj: proj_matrix.into(),
                                 // The rectangle to allow rendering within
@@ -215,32 +536,91 @@ impl<'a> System<'a> for DrawSystem {
                                 depth_target: self.depth_target.clone(),
                             };
 
-                            encoder.draw(&mesh.slice, &gloss_pipe_bundle.pso, &data);
+                            encoder.draw(&mesh.slice, pso, &data);
                         }
                         _ => unimplemented!(),
                     }
+
+                    draw_calls += 1;
                 }
 
-                // Second pass for drawing debug gizmos
-                for (ref mesh, ref _mat, ref trans, ref _gizmo) in
-                    (&meshes, &materials, &transforms, &gizmos).join()
+                // Instances of shared meshes, one call per mesh and texture
+                let mut instance_groups = InstanceGroups::<(&SharedMesh, &GlTexture)>::new();
+                for (instance, mat, trans, global) in
+                    (&mesh_instances, &materials, &transforms, globals.maybe()).join()
                 {
-                    self.draw_gizmo(
-                        &mut encoder,
-                        &*gizmo_pipe_bundle,
-                        mesh,
-                        trans,
-                        view_matrix,
-                        proj_matrix,
-                        &*view_port,
-                    );
+                    if let Material::Basic { texture } = mat {
+                        instance_groups.push(
+                            (instance.shared_mesh(), texture),
+                            GlobalTransform::or_local(global, trans).into(),
+                            |a, b| a.0.ptr_eq(b.0) && Arc::ptr_eq(&a.1.bundle, &b.1.bundle),
+                        );
+                    }
+                }
+
+                for ((shared_mesh, texture), instances) in instance_groups.chunks() {
+                    self.instance_buffer
+                        .reserve(&mut self.factory, instances.len());
+
+                    // Instance data is replaced for each call, since
+                    // the encoder executes updates and draws in order.
+                    encoder
+                        .update_buffer(&self.instance_buffer.buf, instances, 0)
+                        .expect("Failed to update buffer");
+
+                    let mesh = shared_mesh.mesh();
+                    let mut slice = mesh.slice.clone();
+                    slice.instances = Some((instances.len() as u32, 0));
+
+                    let data = instanced_pipe::Data {
+                        vbuf: mesh.vbuf.clone(),
+                        instances: self.instance_buffer.buf.clone(),
+                        sampler: (texture.bundle.view.clone(), texture.bundle.sampler.clone()),
+                        view: view_matrix.into(),
+                        proj: proj_matrix.into(),
+                        // The rectangle to allow rendering within
+                        scissor: view_port.rect,
+                        render_target: self.render_target.clone(),
+                        depth_target: self.depth_target.clone(),
+                    };
+
+                    encoder.draw(&slice, &instanced_pipe_bundle.pso, &data);
+                    draw_calls += 1;
+                }
+
+                // Second pass for drawing debug gizmos
+                if debug_render.is_enabled() {
+                    for (ref mesh, ref _mat, ref trans, global, ref _gizmo) in
+                        (&meshes, &materials, &transforms, globals.maybe(), &gizmos).join()
+                    {
+                        self.draw_gizmo(
+                            &mut encoder,
+                            &*gizmo_pipe_bundle,
+                            mesh,
+                            GlobalTransform::or_local(global, trans),
+                            view_matrix,
+                            proj_matrix,
+                            &*view_port,
+                        );
+                        draw_calls += 1;
+                    }
+                }
+
+                if let Some(ref metrics) = metrics {
+                    metrics
+                        .counter(GRAPHICS_DRAW_CALLS, MetricAggregate::Sum)
+                        .set(draw_calls);
+                }
+
+                // Stopped before sending, so the time spent waiting
+                // for the main loop isn't recorded.
+                if let Some(ref mut render_timer) = render_timer {
+                    render_timer.stop();
                 }
 
                 if let Err(err) = self.channel.send_block(encoder) {
                     eprintln!("{}", err);
                 }
-
-                // render_timer.stop();
             }
             Err(err) => eprintln!("{}", err),
         }
@@ -0,0 +1,216 @@
+use log::warn;
+use specs::{Component, Entities, Entity, LazyUpdate, Read, System, Write};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Inserts a single component into an entity.
+type Inserter = Box<dyn FnOnce(&LazyUpdate, Entity) + Send + Sync>;
+
+/// Queue of entity changes, for systems that can't
+/// create or delete entities while iterating.
+///
+/// Commands are applied in order by `EntityUpkeepSystem`, and
+/// take effect when the world is maintained at the end of the frame.
+///
+/// # Example
+///
+/// ```
+/// # use rengine::comp::{Tag, Transform};
+/// # use rengine::sys::{EntityCommands, EntityUpkeepSystem};
+/// # use rengine::specs::{RunNow, World};
+/// let mut world = World::new();
+/// world.register::<Tag>();
+/// world.register::<Transform>();
+/// world.add_resource(EntityCommands::new());
+///
+/// world
+///     .write_resource::<EntityCommands>()
+///     .spawn()
+///     .with(Tag::new("spark"))
+///     .with(Transform::default())
+///     .build();
+///
+/// EntityUpkeepSystem.run_now(&world.res);
+/// world.maintain();
+/// ```
+#[derive(Default)]
+pub struct EntityCommands(VecDeque<EntityCmd>);
+
+impl EntityCommands {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts building an entity, which is queued
+    /// when the builder is built.
+    pub fn spawn(&mut self) -> SpawnBuilder<'_> {
+        SpawnBuilder {
+            commands: self,
+            inserters: vec![],
+        }
+    }
+
+    /// Queues the deletion of an entity.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.submit(EntityCmd::Despawn(entity));
+    }
+
+    /// Queues adding, or replacing, a component of an existing entity.
+    pub fn insert<C>(&mut self, entity: Entity, component: C)
+    where
+        C: Component + Send + Sync,
+    {
+        self.submit(EntityCmd::Insert(entity, inserter(component)));
+    }
+
+    pub fn submit(&mut self, cmd: EntityCmd) {
+        self.0.push_back(cmd);
+    }
+
+    pub fn pop(&mut self) -> Option<EntityCmd> {
+        self.0.pop_front()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for EntityCommands {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("EntityCommands")
+            .field(&self.0.len())
+            .finish()
+    }
+}
+
+fn inserter<C>(component: C) -> Inserter
+where
+    C: Component + Send + Sync,
+{
+    Box::new(move |lazy: &LazyUpdate, entity| lazy.insert(entity, component))
+}
+
+pub enum EntityCmd {
+    Spawn(Vec<Inserter>),
+    Despawn(Entity),
+    Insert(Entity, Inserter),
+}
+
+/// Collects the components of an entity to be spawned
+/// by `EntityCommands`.
+#[must_use = "The entity is only spawned when built"]
+pub struct SpawnBuilder<'a> {
+    commands: &'a mut EntityCommands,
+    inserters: Vec<Inserter>,
+}
+
+impl<'a> SpawnBuilder<'a> {
+    pub fn with<C>(mut self, component: C) -> Self
+    where
+        C: Component + Send + Sync,
+    {
+        self.inserters.push(inserter(component));
+        self
+    }
+
+    /// Queues the entity to be spawned.
+    pub fn build(self) {
+        self.commands.submit(EntityCmd::Spawn(self.inserters));
+    }
+}
+
+/// Applies the commands queued in `EntityCommands`.
+///
+/// Entities are created and deleted immediately, while
+/// components are added when the world is maintained.
+#[derive(Default)]
+pub struct EntityUpkeepSystem;
+
+impl EntityUpkeepSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for EntityUpkeepSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, EntityCommands>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, (entities, mut cmds, lazy): Self::SystemData) {
+        while let Some(cmd) = cmds.pop() {
+            use EntityCmd::*;
+
+            match cmd {
+                Spawn(inserters) => {
+                    let entity = entities.create();
+                    for insert in inserters {
+                        insert(&*lazy, entity);
+                    }
+                }
+                Despawn(entity) => {
+                    if let Err(err) = entities.delete(entity) {
+                        warn!("Failed to despawn queued entity: {}", err);
+                    }
+                }
+                Insert(entity, insert) => insert(&*lazy, entity),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::comp::{Tag, Transform};
+    use specs::{Join, RunNow, World};
+
+    #[test]
+    fn test_queued_spawn() {
+        let mut world = World::new();
+        world.register::<Tag>();
+        world.register::<Transform>();
+        world.add_resource(EntityCommands::new());
+
+        world
+            .write_resource::<EntityCommands>()
+            .spawn()
+            .with(Tag::new("spark"))
+            .with(Transform::default().with_position([1.0, 2.0, 3.0]))
+            .build();
+        assert_eq!(0, world.entities().join().count());
+
+        EntityUpkeepSystem.run_now(&world.res);
+        world.maintain();
+
+        let (entity, tag) = {
+            let tags = world.read_storage::<Tag>();
+            let (entity, tag) = (&world.entities(), &tags).join().next().unwrap();
+            (entity, tag.to_string())
+        };
+        assert!(world.is_alive(entity));
+        assert_eq!("spark", tag);
+        assert!(world.read_storage::<Transform>().contains(entity));
+        assert!(world.read_resource::<EntityCommands>().is_empty());
+
+        // Components are added to, and removed with, existing entities
+        {
+            let mut cmds = world.write_resource::<EntityCommands>();
+            cmds.insert(entity, Tag::new("ember"));
+            cmds.despawn(entity);
+        }
+
+        EntityUpkeepSystem.run_now(&world.res);
+        world.maintain();
+        assert!(!world.is_alive(entity));
+    }
+}
@@ -1,5 +1,9 @@
 //! Systems
 
 mod draw;
+mod entity_commands;
+mod texture_upkeep;
 
 pub use draw::*;
+pub use entity_commands::*;
+pub use texture_upkeep::*;
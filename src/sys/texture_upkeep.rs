@@ -0,0 +1,71 @@
+use crate::comp::GlTexture;
+use crate::metrics::{builtin_metrics::*, MetricAggregate, MetricHub};
+use crate::res::{TextureAssets, TextureEvents, TextureHandle};
+use log::warn;
+use specs::{Entities, Join, Read, System, Write, WriteExpect, WriteStorage};
+
+/// Replaces the placeholder textures of entities once their
+/// asynchronous loads finish, and publishes `TextureEvent`s.
+///
+/// Runs after `TextureAssets::process_pending` has uploaded
+/// the decoded images.
+#[derive(Default)]
+pub struct TextureLoadSystem;
+
+impl<'a> System<'a> for TextureLoadSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, TextureAssets>,
+        Write<'a, TextureEvents>,
+        WriteStorage<'a, TextureHandle>,
+        WriteStorage<'a, GlTexture>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut textures, mut events, mut handles, mut gl_textures): Self::SystemData,
+    ) {
+        events.iter_write(textures.drain_events());
+
+        let mut finished = vec![];
+        for (entity, handle) in (&entities, &handles).join() {
+            if handle.is_loaded() {
+                if let Err(err) = gl_textures.insert(entity, handle.texture()) {
+                    warn!("Failed to replace texture '{}': {}", handle.path(), err);
+                }
+                finished.push(entity);
+            } else if handle.is_failed() {
+                finished.push(entity);
+            }
+        }
+
+        for entity in finished {
+            handles.remove(entity);
+        }
+    }
+}
+
+/// Evicts textures that are no longer used from `TextureAssets`,
+/// and records the texture statistics when metrics are enabled.
+///
+/// Runs once per frame, before the graphics device is cleaned up.
+#[derive(Default)]
+pub struct TextureUpkeepSystem;
+
+impl<'a> System<'a> for TextureUpkeepSystem {
+    type SystemData = (Option<Read<'a, MetricHub>>, WriteExpect<'a, TextureAssets>);
+
+    fn run(&mut self, (metrics, mut textures): Self::SystemData) {
+        textures.maintain();
+
+        if let Some(ref metrics) = metrics {
+            let stats = textures.stats();
+            metrics
+                .counter(TEXTURE_COUNT, MetricAggregate::Maximum)
+                .set(stats.count as u32);
+            metrics
+                .counter(TEXTURE_MEMORY, MetricAggregate::Maximum)
+                .set((stats.bytes / 1024) as u32);
+        }
+    }
+}
@@ -5,6 +5,13 @@ use std::fmt;
 
 const FPS_COUNTER_WINDOW_SIZE: usize = 60;
 
+/// Fraction of the slowest frames averaged for the low FPS.
+const FPS_COUNTER_LOW_PERCENTILE: f32 = 0.01;
+
+/// Number of frames required before the 1% low is calculated,
+/// so it isn't just the slowest frame of the warm-up.
+const FPS_COUNTER_LOW_MIN_SAMPLES: usize = FPS_COUNTER_WINDOW_SIZE;
+
 #[derive(Component)]
 pub struct FpsCounter {
     /// Sliding window of timer durations for
@@ -13,6 +20,10 @@ pub struct FpsCounter {
 
     /// Current position in frame duration window
     cursor: usize,
+
+    /// Number of frames recorded in the window, which is
+    /// less than the window size while warming up.
+    len: usize,
 }
 
 impl FpsCounter {
@@ -21,28 +32,83 @@ impl FpsCounter {
     }
 
     /// Records the delta time for a frame.
+    ///
+    /// Expects the wall clock duration, `DeltaTime::unscaled`, so
+    /// the frame rate isn't affected by pausing or slow motion.
     pub fn add(&mut self, duration: &::std::time::Duration) {
-        let millis = duration.as_millis();
-        self.frames[self.cursor] = millis as f32 / 1000.0;
+        let micros = duration.as_micros();
+        self.frames[self.cursor] = micros as f32 / 1_000_000.0;
         self.cursor = (self.cursor + 1) % FPS_COUNTER_WINDOW_SIZE;
+        self.len = (self.len + 1).min(FPS_COUNTER_WINDOW_SIZE);
+    }
+
+    /// Durations of the frames recorded in the window, in seconds.
+    #[inline]
+    fn samples(&self) -> &[f32] {
+        // The window is filled from the start, so while warming
+        // up the recorded frames are at the front.
+        &self.frames[..self.len]
     }
 
     /// Calculates the frames per second for the past
     /// frame window.
+    ///
+    /// Returns zero when no frames have been recorded.
     pub fn fps(&self) -> f32 {
-        if FPS_COUNTER_WINDOW_SIZE == 0 {
-            panic!("FPS Counter window size is zero")
-        }
+        average_fps(&self.frames)
+    }
+
+    /// Duration of the slowest frame in the window, in seconds.
+    pub fn max_frame_time(&self) -> f32 {
+        self.samples().iter().fold(0.0, |acc, x| acc.max(*x))
+    }
 
-        let total = self.frames.iter().fold(0.0, |acc, x| acc + x);
-        let average_dt = total / FPS_COUNTER_WINDOW_SIZE as f32;
+    /// Frames per second of the slowest frame in the window.
+    ///
+    /// Returns zero when no frames have been recorded.
+    pub fn min_fps(&self) -> f32 {
+        let max_frame_time = self.max_frame_time();
 
-        if average_dt != 0.0 {
-            1.0 / average_dt
+        if max_frame_time != 0.0 {
+            1.0 / max_frame_time
         } else {
             0.0
         }
     }
+
+    /// Average frames per second of the slowest 1% of frames
+    /// in the window, which shows hitches the average hides.
+    ///
+    /// Until enough frames have been recorded, the average frames
+    /// per second of the recorded frames is returned instead.
+    pub fn one_percent_low(&self) -> f32 {
+        if self.len < FPS_COUNTER_LOW_MIN_SAMPLES {
+            return average_fps(self.samples());
+        }
+
+        let mut frames = self.frames;
+        let samples = &mut frames[..self.len];
+        samples.sort_by(|a, b| b.partial_cmp(a).unwrap_or(::std::cmp::Ordering::Equal));
+
+        let count = (self.len as f32 * FPS_COUNTER_LOW_PERCENTILE).ceil() as usize;
+        average_fps(&samples[..count.max(1)])
+    }
+}
+
+/// Frames per second of the average of the given frame durations.
+fn average_fps(frames: &[f32]) -> f32 {
+    if frames.is_empty() {
+        return 0.0;
+    }
+
+    let total = frames.iter().fold(0.0, |acc, x| acc + x);
+    let average_dt = total / frames.len() as f32;
+
+    if average_dt != 0.0 {
+        1.0 / average_dt
+    } else {
+        0.0
+    }
 }
 
 impl Default for FpsCounter {
@@ -50,6 +116,7 @@ impl Default for FpsCounter {
         FpsCounter {
             frames: [0.0; FPS_COUNTER_WINDOW_SIZE],
             cursor: 0,
+            len: 0,
         }
     }
 }
@@ -67,35 +134,128 @@ impl fmt::Display for FpsCounter {
 use crate::colors;
 use crate::comp::Transform;
 use crate::gui;
+use crate::gui::text::{TextAlignHorizontal, TextAlignVertical, TextShadow};
 use specs::{Builder, Entity, World};
 
 /// Helper to create a basic FPS counter text output.
 ///
 /// The text will be added to the root widget.
 pub fn create_fps_counter_widget(world: &mut World) -> Entity {
+    create_anchored_fps_counter_widget(world, gui::Anchor::TopLeft)
+}
+
+/// Helper to create a basic FPS counter text output, attached
+/// to the given anchor of the root widget.
+///
+/// The text is aligned to match the anchor, so it stays
+/// within the window when it is resized.
+pub fn create_anchored_fps_counter_widget(world: &mut World, anchor: gui::Anchor) -> Entity {
+    create_shadowed_fps_counter_widget(world, anchor, None)
+}
+
+/// Helper to create a basic FPS counter text output, attached
+/// to the given anchor of the root widget.
+///
+/// When a shadow is given, it's drawn behind the text to keep
+/// the counter readable over bright scenes.
+pub fn create_shadowed_fps_counter_widget(
+    world: &mut World,
+    anchor: gui::Anchor,
+    shadow: Option<TextShadow>,
+) -> Entity {
+    let (align_v, align_h) = text_align(anchor);
+    let mut text_batch = gui::text::TextBatch::default()
+        .with("FPS: 0", colors::WHITE)
+        .with_align(align_v, align_h);
+    text_batch.set_shadow(shadow);
+
     let entity = world
         .create_entity()
         .with(FpsCounter::new())
         .with(gui::Placement::new(0.0, 0.0))
+        .with(anchor)
         .with(gui::GlobalPosition::default())
         .with(gui::BoundsRect::new(
             ::std::f32::INFINITY,
             ::std::f32::INFINITY,
         ))
-        .with(
-            gui::text::TextBatch::default()
-                .with("FPS: 0", colors::WHITE)
-                .with_align(
-                    gui::text::TextAlignVertical::Top,
-                    gui::text::TextAlignHorizontal::Left,
-                ),
-        )
+        .with(text_batch)
         .with(Transform::default())
         .build();
 
     let _node_id = world
         .write_resource::<gui::GuiGraph>()
         .insert_entity(entity, None);
+    gui::invalidate_layout(world);
 
     entity
 }
+
+/// Text alignment that keeps text on the inside of the anchor.
+fn text_align(anchor: gui::Anchor) -> (TextAlignVertical, TextAlignHorizontal) {
+    let point = anchor.point();
+
+    let align_v = if point.y >= 1.0 {
+        TextAlignVertical::Bottom
+    } else if point.y > 0.0 {
+        TextAlignVertical::Center
+    } else {
+        TextAlignVertical::Top
+    };
+
+    let align_h = if point.x >= 1.0 {
+        TextAlignHorizontal::Right
+    } else if point.x > 0.0 {
+        TextAlignHorizontal::Center
+    } else {
+        TextAlignHorizontal::Left
+    };
+
+    (align_v, align_h)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fps_counter_warm_up() {
+        let mut counter = FpsCounter::new();
+        assert_eq!(0.0, counter.fps());
+        assert_eq!(0.0, counter.min_fps());
+        assert_eq!(0.0, counter.one_percent_low());
+
+        counter.add(&Duration::from_millis(10));
+        counter.add(&Duration::from_millis(40));
+        assert!((counter.max_frame_time() - 0.04).abs() < 0.0001);
+        assert!((counter.min_fps() - 25.0).abs() < 0.01);
+
+        // Not enough frames for a low percentile, so the
+        // recorded frames are averaged.
+        assert!((counter.one_percent_low() - 40.0).abs() < 0.01);
+        assert!(counter.one_percent_low() >= counter.min_fps());
+    }
+
+    #[test]
+    fn test_fps_counter_one_percent_low() {
+        let mut counter = FpsCounter::new();
+        for _ in 0..FPS_COUNTER_WINDOW_SIZE - 2 {
+            counter.add(&Duration::from_millis(10));
+        }
+        counter.add(&Duration::from_millis(50));
+        counter.add(&Duration::from_millis(100));
+
+        // Slowest frame of the window.
+        assert!((counter.one_percent_low() - 10.0).abs() < 0.01);
+        assert!((counter.min_fps() - 10.0).abs() < 0.01);
+        assert!(counter.fps() > counter.one_percent_low());
+
+        // Hitches roll out of the window.
+        for _ in 0..FPS_COUNTER_WINDOW_SIZE {
+            counter.add(&Duration::from_millis(10));
+        }
+        assert!((counter.one_percent_low() - 100.0).abs() < 0.01);
+        assert!((counter.fps() - 100.0).abs() < 0.01);
+    }
+}
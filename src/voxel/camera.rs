@@ -2,30 +2,40 @@
 
 use crate::camera::{ActiveCamera, CameraProjection, CameraView};
 use crate::option::lift2;
-use crate::res::DeviceDimensions;
-use crate::voxel::{voxel_raycast, VoxelRaycast};
+use crate::res::{DeviceDimensions, ViewPort};
+use crate::voxel::{voxel_raycast, VoxelRayInfo, VoxelRaycast};
 use glutin::dpi::{PhysicalPosition, PhysicalSize};
-use nalgebra::{Matrix4, Perspective3, Point3, Unit};
-use specs::{Read, ReadStorage};
+use nalgebra::{Matrix4, Perspective3, Point3, Unit, Vector3};
+use specs::{Read, ReadExpect, ReadStorage};
+
+/// System data needed to cast rays from the active camera.
+pub type CameraRaycastData<'a> = (
+    Read<'a, ActiveCamera>,
+    Read<'a, DeviceDimensions>,
+    ReadStorage<'a, CameraView>,
+    ReadStorage<'a, CameraProjection>,
+    ReadExpect<'a, ViewPort>,
+);
 
 /// Raycast from camera using system data
 ///
+/// Returns a walker over every voxel the ray passes through,
+/// nearest to the camera first.
+///
+/// The screen position is mapped through the `ViewPort` rectangle,
+/// so positions outside of the view port don't cast a ray.
+///
 /// ## Example
 ///
 /// ```ignore
 /// let raycast = raycast_from_camera(world.system_data(), PhysicalPosition::new(800, 600), 1000)
 /// ```
 pub fn raycast_from_camera(
-    data: (
-        Read<'_, ActiveCamera>,
-        Read<'_, DeviceDimensions>,
-        ReadStorage<'_, CameraView>,
-        ReadStorage<'_, CameraProjection>,
-    ),
+    data: CameraRaycastData<'_>,
     screen_pos: PhysicalPosition,
     steps: u32,
 ) -> Option<VoxelRaycast> {
-    let (active_camera, device_dim, cam_views, cam_projs) = data;
+    let (active_camera, device_dim, cam_views, cam_projs, view_port) = data;
 
     let maybe_cam = active_camera
         .camera_entity()
@@ -47,6 +57,7 @@ pub fn raycast_from_camera(
             projection,
             cam_view.view_matrix(),
             *device_dim.physical_size(),
+            &view_port,
             screen_pos,
             steps,
         );
@@ -55,41 +66,70 @@ pub fn raycast_from_camera(
     None
 }
 
+/// Collects every voxel intersected by a ray from the camera, in order,
+/// for line of sight checks or projectiles passing through voxels.
+///
+/// Returns an empty list when the screen position is outside the view port.
+pub fn raycast_from_camera_all(
+    data: CameraRaycastData<'_>,
+    screen_pos: PhysicalPosition,
+    max_steps: u32,
+) -> Vec<VoxelRayInfo> {
+    raycast_from_camera(data, screen_pos, max_steps)
+        .map(Iterator::collect)
+        .unwrap_or_default()
+}
+
+/// Collects the voxels intersected by a ray from the camera, in order, up
+/// to and including the first voxel satisfying the predicate.
+///
+/// See `VoxelRaycast::until`.
+pub fn raycast_from_camera_until<F>(
+    data: CameraRaycastData<'_>,
+    screen_pos: PhysicalPosition,
+    max_steps: u32,
+    predicate: F,
+) -> Option<Vec<VoxelRayInfo>>
+where
+    F: FnMut(&VoxelRayInfo) -> bool,
+{
+    raycast_from_camera(data, screen_pos, max_steps)?.until(predicate)
+}
+
 pub fn camera_raycast(
     projection: Perspective3<f32>,
     view_matrix: Matrix4<f32>,
     device_size: PhysicalSize,
+    view_port: &ViewPort,
     screen_pos: PhysicalPosition,
     steps: u32,
 ) -> Option<VoxelRaycast> {
-    // Point must be between [0.0, 1.0] to unproject
-    let (device_w, device_h) = (device_size.width as f32, device_size.height as f32);
+    let (world_point, world_direction) =
+        camera_ray(projection, view_matrix, device_size, view_port, screen_pos)?;
+
+    // Create ray walker
+    Some(voxel_raycast(world_point, world_direction, steps))
+}
 
-    // Convert glutin screen position to computer graphics screen coordinates
-    let (screen_w, screen_h) = (
-        screen_pos.x as f32 - (device_w / 2.),
-        -(screen_pos.y as f32 - (device_h / 2.)),
-    );
+/// Computes the world space ray passing through the screen position.
+///
+/// Returns `None` when the position is outside the view port.
+fn camera_ray(
+    projection: Perspective3<f32>,
+    view_matrix: Matrix4<f32>,
+    device_size: PhysicalSize,
+    view_port: &ViewPort,
+    screen_pos: PhysicalPosition,
+) -> Option<(Point3<f32>, Unit<Vector3<f32>>)> {
+    // Convert glutin screen position to normalized device coordinates,
+    // relative to the view port rectangle the camera renders to. Each
+    // axis has a range between -1.0 and 1.0.
+    let [ndc_x, ndc_y] = view_port.to_ndc(screen_pos, device_size.height)?;
 
     // Use screen position to compute two points in clip space, where near
     // and far are -1 and 1 respectively.
-    //
-    // "ndc" = normalized device coordinates
-    //
-    // Multiplying with 2 is required because dividing the screen position
-    // with the device size yields a value between 0.0 and 1.0. Normalized
-    // device coordinates are a double unit cube, meaning each axis has a
-    // range between -1.0 and 1.0.
-    let near_ndc_point = Point3::new(
-        (screen_w / device_w) * 2.0,
-        (screen_h / device_h) * 2.0,
-        -1.0,
-    );
-    let far_ndc_point = Point3::new(
-        (screen_w / device_w) * 2.0,
-        (screen_h / device_h) * 2.0,
-        1.0,
-    );
+    let near_ndc_point = Point3::new(ndc_x, ndc_y, -1.0);
+    let far_ndc_point = Point3::new(ndc_x, ndc_y, 1.0);
 
     // Unproject clip space points to view space
     let near_view_point = projection.unproject_point(&near_ndc_point);
@@ -108,6 +148,41 @@ pub fn camera_raycast(
     let world_point = inverse_view_mat.transform_point(&line_point);
     let world_direction = Unit::new_normalize(inverse_view_mat.transform_vector(&line_direction));
 
-    // Create ray walker
-    Some(voxel_raycast(world_point, world_direction, steps))
+    Some((world_point, world_direction))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_camera_ray_view_port() {
+        // Scene view in the right 600x600 pixels of a 800x600 window
+        let projection = Perspective3::new(1.0, ::std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        let device_size = PhysicalSize::new(800.0, 600.0);
+        let view_port = ViewPort::with_rect(200, 0, 600, 600);
+
+        // Centre of the view port looks straight ahead
+        let (_, direction) = camera_ray(
+            projection,
+            Matrix4::identity(),
+            device_size,
+            &view_port,
+            PhysicalPosition::new(500.0, 300.0),
+        )
+        .unwrap();
+        assert!((direction.x).abs() < 1.0e-5);
+        assert!((direction.y).abs() < 1.0e-5);
+        assert!((direction.z + 1.0).abs() < 1.0e-5);
+
+        // Clicking the panel left of the view port doesn't pick
+        assert!(camera_ray(
+            projection,
+            Matrix4::identity(),
+            device_size,
+            &view_port,
+            PhysicalPosition::new(100.0, 300.0),
+        )
+        .is_none());
+    }
 }
@@ -76,6 +76,28 @@ pub trait VoxelChunk<D: VoxelData> {
 
     /// Sets the voxel data at the given coordinate.
     fn set<V: Into<VoxelCoord>>(&mut self, coord: V, data: D);
+
+    /// Iterates the occupied voxels in the chunk, with
+    /// their global voxel coordinates.
+    ///
+    /// The default implementation looks up each local
+    /// coordinate in turn. Chunks with a storage layout that
+    /// can skip empty space should override it.
+    fn iter_occupied<'a>(&'a self) -> Box<dyn Iterator<Item = (VoxelCoord, &'a D)> + 'a>
+    where
+        D: 'a,
+    {
+        let dim = self.dim() as i32;
+        let offset = *self.voxel_offset();
+
+        Box::new((0..dim * dim * dim).filter_map(move |index| {
+            let local_coord =
+                VoxelCoord::new(index % dim, (index / dim) % dim, index / (dim * dim));
+            self.get_local(local_coord)
+                .filter(|data| data.occupied())
+                .map(|data| (local_coord + offset, data))
+        }))
+    }
 }
 
 /// Trait describing a chunk that keeps adjacency
@@ -277,7 +299,7 @@ fn create_mask(voxel_offset: &[i32; 3]) -> VoxelAdjacencyMask {
 ///
 /// No deduplication or compression is applied to the
 /// data.
-#[derive(Component)]
+#[derive(Component, Clone)]
 #[storage(DenseVecStorage)]
 pub struct VoxelArrayChunk<D: 'static + VoxelData + Sync + Send> {
     /// Unique identifier for this chunk.
@@ -417,6 +439,28 @@ where
         }
     }
 
+    fn iter_occupied<'a>(&'a self) -> Box<dyn Iterator<Item = (VoxelCoord, &'a D)> + 'a>
+    where
+        D: 'a,
+    {
+        let dim = CHUNK_DIM8 as i32;
+        let offset = self.voxel_offset;
+
+        // Walks the packed array, which is ordered the same as `data_index`.
+        Box::new(
+            self.data
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, data))| data.occupied())
+                .map(move |(index, (_, data))| {
+                    let index = index as i32;
+                    let local_coord =
+                        VoxelCoord::new(index % dim, (index / dim) % dim, index / (dim * dim));
+                    (local_coord + offset, data)
+                }),
+        )
+    }
+
     fn set<V>(&mut self, coord: V, data: D)
     where
         V: Into<VoxelCoord>,
@@ -576,4 +620,27 @@ mod test {
         assert!(chunk.mask_local([1, 2, 2]).unwrap().empty_top());
         assert!(!chunk.mask_local([3, 2, 2]).unwrap().empty_left());
     }
+
+    #[test]
+    fn test_iter_occupied() {
+        let mut chunk: VoxelArrayChunk<u16> = VoxelArrayChunk::new([1, 0, -1]);
+        chunk.set([8, 0, -8], 1);
+        chunk.set([10, 3, -2], 2);
+        chunk.set([15, 7, -1], 3);
+        chunk.set([12, 4, -4], 4);
+        chunk.set([12, 4, -4], 0);
+
+        let mut occupied: Vec<(VoxelCoord, u16)> = chunk
+            .iter_occupied()
+            .map(|(coord, data)| (coord, *data))
+            .collect();
+        occupied.sort();
+
+        let expected = vec![
+            (VoxelCoord::new(8, 0, -8), 1),
+            (VoxelCoord::new(10, 3, -2), 2),
+            (VoxelCoord::new(15, 7, -1), 3),
+        ];
+        assert_eq!(expected, occupied);
+    }
 }
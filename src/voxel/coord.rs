@@ -18,6 +18,31 @@ impl VoxelCoord {
         VoxelCoord { i, j, k }
     }
 
+    /// Coordinate of the voxel containing the world position,
+    /// where voxels are cubes of the given size.
+    ///
+    /// ```
+    /// # use rengine::voxel::VoxelCoord;
+    /// # use rengine::glm;
+    /// let coord = VoxelCoord::from_world(glm::vec3(1.5, -0.25, 0.0), 0.5);
+    /// assert_eq!(VoxelCoord::new(3, -1, 0), coord);
+    /// ```
+    pub fn from_world(world_pos: glm::Vec3, voxel_scale: f32) -> Self {
+        VoxelCoord::from(world_pos / voxel_scale)
+    }
+
+    /// World position of the voxel's center, where voxels
+    /// are cubes of the given size.
+    ///
+    /// The inverse of `from_world`.
+    pub fn to_world_center(&self, voxel_scale: f32) -> glm::Vec3 {
+        glm::vec3(
+            self.i as f32 + 0.5,
+            self.j as f32 + 0.5,
+            self.k as f32 + 0.5,
+        ) * voxel_scale
+    }
+
     /// Tests for diagonality on the i, j plane
     pub fn is_diagonal_k(&self, rhs: &VoxelCoord) -> bool {
         let x = rhs.i - self.i;
@@ -128,6 +153,12 @@ impl From<[f32; 3]> for VoxelCoord {
     }
 }
 
+impl From<glm::Vec3> for VoxelCoord {
+    fn from(val: glm::Vec3) -> VoxelCoord {
+        VoxelCoord::from([val.x, val.y, val.z])
+    }
+}
+
 impl From<(i32, i32, i32)> for VoxelCoord {
     fn from(val: (i32, i32, i32)) -> VoxelCoord {
         VoxelCoord {
@@ -235,4 +266,27 @@ mod test {
             "Adding volel coordinate by reference failed"
         );
     }
+
+    #[test]
+    fn test_voxel_coord_from_float() {
+        let coord: VoxelCoord = [-0.5, 0.0, 0.0].into();
+        assert_eq!(VoxelCoord::new(-1, 0, 0), coord);
+        let coord: VoxelCoord = [-1.0, 1.999, 0.5].into();
+        assert_eq!(VoxelCoord::new(-2, 1, 0), coord);
+        assert_eq!(
+            VoxelCoord::new(-1, 0, 3),
+            VoxelCoord::from(glm::vec3(-0.001, 0.0, 3.5))
+        );
+
+        // Round trip through the center, at different scales.
+        for &scale in &[1.0, 0.25, 2.0] {
+            let coord = VoxelCoord::new(-3, 0, 7);
+            let center = coord.to_world_center(scale);
+            assert_eq!(coord, VoxelCoord::from_world(center, scale));
+        }
+        assert_eq!(
+            glm::vec3(-1.0, 1.0, 3.0),
+            VoxelCoord::new(-1, 0, 1).to_world_center(2.0)
+        );
+    }
 }
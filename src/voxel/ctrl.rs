@@ -1,11 +1,15 @@
 use crate::comp::{MeshBuilder, MeshCmd, MeshCommandBuffer};
+use crate::metrics::{builtin_metrics::*, MetricAggregate, MetricHub};
 use crate::voxel::{
-    voxel_to_chunk, ChunkCoord, MaskedChunk, VoxelChunk, VoxelCoord, VoxelData, VoxelMeshGen,
+    voxel_to_chunk, ChunkCoord, MaskedChunk, MeshJob, MeshResult, MeshWorkerPool, VoxelChunk,
+    VoxelCoord, VoxelData, VoxelMeshGen, CHUNK_DIM8,
 };
 use log::warn;
-use specs::{Component, Entity, System, Write, WriteStorage};
-use std::collections::{HashMap, HashSet};
+use specs::{Component, Entities, Entity, NullStorage, Read, System, Write, WriteStorage};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 /// Global control of multiple chunks, to enforce
 /// rules across sibling chunks.
@@ -93,15 +97,27 @@ impl ChunkMapping {
 /// Applies queued updates to chunks, and regenerates
 /// the chunk's mesh.
 ///
+/// Meshes are generated on the calling thread. To generate them
+/// on background threads instead, see `AsyncChunkUpkeepSystem`.
+///
+/// When a remesh budget is set, at most that many chunks are
+/// remeshed per run, nearest to the `RemeshFocus` first. The rest
+/// stay dirty for later runs, and chunks that have waited
+/// `MAX_REMESH_DEFER_FRAMES` runs go first, so none are starved.
+///
 /// Intended to be called at the beginning of a frame update.
 pub struct ChunkUpkeepSystem<D: VoxelData, C: VoxelChunk<D>, G: VoxelMeshGen> {
-    /// Chunks touched by update, that needs updating.
+    /// Chunks touched by update, that needs updating, with
+    /// the number of runs they've been deferred.
     ///
     /// Kept in struct to avoid constnt allocation.
-    dirty: HashSet<ChunkCoord>,
+    dirty: HashMap<ChunkCoord, u32>,
+
+    /// Maximum number of chunks remeshed per run.
+    remesh_budget: Option<usize>,
 
     /// Mesh generator invoked when generating chunks.
-    mesh_gen: G,
+    mesh_gen: Arc<G>,
     _marker: PhantomData<(D, C)>,
 }
 
@@ -115,8 +131,23 @@ where
     chunk_map: Write<'a, ChunkMapping>,
     chunks: WriteStorage<'a, C>,
     mesh_cmds: Write<'a, MeshCommandBuffer>,
+    focus: Option<Read<'a, RemeshFocus>>,
+    metrics: Option<Read<'a, MetricHub>>,
 }
 
+/// Position that dirty chunks are remeshed nearest to first,
+/// when the upkeep system has a remesh budget.
+///
+/// Usually kept at the eye of the active camera. Without the
+/// resource, chunks within the budget are picked in no
+/// particular order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemeshFocus(pub glm::Vec3);
+
+/// Number of runs a dirty chunk can be deferred by the remesh
+/// budget, before it's remeshed ahead of nearer chunks.
+pub const MAX_REMESH_DEFER_FRAMES: u32 = 8;
+
 impl<D, C, G> ChunkUpkeepSystem<D, C, G>
 where
     D: VoxelData,
@@ -125,43 +156,54 @@ where
 {
     pub fn new(mesh_gen: G) -> Self {
         ChunkUpkeepSystem {
-            dirty: HashSet::new(),
-            mesh_gen,
+            dirty: HashMap::new(),
+            remesh_budget: None,
+            mesh_gen: Arc::new(mesh_gen),
             _marker: PhantomData,
         }
     }
+
+    /// Limits the number of chunks remeshed per run, to
+    /// avoid frame spikes when many chunks change at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the budget is zero.
+    pub fn with_remesh_budget(mut self, budget: usize) -> Self {
+        assert!(budget > 0, "Remesh budget must be greater than zero");
+        self.remesh_budget = Some(budget);
+        self
+    }
 }
 
-impl<'a, D, C, G> System<'a> for ChunkUpkeepSystem<D, C, G>
+impl<D, C, G> ChunkUpkeepSystem<D, C, G>
 where
     D: 'static + VoxelData + Send + Sync,
     C: 'static + VoxelChunk<D> + Component + MaskedChunk + Send + Sync,
     G: 'static + VoxelMeshGen + Send + Sync,
 {
-    type SystemData = ChunkUpkeepSystemData<'a, D, C>;
-
-    fn run(&mut self, data: Self::SystemData) {
+    /// Applies queued updates to chunks, marking them dirty,
+    /// and calls `changed` with each chunk entity updated.
+    fn apply_updates<F>(&mut self, data: &mut ChunkUpkeepSystemData<'_, D, C>, mut changed: F)
+    where
+        F: FnMut(Entity),
+    {
         use LazyCommand::*;
-        let ChunkUpkeepSystemData {
-            mut chunk_ctrl,
-            chunk_map,
-            mut chunks,
-            mut mesh_cmds,
-        } = data;
-
-        for cmd in chunk_ctrl.cmds.drain(..).into_iter() {
+
+        for cmd in data.chunk_ctrl.cmds.drain(..).into_iter() {
             match cmd {
                 UpdateData(voxel_coord, voxel_data) => {
                     // Convert voxel coordinate to chunk coordinate
                     let chunk_coord = voxel_to_chunk(&voxel_coord);
 
                     // Retrieve chunk entity
-                    if let Some(entity) = chunk_map.0.get(&chunk_coord) {
+                    if let Some(entity) = data.chunk_map.0.get(&chunk_coord) {
                         // Retireve chunk component
-                        if let Some(chunk) = chunks.get_mut(*entity) {
+                        if let Some(chunk) = data.chunks.get_mut(*entity) {
                             // Update chunk data
                             chunk.set(voxel_coord, voxel_data);
-                            self.dirty.insert(chunk_coord.clone());
+                            self.dirty.entry(chunk_coord.clone()).or_insert(0);
+                            changed(*entity);
                         }
                     } else {
                         warn!("Chunk not found for {}", chunk_coord);
@@ -169,22 +211,264 @@ where
                 }
             }
         }
+    }
 
-        if !self.dirty.is_empty() {
-            for chunk_coord in self.dirty.iter() {
-                // Retrieve chunk entity
-                if let Some(entity) = chunk_map.0.get(&chunk_coord) {
-                    // Retireve chunk component
-                    if let Some(chunk) = chunks.get_mut(*entity) {
-                        mesh_cmds.submit(MeshCmd::AllocateMesh(
-                            *entity,
-                            self.mesh_gen.generate(chunk, MeshBuilder::new()),
-                        ));
-                    }
+    /// Takes the dirty chunks to be remeshed this run, leaving
+    /// the chunks over budget in the dirty set.
+    fn take_remesh_queue(&mut self, data: &ChunkUpkeepSystemData<'_, D, C>) -> Vec<ChunkCoord> {
+        let queue = match self.remesh_budget {
+            Some(budget) if budget < self.dirty.len() => self.take_nearest(data, budget),
+            _ => self.dirty.drain().map(|(coord, _)| coord).collect(),
+        };
+
+        if let Some(ref metrics) = data.metrics {
+            metrics
+                .counter(CHUNK_REMESH_PROCESSED, MetricAggregate::Sum)
+                .set(queue.len() as u32);
+            metrics
+                .counter(CHUNK_REMESH_DEFERRED, MetricAggregate::Maximum)
+                .set(self.dirty.len() as u32);
+        }
+
+        queue
+    }
+
+    fn take_nearest(
+        &mut self,
+        data: &ChunkUpkeepSystemData<'_, D, C>,
+        budget: usize,
+    ) -> Vec<ChunkCoord> {
+        let focus = data.focus.as_ref().map(|focus| focus.0);
+
+        let mut queue: Vec<(ChunkCoord, u32, f32)> = self
+            .dirty
+            .drain()
+            .map(|(coord, waited)| {
+                // Voxels are one unit in size.
+                let center = glm::vec3(
+                    (coord.i as f32 + 0.5) * CHUNK_DIM8 as f32,
+                    (coord.j as f32 + 0.5) * CHUNK_DIM8 as f32,
+                    (coord.k as f32 + 0.5) * CHUNK_DIM8 as f32,
+                );
+                let distance = focus.map_or(0.0, |pos| (center - pos).norm_squared());
+                (coord, waited, distance)
+            })
+            .collect();
+
+        // Overdue chunks first, longest waiting first, then the nearest.
+        queue.sort_by(|a, b| {
+            let a_overdue = a.1 >= MAX_REMESH_DEFER_FRAMES;
+            let b_overdue = b.1 >= MAX_REMESH_DEFER_FRAMES;
+            let order = match (a_overdue, b_overdue) {
+                (true, true) => b.1.cmp(&a.1),
+                _ => b_overdue.cmp(&a_overdue),
+            };
+            order.then_with(|| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal))
+        });
+
+        for (coord, waited, _) in queue.split_off(budget) {
+            self.dirty.insert(coord, waited + 1);
+        }
+
+        queue.into_iter().map(|(coord, _, _)| coord).collect()
+    }
+}
+
+impl<'a, D, C, G> System<'a> for ChunkUpkeepSystem<D, C, G>
+where
+    D: 'static + VoxelData + Send + Sync,
+    C: 'static + VoxelChunk<D> + Component + MaskedChunk + Send + Sync,
+    G: 'static + VoxelMeshGen + Send + Sync,
+{
+    type SystemData = ChunkUpkeepSystemData<'a, D, C>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        self.apply_updates(&mut data, |_| {});
+
+        for chunk_coord in self.take_remesh_queue(&data) {
+            // Retrieve chunk entity
+            if let Some(entity) = data.chunk_map.0.get(&chunk_coord) {
+                // Retireve chunk component
+                if let Some(chunk) = data.chunks.get(*entity) {
+                    data.mesh_cmds.submit(MeshCmd::AllocateMesh(
+                        *entity,
+                        self.mesh_gen.generate(chunk, MeshBuilder::new()),
+                    ));
                 }
             }
+        }
+    }
+}
 
-            self.dirty.clear();
+/// Applies queued updates to chunks, like `ChunkUpkeepSystem`,
+/// and regenerates the chunk meshes on background threads.
+///
+/// The system runs in two phases. The first sends snapshots of
+/// dirty chunks to the workers, marking their entities with
+/// `ChunkMeshPending`. The second applies the meshes that have
+/// completed. Running the system runs both phases.
+pub struct AsyncChunkUpkeepSystem<D: VoxelData, C: VoxelChunk<D>, G: VoxelMeshGen> {
+    upkeep: ChunkUpkeepSystem<D, C, G>,
+
+    /// Background mesh generation.
+    workers: MeshWorkerPool<C>,
+
+    /// Latest generation sent to the workers, for
+    /// each chunk entity waiting on a mesh.
+    in_flight: HashMap<Entity, u64>,
+
+    /// Number of jobs sent to the workers that haven't
+    /// returned, including stale ones.
+    outstanding: usize,
+
+    next_generation: u64,
+}
+
+#[derive(SystemData)]
+pub struct AsyncChunkUpkeepSystemData<'a, D, C>
+where
+    D: 'static + VoxelData + Send + Sync,
+    C: 'static + VoxelChunk<D> + Component + Send + Sync,
+{
+    entities: Entities<'a>,
+    upkeep: ChunkUpkeepSystemData<'a, D, C>,
+    pending: WriteStorage<'a, ChunkMeshPending>,
+}
+
+impl<D, C, G> AsyncChunkUpkeepSystem<D, C, G>
+where
+    D: 'static + VoxelData + Send + Sync,
+    C: 'static + VoxelChunk<D> + Component + MaskedChunk + Clone + Send + Sync,
+    G: 'static + VoxelMeshGen + Send + Sync,
+{
+    /// Creates a system that generates meshes on the
+    /// given number of background threads.
+    pub fn with_workers(mesh_gen: G, thread_count: usize) -> Self {
+        let upkeep = ChunkUpkeepSystem::new(mesh_gen);
+        let workers = MeshWorkerPool::new::<D, G>(upkeep.mesh_gen.clone(), thread_count);
+
+        AsyncChunkUpkeepSystem {
+            upkeep,
+            workers,
+            in_flight: HashMap::new(),
+            outstanding: 0,
+            next_generation: 0,
         }
     }
+
+    /// Limits the number of chunks sent to the workers per run.
+    ///
+    /// See `ChunkUpkeepSystem::with_remesh_budget`.
+    pub fn with_remesh_budget(mut self, budget: usize) -> Self {
+        self.upkeep = self.upkeep.with_remesh_budget(budget);
+        self
+    }
+
+    /// Applies queued updates to chunks, and sends a snapshot
+    /// of the chunks that changed to the workers.
+    pub fn phase_one(&mut self, data: &mut AsyncChunkUpkeepSystemData<'_, D, C>) {
+        // A mesh in-flight is now stale, even when the
+        // new snapshot is deferred by the remesh budget.
+        let in_flight = &mut self.in_flight;
+        self.upkeep.apply_updates(&mut data.upkeep, |entity| {
+            in_flight.remove(&entity);
+        });
+
+        for chunk_coord in self.upkeep.take_remesh_queue(&data.upkeep) {
+            // Retrieve chunk entity
+            if let Some(entity) = data.upkeep.chunk_map.0.get(&chunk_coord).copied() {
+                // Retireve chunk component
+                if let Some(chunk) = data.upkeep.chunks.get(entity) {
+                    self.next_generation += 1;
+                    self.in_flight.insert(entity, self.next_generation);
+                    self.outstanding += 1;
+                    self.workers.submit(MeshJob {
+                        entity,
+                        generation: self.next_generation,
+                        chunk: chunk.clone(),
+                    });
+                    data.pending
+                        .insert(entity, ChunkMeshPending)
+                        .expect("Failed to mark chunk mesh pending");
+                }
+            }
+        }
+    }
+
+    /// Submits meshes completed by the background workers.
+    ///
+    /// When a chunk changed again while its mesh was being
+    /// generated, the older result is discarded, so only the
+    /// latest mesh of a chunk is applied. The chunk stays marked
+    /// with `ChunkMeshPending` until its latest mesh arrives.
+    pub fn phase_two(&mut self, data: &mut AsyncChunkUpkeepSystemData<'_, D, C>) {
+        self.prune_in_flight(data);
+
+        while let Some(result) = self.workers.try_recv() {
+            self.apply_result(data, result);
+        }
+    }
+
+    /// Blocks until every snapshot sent to the workers has been
+    /// meshed, then submits the meshes like `phase_two`.
+    ///
+    /// Useful when loading a world, so all chunks have
+    /// meshes on the first frame.
+    pub fn wait_for_meshes(&mut self, data: &mut AsyncChunkUpkeepSystemData<'_, D, C>) {
+        self.prune_in_flight(data);
+
+        while self.outstanding > 0 {
+            match self.workers.recv() {
+                Some(result) => self.apply_result(data, result),
+                // Workers have stopped.
+                None => self.outstanding = 0,
+            }
+        }
+    }
+
+    /// Forgets the meshes of chunks deleted while waiting,
+    /// so their results are discarded.
+    fn prune_in_flight(&mut self, data: &AsyncChunkUpkeepSystemData<'_, D, C>) {
+        let entities = &data.entities;
+        self.in_flight
+            .retain(|entity, _| entities.is_alive(*entity));
+    }
+
+    fn apply_result(
+        &mut self,
+        data: &mut AsyncChunkUpkeepSystemData<'_, D, C>,
+        result: MeshResult,
+    ) {
+        self.outstanding = self.outstanding.saturating_sub(1);
+
+        if self.in_flight.get(&result.entity) != Some(&result.generation) {
+            return;
+        }
+
+        self.in_flight.remove(&result.entity);
+        data.pending.remove(result.entity);
+        data.upkeep
+            .mesh_cmds
+            .submit(MeshCmd::AllocateMesh(result.entity, result.builder));
+    }
+}
+
+impl<'a, D, C, G> System<'a> for AsyncChunkUpkeepSystem<D, C, G>
+where
+    D: 'static + VoxelData + Send + Sync,
+    C: 'static + VoxelChunk<D> + Component + MaskedChunk + Clone + Send + Sync,
+    G: 'static + VoxelMeshGen + Send + Sync,
+{
+    type SystemData = AsyncChunkUpkeepSystemData<'a, D, C>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        self.phase_one(&mut data);
+        self.phase_two(&mut data);
+    }
 }
+
+/// Marks a chunk entity that is waiting on a mesh
+/// from the background workers.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct ChunkMeshPending;
@@ -0,0 +1,149 @@
+use crate::voxel::{voxel_to_chunk, ChunkMapping, VoxelChunk, VoxelCoord, VoxelData};
+use specs::{Component, ReadStorage};
+use std::collections::{HashSet, VecDeque};
+
+/// Offsets to the six voxels sharing a face.
+const FACE_NEIGHBOURS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+/// Finds the voxels connected to the start voxel, where each
+/// voxel on the way matches the predicate.
+///
+/// Voxels are connected when they share a face. The fill crosses
+/// chunk boundaries by looking up neighbouring chunks in the
+/// `ChunkMapping`, and stops at voxels in chunks that aren't loaded.
+///
+/// At most `max_count` voxels are returned, nearest to the start
+/// first, so large or open spaces don't take unbounded time. When
+/// the start voxel doesn't match, the result is empty.
+///
+/// To find a connected structure, fill occupied voxels with
+/// `|data| data.occupied()`. To find an enclosed space, fill the
+/// empty voxels, and check whether the fill reached its cap.
+pub fn flood_fill<D, C, F>(
+    chunk_map: &ChunkMapping,
+    chunks: &ReadStorage<'_, C>,
+    start: VoxelCoord,
+    max_count: usize,
+    mut predicate: F,
+) -> HashSet<VoxelCoord>
+where
+    D: VoxelData,
+    C: VoxelChunk<D> + Component,
+    F: FnMut(&D) -> bool,
+{
+    let mut matches = |coord: VoxelCoord| {
+        chunk_map
+            .chunk_entity(voxel_to_chunk(&coord))
+            .and_then(|entity| chunks.get(entity))
+            .and_then(|chunk| chunk.get(coord))
+            .map_or(false, |data| predicate(data))
+    };
+
+    let mut reached = HashSet::new();
+    if max_count == 0 || !matches(start) {
+        return reached;
+    }
+
+    // Every voxel checked, matching or not, is visited
+    // once, so the fill always terminates.
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+
+    while let Some(coord) = queue.pop_front() {
+        reached.insert(coord);
+        if reached.len() >= max_count {
+            break;
+        }
+
+        for offset in FACE_NEIGHBOURS.iter() {
+            let neighbour = coord + VoxelCoord::from(offset);
+            if visited.insert(neighbour) && matches(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    reached
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::voxel::VoxelArrayChunk;
+    use specs::{Builder, World};
+
+    type IntChunk = VoxelArrayChunk<u16>;
+
+    fn create_world() -> (World, ChunkMapping) {
+        let mut world = World::new();
+        world.register::<IntChunk>();
+
+        let mut chunk_map = ChunkMapping::new();
+        for i in 0..2 {
+            let entity = world.create_entity().with(IntChunk::new([i, 0, 0])).build();
+            chunk_map.add_chunk(entity, [i, 0, 0]);
+        }
+
+        // Bar along the X axis, crossing into the second
+        // chunk, and a voxel not touching it.
+        for x in 5..12 {
+            set_voxel(&world, &chunk_map, [x, 0, 0]);
+        }
+        set_voxel(&world, &chunk_map, [10, 1, 0]);
+        set_voxel(&world, &chunk_map, [3, 3, 3]);
+
+        (world, chunk_map)
+    }
+
+    fn set_voxel(world: &World, chunk_map: &ChunkMapping, coord: [i32; 3]) {
+        let coord = VoxelCoord::from(coord);
+        let entity = chunk_map.chunk_entity(voxel_to_chunk(&coord)).unwrap();
+        let mut chunks = world.write_storage::<IntChunk>();
+        chunks.get_mut(entity).unwrap().set(coord, 1);
+    }
+
+    #[test]
+    fn test_flood_fill_across_chunks() {
+        let (world, chunk_map) = create_world();
+        let chunks = world.read_storage::<IntChunk>();
+        let occupied = |data: &u16| data.occupied();
+
+        let reached = flood_fill(&chunk_map, &chunks, [5, 0, 0].into(), 100, occupied);
+        assert_eq!(8, reached.len());
+        assert!(reached.contains(&[11, 0, 0].into()));
+        assert!(reached.contains(&[10, 1, 0].into()));
+        assert!(!reached.contains(&[3, 3, 3].into()));
+
+        // Capped, nearest first.
+        let reached = flood_fill(&chunk_map, &chunks, [5, 0, 0].into(), 3, occupied);
+        let expected: HashSet<VoxelCoord> =
+            vec![[5, 0, 0].into(), [6, 0, 0].into(), [7, 0, 0].into()]
+                .into_iter()
+                .collect();
+        assert_eq!(expected, reached);
+
+        // Start doesn't match.
+        let reached = flood_fill(&chunk_map, &chunks, [4, 0, 0].into(), 100, occupied);
+        assert!(reached.is_empty());
+    }
+
+    #[test]
+    fn test_flood_fill_unloaded_chunks() {
+        let (world, chunk_map) = create_world();
+        let chunks = world.read_storage::<IntChunk>();
+
+        // Empty space stops at the edges of the loaded chunks.
+        let empty = |data: &u16| !data.occupied();
+        let reached = flood_fill(&chunk_map, &chunks, [0, 7, 0].into(), 10_000, empty);
+        assert_eq!(2 * 8 * 8 * 8 - 9, reached.len());
+    }
+}
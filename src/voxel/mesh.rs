@@ -1,5 +1,5 @@
 use crate::comp::{GlTexture, MeshBuilder, TexRect};
-use crate::voxel::{wiggle, MaskedChunk, VoxelChunk, VoxelData};
+use crate::voxel::{MaskedChunk, VoxelChunk, VoxelData, WiggleParams};
 
 /// Mesh generator for voxel chunks.
 pub trait VoxelMeshGen {
@@ -72,16 +72,75 @@ impl VoxelMeshGen for VoxelBoxGen {
 /// Deforms the corner points of each voxel
 /// to visually break up the grid.
 pub struct DeformedBoxGen {
-    /// Amount to deform points.
-    force: f32,
+    /// Amount and seed of the corner deformation.
+    params: WiggleParams,
 
-    /// Texture rectangles to be used for each voxel cuboid
-    tex_rects: [TexRect; 6],
+    /// Textures to be used for each voxel cuboid
+    faces: FaceTextures,
+}
+
+/// Per face textures of a voxel cuboid.
+#[derive(Debug, Clone)]
+enum FaceTextures {
+    /// Sub-rectangles of a single texture atlas.
+    Rects([TexRect; 6]),
+
+    /// Whole layers of a texture array.
+    Layers([u32; 6]),
 }
 
 impl DeformedBoxGen {
-    pub fn new(force: f32, tex_rects: [TexRect; 6]) -> Self {
-        DeformedBoxGen { force, tex_rects }
+    pub fn new(params: WiggleParams, tex_rects: [TexRect; 6]) -> Self {
+        DeformedBoxGen {
+            params,
+            faces: FaceTextures::Rects(tex_rects),
+        }
+    }
+
+    /// Creates a generator for a `GlTextureArray`, where
+    /// each face covers a whole layer instead of a sub-rectangle.
+    ///
+    /// Layers are in the same order as the texture rectangles
+    /// of `MeshBuilder::pseudocube_points`.
+    pub fn with_layers(params: WiggleParams, layers: [u32; 6]) -> Self {
+        DeformedBoxGen {
+            params,
+            faces: FaceTextures::Layers(layers),
+        }
+    }
+
+    pub fn with_seed(amplitude: f32, seed: u64, tex_rects: [TexRect; 6]) -> Self {
+        DeformedBoxGen::new(WiggleParams::new(amplitude, seed), tex_rects)
+    }
+
+    #[inline]
+    pub fn params(&self) -> &WiggleParams {
+        &self.params
+    }
+
+    /// Deformed corners of the voxel at the given global coordinate,
+    /// relative to its lowest corner, in the order expected by
+    /// `MeshBuilder::pseudocube_points`.
+    ///
+    /// Corners are shared by neighbouring voxels, so they are
+    /// deformed by their own coordinate, keeping the mesh closed.
+    fn corners(&self, voxel: [i32; 3]) -> [glm::Vec3; 8] {
+        let [i, j, k] = voxel;
+        let corner = |x: i32, y: i32, z: i32| {
+            let offset: glm::Vec3 = self.params.offset(i + x, j + y, k + z).into();
+            glm::vec3(x as f32, y as f32, z as f32) + offset
+        };
+
+        [
+            corner(0, 0, 0), // p0
+            corner(0, 0, 1), // p1
+            corner(0, 1, 0), // p2
+            corner(0, 1, 1), // p3
+            corner(1, 0, 0), // p4
+            corner(1, 0, 1), // p5
+            corner(1, 1, 0), // p6
+            corner(1, 1, 1), // p7
+        ]
     }
 }
 
@@ -93,7 +152,6 @@ impl VoxelMeshGen for DeformedBoxGen {
     {
         let dim = chunk.dim() as i32;
         let o = chunk.voxel_offset();
-        let force = self.force;
 
         for x in 0..dim {
             for y in 0..dim {
@@ -102,44 +160,35 @@ impl VoxelMeshGen for DeformedBoxGen {
                         .get_local([x, y, z])
                         .map(|data| data.occupied())
                         .unwrap_or(false);
-                    let [w0, w1, w2, w3, w4, w5, w6, w7]: [glm::Vec3; 8] = [
-                        wiggle(o.i + x, o.j + y, o.k + z).into(),             // p0
-                        wiggle(o.i + x, o.j + y, o.k + z + 1).into(),         // p1
-                        wiggle(o.i + x, o.j + y + 1, o.k + z).into(),         // p2
-                        wiggle(o.i + x, o.j + y + 1, o.k + z + 1).into(),     // p3
-                        wiggle(o.i + x + 1, o.j + y, o.k + z).into(),         // p4
-                        wiggle(o.i + x + 1, o.j + y, o.k + z + 1).into(),     // p5
-                        wiggle(o.i + x + 1, o.j + y + 1, o.k + z).into(),     // p6
-                        wiggle(o.i + x + 1, o.j + y + 1, o.k + z + 1).into(), // p7
-                    ];
-                    let pos = glm::vec3(x as f32, y as f32, z as f32);
+
                     if occupied {
-                        builder = builder.pseudocube_points(
-                            [
-                                pos + glm::vec3(0.0, 0.0, 0.0)
-                                    + (w0 - glm::vec3(0.5, 0.5, 0.5)) * force, // p0
-                                pos + glm::vec3(0.0, 0.0, 1.0)
-                                    + (w1 - glm::vec3(0.5, 0.5, 0.5)) * force, // p1
-                                pos + glm::vec3(0.0, 1.0, 0.0)
-                                    + (w2 - glm::vec3(0.5, 0.5, 0.5)) * force, // p2
-                                pos + glm::vec3(0.0, 1.0, 1.0)
-                                    + (w3 - glm::vec3(0.5, 0.5, 0.5)) * force, // p3
-                                pos + glm::vec3(1.0, 0.0, 0.0)
-                                    + (w4 - glm::vec3(0.5, 0.5, 0.5)) * force, // p4
-                                pos + glm::vec3(1.0, 0.0, 1.0)
-                                    + (w5 - glm::vec3(0.5, 0.5, 0.5)) * force, // p5
-                                pos + glm::vec3(1.0, 1.0, 0.0)
-                                    + (w6 - glm::vec3(0.5, 0.5, 0.5)) * force, // p6
-                                pos + glm::vec3(1.0, 1.0, 1.0)
-                                    + (w7 - glm::vec3(0.5, 0.5, 0.5)) * force, // p7
-                            ],
-                            self.tex_rects.clone(),
-                        );
+                        let pos = glm::vec3(x as f32, y as f32, z as f32);
+                        let [c0, c1, c2, c3, c4, c5, c6, c7] =
+                            self.corners([o.i + x, o.j + y, o.k + z]);
+                        let points = [
+                            pos + c0,
+                            pos + c1,
+                            pos + c2,
+                            pos + c3,
+                            pos + c4,
+                            pos + c5,
+                            pos + c6,
+                            pos + c7,
+                        ];
+                        builder = match &self.faces {
+                            FaceTextures::Rects(tex_rects) => {
+                                builder.pseudocube_points(points, tex_rects.clone())
+                            }
+                            FaceTextures::Layers(layers) => {
+                                builder.pseudocube_points_layered(points, *layers)
+                            }
+                        };
                     }
                 }
             }
         }
 
+        // Face normals follow the deformed corners.
         builder
     }
 }
@@ -163,3 +212,45 @@ impl VoxelMeshGen for NoOpVoxelMeshGen {
         _builder
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tex_rects() -> [TexRect; 6] {
+        [
+            TexRect::default(),
+            TexRect::default(),
+            TexRect::default(),
+            TexRect::default(),
+            TexRect::default(),
+            TexRect::default(),
+        ]
+    }
+
+    #[test]
+    fn test_deformed_box_seed() {
+        let a = DeformedBoxGen::with_seed(0.1, 42, tex_rects());
+        let b = DeformedBoxGen::with_seed(0.1, 42, tex_rects());
+        let other = DeformedBoxGen::with_seed(0.1, 7, tex_rects());
+
+        for voxel in &[[0, 0, 0], [3, -2, 7], [-100, 50, 12]] {
+            assert_eq!(a.corners(*voxel), b.corners(*voxel));
+            assert_ne!(a.corners(*voxel), other.corners(*voxel));
+        }
+
+        // Neighbours share corners.
+        assert_eq!(
+            a.corners([0, 0, 0])[4],
+            a.corners([1, 0, 0])[0] + glm::vec3(1.0, 0.0, 0.0)
+        );
+
+        // Within the amplitude of the undeformed corner.
+        for corner in a.corners([5, 5, 5]).iter() {
+            for &n in corner.iter() {
+                let offset = n - n.round();
+                assert!(offset.abs() <= 0.05);
+            }
+        }
+    }
+}
@@ -3,15 +3,19 @@ mod chunk;
 mod coord;
 mod ctrl;
 mod data;
+mod fill;
 mod mesh;
 mod ray;
 mod wiggle;
+mod worker;
 
 pub use camera::*;
 pub use chunk::*;
 pub use coord::*;
 pub use ctrl::*;
 pub use data::*;
+pub use fill::*;
 pub use mesh::*;
 pub use ray::*;
 pub use wiggle::*;
+pub use worker::*;
@@ -162,6 +162,29 @@ impl VoxelRaycast {
     pub fn direction(&self) -> Unit<Vector3<f32>> {
         self.direction
     }
+
+    /// Collects the intersected voxels up to and including the
+    /// first that satisfies the predicate.
+    ///
+    /// Returns `None` when no voxel within the ray's steps
+    /// satisfies the predicate.
+    pub fn until<F>(self, mut predicate: F) -> Option<Vec<VoxelRayInfo>>
+    where
+        F: FnMut(&VoxelRayInfo) -> bool,
+    {
+        let mut path = vec![];
+
+        for ray_info in self {
+            let found = predicate(&ray_info);
+            path.push(ray_info);
+
+            if found {
+                return Some(path);
+            }
+        }
+
+        None
+    }
 }
 
 impl Iterator for VoxelRaycast {
@@ -266,4 +289,19 @@ mod test {
         assert_eq!(target, info.voxel);
         assert_eq!(4, cursor);
     }
+
+    #[test]
+    fn test_all_hits() {
+        let cast = || voxel_raycast([0.5, 0.5, 0.5].into(), Vector3::x_axis(), 3);
+        let expected: Vec<VoxelCoord> = vec![[0, 0, 0].into(), [1, 0, 0].into(), [2, 0, 0].into()];
+
+        let path: Vec<VoxelCoord> = cast().map(|ray_info| ray_info.voxel).collect();
+        assert_eq!(expected, path);
+
+        let path = cast().until(|ray_info| ray_info.voxel.i == 1).unwrap();
+        assert_eq!(2, path.len());
+        assert_eq!(expected[1], path[1].voxel);
+
+        assert!(cast().until(|ray_info| ray_info.voxel.i == 3).is_none());
+    }
 }
@@ -1,3 +1,40 @@
+//! Deterministic pseudo-random offsets for voxel coordinates,
+//! used to jitter the corners of voxel meshes.
+//!
+//! For motion of entities over time, see `comp::Wiggle`.
+
+/// Odd constant derived from the golden ratio, used to scatter seeds.
+const SEED_MULTIPLIER: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Parameters of the random jitter applied to the corners of voxels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WiggleParams {
+    /// Scale of the jitter, in voxels. Corners are moved up to half
+    /// the amplitude along each axis.
+    pub amplitude: f32,
+    /// Seed mixed into the voxel coordinates, so the same seed
+    /// always produces the same jitter.
+    pub seed: u64,
+}
+
+impl WiggleParams {
+    pub fn new(amplitude: f32, seed: u64) -> Self {
+        WiggleParams { amplitude, seed }
+    }
+
+    /// Offset of the corner at the given voxel coordinate, between
+    /// `-amplitude / 2` and `amplitude / 2` along each axis.
+    pub fn offset(&self, i: i32, j: i32, k: i32) -> [f32; 3] {
+        let [x, y, z] = wiggle_seeded(i, j, k, self.seed);
+
+        [
+            (x - 0.5) * self.amplitude,
+            (y - 0.5) * self.amplitude,
+            (z - 0.5) * self.amplitude,
+        ]
+    }
+}
+
 /// Given a voxel coordinate, return three deterministic pseudo-random numbers
 /// between 0.0 and 1.0.
 ///
@@ -5,6 +42,14 @@
 /// position. Example use-case would be randomizing mesh or texture
 /// regeneration.
 pub fn wiggle(i: i32, j: i32, k: i32) -> [f32; 3] {
+    wiggle_seeded(i, j, k, 0)
+}
+
+/// Same as `wiggle`, with the seed mixed into the voxel coordinate,
+/// so different seeds produce different numbers.
+///
+/// A seed of zero produces the same numbers as `wiggle`.
+pub fn wiggle_seeded(i: i32, j: i32, k: i32, seed: u64) -> [f32; 3] {
     // Cast to larger type so we have room to shift.
     let (i, j, k) = (i as u64, j as u64, k as u64);
 
@@ -30,6 +75,11 @@ pub fn wiggle(i: i32, j: i32, k: i32) -> [f32; 3] {
         (k << 32) ^ (i << 16) ^ j,
     );
 
+    // Spread the seed's bits, so consecutive seeds
+    // don't produce similar numbers.
+    let seed = seed.wrapping_mul(SEED_MULTIPLIER);
+    let (i, j, k) = (i ^ seed, j ^ seed.rotate_left(21), k ^ seed.rotate_left(42));
+
     // The number 65,535 is chosen because it is small enough to offer good
     // modulo wrap-around, but also gives good spread between 0.0 and 1.0.
     [
@@ -0,0 +1,121 @@
+//! Background mesh generation for voxel chunks.
+use crate::comp::MeshBuilder;
+use crate::voxel::{MaskedChunk, VoxelChunk, VoxelData, VoxelMeshGen};
+use crossbeam::channel::{self, Receiver, Sender};
+use log::warn;
+use specs::Entity;
+use std::sync::Arc;
+use std::thread;
+
+/// Snapshot of a chunk, to be meshed on a worker thread.
+pub struct MeshJob<C> {
+    pub entity: Entity,
+
+    /// Incremented each time a chunk is sent to the workers,
+    /// so results from older snapshots can be discarded.
+    pub generation: u64,
+
+    pub chunk: C,
+}
+
+/// Mesh generated on a worker thread.
+pub struct MeshResult {
+    pub entity: Entity,
+    pub generation: u64,
+    pub builder: MeshBuilder,
+}
+
+/// Pool of threads that generate chunk meshes
+/// in the background.
+///
+/// Threads are stopped and joined when the pool is dropped.
+pub struct MeshWorkerPool<C> {
+    /// Optional so the sender can be dropped before
+    /// joining the workers.
+    jobs: Option<Sender<MeshJob<C>>>,
+    results: Receiver<MeshResult>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl<C> MeshWorkerPool<C>
+where
+    C: 'static + Send,
+{
+    /// Spawns the given number of worker threads, with
+    /// a minimum of one.
+    pub fn new<D, G>(mesh_gen: Arc<G>, thread_count: usize) -> Self
+    where
+        D: 'static + VoxelData,
+        C: VoxelChunk<D> + MaskedChunk,
+        G: 'static + VoxelMeshGen + Send + Sync,
+    {
+        let (job_send, job_recv) = channel::unbounded::<MeshJob<C>>();
+        let (result_send, result_recv) = channel::unbounded();
+
+        let workers = (0..thread_count.max(1))
+            .map(|i| {
+                let job_recv = job_recv.clone();
+                let result_send = result_send.clone();
+                let mesh_gen = mesh_gen.clone();
+
+                thread::Builder::new()
+                    .name(format!("mesh_worker:{}", i))
+                    .spawn(move || {
+                        // Runs until the pool drops the job sender.
+                        for job in job_recv.iter() {
+                            let builder = mesh_gen.generate::<D, C>(&job.chunk, MeshBuilder::new());
+                            let result = MeshResult {
+                                entity: job.entity,
+                                generation: job.generation,
+                                builder,
+                            };
+
+                            if result_send.send(result).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        MeshWorkerPool {
+            jobs: Some(job_send),
+            results: result_recv,
+            workers,
+        }
+    }
+
+    /// Queues a chunk for mesh generation.
+    pub fn submit(&self, job: MeshJob<C>) {
+        if let Some(ref jobs) = self.jobs {
+            if jobs.send(job).is_err() {
+                warn!("Mesh workers have stopped, job dropped");
+            }
+        }
+    }
+
+    /// Retrieves a completed mesh, without blocking.
+    pub fn try_recv(&self) -> Option<MeshResult> {
+        self.results.try_recv().ok()
+    }
+
+    /// Blocks until a mesh is completed. Returns `None`
+    /// when the workers have stopped.
+    pub fn recv(&self) -> Option<MeshResult> {
+        self.results.recv().ok()
+    }
+}
+
+impl<C> Drop for MeshWorkerPool<C> {
+    fn drop(&mut self) {
+        // Closing the channel stops the workers.
+        self.jobs.take();
+
+        for handle in self.workers.drain(..) {
+            if handle.join().is_err() {
+                warn!("Mesh worker thread panic");
+            }
+        }
+    }
+}
@@ -1,15 +1,19 @@
 extern crate rengine;
 
-use rengine::comp::MeshCommandBuffer;
-use rengine::specs::{Builder, RunNow, World};
+use rengine::comp::{MeshCmd, MeshCommandBuffer};
+use rengine::glm;
+use rengine::specs::{Builder, Entity, RunNow, World};
 use rengine::voxel::{
-    ChunkControl, ChunkMapping, ChunkUpkeepSystem, NoOpVoxelMeshGen, VoxelArrayChunk, VoxelChunk,
+    AsyncChunkUpkeepSystem, ChunkControl, ChunkMapping, ChunkMeshPending, ChunkUpkeepSystem,
+    NoOpVoxelMeshGen, RemeshFocus, VoxelArrayChunk, VoxelChunk, CHUNK_DIM8,
+    MAX_REMESH_DEFER_FRAMES,
 };
 
 type IntVoxel = u16;
 type IntVoxelChunk = VoxelArrayChunk<IntVoxel>;
 type IntChunkCtrl = ChunkControl<u16, IntVoxelChunk>;
 type IntUpkeepSystem = ChunkUpkeepSystem<IntVoxel, IntVoxelChunk, NoOpVoxelMeshGen>;
+type IntAsyncUpkeepSystem = AsyncChunkUpkeepSystem<IntVoxel, IntVoxelChunk, NoOpVoxelMeshGen>;
 
 /// Ensure update queue is drained on maintain
 #[test]
@@ -55,3 +59,199 @@ fn test_lazy_update() {
         assert_eq!(Some(&3), chunks.get(entity).and_then(|c| c.get([2, 0, 0])));
     }
 }
+
+fn create_chunk_world() -> (World, Entity) {
+    let mut world = World::new();
+    world.register::<IntVoxelChunk>();
+    world.register::<ChunkMeshPending>();
+    let entity = world
+        .create_entity()
+        .with(IntVoxelChunk::new([0, 0, 0]))
+        .build();
+
+    let mut chunk_map = ChunkMapping::new();
+    chunk_map.add_chunk(entity, [0, 0, 0]);
+    world.add_resource(chunk_map);
+    world.add_resource(IntChunkCtrl::default());
+    world.add_resource(MeshCommandBuffer::new());
+
+    (world, entity)
+}
+
+/// Ensure a chunk changed while its mesh is in-flight
+/// only receives its latest mesh.
+#[test]
+fn test_background_mesh_applied_once() {
+    let (world, entity) = create_chunk_world();
+    let mut upkeep_system = IntAsyncUpkeepSystem::with_workers(NoOpVoxelMeshGen, 2);
+
+    // Two snapshots of the same chunk are sent to the workers.
+    world
+        .write_resource::<IntChunkCtrl>()
+        .lazy_update([0, 0, 0], 1);
+    upkeep_system.phase_one(&mut world.system_data());
+    world
+        .write_resource::<IntChunkCtrl>()
+        .lazy_update([1, 0, 0], 2);
+    upkeep_system.phase_one(&mut world.system_data());
+    assert!(world
+        .read_storage::<ChunkMeshPending>()
+        .get(entity)
+        .is_some());
+
+    // Both results arrive, and the stale one is discarded.
+    upkeep_system.wait_for_meshes(&mut world.system_data());
+    assert!(world
+        .read_storage::<ChunkMeshPending>()
+        .get(entity)
+        .is_none());
+
+    let mut mesh_cmds = world.write_resource::<MeshCommandBuffer>();
+    let mut count = 0;
+    while let Some(MeshCmd::AllocateMesh(cmd_entity, _)) = mesh_cmds.pop() {
+        assert_eq!(cmd_entity, entity);
+        count += 1;
+    }
+    assert_eq!(count, 1);
+}
+
+/// Ensure a chunk deleted while its mesh is in-flight
+/// doesn't receive a mesh.
+#[test]
+fn test_background_mesh_chunk_deleted() {
+    let (mut world, entity) = create_chunk_world();
+    let mut upkeep_system = IntAsyncUpkeepSystem::with_workers(NoOpVoxelMeshGen, 1);
+
+    world
+        .write_resource::<IntChunkCtrl>()
+        .lazy_update([0, 0, 0], 1);
+    upkeep_system.phase_one(&mut world.system_data());
+    world.delete_entity(entity).unwrap();
+    world.maintain();
+
+    upkeep_system.wait_for_meshes(&mut world.system_data());
+    assert!(world.write_resource::<MeshCommandBuffer>().pop().is_none());
+}
+
+/// Creates a row of chunks along the X axis, with
+/// the remesh focus at the last one.
+fn create_chunk_row(count: i32) -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.register::<IntVoxelChunk>();
+    world.register::<ChunkMeshPending>();
+
+    let mut chunk_map = ChunkMapping::new();
+    let entities: Vec<Entity> = (0..count)
+        .map(|i| {
+            let entity = world
+                .create_entity()
+                .with(IntVoxelChunk::new([i, 0, 0]))
+                .build();
+            chunk_map.add_chunk(entity, [i, 0, 0]);
+            entity
+        })
+        .collect();
+    world.add_resource(chunk_map);
+    world.add_resource(IntChunkCtrl::default());
+    world.add_resource(MeshCommandBuffer::new());
+
+    let x = (count as f32 - 0.5) * CHUNK_DIM8 as f32;
+    world.add_resource(RemeshFocus(glm::vec3(x, 4.0, 4.0)));
+
+    (world, entities)
+}
+
+fn touch_chunk(world: &World, i: i32) {
+    let voxel_x = i * CHUNK_DIM8 as i32;
+    world
+        .write_resource::<IntChunkCtrl>()
+        .lazy_update([voxel_x, 0, 0], 1);
+}
+
+fn remeshed_entities(world: &World) -> Vec<Entity> {
+    let mut mesh_cmds = world.write_resource::<MeshCommandBuffer>();
+    let mut entities = vec![];
+    while let Some(MeshCmd::AllocateMesh(entity, _)) = mesh_cmds.pop() {
+        entities.push(entity);
+    }
+    entities
+}
+
+/// Ensure the remesh budget defers distant chunks
+/// to later runs.
+#[test]
+fn test_remesh_budget_nearest_first() {
+    let (world, entities) = create_chunk_row(3);
+    let mut upkeep_system = IntUpkeepSystem::new(NoOpVoxelMeshGen).with_remesh_budget(1);
+
+    for i in 0..3 {
+        touch_chunk(&world, i);
+    }
+
+    let mut order = vec![];
+    for _ in 0..3 {
+        upkeep_system.run_now(&world.res);
+        let remeshed = remeshed_entities(&world);
+        assert_eq!(1, remeshed.len());
+        order.extend(remeshed);
+    }
+
+    assert_eq!(vec![entities[2], entities[1], entities[0]], order);
+    upkeep_system.run_now(&world.res);
+    assert!(remeshed_entities(&world).is_empty());
+}
+
+/// Ensure distant chunks are remeshed even when
+/// nearer chunks change every frame.
+#[test]
+fn test_remesh_budget_no_starvation() {
+    let (world, entities) = create_chunk_row(3);
+    let mut upkeep_system = IntUpkeepSystem::new(NoOpVoxelMeshGen).with_remesh_budget(1);
+
+    for i in 0..3 {
+        touch_chunk(&world, i);
+    }
+
+    let mut remeshed = vec![];
+    for _ in 0..(MAX_REMESH_DEFER_FRAMES + 3) {
+        touch_chunk(&world, 2);
+        upkeep_system.run_now(&world.res);
+        remeshed.extend(remeshed_entities(&world));
+    }
+
+    assert!(remeshed.contains(&entities[0]));
+    assert!(remeshed.contains(&entities[1]));
+}
+
+fn is_pending(world: &World, entity: Entity) -> bool {
+    world.read_storage::<ChunkMeshPending>().contains(entity)
+}
+
+/// Ensure a mesh in-flight is discarded when its chunk
+/// changes again, and the new snapshot is deferred.
+#[test]
+fn test_background_mesh_stale_when_deferred() {
+    let (world, entities) = create_chunk_row(2);
+    let mut upkeep_system =
+        IntAsyncUpkeepSystem::with_workers(NoOpVoxelMeshGen, 1).with_remesh_budget(1);
+
+    touch_chunk(&world, 0);
+    upkeep_system.phase_one(&mut world.system_data());
+
+    // The nearer chunk takes the budget, deferring the far chunk.
+    touch_chunk(&world, 0);
+    touch_chunk(&world, 1);
+    upkeep_system.phase_one(&mut world.system_data());
+
+    // The older mesh of the far chunk is discarded.
+    upkeep_system.wait_for_meshes(&mut world.system_data());
+    assert!(is_pending(&world, entities[0]));
+
+    upkeep_system.phase_one(&mut world.system_data());
+    upkeep_system.wait_for_meshes(&mut world.system_data());
+    assert!(!is_pending(&world, entities[0]));
+
+    let remeshed = remeshed_entities(&world);
+    assert_eq!(1, remeshed.iter().filter(|e| **e == entities[0]).count());
+    assert_eq!(1, remeshed.iter().filter(|e| **e == entities[1]).count());
+}